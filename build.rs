@@ -0,0 +1,4 @@
+// Migrations are embedded at build time, so a new migration must trigger a rebuild
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
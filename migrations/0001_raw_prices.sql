@@ -0,0 +1,26 @@
+-- Raw prices of every feed, as a TimescaleDB hypertable
+CREATE EXTENSION IF NOT EXISTS timescaledb CASCADE;
+
+CREATE TABLE IF NOT EXISTS raw_price_data (
+    id SERIAL,
+    feed_id TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (id, timestamp)
+);
+
+-- A table created as a plain table by an early version is converted in place, keeping its rows
+SELECT create_hypertable('raw_price_data', 'timestamp',
+                         chunk_time_interval => INTERVAL '1 day',
+                         if_not_exists => TRUE,
+                         migrate_data => TRUE);
+
+CREATE INDEX IF NOT EXISTS idx_raw_price_data_timestamp ON raw_price_data (timestamp);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_raw_price_data_feed_timestamp ON raw_price_data (feed_id, timestamp);
+
+-- Latest value per feed, read by distribution servers instead of scanning the hypertable
+CREATE TABLE IF NOT EXISTS latest_price_data (
+    feed_id TEXT PRIMARY KEY,
+    timestamp TIMESTAMPTZ NOT NULL,
+    price DOUBLE PRECISION NOT NULL
+);
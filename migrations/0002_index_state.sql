@@ -0,0 +1,64 @@
+-- Divisors of divisor-based indices, restored on startup to keep their levels continuous
+CREATE TABLE IF NOT EXISTS index_divisors (
+    index_name TEXT PRIMARY KEY,
+    divisor DOUBLE PRECISION NOT NULL,
+    level DOUBLE PRECISION NOT NULL,
+    constituents JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+-- Published index values, read back on startup to continue the index histories
+CREATE TABLE IF NOT EXISTS index_values (
+    index_name TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    epoch BIGINT NOT NULL,
+    PRIMARY KEY (index_name, timestamp)
+);
+
+-- Columns added after the table was first created by schemas predating migrations
+ALTER TABLE index_values ADD COLUMN IF NOT EXISTS methodology_version INTEGER NOT NULL DEFAULT 0;
+-- Value before smoothing, absent on values stored before it was recorded
+ALTER TABLE index_values ADD COLUMN IF NOT EXISTS raw_value DOUBLE PRECISION;
+
+SELECT create_hypertable('index_values', 'timestamp',
+                         chunk_time_interval => INTERVAL '1 day',
+                         if_not_exists => TRUE);
+
+-- Smoothing state of every index, restored on startup so smoothing continues seamlessly
+CREATE TABLE IF NOT EXISTS index_smoothing (
+    index_name TEXT PRIMARY KEY,
+    smoothing JSONB NOT NULL,
+    state JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+-- Levels of log-return indices and the prices their next returns are measured from
+CREATE TABLE IF NOT EXISTS index_returns (
+    index_name TEXT PRIMARY KEY,
+    level DOUBLE PRECISION NOT NULL,
+    prices JSONB NOT NULL,
+    anchored_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+-- Index values recalculated from stored raw prices with a corrected definition, kept apart
+-- from the published ones
+CREATE TABLE IF NOT EXISTS recomputed_index_values (
+    index_name TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    fingerprint TEXT NOT NULL,
+    recomputed_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (index_name, recomputed_at, timestamp)
+);
+
+-- Every version of every index definition, to explain jumps caused by reconfiguration
+CREATE TABLE IF NOT EXISTS index_methodologies (
+    index_name TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    fingerprint TEXT NOT NULL,
+    definition TEXT NOT NULL,
+    effective_from TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (index_name, version)
+);
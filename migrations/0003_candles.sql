@@ -0,0 +1,53 @@
+-- no-transaction
+-- Continuous aggregates cannot be created within a transaction
+
+-- 1m and 1h OHLC candles of index values and raw prices, kept up to date by TimescaleDB. Every
+-- refresh recalculates the buckets of the last hour (1m) or day (1h), covering late writes.
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS index_candles_1m WITH (timescaledb.continuous) AS
+SELECT index_name, time_bucket(INTERVAL '1 minute', timestamp) AS bucket,
+       first(value, timestamp) AS open, max(value) AS high, min(value) AS low,
+       last(value, timestamp) AS close, count(*) AS samples
+FROM index_values
+GROUP BY index_name, bucket
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('index_candles_1m',
+    start_offset => INTERVAL '1 hour', end_offset => INTERVAL '1 minute',
+    schedule_interval => INTERVAL '1 minute', if_not_exists => TRUE);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS index_candles_1h WITH (timescaledb.continuous) AS
+SELECT index_name, time_bucket(INTERVAL '1 hour', timestamp) AS bucket,
+       first(value, timestamp) AS open, max(value) AS high, min(value) AS low,
+       last(value, timestamp) AS close, count(*) AS samples
+FROM index_values
+GROUP BY index_name, bucket
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('index_candles_1h',
+    start_offset => INTERVAL '1 day', end_offset => INTERVAL '1 hour',
+    schedule_interval => INTERVAL '1 hour', if_not_exists => TRUE);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS price_candles_1m WITH (timescaledb.continuous) AS
+SELECT feed_id, time_bucket(INTERVAL '1 minute', timestamp) AS bucket,
+       first(price, timestamp) AS open, max(price) AS high, min(price) AS low,
+       last(price, timestamp) AS close, count(*) AS samples
+FROM raw_price_data
+GROUP BY feed_id, bucket
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('price_candles_1m',
+    start_offset => INTERVAL '1 hour', end_offset => INTERVAL '1 minute',
+    schedule_interval => INTERVAL '1 minute', if_not_exists => TRUE);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS price_candles_1h WITH (timescaledb.continuous) AS
+SELECT feed_id, time_bucket(INTERVAL '1 hour', timestamp) AS bucket,
+       first(price, timestamp) AS open, max(price) AS high, min(price) AS low,
+       last(price, timestamp) AS close, count(*) AS samples
+FROM raw_price_data
+GROUP BY feed_id, bucket
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('price_candles_1h',
+    start_offset => INTERVAL '1 day', end_offset => INTERVAL '1 hour',
+    schedule_interval => INTERVAL '1 hour', if_not_exists => TRUE);
@@ -0,0 +1,16 @@
+-- Telemetry of every fetch of a feed's price: latency, last status, retries and error class
+CREATE TABLE IF NOT EXISTS feed_metrics (
+    feed_id TEXT NOT NULL,
+    exchange TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    latency_ms DOUBLE PRECISION NOT NULL,
+    status INTEGER,
+    retries INTEGER NOT NULL,
+    error_class TEXT
+);
+
+SELECT create_hypertable('feed_metrics', 'timestamp',
+                         chunk_time_interval => INTERVAL '1 day',
+                         if_not_exists => TRUE);
+
+CREATE INDEX IF NOT EXISTS idx_feed_metrics_feed_timestamp ON feed_metrics (feed_id, timestamp DESC);
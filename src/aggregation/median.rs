@@ -0,0 +1,33 @@
+use crate::index::decimal::Arithmetic;
+use super::AggregationStrategy;
+
+/// Median of the prices, ignoring weights
+pub struct Median;
+
+impl AggregationStrategy for Median {
+    fn aggregate(&self, values: &[(f64, f64)], _arithmetic: Arithmetic) -> Option<f64> {
+        let prices: Vec<f64> = values.iter().map(|(price, _)| *price).collect();
+        median(&prices)
+    }
+
+    fn uses_weights(&self) -> bool {
+        false
+    }
+}
+
+/// Median of a set of values, `None` if empty
+pub fn median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+
+    if sorted.len().is_multiple_of(2) {
+        Some((sorted[mid - 1] + sorted[mid]) / 2.0)
+    } else {
+        Some(sorted[mid])
+    }
+}
@@ -0,0 +1,50 @@
+mod weighted_mean;
+mod median;
+mod trimmed_mean;
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use crate::index::decimal::Arithmetic;
+use crate::models::Aggregation;
+
+pub use median::median;
+pub use trimmed_mean::trimmed_mean;
+pub use weighted_mean::weighted_mean;
+
+/// Trait for aggregation algorithms, combining the constituent prices of an index into its value
+pub trait AggregationStrategy: Send + Sync {
+    /// Combine the `(price, weight)` pairs of the constituents, all priced and positive, into
+    /// the index value; `None` if they cannot be combined, e.g. when there are none
+    fn aggregate(&self, values: &[(f64, f64)], arithmetic: Arithmetic) -> Option<f64>;
+
+    /// Whether the value depends on the constituents' weights. Indices aggregated ignoring
+    /// weights need no weights summing to 100 and cannot be weighted by market cap.
+    fn uses_weights(&self) -> bool {
+        true
+    }
+}
+
+fn registry() -> &'static RwLock<HashMap<String, Arc<dyn AggregationStrategy>>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, Arc<dyn AggregationStrategy>>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Register a custom aggregation, selected by indices with `aggregation = "<name>"`. Must be
+/// called before the configuration is loaded; the built-in names cannot be overridden.
+pub fn register_strategy(name: &str, strategy: impl AggregationStrategy + 'static) {
+    registry().write().unwrap().insert(name.to_string(), Arc::new(strategy));
+}
+
+/// Factory function to create aggregation algorithm instances, `None` for a custom aggregation
+/// that is not registered
+pub fn create_strategy(aggregation: &Aggregation, trim_pct: f64) -> Option<Arc<dyn AggregationStrategy>> {
+    match aggregation {
+        Aggregation::WeightedMean => Some(Arc::new(weighted_mean::WeightedMean)),
+        Aggregation::Median => Some(Arc::new(median::Median)),
+        Aggregation::TrimmedMean => Some(Arc::new(trimmed_mean::TrimmedMean::new(trim_pct))),
+        Aggregation::Custom(name) => registry().read().unwrap().get(name).cloned(),
+    }
+}
@@ -0,0 +1,49 @@
+use super::{create_strategy, register_strategy, AggregationStrategy};
+use crate::index::decimal::Arithmetic;
+use crate::models::Aggregation;
+use crate::test_support::{IndexDefinitionBuilder, IndexHarness};
+
+#[cfg(test)]
+mod aggregation_tests {
+    use super::*;
+
+    /// Highest price, ignoring weights
+    struct Highest;
+
+    impl AggregationStrategy for Highest {
+        fn aggregate(&self, values: &[(f64, f64)], _arithmetic: Arithmetic) -> Option<f64> {
+            values.iter().map(|(price, _)| *price).reduce(f64::max)
+        }
+
+        fn uses_weights(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_built_in_aggregations() {
+        let values = [(100.0, 10.0), (101.0, 30.0), (110.0, 60.0)];
+        let aggregate = |aggregation: Aggregation| {
+            create_strategy(&aggregation, 34.0).unwrap().aggregate(&values, Arithmetic::Float).unwrap()
+        };
+
+        assert_eq!(aggregate(Aggregation::WeightedMean), 106.3);
+        assert_eq!(aggregate(Aggregation::Median), 101.0);
+        assert_eq!(aggregate(Aggregation::TrimmedMean), 101.0);
+        assert!(create_strategy(&Aggregation::from("unregistered".to_string()), 0.0).is_none());
+    }
+
+    #[test]
+    fn test_registered_aggregation_is_selected_by_name() {
+        register_strategy("highest", Highest);
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX")
+            .feed("a", 50)
+            .feed("b", 50)
+            .aggregation(Aggregation::from("highest".to_string()))
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 100.0).push("b", 105.0);
+
+        assert_eq!(harness.calculate()[0].value, 105.0);
+    }
+}
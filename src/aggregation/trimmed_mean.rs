@@ -0,0 +1,45 @@
+use crate::index::decimal::{self, Arithmetic};
+use super::AggregationStrategy;
+
+/// Average of the prices left after dropping the highest and lowest ones, ignoring weights
+pub struct TrimmedMean {
+    trim_pct: f64,
+}
+
+impl TrimmedMean {
+    pub fn new(trim_pct: f64) -> Self {
+        Self { trim_pct }
+    }
+}
+
+impl AggregationStrategy for TrimmedMean {
+    fn aggregate(&self, values: &[(f64, f64)], arithmetic: Arithmetic) -> Option<f64> {
+        let prices: Vec<f64> = values.iter().map(|(price, _)| *price).collect();
+        trimmed_mean_in(&prices, self.trim_pct, arithmetic)
+    }
+
+    fn uses_weights(&self) -> bool {
+        false
+    }
+}
+
+/// Mean of the values left after dropping `trim_pct` percent of them (rounded down) at each end,
+/// `None` if empty
+pub fn trimmed_mean(values: &[f64], trim_pct: f64) -> Option<f64> {
+    trimmed_mean_in(values, trim_pct, Arithmetic::Float)
+}
+
+fn trimmed_mean_in(values: &[f64], trim_pct: f64, arithmetic: Arithmetic) -> Option<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+
+    let trimmed = (sorted.len() as f64 * trim_pct / 100.0).floor() as usize;
+    let kept = sorted.get(trimmed..sorted.len().saturating_sub(trimmed))?;
+    if kept.is_empty() {
+        return None;
+    }
+    match arithmetic {
+        Arithmetic::Float => Some(kept.iter().sum::<f64>() / kept.len() as f64),
+        Arithmetic::Decimal => decimal::weighted_mean(kept.iter().map(|value| (*value, 1.0))),
+    }
+}
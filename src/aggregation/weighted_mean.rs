@@ -0,0 +1,31 @@
+use crate::index::decimal::{self, Arithmetic};
+use super::AggregationStrategy;
+
+/// Average of the prices weighted by the constituents' weights
+pub struct WeightedMean;
+
+impl AggregationStrategy for WeightedMean {
+    fn aggregate(&self, values: &[(f64, f64)], arithmetic: Arithmetic) -> Option<f64> {
+        weighted_mean(values, arithmetic)
+    }
+}
+
+/// Mean of `(value, weight)` pairs, or `None` if their weights sum to zero
+pub fn weighted_mean(values: &[(f64, f64)], arithmetic: Arithmetic) -> Option<f64> {
+    if arithmetic == Arithmetic::Decimal {
+        return decimal::weighted_mean(values.iter().copied());
+    }
+
+    let mut weighted_sum = 0.0;
+    let mut total_weights = 0.0;
+    for (value, weight) in values {
+        weighted_sum += value * weight;
+        total_weights += weight;
+    }
+
+    if total_weights <= 0.0 {
+        return None;
+    }
+
+    Some(weighted_sum / total_weights)
+}
@@ -0,0 +1,6 @@
+mod server;
+
+#[cfg(test)]
+mod tests;
+
+pub use server::{router, start_http_server, ApiContext, ApiError, HistoryQuery};
@@ -0,0 +1,176 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{error, info};
+
+use crate::error::{AppError, AppResult};
+use crate::health::{FeedHealthRegistry, FeedStatus};
+use crate::index::{IndexCalculator, IndexCatalogEntry};
+use crate::models::IndexDefinition;
+use crate::storage::{Database, StoredIndexValue};
+use crate::websocket::{CatalogIndex, IndexUpdate, LatestIndexValues};
+
+/// History returned when a request gives no range
+const DEFAULT_HISTORY_HOURS: i64 = 24;
+
+/// Shared state of the API handlers
+#[derive(Clone)]
+pub struct ApiContext {
+    /// Current index definitions, for the catalog
+    pub indices: Arc<Vec<IndexDefinition>>,
+    /// Calculator of the published values, for the health of every index
+    pub index_calc: Arc<RwLock<IndexCalculator>>,
+    /// Latest published value of every index
+    pub latest: Arc<LatestIndexValues>,
+    pub feed_health: Arc<FeedHealthRegistry>,
+    /// Stored index values, history requests are unavailable without it
+    pub database: Option<Database>,
+    /// Longest range a history request may span
+    pub max_history: Duration,
+}
+
+/// Range of a `GET /indices/{name}/history` request, the last day by default
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Only the last value of every bucket of this many seconds
+    pub resolution_secs: Option<u64>,
+}
+
+impl HistoryQuery {
+    /// `[from, to]` of the request, at most `max` long
+    pub fn range(&self, max: Duration) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+        let to = self.to.unwrap_or_else(Utc::now);
+        let from = self.from.unwrap_or(to - Duration::hours(DEFAULT_HISTORY_HOURS).min(max));
+        if from > to {
+            return Err("from must not be after to".to_string());
+        }
+        if to - from > max {
+            return Err(format!("History requests may span at most {} hours", max.num_hours()));
+        }
+        Ok((from, to))
+    }
+}
+
+/// Failed request, answered with its status and a JSON `{"error": ...}` body
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+}
+
+impl From<AppError> for ApiError {
+    fn from(e: AppError) -> Self {
+        error!("[HTTP API] Request failed: {}", e);
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal error")
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({ "error": self.message }))).into_response()
+    }
+}
+
+/// Routes of the API
+pub fn router(context: ApiContext) -> Router {
+    Router::new()
+        .route("/indices", get(list_indices))
+        .route("/indices/{name}", get(get_index))
+        .route("/indices/{name}/history", get(get_history))
+        .route("/feeds", get(list_feeds))
+        .with_state(context)
+}
+
+/// Serve the API until shutdown
+pub async fn start_http_server(
+    address: &str,
+    context: ApiContext,
+    mut shutdown: broadcast::Receiver<()>,
+) -> AppResult<()> {
+    let addr: SocketAddr = address.parse()
+        .map_err(|e| format!("Invalid HTTP API address {}: {}", address, e))?;
+    let listener = TcpListener::bind(addr).await
+        .map_err(|e| format!("Failed to bind HTTP API to {}: {}", address, e))?;
+
+    info!("[HTTP API] Listening on: {}", address);
+    axum::serve(listener, router(context))
+        .with_graceful_shutdown(async move {
+            let _ = shutdown.recv().await;
+        })
+        .await?;
+
+    info!("[HTTP API] Server stopped gracefully");
+    Ok(())
+}
+
+/// `GET /indices`: catalog of every index with its current health
+async fn list_indices(State(context): State<ApiContext>) -> Json<Vec<CatalogIndex>> {
+    let calculator = context.index_calc.read().await;
+    Json(context.indices.iter()
+        .map(|index| CatalogIndex { entry: IndexCatalogEntry::from(index), health: calculator.index_health(&index.name) })
+        .collect())
+}
+
+/// `GET /indices/{name}`: latest published value of an index, unavailable once it is older than
+/// the index's `max_staleness_secs`
+async fn get_index(State(context): State<ApiContext>, Path(name): Path<String>) -> Result<Json<IndexUpdate>, ApiError> {
+    let index = known_index(&context, &name)?;
+    let Some(value) = context.latest.get(&index.name) else {
+        return Err(ApiError::new(StatusCode::SERVICE_UNAVAILABLE, format!("Index {} has not been published yet", name)));
+    };
+    let age = Utc::now() - value.timestamp;
+    if age > Duration::seconds(index.max_staleness_secs as i64) {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("Latest value of index {} is stale: {}s old, at most {}s allowed", name, age.num_seconds(), index.max_staleness_secs),
+        ));
+    }
+    Ok(Json(IndexUpdate::new(&value, None)))
+}
+
+/// `GET /indices/{name}/history`: stored values of an index, oldest first
+async fn get_history(
+    State(context): State<ApiContext>,
+    Path(name): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<StoredIndexValue>>, ApiError> {
+    known_index(&context, &name)?;
+    let database = context.database.as_ref()
+        .ok_or_else(|| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "History needs database persistence to be enabled"))?;
+    let (from, to) = query.range(context.max_history)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+    let resolution = match query.resolution_secs {
+        Some(0) => return Err(ApiError::new(StatusCode::BAD_REQUEST, "resolution_secs must be positive")),
+        Some(secs) => Some(Duration::seconds(secs as i64)),
+        None => None,
+    };
+
+    Ok(Json(database.get_index_history(&name, from, to, resolution).await?))
+}
+
+/// `GET /feeds`: health of every polled feed, by id
+async fn list_feeds(State(context): State<ApiContext>) -> Json<Vec<FeedStatus>> {
+    Json(context.feed_health.snapshot())
+}
+
+fn known_index<'a>(context: &'a ApiContext, name: &str) -> Result<&'a IndexDefinition, ApiError> {
+    context.indices.iter().find(|index| index.name == name)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, format!("Unknown index {}", name)))
+}
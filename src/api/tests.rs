@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, RwLock};
+
+use super::*;
+use crate::health::FeedHealthRegistry;
+use crate::index::{IndexCalculator, IndexResult};
+use crate::models::Denomination;
+use crate::test_support::IndexDefinitionBuilder;
+use crate::websocket::LatestIndexValues;
+
+/// Serve the API for one index and one feed on an ephemeral port, without a database,
+/// returning its base URL
+async fn serve() -> (String, Arc<LatestIndexValues>) {
+    let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("coinbase_btc", 100).build();
+    let (_, receiver) = mpsc::channel(1);
+    let feed_health = Arc::new(FeedHealthRegistry::new(60));
+    feed_health.register("coinbase_btc");
+    let latest = Arc::new(LatestIndexValues::default());
+
+    let context = ApiContext {
+        indices: Arc::new(vec![index.clone()]),
+        index_calc: Arc::new(RwLock::new(IndexCalculator::new(vec![index], receiver))),
+        latest: latest.clone(),
+        feed_health,
+        database: None,
+        max_history: Duration::hours(168),
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router(context)).await });
+    (url, latest)
+}
+
+/// Published value of the index at `timestamp`
+fn value_at(timestamp: DateTime<Utc>) -> IndexResult {
+    IndexResult {
+        name: "BTC-USD-INDEX".to_string(),
+        timestamp,
+        value: 64000.0,
+        epoch: 7,
+        denomination: Denomination::default(),
+        quality: Default::default(),
+        change: Default::default(),
+        methodology_version: 1,
+        raw_value: None,
+        warming_up: false,
+    }
+}
+
+#[cfg(test)]
+mod route_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_indices_and_feeds() {
+        let (url, latest) = serve().await;
+
+        let indices: serde_json::Value = reqwest::get(format!("{}/indices", url)).await.unwrap().json().await.unwrap();
+        assert_eq!(indices[0]["name"], "BTC-USD-INDEX");
+        assert_eq!(indices[0]["constituents"], serde_json::json!(["coinbase_btc"]));
+        assert_eq!(indices[0]["health"], "pending");
+
+        let feeds: serde_json::Value = reqwest::get(format!("{}/feeds", url)).await.unwrap().json().await.unwrap();
+        assert_eq!(feeds[0]["feed_id"], "coinbase_btc");
+        assert_eq!(feeds[0]["health"], "stale");
+
+        let response = reqwest::get(format!("{}/indices/BTC-USD-INDEX", url)).await.unwrap();
+        assert_eq!(response.status(), 503);
+        let response = reqwest::get(format!("{}/indices/ETH-USD-INDEX", url)).await.unwrap();
+        assert_eq!(response.status(), 404);
+        let error: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(error["error"], "Unknown index ETH-USD-INDEX");
+
+        latest.update(value_at(Utc::now()));
+        let value: serde_json::Value = reqwest::get(format!("{}/indices/BTC-USD-INDEX", url)).await.unwrap().json().await.unwrap();
+        assert_eq!(value["index"], "BTC-USD-INDEX");
+        assert_eq!(value["value"], 64000.0);
+        assert!(value.get("sequence").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stale_value_is_unavailable() {
+        let (url, latest) = serve().await;
+
+        latest.update(value_at(Utc::now() - Duration::hours(1)));
+        let response = reqwest::get(format!("{}/indices/BTC-USD-INDEX", url)).await.unwrap();
+        assert_eq!(response.status(), 503);
+        let error: serde_json::Value = response.json().await.unwrap();
+        let message = error["error"].as_str().unwrap();
+        assert!(message.starts_with("Latest value of index BTC-USD-INDEX is stale: 3600s old"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_history_needs_a_database() {
+        let (url, _) = serve().await;
+
+        let response = reqwest::get(format!("{}/indices/BTC-USD-INDEX/history", url)).await.unwrap();
+        assert_eq!(response.status(), 503);
+        let response = reqwest::get(format!("{}/indices/ETH-USD-INDEX/history", url)).await.unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
+
+#[cfg(test)]
+mod history_query_tests {
+    use super::*;
+
+    #[test]
+    fn test_range_defaults_to_the_last_day_and_is_capped() {
+        let to = Utc.with_ymd_and_hms(2024, 5, 8, 0, 0, 0).unwrap();
+        let query = HistoryQuery { to: Some(to), ..Default::default() };
+        assert_eq!(query.range(Duration::hours(168)).unwrap(), (to - Duration::hours(24), to));
+        assert_eq!(query.range(Duration::hours(6)).unwrap(), (to - Duration::hours(6), to));
+
+        let query = HistoryQuery { from: Some(to - Duration::days(8)), to: Some(to), resolution_secs: None };
+        assert!(query.range(Duration::hours(168)).unwrap_err().contains("168 hours"));
+        let query = HistoryQuery { from: Some(to), to: Some(to - Duration::hours(1)), resolution_secs: None };
+        assert!(query.range(Duration::hours(168)).is_err());
+    }
+}
@@ -7,6 +7,9 @@ use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use tracing::{info, error, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
+use crypto_index_collector::serialization::WireFormat;
+use crypto_index_collector::websocket::{ClientOp, ClientRequest, CloseReason, ServerMessage, StreamMode, PROTOCOL_VERSION};
+
 /// Crypto Index Client - WebSocket client for receiving crypto index updates
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -22,6 +25,31 @@ struct Args {
     /// Reconnection delay in seconds
     #[arg(long, default_value_t = 5)]
     reconnect_delay: u64,
+
+    /// Encoding of index updates: json, msgpack or cbor
+    #[arg(long, default_value = "json", value_parser = parse_encoding)]
+    encoding: WireFormat,
+
+    /// Start from a snapshot of every index, then only receive updates changing a value
+    #[arg(long)]
+    delta: bool,
+
+    /// Receive updates at most every this many milliseconds, only the latest of every index
+    #[arg(long)]
+    interval_ms: Option<u64>,
+
+    /// Protocol version to speak, the server's current one if absent
+    #[arg(long)]
+    protocol_version: Option<u32>,
+
+    /// Also receive the messages of a channel, e.g. feed:coinbase_btc_usd; repeatable
+    #[arg(long = "channel")]
+    channels: Vec<String>,
+}
+
+fn parse_encoding(encoding: &str) -> Result<WireFormat, String> {
+    serde_json::from_value(serde_json::Value::String(encoding.to_string()))
+        .map_err(|_| format!("Unknown encoding {}, expected json, msgpack or cbor", encoding))
 }
 
 #[tokio::main]
@@ -41,7 +69,7 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     let mut reconnect_attempts = 0;
 
     loop {
-        match connect_to_server(&args.server).await {
+        match connect_to_server(&args).await {
             Ok(()) => {
                 // Connection closed normally, reset reconnect attempts
                 reconnect_attempts = 0;
@@ -73,13 +101,33 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     Ok(())
 }
 
-async fn connect_to_server(server_url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+async fn connect_to_server(args: &Args) -> Result<(), Box<dyn Error + Send + Sync>> {
     // Connect to the WebSocket server
-    let (ws_stream, _) = connect_async(server_url).await?;
+    let (ws_stream, _) = connect_async(&args.server).await?;
     info!("[CLIENT] Connected to the server successfully");
 
     // Split the WebSocket stream
     let (mut write, mut read) = ws_stream.split();
+    let mut last_sequence = 0;
+
+    if let Some(version) = args.protocol_version {
+        let request = ClientRequest { id: None, op: ClientOp::Hello { version: Some(version) } };
+        write.send(Message::Text(serde_json::to_string(&request)?.into())).await?;
+    }
+    let encoding = args.encoding;
+    if encoding != WireFormat::Json {
+        let request = ClientRequest { id: None, op: ClientOp::SetEncoding { encoding } };
+        write.send(Message::Text(serde_json::to_string(&request)?.into())).await?;
+    }
+    if args.delta || args.interval_ms.is_some() {
+        let mode = if args.delta { StreamMode::Delta } else { StreamMode::Full };
+        let request = ClientRequest { id: None, op: ClientOp::Subscribe { mode, interval_ms: args.interval_ms } };
+        write.send(Message::Text(serde_json::to_string(&request)?.into())).await?;
+    }
+    for channel in &args.channels {
+        let request = ClientRequest { id: None, op: ClientOp::Join { channel: channel.clone() } };
+        write.send(Message::Text(serde_json::to_string(&request)?.into())).await?;
+    }
 
     // Process incoming messages with Ctrl+C handling
     loop {
@@ -88,10 +136,20 @@ async fn connect_to_server(server_url: &str) -> Result<(), Box<dyn Error + Send
             message = read.next() => {
                 match message {
                     Some(Ok(msg)) => {
-                        if msg.is_text() {
-                            process_message(msg);
-                        } else if msg.is_close() {
-                            info!("[CLIENT] Received close frame from server");
+                        if msg.is_text() || msg.is_binary() {
+                            process_message(msg, encoding, &mut last_sequence);
+                        } else if let Message::Close(frame) = msg {
+                            match frame {
+                                Some(frame) => {
+                                    let code = u16::from(frame.code);
+                                    match CloseReason::from_code(code) {
+                                        Some(CloseReason::Shutdown) => info!("[CLIENT] Server is shutting down ({})", code),
+                                        Some(_) => warn!("[CLIENT] Server closed the connection ({}): {}", code, frame.reason),
+                                        None => info!("[CLIENT] Received close frame from server ({}): {}", code, frame.reason),
+                                    }
+                                }
+                                None => info!("[CLIENT] Received close frame from server"),
+                            }
                             break;
                         }
                     }
@@ -122,35 +180,84 @@ async fn connect_to_server(server_url: &str) -> Result<(), Box<dyn Error + Send
     Ok(())
 }
 
-fn process_message(msg: Message) {
-    if let Message::Text(text) = msg {
-        // Check if it's an index update message
-        if text.starts_with("INDEX:") {
-            // Parse the index data
-            let parts: Vec<&str> = text.split('|').collect();
-            if parts.len() >= 3 {
-                let index_part = parts[0].trim();
-                let timestamp_part = parts[1].trim();
-                let value_part = parts[2].trim();
-
-                // Extract the index name
-                let index_name = index_part.strip_prefix("INDEX:").unwrap_or(index_part).trim();
-
-                // Extract the timestamp
-                let timestamp = timestamp_part.strip_prefix("TIMESTAMP:").unwrap_or(timestamp_part).trim();
-
-                // Extract the value
-                let value = value_part.strip_prefix("VALUE:").unwrap_or(value_part).trim();
-
-                // Display the index update
-                info!("[INDEX UPDATE] {} = {} ({})", index_name, value, timestamp);
-            } else {
-                warn!("[CLIENT] Received malformed index message: {}", text);
-            }
-        } else {
+fn process_message(msg: Message, encoding: WireFormat, last_sequence: &mut u64) {
+    match msg {
+        Message::Text(text) => match ServerMessage::from_json(&text) {
+            Ok(message) => process_server_message(message, last_sequence),
+            // Servers running with `legacy_text_frames`
+            Err(_) if text.starts_with("INDEX:") => process_legacy_index_message(&text),
             // Just display the message as-is
-            info!("[SERVER MESSAGE] {}", text);
+            Err(_) => info!("[SERVER MESSAGE] {}", text),
+        },
+        Message::Binary(bytes) => match ServerMessage::from_binary(&bytes, encoding) {
+            Ok(message) => process_server_message(message, last_sequence),
+            Err(e) => warn!("[CLIENT] Received malformed binary message: {}", e),
+        },
+        _ => {}
+    }
+}
+
+fn process_server_message(message: ServerMessage, last_sequence: &mut u64) {
+    match message {
+        ServerMessage::Index(update) => {
+            // Sequences restart with every connection
+            let sequence = update.sequence.unwrap_or_default();
+            if sequence > *last_sequence + 1 && *last_sequence > 0 {
+                warn!("[CLIENT] Missed {} index updates", sequence - *last_sequence - 1);
+            }
+            *last_sequence = sequence;
+            info!("[INDEX UPDATE] {} = {} ({})", update.index, update.value, update.timestamp);
+        }
+        ServerMessage::Snapshot { sequence, indices, .. } => {
+            *last_sequence = sequence;
+            for update in indices {
+                info!("[INDEX SNAPSHOT] {} = {} ({})", update.index, update.value, update.timestamp);
+            }
+        }
+        ServerMessage::Hello { protocol_version, server_version, capabilities, .. } => {
+            info!("[CLIENT] Server {} speaks protocol version {}, capabilities: {}",
+                  server_version, protocol_version, capabilities.join(", "));
+            if protocol_version > PROTOCOL_VERSION {
+                warn!("[CLIENT] Server protocol version {} is newer than this client's {}", protocol_version, PROTOCOL_VERSION);
+            }
+        }
+        ServerMessage::Catalog { indices, feeds, .. } => {
+            for index in indices {
+                info!("[CATALOG] Index {} ({:?}) from {}", index.entry.name, index.health, index.entry.constituents.join(", "));
+            }
+            for feed in feeds {
+                info!("[CATALOG] Feed {} ({})", feed.feed_id, feed.health);
+            }
         }
+        ServerMessage::Feed(tick) => info!("[FEED UPDATE] {} = {} ({})", tick.feed_id, tick.price, tick.timestamp),
+        ServerMessage::Channels { channels, .. } => info!("[CLIENT] Joined channels: {}", channels.join(", ")),
+        ServerMessage::Encoding { encoding, .. } => info!("[CLIENT] Receiving index updates as {:?}", encoding),
+        ServerMessage::Error { message, .. } => warn!("[CLIENT] Server error: {}", message),
+        message => info!("[SERVER MESSAGE] {:?}", message),
+    }
+}
+
+fn process_legacy_index_message(text: &str) {
+    // Parse the index data
+    let parts: Vec<&str> = text.split('|').collect();
+    if parts.len() >= 3 {
+        let index_part = parts[0].trim();
+        let timestamp_part = parts[1].trim();
+        let value_part = parts[2].trim();
+
+        // Extract the index name
+        let index_name = index_part.strip_prefix("INDEX:").unwrap_or(index_part).trim();
+
+        // Extract the timestamp
+        let timestamp = timestamp_part.strip_prefix("TIMESTAMP:").unwrap_or(timestamp_part).trim();
+
+        // Extract the value
+        let value = value_part.strip_prefix("VALUE:").unwrap_or(value_part).trim();
+
+        // Display the index update
+        info!("[INDEX UPDATE] {} = {} ({})", index_name, value, timestamp);
+    } else {
+        warn!("[CLIENT] Received malformed index message: {}", text);
     }
 }
 
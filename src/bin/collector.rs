@@ -1,18 +1,34 @@
+use std::collections::HashMap;
 use std::error::Error;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{mpsc, RwLock, broadcast};
 use tokio::signal;
-use tracing::{info, error, warn};
-use clap::Parser;
+use tokio::task::JoinHandle;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, info_span, error, warn, Instrument};
+use clap::{Parser, Subcommand};
+use futures::StreamExt;
 
-use crypto_index_collector::config;
-use crypto_index_collector::exchange;
-use crypto_index_collector::index::IndexCalculator;
-use crypto_index_collector::models::FeedData;
-use crypto_index_collector::storage::Database;
-use crypto_index_collector::websocket;
-use crypto_index_collector::logging;
+use crypto_index_collector::bundle::Bundle;
+use crypto_index_collector::config::{self, Config};
+use crypto_index_collector::exchange::{self, telemetry, Exchange, FetchMetric};
+use crypto_index_collector::index::{self, run_publisher, run_state_persistence, CalculatorState, IndexCalculator, IndexResult, SeriesChecksum};
+use crypto_index_collector::serialization::{StreamRecord, WireFormat};
+use crypto_index_collector::models::{ChangeFilter, Failover, FeedData, FeedSource, Forward, IndexDefinition, PriceFeed};
+use crypto_index_collector::error::{AppError, AppResult};
+use crypto_index_collector::storage::{self, run_storage_sink, CoverageReport, Database, ExportFormat, ExportWriter, FileSink, PriceWriter, SinkSettings};
+use crypto_index_collector::websocket::{self, LatestIndexValues};
+use crypto_index_collector::api;
+use crypto_index_collector::multicast::MulticastPublisher;
+use crypto_index_collector::logging::{self, PipelineStage};
+use crypto_index_collector::drill::{self, Drill, DrillSink, DrillState};
+use crypto_index_collector::notification::{ConsoleNotifier, NotificationQueue, WebhookDispatcher};
+use crypto_index_collector::limits::ResourceGuard;
+use crypto_index_collector::health::{FeedHealthRegistry, HealthEvent};
+use crypto_index_collector::market_cap;
+use crypto_index_collector::metrics::{metrics, Subscriber};
 
 /// Crypto Index Collector - Fetches cryptocurrency prices and calculates indices
 #[derive(Parser, Debug)]
@@ -21,34 +37,174 @@ struct Args {
     /// Path to the configuration file
     #[arg(short, long, default_value = "config.toml")]
     config: String,
+
+    /// Write every published index value as an NDJSON line to stdout (logs go to stderr)
+    #[arg(long)]
+    stdout_ndjson: bool,
+
+    /// Also write raw price ticks to stdout in NDJSON mode
+    #[arg(long, requires = "stdout_ndjson")]
+    ndjson_ticks: bool,
+
+    /// Instead of collecting, replay the ticks of an NDJSON file (as written with --ndjson-ticks)
+    /// and write the resulting index values as NDJSON to stdout
+    #[arg(long)]
+    replay: Option<String>,
+
+    /// Replay with a fixed calculation order and decimal arithmetic, so that the series and its
+    /// checksum are exactly reproducible
+    #[arg(long, requires = "replay")]
+    deterministic: bool,
+
+    /// Instead of collecting, write a disaster recovery bundle to this file: the configuration,
+    /// the state of the index calculation and recent raw and index data (needs the database)
+    #[arg(long, conflicts_with = "replay")]
+    export_bundle: Option<String>,
+
+    /// Hours of raw and index data to include in the bundle
+    #[arg(long, default_value_t = 24, requires = "export_bundle")]
+    bundle_hours: u32,
+
+    /// Instead of collecting, recalculate this index from the stored raw prices between --from
+    /// and --to with its current definition, writing the values to recomputed_index_values
+    #[arg(long, requires_all = ["from", "to"], conflicts_with_all = ["replay", "export_bundle"])]
+    recompute: Option<String>,
+
+    /// Start of the recomputed period (RFC 3339)
+    #[arg(long, requires = "recompute")]
+    from: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// End of the recomputed period (RFC 3339)
+    #[arg(long, requires = "recompute")]
+    to: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Write a bundle or recomputation even though stored raw prices do not cover the whole
+    /// period, leaving gaps in the index values; by default gaps are an error
+    #[arg(long)]
+    allow_gaps: bool,
+
+    /// Start from a bundle written with --export-bundle, using its configuration instead of
+    /// --config and continuing its index calculation
+    #[arg(long, conflicts_with_all = ["replay", "export_bundle", "recompute"])]
+    restore_bundle: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Instead of collecting, write the stored raw prices of feeds or values of indices between
+    /// --from and --to as CSV or JSON lines (needs the database)
+    Export(ExportArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct ExportArgs {
+    /// Feed whose raw prices to export; repeat for several feeds
+    #[arg(long, required_unless_present = "index", conflicts_with = "index")]
+    feed: Vec<String>,
+
+    /// Index whose values to export; repeat for several indices
+    #[arg(long)]
+    index: Vec<String>,
+
+    /// Start of the exported period (RFC 3339)
+    #[arg(long)]
+    from: chrono::DateTime<chrono::Utc>,
+
+    /// End of the exported period (RFC 3339), now by default
+    #[arg(long)]
+    to: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// `csv` or `json` (one object per line)
+    #[arg(long, default_value = "csv")]
+    format: ExportFormat,
+
+    /// File to write to instead of stdout
+    #[arg(short, long)]
+    output: Option<String>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Set up logging
-    logging::setup_logging()?;
-
     // Parse command line arguments
     let args = Args::parse();
 
+    // Set up logging, on stderr when stdout carries data
+    let logging = if args.stdout_ndjson || args.replay.is_some() || args.command.is_some() {
+        logging::setup_logging_to_stderr()?
+    } else {
+        logging::setup_logging()?
+    };
+
     info!("[STARTUP] Starting Crypto Index Collector...");
-    info!("[CONFIG] Using configuration file: {}", args.config);
+
+    // A restored collector takes over the configuration and state of the bundled one
+    let bundle = match &args.restore_bundle {
+        Some(path) => {
+            let bundle = Bundle::read(Path::new(path))?;
+            info!("[BUNDLE] Restoring bundle {} created at {} with {} raw prices and {} index values",
+                  path, bundle.manifest.created_at, bundle.raw_prices.len(), bundle.index_values.len());
+            Some(bundle)
+        }
+        None => None,
+    };
 
     // Load configuration
-    let config = config::load_config(&args.config)?;
+    let config = match &bundle {
+        Some(bundle) => {
+            info!("[CONFIG] Using the configuration of the restored bundle");
+            Config::from_toml(&bundle.config).map_err(|e| format!("Invalid configuration in bundle: {}", e))?
+        }
+        None => {
+            info!("[CONFIG] Using configuration file: {}", args.config);
+            config::load_config(&args.config)?
+        }
+    };
 
     info!("[CONFIG] Configuration loaded successfully with {} indices defined", config.indices.len());
 
+    if let Some(path) = &args.replay {
+        let indices = config.to_internal_model()
+            .map_err(|e| format!("Failed to convert configuration to internal model: {}", e))?;
+        return replay_file(path, indices, args.deterministic).await;
+    }
+
+    if let Some(path) = &args.export_bundle {
+        let config_file = tokio::fs::read_to_string(&args.config).await?;
+        return export_bundle(path, &config, config_file, args.bundle_hours, args.allow_gaps).await;
+    }
+
+    if let (Some(index), Some(from), Some(to)) = (&args.recompute, args.from, args.to) {
+        return recompute_index(&config, index, from, to, args.allow_gaps).await;
+    }
+
+    if let Some(Command::Export(export)) = &args.command {
+        return export_records(&config, export).await;
+    }
+
+    // Export the pipeline's spans and the metrics, if configured
+    let telemetry_export = logging.enable_telemetry(&config.telemetry)?;
+
     // Set up database connection if enabled
     let database = if config.database.enabled {
-        Some(Database::new(&config.database.url, true).await?)
+        Some(Database::new(&config.database).await?)
     } else {
         None
     };
 
     // Set up retention policy if database is enabled
     if let Some(db) = &database {
-        db.setup_retention_policy(config.database.retention_days).await?;
+        db.setup_retention_policy(config.database.retention_days, config.database.compress_after_days).await?;
+    }
+
+    // Take over the raw data of the bundled collector
+    if let (Some(bundle), Some(db)) = (&bundle, &database) {
+        for batch in bundle.raw_prices.chunks(config.database.write_batch_size) {
+            db.save_price_batch(batch).await?;
+        }
+        info!("[BUNDLE] Imported {} raw prices", bundle.raw_prices.len());
     }
 
     // Create channel for price updates
@@ -57,41 +213,274 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     // Convert configuration to internal model
     let indices = config.to_internal_model()
         .map_err(|e| format!("Failed to convert configuration to internal model: {}", e))?;
+    let alert_rules = config.alert_rules()
+        .map_err(|e| format!("Failed to convert alert rules to internal model: {}", e))?;
+
+    // Every polled feed with the index (or alert, for reference feeds) consuming it; a feed
+    // shared by several consumers appears once per consumer
+    let feed_consumers = || indices.iter()
+        .flat_map(|index| index.feeds.iter().chain(&index.conversion_feeds).map(|feed| (index.name.as_str(), feed)))
+        .chain(alert_rules.iter().filter_map(|rule| rule.reference_feed().map(|feed| (rule.name.as_str(), feed))));
+    let polled_feeds = || feed_consumers().map(|(_, feed)| feed);
+
+    // Create one adapter per exchange so that all of its feeds share the same client and rate limiter
+    let mut exchanges: HashMap<String, Arc<dyn Exchange>> = HashMap::new();
+    for feed in polled_feeds() {
+        let backup_exchange = feed.backup.as_ref().map(|backup| &backup.exchange);
+        for name in std::iter::once(&feed.exchange).chain(backup_exchange) {
+            if !exchanges.contains_key(name) {
+                let adapter = exchange::create_exchange(name, &config.exchange_config(name))?;
+                exchanges.insert(name.clone(), Arc::from(adapter));
+            }
+        }
+    }
+    let exchanges = Arc::new(exchanges);
+
+    // Feed and index health transitions, for subscribed monitoring clients
+    let (health_tx, _) = broadcast::channel::<HealthEvent>(256);
+
+    // Deliver notifications in the background so they never hold up the calculation or fetch loops
+    let (notifier, _notification_handle) = NotificationQueue::spawn(Box::new(ConsoleNotifier), &config.notifications);
 
     // Create index calculator
     let index_calc = Arc::new(RwLock::new(IndexCalculator::new(
         indices.clone(),
         rx,
-    )));
+    ).with_notifier(notifier.clone())
+        .with_alerts(alert_rules.clone())
+        .with_health_events(health_tx.clone())
+        .with_tick_distributions(
+            chrono::Duration::seconds(config.distribution.window_secs as i64),
+            config.distribution.compression,
+        )));
+
+    // Stamp index values with the version of their definition, bumped on reconfiguration
+    if let Some(db) = &database {
+        let versions = db.register_methodologies(&indices).await?;
+        index_calc.write().await.set_methodology_versions(versions);
+    }
+
+    // Continue the index calculation of the bundled collector, or of the previous run, instead of a cold start
+    let restoring_bundle = bundle.is_some();
+    let checkpoint = match &config.checkpoint.path {
+        Some(path) if bundle.is_none() => index::read_checkpoint(Path::new(path))?,
+        _ => None,
+    };
+    if let Some(bundle) = bundle {
+        index_calc.write().await.restore_state(bundle.state);
+    } else if let Some(state) = checkpoint {
+        info!("[RESTORE] Loaded checkpoint of {} indices and history of {} feeds",
+              state.smoothing.len(), state.feed_history.len());
+        index_calc.write().await.restore_state(state);
+    } else if let Some(db) = &database {
+        let mut feed_ids: Vec<String> = polled_feeds().map(|feed| feed.id.clone()).collect();
+        feed_ids.sort();
+        feed_ids.dedup();
+        let state = db.load_calculator_state(&feed_ids, &indices, index::calculator::MAX_HISTORY_SIZE as i64).await?;
+        info!("[RESTORE] Loaded stored state of {} indices and history of {} feeds",
+              state.smoothing.len(), state.feed_history.len());
+        index_calc.write().await.restore_state(state);
+    }
+    if !restoring_bundle && config.bootstrap.enabled {
+        // Seed smoothing history from exchange candles for feeds without stored history
+        let closes = fetch_bootstrap_closes(&indices, &exchanges, database.as_ref(), config.bootstrap.candles).await;
+        index_calc.write().await.bootstrap_history(&closes);
+    }
 
     // Create a shutdown channel
     let (shutdown_tx, _) = broadcast::channel::<()>(1);
 
+    // Start monitoring the self-imposed resource limits
+    let resources = Arc::new(ResourceGuard::new(config.limits.clone()));
+    let limits_handle = tokio::spawn(resources.clone().run(shutdown_tx.subscribe()));
+
+    // Calculate indices on their publication schedules and broadcast them to all consumers
+    let (index_tx, _) = broadcast::channel::<IndexResult>(256);
+    let (tick_tx, _) = broadcast::channel::<FeedData>(1024);
+    let publisher_handle = tokio::spawn(run_publisher(index_calc.clone(), index_tx.clone(), shutdown_tx.subscribe()));
+
+    // Stream index values (and optionally raw ticks) to stdout for piping
+    let ndjson_handle = args.stdout_ndjson.then(|| {
+        let ticks = args.ndjson_ticks.then(|| Subscriber::new(tick_tx.subscribe(), "tick", "ndjson").with_notifier(notifier.clone()));
+        let indices = Subscriber::new(index_tx.subscribe(), "index", "ndjson").with_notifier(notifier.clone());
+        tokio::spawn(write_ndjson(indices, ticks, shutdown_tx.subscribe()))
+    });
+
+    // Track the health of every polled feed
+    let stale_after = indices.iter().map(|index| index.max_staleness_secs).min().unwrap_or(60);
+    let feed_health = Arc::new(FeedHealthRegistry::new(stale_after)
+        .with_notifier(notifier.clone())
+        .with_events(health_tx.clone()));
+    for feed in polled_feeds() {
+        feed_health.register(&feed.id);
+    }
+
     // Start WebSocket server with shutdown channel
-    let websocket_address = config.websocket.address.clone();
+    let websocket_listeners = config.websocket.all_listeners();
+    let ws_context = websocket::ServerContext {
+        index_updates: index_tx.clone(),
+        index_calc: index_calc.clone(),
+        resources: resources.clone(),
+        feed_health: feed_health.clone(),
+        health_events: health_tx.clone(),
+        raw_ticks: tick_tx.clone(),
+        indices: Arc::new(indices.clone()),
+        database: database.clone(),
+        what_if: Arc::new(websocket::WhatIfLimiter::new(config.websocket.what_if.clone())),
+        notifier: notifier.clone(),
+        legacy_text_frames: config.websocket.legacy_text_frames,
+        allowlist: websocket::Allowlist::new(&config.websocket.allowed_networks, &config.websocket.allowed_origins)?,
+        send_queue_capacity: config.websocket.send_queue_capacity,
+        slow_client_policy: config.websocket.slow_client_policy,
+    };
     let ws_shutdown_rx = shutdown_tx.subscribe();
     let ws_handle = tokio::spawn(async move {
-        if let Err(e) = websocket::start_websocket_server(&websocket_address, index_calc.clone(), ws_shutdown_rx).await {
+        if let Err(e) = websocket::start_websocket_server(&websocket_listeners, ws_context, ws_shutdown_rx).await {
             error!("WebSocket server error: {}", e);
         }
     });
 
-    // Start price feed tasks
-    let mut feed_handles = Vec::new();
+    // Serve the REST API for polling consumers, if configured
+    let http_handle = config.http.address.clone().map(|address| {
+        let latest = Arc::new(LatestIndexValues::default());
+        let updates = Subscriber::new(index_tx.subscribe(), "index", "http:latest");
+        tokio::spawn({
+            let latest = latest.clone();
+            async move { latest.track(updates).await }
+        });
+        let api_context = api::ApiContext {
+            indices: Arc::new(indices.clone()),
+            index_calc: index_calc.clone(),
+            latest,
+            feed_health: feed_health.clone(),
+            database: database.clone(),
+            max_history: chrono::Duration::hours(config.http.max_history_hours as i64),
+        };
+        let shutdown = shutdown_tx.subscribe();
+        tokio::spawn(async move {
+            if let Err(e) = api::start_http_server(&address, api_context, shutdown).await {
+                error!("HTTP API error: {}", e);
+            }
+        })
+    });
 
-    for index in &indices {
-        for feed in &index.feeds {
-            let feed = feed.clone();
-            let tx = tx.clone();
-            let db_clone = database.clone();
-            let feed_shutdown_rx = shutdown_tx.subscribe();
+    // Shared drill state, only ever activated when failover drills are enabled
+    let drill_state = Arc::new(DrillState::new());
 
-            let handle = tokio::spawn(async move {
-                fetch_price_loop(feed, tx, db_clone, feed_shutdown_rx).await;
-            });
+    // Telemetry of every fetch, only consumed when stored
+    let (fetch_tx, _) = broadcast::channel::<FetchMetric>(1024);
+
+    // Store raw prices and index values in the database in batches rather than one round-trip
+    // per record. Prices are queued rather than broadcast, so none are lost to a slow database.
+    let mut price_writer_handle = None;
+    let price_writer = database.clone().map(|db| {
+        let (writer, prices) = PriceWriter::new(config.database.write_batch_size * 2);
+        let values = Subscriber::new(index_tx.subscribe(), "index", "database").with_notifier(notifier.clone());
+        let fetches = config.database.fetch_metrics
+            .then(|| Subscriber::new(fetch_tx.subscribe(), "fetch", "database").with_notifier(notifier.clone()));
+        let settings = SinkSettings::database(&config.database).with_wal(config.storage.wal.as_ref());
+        // Drills take the database down behind the write buffers, so they exercise the real outage handling
+        let sink = Arc::new(DrillSink::new(Arc::new(db), drill_state.clone()));
+        price_writer_handle = Some(tokio::spawn(run_storage_sink(sink, prices, values, fetches, settings, Some(notifier.clone()), shutdown_tx.subscribe())));
+        writer
+    });
 
-            feed_handles.push(handle);
+    // Start price feed tasks, one per feed however many indices consume it
+    let feed_context = FeedTaskContext {
+        exchanges: exchanges.clone(),
+        tx: tx.clone(),
+        price_writer: price_writer.clone(),
+        drill: drill_state.clone(),
+        resources: resources.clone(),
+        feed_health: feed_health.clone(),
+        raw_ticks: tick_tx.clone(),
+        fetch_metrics: fetch_tx.clone(),
+    };
+
+    let mut feed_tasks = FeedTaskManager::default();
+    for (consumer, feed) in feed_consumers() {
+        feed_tasks.spawn(feed, consumer, &feed_context, &shutdown_tx);
+    }
+    feed_tasks.log_shared_feeds();
+    let mut feed_handles = feed_tasks.into_handles();
+    feed_handles.extend(price_writer_handle);
+
+    // Start scheduled failover drills
+    if config.drill.enabled {
+        let mut drills: Vec<Drill> = indices.iter()
+            .flat_map(|index| index.feeds.iter().map(|feed| Drill::feed(&feed.id, feed.backup.is_some())))
+            .collect();
+        drills.sort_by_key(|drill| drill.target.to_string());
+        drills.dedup_by(|a, b| a.target == b.target);
+        if database.is_some() && config.drill.include_database {
+            drills.push(Drill::database(
+                Duration::from_secs(config.drill.duration_secs),
+                Duration::from_secs(config.database.outage_notify_after_secs),
+            ));
         }
+
+        let drill_config = config.drill.clone();
+        let drill_clone = drill_state.clone();
+        let drill_shutdown_rx = shutdown_tx.subscribe();
+        let drill_notifier = notifier.clone();
+
+        feed_handles.push(tokio::spawn(async move {
+            drill::run_drills(drill_config, drill_clone, drills, drill_notifier, drill_shutdown_rx).await;
+        }));
+    }
+
+    // Save smoothing states and divisors so the indices continue across restarts
+    if let Some(db) = &database {
+        feed_handles.push(tokio::spawn(run_state_persistence(index_calc.clone(), db.clone(), shutdown_tx.subscribe())));
+    }
+    // Store raw prices and index values in every other configured sink (files, Redis, ...), each
+    // with its own buffers so one failing sink does not hold up the others
+    for (sink, settings) in storage::configured_sinks(&config)? {
+        let ticks = Subscriber::new(tick_tx.subscribe(), "tick", sink.name()).with_notifier(notifier.clone());
+        let values = Subscriber::new(index_tx.subscribe(), "index", sink.name()).with_notifier(notifier.clone());
+        feed_handles.push(tokio::spawn(run_storage_sink(sink, ticks, values, None::<Subscriber<FetchMetric>>, settings, Some(notifier.clone()), shutdown_tx.subscribe())));
+    }
+    // Send index values to a multicast group, if configured
+    if let Some(multicast) = &config.multicast {
+        let updates = Subscriber::new(index_tx.subscribe(), "index", "multicast").with_notifier(notifier.clone());
+        let publisher = MulticastPublisher::bind(multicast)?;
+        feed_handles.push(tokio::spawn(publisher.run(updates, shutdown_tx.subscribe())));
+    }
+    // Push index updates, or only threshold crossings, to webhooks
+    for (i, webhook) in config.webhooks.iter().enumerate() {
+        let updates = Subscriber::new(index_tx.subscribe(), "index", format!("webhook{}", i)).with_notifier(notifier.clone());
+        let dispatcher = WebhookDispatcher::new(webhook.clone())?;
+        feed_handles.push(tokio::spawn(dispatcher.run(updates, shutdown_tx.subscribe())));
+    }
+    if let Some(path) = &config.checkpoint.path {
+        let interval = Duration::from_secs(config.checkpoint.interval_secs);
+        feed_handles.push(tokio::spawn(index::run_checkpointing(index_calc.clone(), PathBuf::from(path), interval, shutdown_tx.subscribe())));
+    }
+
+    // Keep the weights of market-cap weighted indices following the market
+    let mut market_cap_assets: Vec<String> = indices.iter()
+        .flat_map(|index| index.feeds.iter().filter_map(|feed| feed.market_cap_id.clone()))
+        .collect();
+    market_cap_assets.sort();
+    market_cap_assets.dedup();
+    if !market_cap_assets.is_empty() {
+        let source = market_cap::create_source(&config)?;
+        let refresh = Duration::from_secs(config.market_cap.refresh_secs);
+        info!("[MARKET CAP] Refreshing market caps of {} assets every {}s", market_cap_assets.len(), refresh.as_secs());
+        feed_handles.push(tokio::spawn(market_cap::run_market_cap_updates(
+            source, index_calc.clone(), market_cap_assets, refresh, shutdown_tx.subscribe(),
+        )));
+    }
+
+    // Rebalance indices with a schedule
+    let rebalances: Vec<_> = config.indices.iter()
+        .filter_map(|index| index.rebalance.clone().map(|rebalance| (index.name.clone(), rebalance)))
+        .collect();
+    if !rebalances.is_empty() {
+        info!("[REBALANCE] Rebalancing {} indices on their schedules", rebalances.len());
+        feed_handles.push(tokio::spawn(index::run_rebalancing(
+            index_calc.clone(), rebalances, PathBuf::from(&args.config), shutdown_tx.subscribe(),
+        )));
     }
 
     // Wait for shutdown signal
@@ -109,6 +498,12 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
                 error!("[SHUTDOWN] Error waiting for WebSocket server to shut down: {}", e);
             }
 
+            if let Some(handle) = http_handle {
+                if let Err(e) = handle.await {
+                    error!("[SHUTDOWN] Error waiting for HTTP API to shut down: {}", e);
+                }
+            }
+
             // Wait for all price feed tasks to complete
             for handle in feed_handles {
                 if let Err(e) = handle.await {
@@ -116,6 +511,26 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
                 }
             }
 
+            if let Err(e) = publisher_handle.await {
+                error!("[SHUTDOWN] Error waiting for index publisher to stop: {}", e);
+            }
+
+            if let Some(handle) = ndjson_handle {
+                if let Err(e) = handle.await {
+                    error!("[SHUTDOWN] Error waiting for NDJSON export to stop: {}", e);
+                }
+            }
+
+            if let Err(e) = limits_handle.await {
+                error!("[SHUTDOWN] Error waiting for resource monitor to stop: {}", e);
+            }
+
+            if let Some(export) = telemetry_export {
+                if let Err(e) = tokio::task::spawn_blocking(move || export.shutdown()).await {
+                    error!("[SHUTDOWN] Error waiting for telemetry export to finish: {}", e);
+                }
+            }
+
             info!("[SHUTDOWN] Graceful shutdown complete");
         }
         Err(err) => {
@@ -126,13 +541,360 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     Ok(())
 }
 
-async fn fetch_price_loop(
-    feed: crypto_index_collector::models::PriceFeed,
+/// Write index values and raw ticks as NDJSON lines to stdout until shutdown
+async fn write_ndjson(
+    mut indices: Subscriber<IndexResult>,
+    mut ticks: Option<Subscriber<FeedData>>,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let mut stdout = tokio::io::stdout();
+
+    loop {
+        let record = tokio::select! {
+            index = indices.recv() => index.map(|index| StreamRecord::Index(Box::new(index))),
+            tick = async { ticks.as_mut().unwrap().recv().await }, if ticks.is_some() => tick.map(StreamRecord::Tick),
+            _ = shutdown.recv() => return,
+        };
+
+        let Some(record) = record else {
+            return;
+        };
+
+        let mut line = match WireFormat::Json.encode(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("[NDJSON] Failed to encode record: {}", e);
+                continue;
+            }
+        };
+        line.push(b'\n');
+
+        // A closed pipe (e.g. `| head`) ends the export, not the collector
+        if let Err(e) = async { stdout.write_all(&line).await?; stdout.flush().await }.await {
+            error!("[NDJSON] Failed to write to stdout, stopping export: {}", e);
+            return;
+        }
+    }
+}
+
+/// Replay the ticks recorded in an NDJSON file, writing the index values to stdout and logging
+/// the checksum of the series
+async fn replay_file(path: &str, indices: Vec<IndexDefinition>, deterministic: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let content = tokio::fs::read_to_string(path).await?;
+    let mut ticks = Vec::new();
+    for (number, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        match WireFormat::Json.decode(line.as_bytes()) {
+            Ok(StreamRecord::Tick(tick)) => ticks.push(tick),
+            Ok(StreamRecord::Index(_)) => {}
+            Err(e) => return Err(format!("Invalid record on line {} of {}: {}", number + 1, path, e).into()),
+        }
+    }
+
+    info!("[REPLAY] Replaying {} ticks from {}{}", ticks.len(), path, if deterministic { " (deterministic)" } else { "" });
+    let tick_count = ticks.len();
+    let results = index::replay(indices, ticks, deterministic)?;
+
+    let mut checksum = SeriesChecksum::default();
+    let mut stdout = tokio::io::stdout();
+    for result in results {
+        checksum.update(&result);
+        let mut line = WireFormat::Json.encode(&StreamRecord::Index(Box::new(result)))?;
+        line.push(b'\n');
+        stdout.write_all(&line).await?;
+    }
+    stdout.flush().await?;
+
+    info!("[REPLAY] {} ticks produced {} index values, checksum sha256:{}", tick_count, checksum.count(), checksum.finish());
+    Ok(())
+}
+
+/// Write a disaster recovery bundle of the last `hours` of stored raw data.
+///
+/// Index values and the state of the index calculation are recalculated from the raw data,
+/// starting from the stored divisors, so the bundle can be written by any host with access to
+/// the database while the collector keeps running.
+async fn export_bundle(
+    path: &str,
+    config: &Config,
+    config_file: String,
+    hours: u32,
+    allow_gaps: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !config.database.enabled {
+        return Err("Exporting a bundle needs database persistence to be enabled".into());
+    }
+    let database = Database::new(&config.database).await?;
+    let indices = config.to_internal_model()
+        .map_err(|e| format!("Failed to convert configuration to internal model: {}", e))?;
+
+    let mut feed_ids: Vec<String> = indices.iter()
+        .flat_map(|index| index.feeds.iter().chain(&index.conversion_feeds))
+        .map(|feed| feed.id.clone())
+        .collect();
+    feed_ids.sort();
+    feed_ids.dedup();
+
+    let to = chrono::Utc::now();
+    let from = to - chrono::Duration::hours(hours as i64);
+    // A bundle with gaps would restore a collector with a wrong view of the recent past
+    let max_gap = chrono::Duration::seconds(indices.iter().map(|index| index.max_staleness_secs).max().unwrap_or(0) as i64);
+    let archived = archived_prices("[BUNDLE]", config, &feed_ids, from, to)?;
+    let coverage = database.check_coverage(&feed_ids, from, to, max_gap, config.database.retention_days, &archived).await?;
+    check_coverage("[BUNDLE]", &coverage, allow_gaps)?;
+
+    let raw_prices = with_archived(database.get_price_range(&feed_ids, from, to).await?, archived);
+    let divisors = database.load_divisors().await?;
+    info!("[BUNDLE] Recalculating indices from {} raw prices since {}", raw_prices.len(), from);
+
+    let start = CalculatorState { divisors: divisors.into_iter().collect(), ..Default::default() };
+    let (index_values, state) = index::replay_from(start, indices.clone(), raw_prices.clone(), false)?;
+
+    let names = indices.into_iter().map(|index| index.name).collect();
+    let bundle = Bundle::new(config_file, state, names, from, to, raw_prices, index_values);
+    bundle.write(Path::new(path))?;
+
+    info!("[BUNDLE] Wrote bundle {} with {} raw prices and {} index values",
+          path, bundle.manifest.raw_prices, bundle.manifest.index_values);
+    Ok(())
+}
+
+/// Recalculate an index (and the indices it is built from) from the raw prices stored for
+/// `[from, to]` with its current definition, e.g. to restate history after fixing the
+/// methodology, and store the values apart from the published ones.
+///
+/// Like a bundle export, the calculation starts from the stored divisors.
+async fn recompute_index(
+    config: &Config,
+    name: &str,
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+    allow_gaps: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !config.database.enabled {
+        return Err("Recomputing an index needs database persistence to be enabled".into());
+    }
+    if from >= to {
+        return Err("--from must be before --to".into());
+    }
+    let database = Database::new(&config.database).await?;
+    let all_indices = config.to_internal_model()
+        .map_err(|e| format!("Failed to convert configuration to internal model: {}", e))?;
+    let indices = index::with_constituent_indices(&all_indices, name);
+    let Some(target) = indices.iter().find(|index| index.name == name) else {
+        return Err(format!("Index {} is not configured", name).into());
+    };
+    let fingerprint = target.methodology_fingerprint();
+
+    let mut feed_ids: Vec<String> = indices.iter()
+        .flat_map(|index| index.feeds.iter().chain(&index.conversion_feeds))
+        .map(|feed| feed.id.clone())
+        .collect();
+    feed_ids.sort();
+    feed_ids.dedup();
+
+    // Gaps in the raw data would leave gaps in the recomputed series
+    let max_gap = chrono::Duration::seconds(target.max_staleness_secs as i64);
+    let archived = archived_prices("[RECOMPUTE]", config, &feed_ids, from, to)?;
+    let coverage = database.check_coverage(&feed_ids, from, to, max_gap, config.database.retention_days, &archived).await?;
+    check_coverage("[RECOMPUTE]", &coverage, allow_gaps)?;
+
+    let raw_prices = with_archived(database.get_price_range(&feed_ids, from, to).await?, archived);
+    info!("[RECOMPUTE] Recalculating index {} from {} raw prices between {} and {}", name, raw_prices.len(), from, to);
+    let divisors = database.load_divisors().await?;
+    let start = CalculatorState { divisors: divisors.into_iter().collect(), ..Default::default() };
+    let (results, _) = index::replay_from(start, indices, raw_prices, false)?;
+    let values: Vec<IndexResult> = results.into_iter().filter(|result| result.name == name).collect();
+
+    let recomputed_at = chrono::Utc::now();
+    database.save_recomputed_index_values(&values, &fingerprint, recomputed_at).await?;
+    info!("[RECOMPUTE] Stored {} values of index {} with methodology {} as recomputation {}",
+          values.len(), name, fingerprint, recomputed_at);
+    Ok(())
+}
+
+/// Raw prices of `[from, to]` archived by the file sink, if one is configured, to fill in what
+/// the database no longer holds
+fn archived_prices(
+    prefix: &str,
+    config: &Config,
+    feed_ids: &[String],
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+) -> AppResult<Vec<FeedData>> {
+    let Some(file) = &config.storage.file else {
+        return Ok(Vec::new());
+    };
+    let archived = FileSink::new(&file.directory, file.format).read_raw_prices(feed_ids, from, to)?;
+    if !archived.is_empty() {
+        info!("{} Read {} archived raw prices from {}", prefix, archived.len(), file.directory);
+    }
+    Ok(archived)
+}
+
+/// Stored raw prices along with the archived ones, oldest first, each price once
+fn with_archived(stored: Vec<FeedData>, archived: Vec<FeedData>) -> Vec<FeedData> {
+    if archived.is_empty() {
+        return stored;
+    }
+    let mut prices = stored;
+    prices.extend(archived);
+    prices.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.feed_id.cmp(&b.feed_id)));
+    prices.dedup_by(|a, b| a.timestamp == b.timestamp && a.feed_id == b.feed_id);
+    prices
+}
+
+/// Fail on gaps in the coverage of stored raw prices, unless they are allowed, in which case
+/// they are only logged
+fn check_coverage(prefix: &str, coverage: &CoverageReport, allow_gaps: bool) -> AppResult<()> {
+    if allow_gaps && !coverage.is_complete() {
+        warn!("{} {}", prefix, coverage);
+        return Ok(());
+    }
+    coverage.ensure_complete()
+}
+
+/// Write stored raw prices or index values to stdout or a file, streamed from the database so
+/// exports of any length run in constant memory
+async fn export_records(config: &Config, args: &ExportArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !config.database.enabled {
+        return Err("Exporting needs database persistence to be enabled".into());
+    }
+    let to = args.to.unwrap_or_else(chrono::Utc::now);
+    if args.from >= to {
+        return Err("--from must be before --to".into());
+    }
+    let database = Database::new(&config.database).await?;
+    let out: Box<dyn std::io::Write> = match &args.output {
+        Some(path) => Box::new(std::fs::File::create(path)?),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut writer = ExportWriter::new(std::io::BufWriter::new(out), args.format);
+
+    if args.index.is_empty() {
+        let mut prices = database.stream_price_range(&args.feed, args.from, to);
+        while let Some(price) = prices.next().await {
+            writer.write(&price?)?;
+        }
+    } else {
+        let mut values = database.stream_index_values(&args.index, args.from, to);
+        while let Some(value) = values.next().await {
+            writer.write(&value?)?;
+        }
+    }
+
+    let rows = writer.finish()?;
+    info!("[EXPORT] Wrote {} records between {} and {} to {}", rows, args.from, to, args.output.as_deref().unwrap_or("stdout"));
+    Ok(())
+}
+
+/// Fetch recent candle closes for every feed without stored history
+async fn fetch_bootstrap_closes(
+    indices: &[IndexDefinition],
+    exchanges: &HashMap<String, Arc<dyn Exchange>>,
+    database: Option<&Database>,
+    candles: usize,
+) -> HashMap<String, Vec<f64>> {
+    let mut closes = HashMap::new();
+
+    for feed in indices.iter().flat_map(|index| index.feeds.iter().chain(&index.conversion_feeds)) {
+        if closes.contains_key(&feed.id) {
+            continue;
+        }
+
+        // Only bootstrap on a first-ever start, stored history takes precedence
+        if let Some(db) = database {
+            match db.get_recent_prices(&feed.id, 1).await {
+                Ok(prices) if !prices.is_empty() => {
+                    info!("[BOOTSTRAP] Feed {} has stored history, skipping candle bootstrap", feed.id);
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) => warn!("[BOOTSTRAP] Failed to check stored history for {}: {}", feed.id, e),
+            }
+        }
+
+        match exchanges[&feed.exchange].fetch_recent_closes(&feed.symbol, candles).await {
+            Ok(series) => {
+                info!("[BOOTSTRAP] Fetched {} candle closes for feed {}", series.len(), feed.id);
+                closes.insert(feed.id.clone(), series);
+            }
+            Err(e) => warn!("[BOOTSTRAP] Failed to fetch candles for feed {}: {}", feed.id, e),
+        }
+    }
+
+    closes
+}
+
+/// Fetch loops keyed by market (exchange and symbol), so a feed shared by several indices, or
+/// several feed ids for the same market, are polled once.
+///
+/// Every update goes to the index calculator once per feed id and is fanned out to all indices
+/// referencing the feed there, as they read the same latest feed values.
+#[derive(Default)]
+struct FeedTaskManager {
+    tasks: HashMap<(String, String), JoinHandle<()>>,
+    /// Indices (or alerts) consuming each feed
+    consumers: HashMap<String, Vec<String>>,
+}
+
+impl FeedTaskManager {
+    /// Start polling a feed for a consumer, unless it is polled already
+    fn spawn(&mut self, feed: &PriceFeed, consumer: &str, context: &FeedTaskContext, shutdown: &broadcast::Sender<()>) {
+        let consumers = self.consumers.entry(feed.id.clone()).or_default();
+        if !consumers.iter().any(|existing| existing == consumer) {
+            consumers.push(consumer.to_string());
+        }
+
+        // Aliases of an already polled feed are served by its task
+        let market = (feed.exchange.clone(), feed.symbol.clone());
+        if self.tasks.contains_key(&market) {
+            return;
+        }
+
+        let feed = feed.clone();
+        let context = context.clone();
+        let shutdown = shutdown.subscribe();
+        self.tasks.insert(market, tokio::spawn(async move {
+            fetch_price_loop(feed, context, shutdown).await;
+        }));
+    }
+
+    fn log_shared_feeds(&self) {
+        for (feed_id, consumers) in &self.consumers {
+            if consumers.len() > 1 {
+                info!("[STARTUP] Feed {} is polled once for {}", feed_id, consumers.join(", "));
+            }
+        }
+        info!("[STARTUP] Started {} price feed tasks", self.tasks.len());
+    }
+
+    fn into_handles(self) -> Vec<JoinHandle<()>> {
+        self.tasks.into_values().collect()
+    }
+}
+
+/// Shared state of the price feed tasks
+#[derive(Clone)]
+struct FeedTaskContext {
+    exchanges: Arc<HashMap<String, Arc<dyn Exchange>>>,
     tx: mpsc::Sender<FeedData>,
-    database: Option<Database>,
+    price_writer: Option<PriceWriter>,
+    drill: Arc<DrillState>,
+    resources: Arc<ResourceGuard>,
+    feed_health: Arc<FeedHealthRegistry>,
+    raw_ticks: broadcast::Sender<FeedData>,
+    fetch_metrics: broadcast::Sender<FetchMetric>,
+}
+
+async fn fetch_price_loop(
+    feed: PriceFeed,
+    context: FeedTaskContext,
     mut shutdown: broadcast::Receiver<()>,
 ) {
-    let mut consecutive_failures = 0;
+    let FeedTaskContext { exchanges, tx, price_writer, drill, resources, feed_health, raw_ticks, fetch_metrics } = context;
+    let mut failover = Failover::new(feed.backup.as_ref());
+    let mut last_price = None;
+    let mut change_filter = ChangeFilter::new(feed.heartbeat_secs);
 
     loop {
         // Check for shutdown signal
@@ -140,63 +902,98 @@ async fn fetch_price_loop(
             info!("[SHUTDOWN] Received shutdown signal in price feed loop for {}", feed.id);
             return;
         }
-        match fetch_price(&feed).await {
-            Ok(price) => {
-                consecutive_failures = 0;
 
-                let timestamp = chrono::Utc::now();
-                let feed_data = FeedData {
-                    feed_id: feed.id.clone(),
-                    timestamp,
-                    price,
-                };
+        // Low-priority feeds stop polling while the collector is over its resource limits
+        if resources.should_pause(feed.priority) {
+            metrics().increment("limits.feed_polls_paused");
+            feed.ids().for_each(|id| feed_health.check_stale(id));
+            tokio::time::sleep(resources.pause_interval()).await;
+            continue;
+        }
+
+        // Price of this poll and the backup feed it came from, if any
+        let mut polled = None;
+        // Failed-over feeds go straight to their backup instead of waiting for the primary's retries
+        let mut poll_backup = failover.source() == FeedSource::Backup;
 
-                info!("[RAW DATA] Exchange: {}, Symbol: {}, Price: {}, Time: {}",
-                      feed.exchange, feed.symbol, price, timestamp);
+        if !poll_backup {
+            let result = fetch_from(&exchanges, &drill, &fetch_metrics, &feed.id, &feed.exchange, &feed.symbol).await
+                .and_then(|price| check_bounds(&feed, price, last_price));
 
-                // Save to database if enabled
-                if let Some(db) = &database {
-                    if let Err(e) = db.save_price_data(&feed_data).await {
-                        error!("Failed to save price data to database: {}", e);
+            match result {
+                Ok(price) => {
+                    if failover.primary_succeeded() {
+                        info!("[FAILOVER] Feed {} switched back to primary exchange {}", feed.id, feed.exchange);
+                        metrics().increment("feeds.failbacks");
+                    }
+                    last_price = Some(price);
+                    feed.ids().for_each(|id| feed_health.record_success(id, false));
+                    polled = Some((price, None));
+                }
+                Err(e) => {
+                    poll_backup = failover.primary_failed();
+                    let consecutive_failures = failover.consecutive_failures();
+
+                    if consecutive_failures >= 5 {
+                        // Don't let the tick-change bound lock a feed out forever after a genuine jump
+                        last_price = None;
+                        warn!(
+                            "[EXCHANGE ERROR] Failed to fetch price from {} for {} {} times consecutively: {}",
+                            feed.exchange, feed.symbol, consecutive_failures, e
+                        );
                     } else {
-                        info!("[DATABASE] Saved price data for feed: {}", feed_data.feed_id);
+                        error!("[EXCHANGE ERROR] Failed to fetch price from {} for {}: {}",
+                               feed.exchange, feed.symbol, e);
                     }
                 }
+            }
+        }
 
-                // Store feed_id before sending feed_data since send() moves the value
-                let feed_id = feed_data.feed_id.clone();
-
-                match tx.send(feed_data).await {
-                    Ok(_) => {
-                        info!("[INTERNAL] Sent price update for feed: {} to index calculator", feed_id);
-                    },
-                    Err(e) => {
-                        if e.to_string().contains("channel closed") {
-                            warn!("[CHANNEL] Channel to index calculator closed. This is normal during shutdown.");
-                            // During normal shutdown, the receiver might be dropped
-                            // We can continue running to collect data for the database
-                            if database.is_none() {
-                                // If no database is configured, there's no point in continuing
-                                info!("[SHUTDOWN] No database configured and channel closed. Exiting feed loop.");
-                                return;
-                            }
-                        } else {
-                            error!("Failed to send price update: {}", e);
-                        }
+        // Keep the index publishing from the backup source while the primary is down
+        if let Some(backup) = feed.backup.as_ref().filter(|_| poll_backup) {
+            let backup_result = fetch_from(&exchanges, &drill, &fetch_metrics, &backup.feed_id, &backup.exchange, &backup.symbol).await
+                .and_then(|price| check_bounds(&feed, price, last_price));
+            match backup_result {
+                Ok(price) => {
+                    last_price = Some(price);
+                    if failover.backup_succeeded() {
+                        warn!("[FAILOVER] Feed {} switched to backup {} on {} after {} consecutive failures",
+                              feed.id, backup.feed_id, backup.exchange, failover.consecutive_failures());
+                        metrics().increment("feeds.failovers");
                     }
+                    feed.ids().for_each(|id| feed_health.record_success(id, true));
+                    polled = Some((price, Some(backup.feed_id.clone())));
+                }
+                Err(e) => {
+                    failover.backup_failed();
+                    error!("[FAILOVER] Backup {} of feed {} failed: {}", backup.feed_id, feed.id, e);
                 }
             }
-            Err(e) => {
-                consecutive_failures += 1;
-
-                if consecutive_failures >= 5 {
-                    warn!(
-                        "[EXCHANGE ERROR] Failed to fetch price from {} for {} {} times consecutively: {}",
-                        feed.exchange, feed.symbol, consecutive_failures, e
-                    );
-                } else {
-                    error!("[EXCHANGE ERROR] Failed to fetch price from {} for {}: {}",
-                           feed.exchange, feed.symbol, e);
+        }
+
+        if polled.is_none() {
+            feed.ids().for_each(|id| feed_health.record_failure(id));
+        }
+
+        if let Some((price, backup_feed)) = polled {
+            match change_filter.classify(price, backup_feed.as_deref(), tokio::time::Instant::now()) {
+                Forward::Skip => metrics().increment("feeds.unchanged_skipped"),
+                forward => {
+                    // Feeds for the same market under other ids get the same observation
+                    let timestamp = chrono::Utc::now();
+                    for feed_id in feed.ids() {
+                        let feed_data = FeedData {
+                            feed_id: feed_id.clone(),
+                            timestamp,
+                            price,
+                            backup_feed: backup_feed.clone(),
+                            heartbeat: matches!(forward, Forward::Heartbeat),
+                            denomination: Some(feed.denomination.clone()),
+                        };
+                        if !forward_price(&feed, feed_data, &tx, price_writer.as_ref(), &raw_ticks).await {
+                            return;
+                        }
+                    }
                 }
             }
         }
@@ -206,17 +1003,102 @@ async fn fetch_price_loop(
     }
 }
 
-async fn fetch_price(feed: &crypto_index_collector::models::PriceFeed) -> Result<f64, Box<dyn Error + Send + Sync>> {
-    // Get the exchange implementation
-    let exchange = exchange::create_exchange(&feed.exchange)
-        .ok_or_else(|| format!("Unsupported exchange: {}", feed.exchange))?;
+/// Fetch a price from an exchange, unless a failover drill simulates an outage of the feed, and
+/// publish the telemetry of the fetch
+async fn fetch_from(
+    exchanges: &HashMap<String, Arc<dyn Exchange>>,
+    drill: &DrillState,
+    fetch_metrics: &broadcast::Sender<FetchMetric>,
+    feed_id: &str,
+    exchange: &str,
+    symbol: &str,
+) -> AppResult<f64> {
+    if drill.is_feed_disabled(feed_id) {
+        drill.record_simulated_failure();
+        return Err("Simulated outage (failover drill)".into());
+    }
 
-    // Fetch the price
-    let price = exchange.fetch_price(&feed.symbol).await?;
+    let started_at = chrono::Utc::now();
+    let span = info_span!("exchange.fetch", feed_id, exchange, symbol);
+    let (result, stats) = telemetry::measure(exchanges[exchange].fetch_price(symbol)).instrument(span).await;
+    metrics().set_gauge(&format!("feeds.{}.fetch_latency_ms", feed_id), stats.latency.as_secs_f64() * 1000.0);
+    logging::record_stage(PipelineStage::Fetch, stats.latency.as_secs_f64() * 1000.0);
+    metrics().increment_by("exchange.retries", stats.retries as u64);
+    // Only consumed when fetch telemetry is stored, nobody listening is fine
+    let _ = fetch_metrics.send(FetchMetric::new(feed_id, exchange, started_at, &stats, result.is_err()));
+    result
+}
 
-    // The price is fetched directly from the exchange using the appropriate symbol format
+/// Reject prices outside the feed's sanity bounds
+fn check_bounds(feed: &PriceFeed, price: f64, last_price: Option<f64>) -> AppResult<f64> {
+    feed.bounds.check(price, last_price)
+        .map(|_| price)
+        .map_err(|reason| {
+            metrics().increment("feeds.rejected_prices");
+            AppError::Exchange(format!("Rejected price {} for feed {}: {}", price, feed.id, reason))
+        })
+}
+
+/// Save a fetched price and pass it on to the index calculator.
+///
+/// Heartbeats only refresh the feed's update time downstream and are neither stored nor
+/// exported as ticks. Returns `false` when the feed loop should stop because nothing consumes
+/// its prices anymore.
+async fn forward_price(
+    feed: &PriceFeed,
+    feed_data: FeedData,
+    tx: &mpsc::Sender<FeedData>,
+    price_writer: Option<&PriceWriter>,
+    raw_ticks: &broadcast::Sender<FeedData>,
+) -> bool {
+    let (exchange, symbol) = match feed.backup.as_ref().filter(|_| feed_data.backup_feed.is_some()) {
+        Some(backup) => (&backup.exchange, &backup.symbol),
+        None => (&feed.exchange, &feed.symbol),
+    };
+
+    if feed_data.heartbeat {
+        debug!("[HEARTBEAT] Exchange: {}, Symbol: {}, Price unchanged: {}", exchange, symbol, feed_data.price);
+    } else {
+        info!("[RAW DATA] Exchange: {}, Symbol: {}, Price: {}, Time: {}",
+              exchange, symbol, feed_data.price, feed_data.timestamp);
+    }
+
+    // Queue for the database if enabled
+    if let Some(writer) = price_writer.filter(|_| !feed_data.heartbeat) {
+        if !writer.queue(feed_data.clone()).await {
+            error!("Failed to queue price data for the database: the price writer has stopped");
+        }
+    }
+
+    // Only consumed when raw ticks are exported or streamed to WebSocket clients, nobody listening is fine
+    if !feed_data.heartbeat {
+        let _ = raw_ticks.send(feed_data.clone());
+    }
+
+    // Store feed_id before sending feed_data since send() moves the value
+    let feed_id = feed_data.feed_id.clone();
+
+    match tx.send(feed_data).await {
+        Ok(_) => {
+            info!("[INTERNAL] Sent price update for feed: {} to index calculator", feed_id);
+        },
+        Err(e) => {
+            if e.to_string().contains("channel closed") {
+                warn!("[CHANNEL] Channel to index calculator closed. This is normal during shutdown.");
+                // During normal shutdown, the receiver might be dropped
+                // We can continue running to collect data for the database
+                if price_writer.is_none() {
+                    // If no database is configured, there's no point in continuing
+                    info!("[SHUTDOWN] No database configured and channel closed. Exiting feed loop.");
+                    return false;
+                }
+            } else {
+                error!("Failed to send price update: {}", e);
+            }
+        }
+    }
 
-    Ok(price)
+    true
 }
 
 // Removed unused function
@@ -1,39 +1,84 @@
 use std::error::Error;
-use std::process::{Command, Stdio};
+use std::process::{ExitStatus, Stdio};
 use std::time::{Duration, Instant};
 use clap::Parser;
+use tokio::net::TcpStream;
+use tokio::process::Command;
 use tokio::time;
 use tracing::{info, error, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
-use crypto_index_collector::notification::{Notifier, ConsoleNotifier, ScriptNotifier};
+use crypto_index_collector::config::{HealthProbeConfig, NotificationRoutingConfig, NotificationTarget, SupervisorConfig};
+use crypto_index_collector::notification::{Notifier, ConsoleNotifier, ScriptNotifier, RoutingNotifier, NotificationQueue};
 use crypto_index_collector::notification::sender::Severity;
 
+#[cfg(test)]
+#[path = "supervisor/tests.rs"]
+mod tests;
+
 /// Supervisor for Crypto Index Collector - Monitors and automatically restarts the main application
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Maximum number of restarts in the monitoring period before giving up
-    #[arg(long, default_value_t = 5)]
-    max_restarts: u32,
+    /// Path to the supervisor configuration file; flags below override its values
+    #[arg(short, long)]
+    config: Option<String>,
+
+    /// Maximum number of restarts in the monitoring period before giving up [default: 5]
+    #[arg(long)]
+    max_restarts: Option<u32>,
 
-    /// Monitoring period in minutes
-    #[arg(long, default_value_t = 10)]
-    monitoring_period_minutes: u64,
+    /// Monitoring period in minutes [default: 10]
+    #[arg(long)]
+    monitoring_period_minutes: Option<u64>,
 
-    /// Initial delay before restarting after a failure (in seconds)
-    #[arg(long, default_value_t = 5)]
-    initial_restart_delay: u64,
+    /// Initial delay before restarting after a failure (in seconds) [default: 5]
+    #[arg(long)]
+    initial_restart_delay: Option<u64>,
 
-    /// Maximum delay between restarts (in seconds)
-    #[arg(long, default_value_t = 60)]
-    max_restart_delay: u64,
+    /// Maximum delay between restarts (in seconds) [default: 60]
+    #[arg(long)]
+    max_restart_delay: Option<u64>,
 
     /// Path to the notification script (if any)
     #[arg(long)]
     notification_script: Option<String>,
 }
 
+impl Args {
+    /// Load the configuration file (if any) and apply command-line overrides
+    fn into_config(self) -> Result<SupervisorConfig, Box<dyn Error + Send + Sync>> {
+        let mut config = match &self.config {
+            Some(path) => SupervisorConfig::from_file(path)?,
+            None => SupervisorConfig::default(),
+        };
+
+        if let Some(max_restarts) = self.max_restarts {
+            config.restart.max_restarts = max_restarts;
+        }
+        if let Some(period) = self.monitoring_period_minutes {
+            config.restart.monitoring_period_minutes = period;
+        }
+        if let Some(delay) = self.initial_restart_delay {
+            config.restart.initial_delay_secs = delay;
+        }
+        if let Some(delay) = self.max_restart_delay {
+            config.restart.max_delay_secs = delay;
+        }
+        if let Some(script) = self.notification_script {
+            config.notifications.script = Some(script);
+        }
+
+        Ok(config)
+    }
+}
+
+/// Why the child process stopped
+enum ChildOutcome {
+    Exited(ExitStatus),
+    Unhealthy,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     // Setup logging
@@ -42,22 +87,23 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         .finish();
     tracing::subscriber::set_global_default(subscriber)?;
 
-    // Parse command line arguments
-    let args = Args::parse();
-    
-    // Create notifier
-    let notifier: Box<dyn Notifier> = match &args.notification_script {
-        Some(script) => Box::new(ScriptNotifier::new(script.clone())),
-        None => Box::new(ConsoleNotifier),
-    };
-    
+    // Parse command line arguments and merge them into the configuration
+    let config = Args::parse().into_config()?;
+    let restart = &config.restart;
+
+    // Deliver notifications in the background so a slow script never delays a restart
+    let (notifier, notification_handle) = NotificationQueue::spawn(
+        create_notifier(&config.notifications),
+        &config.notifications.delivery,
+    );
+
     info!("[SUPERVISOR] Starting Crypto Index Collector supervisor");
-    
+
     // Track restart attempts
     let mut restart_count = 0;
     let monitoring_start = Instant::now();
-    let monitoring_period = Duration::from_secs(args.monitoring_period_minutes * 60);
-    
+    let monitoring_period = Duration::from_secs(restart.monitoring_period_minutes * 60);
+
     loop {
         // Reset restart count if monitoring period has elapsed
         if monitoring_start.elapsed() > monitoring_period {
@@ -66,25 +112,22 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
             }
             restart_count = 0;
         }
-        
+
         // Check if we've exceeded the maximum number of restarts
-        if restart_count >= args.max_restarts {
-            error!("[SUPERVISOR] Exceeded maximum number of restarts ({}) within monitoring period. Giving up.", args.max_restarts);
-            notifier.notify(Severity::Critical, "Crypto Index Collector failed to start after multiple attempts")?;
+        if restart_count >= restart.max_restarts {
+            error!("[SUPERVISOR] Exceeded maximum number of restarts ({}) within monitoring period. Giving up.", restart.max_restarts);
+            notifier.send(Severity::Critical, "Crypto Index Collector failed to start after multiple attempts");
+            // Wait for pending notifications before exiting
+            drop(notifier);
+            let _ = notification_handle.await;
             return Err("Too many restart attempts".into());
         }
-        
+
         // Start the main application
         info!("[SUPERVISOR] Starting Crypto Index Collector");
-        
-        let status = Command::new("cargo")
-            .args(["run", "--bin", "crypto-index-collector"])
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .status();
-        
-        match status {
-            Ok(exit_status) => {
+
+        match run_child(&config).await {
+            Ok(ChildOutcome::Exited(exit_status)) => {
                 if exit_status.success() {
                     info!("[SUPERVISOR] Crypto Index Collector exited normally");
                     // If the application exited normally, we're done
@@ -94,47 +137,131 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
                     restart_count += 1;
                     let exit_code = exit_status.code().unwrap_or(-1);
                     warn!("[SUPERVISOR] Crypto Index Collector failed with exit code: {}", exit_code);
-                    
+
                     // Calculate backoff delay
-                    let delay = calculate_backoff_delay(restart_count, args.initial_restart_delay, args.max_restart_delay);
-                    
+                    let delay = calculate_backoff_delay(restart_count, restart.initial_delay_secs, restart.max_delay_secs);
+
                     // Send notification about the restart
                     let message = format!(
                         "Crypto Index Collector crashed with exit code {}. Restarting in {} seconds (attempt {}/{})",
-                        exit_code, delay, restart_count, args.max_restarts
+                        exit_code, delay, restart_count, restart.max_restarts
                     );
-                    notifier.notify(Severity::Warning, &message)?;
-                    
-                    info!("[SUPERVISOR] Restarting in {} seconds (attempt {}/{})", 
-                          delay, restart_count, args.max_restarts);
+                    notifier.send(Severity::Warning, message);
+
+                    info!("[SUPERVISOR] Restarting in {} seconds (attempt {}/{})",
+                          delay, restart_count, restart.max_restarts);
                     time::sleep(Duration::from_secs(delay)).await;
                 }
             },
+            Ok(ChildOutcome::Unhealthy) => {
+                // Application stopped responding to health probes and was killed
+                restart_count += 1;
+                let delay = calculate_backoff_delay(restart_count, restart.initial_delay_secs, restart.max_delay_secs);
+
+                let message = format!(
+                    "Crypto Index Collector failed {} consecutive health probes on {}. Restarting in {} seconds (attempt {}/{})",
+                    config.health_probe.failure_threshold, config.health_probe.address, delay, restart_count, restart.max_restarts
+                );
+                notifier.send(Severity::Error, message);
+
+                info!("[SUPERVISOR] Restarting in {} seconds (attempt {}/{})",
+                      delay, restart_count, restart.max_restarts);
+                time::sleep(Duration::from_secs(delay)).await;
+            },
             Err(e) => {
                 // Failed to start the application
                 restart_count += 1;
                 error!("[SUPERVISOR] Failed to start Crypto Index Collector: {}", e);
-                
+
                 // Calculate backoff delay
-                let delay = calculate_backoff_delay(restart_count, args.initial_restart_delay, args.max_restart_delay);
-                
+                let delay = calculate_backoff_delay(restart_count, restart.initial_delay_secs, restart.max_delay_secs);
+
                 // Send notification about the restart
                 let message = format!(
                     "Failed to start Crypto Index Collector: {}. Retrying in {} seconds (attempt {}/{})",
-                    e, delay, restart_count, args.max_restarts
+                    e, delay, restart_count, restart.max_restarts
                 );
-                notifier.notify(Severity::Error, &message)?;
-                
-                info!("[SUPERVISOR] Retrying in {} seconds (attempt {}/{})", 
-                      delay, restart_count, args.max_restarts);
+                notifier.send(Severity::Error, message);
+
+                info!("[SUPERVISOR] Retrying in {} seconds (attempt {}/{})",
+                      delay, restart_count, restart.max_restarts);
                 time::sleep(Duration::from_secs(delay)).await;
             }
         }
     }
-    
+
+    drop(notifier);
+    let _ = notification_handle.await;
     Ok(())
 }
 
+/// Build the notifier routing each severity to the console or the notification script
+fn create_notifier(config: &NotificationRoutingConfig) -> Box<dyn Notifier> {
+    let target_notifier = |target: NotificationTarget| -> Box<dyn Notifier> {
+        match (target, &config.script) {
+            (NotificationTarget::Script, Some(script)) => Box::new(ScriptNotifier::new(script.clone())),
+            _ => Box::new(ConsoleNotifier),
+        }
+    };
+
+    let fallback = if config.script.is_some() { NotificationTarget::Script } else { NotificationTarget::Console };
+    let mut notifier = RoutingNotifier::new(target_notifier(fallback));
+    for (&severity, &target) in &config.routes {
+        notifier = notifier.route(severity, target_notifier(target));
+    }
+
+    Box::new(notifier)
+}
+
+/// Run the child process until it exits or fails its health probe
+async fn run_child(config: &SupervisorConfig) -> std::io::Result<ChildOutcome> {
+    let mut child = Command::new(&config.child.command)
+        .args(&config.child.args)
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    if !config.health_probe.enabled {
+        return Ok(ChildOutcome::Exited(child.wait().await?));
+    }
+
+    tokio::select! {
+        status = child.wait() => Ok(ChildOutcome::Exited(status?)),
+        _ = wait_until_unhealthy(&config.health_probe) => {
+            warn!("[SUPERVISOR] Health probe failed {} times in a row, killing Crypto Index Collector",
+                  config.health_probe.failure_threshold);
+            child.kill().await?;
+            Ok(ChildOutcome::Unhealthy)
+        }
+    }
+}
+
+/// Probe the child's address periodically, returning once it failed too many times in a row
+async fn wait_until_unhealthy(probe: &HealthProbeConfig) {
+    time::sleep(Duration::from_secs(probe.startup_grace_secs)).await;
+
+    let mut consecutive_failures = 0;
+    let mut interval = time::interval(Duration::from_secs(probe.interval_secs.max(1)));
+
+    loop {
+        interval.tick().await;
+
+        let connect = time::timeout(Duration::from_millis(probe.timeout_ms), TcpStream::connect(&probe.address)).await;
+        if matches!(connect, Ok(Ok(_))) {
+            consecutive_failures = 0;
+            continue;
+        }
+
+        consecutive_failures += 1;
+        warn!("[SUPERVISOR] Health probe to {} failed ({}/{})",
+              probe.address, consecutive_failures, probe.failure_threshold);
+        if consecutive_failures >= probe.failure_threshold {
+            return;
+        }
+    }
+}
+
 fn calculate_backoff_delay(attempts: u32, base_delay: u64, max_delay: u64) -> u64 {
     // Exponential backoff with a maximum delay
     let delay = base_delay * (1 << attempts.saturating_sub(1));
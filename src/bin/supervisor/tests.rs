@@ -0,0 +1,108 @@
+use std::time::Duration;
+use clap::Parser;
+use tokio::net::TcpListener;
+use tokio::time;
+
+use super::*;
+
+fn probe(address: String, failure_threshold: u32) -> HealthProbeConfig {
+    HealthProbeConfig {
+        enabled: true,
+        address,
+        interval_secs: 1,
+        timeout_ms: 200,
+        failure_threshold,
+        startup_grace_secs: 0,
+    }
+}
+
+#[cfg(test)]
+mod args_tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_without_config_file_or_flags() {
+        let config = Args::parse_from(["crypto-index-supervisor"]).into_config().unwrap();
+
+        assert_eq!(config.restart.max_restarts, 5);
+        assert_eq!(config.restart.monitoring_period_minutes, 10);
+        assert_eq!(config.restart.initial_delay_secs, 5);
+        assert_eq!(config.restart.max_delay_secs, 60);
+        assert!(config.notifications.script.is_none());
+    }
+
+    #[test]
+    fn test_flags_override_the_config_file() {
+        let path = std::env::temp_dir().join(format!("supervisor-args-{}.toml", std::process::id()));
+        std::fs::write(&path, r#"
+            [restart]
+            max_restarts = 8
+            monitoring_period_minutes = 30
+            initial_delay_secs = 2
+
+            [notifications]
+            script = "/usr/local/bin/page.sh"
+        "#).unwrap();
+
+        let config = Args::parse_from([
+            "crypto-index-supervisor",
+            "--config", path.to_str().unwrap(),
+            "--max-restarts", "3",
+            "--max-restart-delay", "120",
+        ]).into_config();
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        // Overridden by flags
+        assert_eq!(config.restart.max_restarts, 3);
+        assert_eq!(config.restart.max_delay_secs, 120);
+        // From the file
+        assert_eq!(config.restart.monitoring_period_minutes, 30);
+        assert_eq!(config.restart.initial_delay_secs, 2);
+        assert_eq!(config.notifications.script.as_deref(), Some("/usr/local/bin/page.sh"));
+    }
+
+    #[test]
+    fn test_missing_config_file_is_an_error() {
+        let args = Args::parse_from(["crypto-index-supervisor", "--config", "/nonexistent/supervisor.toml"]);
+        assert!(args.into_config().is_err());
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_maximum() {
+        let delays: Vec<u64> = (1..=6).map(|attempt| calculate_backoff_delay(attempt, 5, 60)).collect();
+        assert_eq!(delays, [5, 10, 20, 40, 60, 60]);
+    }
+}
+
+#[cfg(test)]
+mod health_probe_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unreachable_child_is_unhealthy_after_the_threshold() {
+        // Nothing listens on the port once the listener is dropped
+        let address = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().to_string();
+
+        let started = time::Instant::now();
+        time::timeout(Duration::from_secs(5), wait_until_unhealthy(&probe(address, 2))).await
+            .expect("unreachable child not detected");
+        // The first probe is immediate, the second one an interval later
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_listening_child_stays_healthy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let accept = tokio::spawn(async move {
+            loop {
+                let _ = listener.accept().await;
+            }
+        });
+
+        let result = time::timeout(Duration::from_millis(2500), wait_until_unhealthy(&probe(address, 1))).await;
+        accept.abort();
+        assert!(result.is_err(), "healthy child reported unhealthy");
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::error::AppResult;
+
+/// Write named files into a gzipped tar archive at `path`
+pub fn write(path: &Path, files: &[(&str, Vec<u8>)]) -> AppResult<()> {
+    let encoder = GzEncoder::new(File::create(path)?, Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+        header.set_cksum();
+        archive.append_data(&mut header, name, content.as_slice())?;
+    }
+
+    archive.into_inner()?.finish()?.sync_all()?;
+    Ok(())
+}
+
+/// Files of a gzipped tar archive by name
+pub fn read(path: &Path) -> AppResult<HashMap<String, Vec<u8>>> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    let mut files = HashMap::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        files.insert(name, content);
+    }
+
+    Ok(files)
+}
@@ -0,0 +1,120 @@
+mod archive;
+pub(crate) mod tables;
+
+#[cfg(test)]
+mod tests;
+
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppResult;
+use crate::index::{CalculatorState, IndexResult};
+use crate::models::FeedData;
+
+/// Version of the bundle layout, bumped whenever a file changes incompatibly
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const CONFIG_FILE: &str = "config.toml";
+const STATE_FILE: &str = "state.json";
+const RAW_PRICES_FILE: &str = "raw_prices.parquet";
+const INDEX_VALUES_FILE: &str = "index_values.parquet";
+
+/// Everything a standby collector needs to take over from the primary: its configuration, the
+/// state of the index calculation and recent raw and index data.
+///
+/// Written as a gzipped tar archive of `manifest.json`, `config.toml`, `state.json`,
+/// `raw_prices.parquet` and `index_values.parquet`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    pub manifest: BundleManifest,
+    /// Configuration file the collector ran with
+    pub config: String,
+    pub state: CalculatorState,
+    /// Raw prices of the covered period, oldest first
+    pub raw_prices: Vec<FeedData>,
+    /// Index values of the covered period, in publication order
+    pub index_values: Vec<IndexResult>,
+}
+
+/// Description of a bundle, readable without decoding the data files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub created_at: DateTime<Utc>,
+    /// Start of the covered period
+    pub from: DateTime<Utc>,
+    /// End of the covered period
+    pub to: DateTime<Utc>,
+    /// Names of the configured indices
+    pub indices: Vec<String>,
+    pub raw_prices: usize,
+    pub index_values: usize,
+}
+
+impl Bundle {
+    pub fn new(
+        config: String,
+        state: CalculatorState,
+        indices: Vec<String>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        raw_prices: Vec<FeedData>,
+        index_values: Vec<IndexResult>,
+    ) -> Self {
+        Self {
+            manifest: BundleManifest {
+                format_version: BUNDLE_FORMAT_VERSION,
+                created_at: Utc::now(),
+                from,
+                to,
+                indices,
+                raw_prices: raw_prices.len(),
+                index_values: index_values.len(),
+            },
+            config,
+            state,
+            raw_prices,
+            index_values,
+        }
+    }
+
+    /// Write the bundle to `path`, replacing any existing file only once it is complete
+    pub fn write(&self, path: &Path) -> AppResult<()> {
+        let files = [
+            (MANIFEST_FILE, serde_json::to_vec_pretty(&self.manifest)?),
+            (CONFIG_FILE, self.config.clone().into_bytes()),
+            (STATE_FILE, serde_json::to_vec_pretty(&self.state)?),
+            (RAW_PRICES_FILE, tables::write_raw_prices(&self.raw_prices)?),
+            (INDEX_VALUES_FILE, tables::write_index_values(&self.index_values)?),
+        ];
+
+        let partial = path.with_extension("partial");
+        archive::write(&partial, &files)?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    /// Read a bundle written by [`Bundle::write`]
+    pub fn read(path: &Path) -> AppResult<Self> {
+        let mut files = archive::read(path)?;
+        let mut take = |name: &str| files.remove(name)
+            .ok_or_else(|| format!("Bundle {} has no {}", path.display(), name));
+
+        let manifest: BundleManifest = serde_json::from_slice(&take(MANIFEST_FILE)?)?;
+        if manifest.format_version != BUNDLE_FORMAT_VERSION {
+            return Err(format!("Bundle {} has format version {}, expected {}",
+                               path.display(), manifest.format_version, BUNDLE_FORMAT_VERSION).into());
+        }
+
+        Ok(Self {
+            config: String::from_utf8(take(CONFIG_FILE)?)
+                .map_err(|e| format!("Bundle {} has an invalid {}: {}", path.display(), CONFIG_FILE, e))?,
+            state: serde_json::from_slice(&take(STATE_FILE)?)?,
+            raw_prices: tables::read_raw_prices(take(RAW_PRICES_FILE)?)?,
+            index_values: tables::read_index_values(take(INDEX_VALUES_FILE)?)?,
+            manifest,
+        })
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+use arrow_array::{Array, Float64Array, RecordBatch, StringArray, TimestampMillisecondArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+
+use crate::error::{AppError, AppResult};
+use crate::index::IndexResult;
+use crate::models::FeedData;
+
+fn timestamp_field() -> Field {
+    Field::new("timestamp", DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())), false)
+}
+
+/// Raw prices as a Parquet table of `feed_id`, `timestamp` and `price`
+pub fn write_raw_prices(prices: &[FeedData]) -> AppResult<Vec<u8>> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("feed_id", DataType::Utf8, false),
+        timestamp_field(),
+        Field::new("price", DataType::Float64, false),
+    ]));
+    let batch = RecordBatch::try_new(schema.clone(), vec![
+        Arc::new(StringArray::from_iter_values(prices.iter().map(|price| price.feed_id.as_str()))),
+        Arc::new(timestamps(prices.iter().map(|price| price.timestamp))),
+        Arc::new(Float64Array::from_iter_values(prices.iter().map(|price| price.price))),
+    ])?;
+    write_table(schema, batch)
+}
+
+/// Index values as a Parquet table of `name`, `timestamp`, `value`, `epoch` and the nullable
+/// `raw_value`
+pub fn write_index_values(values: &[IndexResult]) -> AppResult<Vec<u8>> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("name", DataType::Utf8, false),
+        timestamp_field(),
+        Field::new("value", DataType::Float64, false),
+        Field::new("epoch", DataType::UInt64, false),
+        Field::new("raw_value", DataType::Float64, true),
+    ]));
+    let batch = RecordBatch::try_new(schema.clone(), vec![
+        Arc::new(StringArray::from_iter_values(values.iter().map(|value| value.name.as_str()))),
+        Arc::new(timestamps(values.iter().map(|value| value.timestamp))),
+        Arc::new(Float64Array::from_iter_values(values.iter().map(|value| value.value))),
+        Arc::new(UInt64Array::from_iter_values(values.iter().map(|value| value.epoch))),
+        Arc::new(values.iter().map(|value| value.raw_value).collect::<Float64Array>()),
+    ])?;
+    write_table(schema, batch)
+}
+
+/// Raw prices of a table written by [`write_raw_prices`]
+pub fn read_raw_prices(table: Vec<u8>) -> AppResult<Vec<FeedData>> {
+    let mut prices = Vec::new();
+    for batch in read_table(table)? {
+        let feed_ids: &StringArray = column(&batch, "feed_id")?;
+        let timestamps: &TimestampMillisecondArray = column(&batch, "timestamp")?;
+        let values: &Float64Array = column(&batch, "price")?;
+        for row in 0..batch.num_rows() {
+            prices.push(FeedData {
+                feed_id: feed_ids.value(row).to_string(),
+                timestamp: timestamp(timestamps.value(row))?,
+                price: values.value(row),
+                backup_feed: None,
+                heartbeat: false,
+                denomination: None,
+            });
+        }
+    }
+    Ok(prices)
+}
+
+/// Index values of a table written by [`write_index_values`], without denomination, quality,
+/// change and methodology version
+pub fn read_index_values(table: Vec<u8>) -> AppResult<Vec<IndexResult>> {
+    let mut values = Vec::new();
+    for batch in read_table(table)? {
+        let names: &StringArray = column(&batch, "name")?;
+        let timestamps: &TimestampMillisecondArray = column(&batch, "timestamp")?;
+        let index_values: &Float64Array = column(&batch, "value")?;
+        let epochs: &UInt64Array = column(&batch, "epoch")?;
+        // Absent from bundles written before raw values were recorded
+        let raw_values: Option<&Float64Array> = column(&batch, "raw_value").ok();
+        for row in 0..batch.num_rows() {
+            values.push(IndexResult {
+                name: names.value(row).to_string(),
+                timestamp: timestamp(timestamps.value(row))?,
+                value: index_values.value(row),
+                raw_value: raw_values.filter(|raw_values| raw_values.is_valid(row)).map(|raw_values| raw_values.value(row)),
+                epoch: epochs.value(row),
+                denomination: Default::default(),
+                quality: Default::default(),
+                change: Default::default(),
+                methodology_version: 0,
+                warming_up: false,
+            });
+        }
+    }
+    Ok(values)
+}
+
+fn timestamps(values: impl Iterator<Item = DateTime<Utc>>) -> TimestampMillisecondArray {
+    TimestampMillisecondArray::from_iter_values(values.map(|timestamp| timestamp.timestamp_millis())).with_timezone("UTC")
+}
+
+fn timestamp(millis: i64) -> AppResult<DateTime<Utc>> {
+    DateTime::from_timestamp_millis(millis)
+        .ok_or_else(|| AppError::Serialization(format!("Timestamp {}ms is out of range", millis)))
+}
+
+fn write_table(schema: Arc<Schema>, batch: RecordBatch) -> AppResult<Vec<u8>> {
+    let mut table = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut table, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(table)
+}
+
+fn read_table(table: Vec<u8>) -> AppResult<Vec<RecordBatch>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(table))?.build()?;
+    Ok(reader.collect::<Result<_, _>>()?)
+}
+
+/// Column of a table by name, of the type it was written with
+fn column<'a, T: Array + 'static>(batch: &'a RecordBatch, name: &str) -> AppResult<&'a T> {
+    batch.column_by_name(name)
+        .and_then(|column| column.as_any().downcast_ref::<T>())
+        .ok_or_else(|| AppError::Serialization(format!("Table has no column {} of the expected type", name)))
+}
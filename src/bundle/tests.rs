@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+use chrono::{Duration, TimeZone, Utc};
+
+use super::Bundle;
+use crate::index::{CalculatorState, IndexResult, SmoothingSnapshot};
+use crate::models::{FeedData, SmoothingType};
+
+#[cfg(test)]
+mod bundle_tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_round_trips_through_an_archive() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let tick = |feed_id: &str, secs: i64, price: f64| FeedData {
+            feed_id: feed_id.to_string(),
+            timestamp: start + Duration::seconds(secs),
+            price,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        };
+        let value = IndexResult {
+            name: "BTC-USD-INDEX".to_string(),
+            timestamp: start + Duration::seconds(1),
+            value: 60100.5,
+            epoch: 7,
+            denomination: Default::default(),
+            quality: Default::default(),
+            change: Default::default(),
+            methodology_version: 0,
+            raw_value: Some(60090.0),
+            warming_up: false,
+        };
+        let state = CalculatorState {
+            smoothing: BTreeMap::from([("BTC-USD-INDEX".to_string(), SmoothingSnapshot {
+                smoothing: SmoothingType::Ema,
+                state: vec![60090.0],
+                history: vec![60090.0, 60080.0],
+            })]),
+            divisors: BTreeMap::new(),
+            feed_history: BTreeMap::from([("a".to_string(), vec![60000.0])]),
+            returns: BTreeMap::new(),
+            index_history: BTreeMap::new(),
+        };
+        let bundle = Bundle::new(
+            "[feeds]\n".to_string(),
+            state,
+            vec!["BTC-USD-INDEX".to_string()],
+            start,
+            start + Duration::hours(1),
+            vec![tick("a", 0, 60000.0), tick("b", 1, 60201.0)],
+            vec![value],
+        );
+
+        let dir = std::env::temp_dir().join(format!("bundle-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("standby.tar.gz");
+        bundle.write(&path).unwrap();
+        let read = Bundle::read(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(read.unwrap(), bundle);
+    }
+}
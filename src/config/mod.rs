@@ -1,6 +1,13 @@
 mod models;
+mod supervisor;
 
-pub use models::{Config, DatabaseConfig, WebsocketConfig};
+#[cfg(test)]
+mod tests;
+
+pub use models::{Config, DatabaseConfig, StorageConfig, FileStorageConfig, FileFormat, RedisStorageConfig, RedisBroadcastConfig, KafkaStorageConfig, KafkaFormat, KafkaIndexConfig, KafkaIndexKey, MqttStorageConfig, WalConfig, WebsocketConfig, WhatIfConfig, SlowClientPolicy, ListenerConfig, TlsConfig, HttpApiConfig, MulticastConfig, TelemetryConfig, Environment, DrillConfig, LimitsConfig, BootstrapConfig, CheckpointConfig, ExchangeConfig, RateLimitConfig, RetryConfig, HttpClientConfig, ProxyConfig, ResponseCacheConfig, CredentialsConfig, LatestCacheConfig, AlertConfig, AlertReferenceConfig, MarketCapConfig, DistributionConfig, NotificationDeliveryConfig, WebhookConfig, RebalanceConfig, RebalanceSchedule};
+pub use supervisor::{
+    SupervisorConfig, ChildConfig, RestartPolicyConfig, HealthProbeConfig, NotificationRoutingConfig, NotificationTarget,
+};
 
 use crate::error::AppResult;
 use std::path::Path;
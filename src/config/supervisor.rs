@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::notification::Severity;
+use super::NotificationDeliveryConfig;
+
+/// Configuration of the supervisor process
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SupervisorConfig {
+    #[serde(default)]
+    pub child: ChildConfig,
+    #[serde(default)]
+    pub restart: RestartPolicyConfig,
+    #[serde(default)]
+    pub health_probe: HealthProbeConfig,
+    #[serde(default)]
+    pub notifications: NotificationRoutingConfig,
+}
+
+impl SupervisorConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let content = fs::read_to_string(path)?;
+        let config: SupervisorConfig = toml::from_str(&content)?;
+
+        if config.child.command.is_empty() {
+            return Err("Supervisor child command must not be empty".into());
+        }
+
+        config.notifications.delivery.validate()?;
+
+        Ok(config)
+    }
+}
+
+/// The supervised process
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChildConfig {
+    #[serde(default = "default_child_command")]
+    pub command: String,
+    #[serde(default = "default_child_args")]
+    pub args: Vec<String>,
+}
+
+impl Default for ChildConfig {
+    fn default() -> Self {
+        Self {
+            command: default_child_command(),
+            args: default_child_args(),
+        }
+    }
+}
+
+fn default_child_command() -> String {
+    "cargo".to_string()
+}
+
+fn default_child_args() -> Vec<String> {
+    vec!["run".to_string(), "--bin".to_string(), "crypto-index-collector".to_string()]
+}
+
+/// Restart limits and backoff
+#[derive(Debug, Clone, Deserialize)]
+pub struct RestartPolicyConfig {
+    /// Maximum number of restarts in the monitoring period before giving up
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
+    #[serde(default = "default_monitoring_period_minutes")]
+    pub monitoring_period_minutes: u64,
+    /// Initial delay before restarting after a failure (in seconds)
+    #[serde(default = "default_initial_restart_delay")]
+    pub initial_delay_secs: u64,
+    /// Maximum delay between restarts (in seconds)
+    #[serde(default = "default_max_restart_delay")]
+    pub max_delay_secs: u64,
+}
+
+impl Default for RestartPolicyConfig {
+    fn default() -> Self {
+        Self {
+            max_restarts: default_max_restarts(),
+            monitoring_period_minutes: default_monitoring_period_minutes(),
+            initial_delay_secs: default_initial_restart_delay(),
+            max_delay_secs: default_max_restart_delay(),
+        }
+    }
+}
+
+fn default_max_restarts() -> u32 {
+    5
+}
+
+fn default_monitoring_period_minutes() -> u64 {
+    10
+}
+
+fn default_initial_restart_delay() -> u64 {
+    5
+}
+
+fn default_max_restart_delay() -> u64 {
+    60
+}
+
+/// TCP probe of the child's listening address; the child is restarted after repeated failures
+#[derive(Debug, Clone, Deserialize)]
+pub struct HealthProbeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address the child listens on, e.g. the collector's WebSocket address
+    #[serde(default = "default_probe_address")]
+    pub address: String,
+    #[serde(default = "default_probe_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_probe_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Consecutive failed probes before the child is restarted
+    #[serde(default = "default_probe_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Grace period after start before the first probe
+    #[serde(default = "default_probe_startup_grace_secs")]
+    pub startup_grace_secs: u64,
+}
+
+impl Default for HealthProbeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: default_probe_address(),
+            interval_secs: default_probe_interval_secs(),
+            timeout_ms: default_probe_timeout_ms(),
+            failure_threshold: default_probe_failure_threshold(),
+            startup_grace_secs: default_probe_startup_grace_secs(),
+        }
+    }
+}
+
+fn default_probe_address() -> String {
+    "127.0.0.1:9000".to_string()
+}
+
+fn default_probe_interval_secs() -> u64 {
+    30
+}
+
+fn default_probe_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_probe_failure_threshold() -> u32 {
+    3
+}
+
+fn default_probe_startup_grace_secs() -> u64 {
+    60
+}
+
+/// Where notifications of each severity are delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationTarget {
+    Console,
+    Script,
+}
+
+/// Notification delivery, routed by severity
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NotificationRoutingConfig {
+    /// Path to the notification script (if any)
+    #[serde(default)]
+    pub script: Option<String>,
+    /// Per-severity target; severities without a route go to the script if one is set, else the console
+    #[serde(default)]
+    pub routes: HashMap<Severity, NotificationTarget>,
+    /// Queueing, batching and retries of deliveries
+    #[serde(default)]
+    pub delivery: NotificationDeliveryConfig,
+}
@@ -0,0 +1,567 @@
+use super::Config;
+
+#[cfg(test)]
+mod duplicate_market_tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [feeds]
+        coinbase_btc = { exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }
+        cb_btc_usd = { exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }
+        binance_btc = { exchange = "binance", base_currency = "BTC", quote_currency = "USD" }
+
+        [[indices]]
+        name = "BTC-USD-INDEX"
+        smoothing = "none"
+        feeds = [{ id = "coinbase_btc", weight = 50 }, { id = "binance_btc", weight = 50 }]
+
+        [[indices]]
+        name = "BTC-USD-ALT"
+        smoothing = "none"
+        feeds = [{ id = "cb_btc_usd", weight = 100 }]
+    "#;
+
+    #[test]
+    fn test_feeds_for_the_same_market_are_aliases() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+
+        assert_eq!(config.duplicate_markets(), vec![(
+            "coinbase".to_string(), "BTC-USD".to_string(), vec!["cb_btc_usd".to_string(), "coinbase_btc".to_string()],
+        )]);
+
+        let indices = config.to_internal_model().unwrap();
+        assert_eq!(indices[0].feeds[0].aliases, vec!["cb_btc_usd".to_string()]);
+        assert!(indices[0].feeds[1].aliases.is_empty());
+        assert_eq!(indices[1].feeds[0].aliases, vec!["coinbase_btc".to_string()]);
+    }
+
+    #[test]
+    fn test_feeds_no_index_consumes_are_not_aliases() {
+        let config: Config = toml::from_str(r#"
+            [feeds]
+            coinbase_btc = { exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }
+            cb_btc_usd = { exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }
+
+            [[indices]]
+            name = "BTC-USD-INDEX"
+            smoothing = "none"
+            feeds = [{ id = "coinbase_btc", weight = 100 }]
+        "#).unwrap();
+
+        assert!(config.duplicate_markets().is_empty());
+        assert!(config.to_internal_model().unwrap()[0].feeds[0].aliases.is_empty());
+    }
+
+    #[test]
+    fn test_aliases_disagreeing_on_polling_settings_are_rejected() {
+        let settings = [
+            ("priority = \"low\"", "differ in priority;"),
+            ("max_price = 100000.0", "differ in min_price, max_price or max_tick_change_pct;"),
+            ("change_only = true", "differ in change_only or heartbeat_secs;"),
+            ("decimals = 2", "differ in decimals;"),
+        ];
+        for (setting, difference) in settings {
+            let result = Config::from_toml(&CONFIG.replace(
+                "cb_btc_usd = { exchange = \"coinbase\",",
+                &format!("cb_btc_usd = {{ {}, exchange = \"coinbase\",", setting),
+            ));
+            let err = result.err().unwrap_or_else(|| panic!("{} accepted", setting)).to_string();
+            assert!(err.contains("cb_btc_usd and coinbase_btc both poll BTC-USD on coinbase"), "{}", err);
+            assert!(err.contains(difference), "{}", err);
+        }
+        assert!(Config::from_toml(CONFIG).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod denomination_tests {
+    use super::*;
+
+    #[test]
+    fn test_index_denomination_comes_from_name_and_feeds() {
+        let config: Config = toml::from_str(r#"
+            [feeds]
+            kraken_btc_eur = { exchange = "kraken", base_currency = "BTC", quote_currency = "EUR", decimals = 1 }
+            coinbase_btc_eur = { exchange = "coinbase", base_currency = "BTC", quote_currency = "EUR", decimals = 2 }
+
+            [[indices]]
+            name = "BTC-EUR-INDEX"
+            smoothing = "none"
+            feeds = [{ id = "kraken_btc_eur", weight = 50 }, { id = "coinbase_btc_eur", weight = 50 }]
+        "#).unwrap();
+
+        let index = &config.to_internal_model().unwrap()[0];
+        assert_eq!(index.feeds[0].denomination.decimals, 1);
+        assert_eq!(
+            (index.denomination.base_currency.as_str(), index.denomination.quote_currency.as_str(), index.denomination.decimals),
+            ("BTC", "EUR", 2),
+        );
+    }
+}
+
+#[cfg(test)]
+mod conversion_tests {
+    use super::*;
+
+    /// BTC-USD index with a BTC-EUR feed, given the rate feeds and the feed's reference
+    fn btc_usd_config(rate_feeds: &str, feed_ref: &str) -> Config {
+        toml::from_str(&format!(r#"
+            [feeds]
+            coinbase_btc_usd = {{ exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }}
+            kraken_btc_eur = {{ exchange = "kraken", base_currency = "BTC", quote_currency = "EUR" }}
+            {}
+
+            [[indices]]
+            name = "BTC-USD-INDEX"
+            smoothing = "none"
+            feeds = [{{ id = "coinbase_btc_usd", weight = 50 }}, {} ]
+        "#, rate_feeds, feed_ref)).unwrap()
+    }
+
+    fn conversion(config: &Config) -> Option<(String, bool)> {
+        let index = &config.to_internal_model().unwrap()[0];
+        assert!(index.feeds[0].conversion.is_none());
+        index.feeds[1].conversion.as_ref().map(|conversion| (conversion.feed_id.clone(), conversion.invert))
+    }
+
+    #[test]
+    fn test_direct_pair_multiplies_by_the_rate() {
+        let config = btc_usd_config(
+            r#"kraken_eur_usd = { exchange = "kraken", base_currency = "EUR", quote_currency = "USD" }"#,
+            r#"{ id = "kraken_btc_eur", weight = 50 }"#,
+        );
+
+        assert_eq!(conversion(&config), Some(("kraken_eur_usd".to_string(), false)));
+        let index = &config.to_internal_model().unwrap()[0];
+        assert_eq!(index.conversion_feeds.iter().map(|feed| feed.id.as_str()).collect::<Vec<_>>(), ["kraken_eur_usd"]);
+    }
+
+    #[test]
+    fn test_inverted_pair_divides_by_the_rate() {
+        let config = btc_usd_config(
+            r#"kraken_usd_eur = { exchange = "kraken", base_currency = "USD", quote_currency = "EUR" }"#,
+            r#"{ id = "kraken_btc_eur", weight = 50, conversion = "kraken_usd_eur" }"#,
+        );
+
+        assert_eq!(conversion(&config), Some(("kraken_usd_eur".to_string(), true)));
+    }
+
+    #[test]
+    fn test_direct_pair_is_preferred_over_an_inverted_one() {
+        let config = btc_usd_config(
+            r#"a_usd_eur = { exchange = "kraken", base_currency = "USD", quote_currency = "EUR" }
+            z_eur_usd = { exchange = "coinbase", base_currency = "EUR", quote_currency = "USD" }"#,
+            r#"{ id = "kraken_btc_eur", weight = 50 }"#,
+        );
+
+        assert_eq!(conversion(&config), Some(("z_eur_usd".to_string(), false)));
+    }
+
+    #[test]
+    fn test_missing_leg_is_rejected() {
+        let config = btc_usd_config(
+            r#"kraken_eur_gbp = { exchange = "kraken", base_currency = "EUR", quote_currency = "GBP" }"#,
+            r#"{ id = "kraken_btc_eur", weight = 50 }"#,
+        );
+        let error = config.to_internal_model().unwrap_err();
+        assert!(error.contains("no conversion feed for EUR-USD is configured"), "{}", error);
+
+        let config = btc_usd_config(
+            r#"kraken_eur_gbp = { exchange = "kraken", base_currency = "EUR", quote_currency = "GBP" }"#,
+            r#"{ id = "kraken_btc_eur", weight = 50, conversion = "kraken_eur_gbp" }"#,
+        );
+        let error = config.to_internal_model().unwrap_err();
+        assert!(error.contains("cannot convert feed 'kraken_btc_eur' from 'EUR' to 'USD'"), "{}", error);
+    }
+}
+
+#[cfg(test)]
+mod composite_tests {
+    use super::*;
+
+    #[test]
+    fn test_composite_cycles_are_rejected() {
+        let path = std::env::temp_dir().join(format!("composite-cycle-{}.toml", std::process::id()));
+        std::fs::write(&path, r#"
+            [feeds]
+            coinbase_btc = { exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }
+
+            [[indices]]
+            name = "BTC-USD-INDEX"
+            smoothing = "none"
+            feeds = [{ id = "coinbase_btc", weight = 100 }]
+
+            [[indices]]
+            name = "A-USD-INDEX"
+            smoothing = "none"
+            components = [{ index = "BTC-USD-INDEX", weight = 50 }, { index = "B-USD-INDEX", weight = 50 }]
+
+            [[indices]]
+            name = "B-USD-INDEX"
+            smoothing = "none"
+            components = [{ index = "A-USD-INDEX", weight = 100 }]
+        "#).unwrap();
+
+        let error = Config::from_file(&path).unwrap_err().to_string();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.contains("A-USD-INDEX depends on itself"), "{}", error);
+    }
+}
+
+#[cfg(test)]
+mod derived_tests {
+    use super::*;
+
+    const FEEDS: &str = r#"
+        [feeds]
+        coinbase_btc = { exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }
+        coinbase_eth = { exchange = "coinbase", base_currency = "ETH", quote_currency = "USD" }
+        kraken_eth_btc = { exchange = "kraken", base_currency = "ETH", quote_currency = "BTC" }
+
+        [[indices]]
+        name = "BTC-USD-INDEX"
+        smoothing = "none"
+        feeds = [{ id = "coinbase_btc", weight = 100 }]
+    "#;
+
+    #[test]
+    fn test_derived_index_fetches_referenced_feeds() {
+        let config = Config::from_toml(&format!(r#"{}
+            [[indices]]
+            name = "ETH-BTC-INDEX"
+            smoothing = "none"
+            expression = "(coinbase_eth / BTC-USD-INDEX + kraken_eth_btc) / 2"
+        "#, FEEDS)).unwrap();
+
+        let indices = config.to_internal_model().unwrap();
+        let derived = indices.iter().find(|index| index.name == "ETH-BTC-INDEX").unwrap();
+        let fetched: Vec<&str> = derived.conversion_feeds.iter().map(|feed| feed.id.as_str()).collect();
+        assert_eq!(fetched, vec!["coinbase_eth", "kraken_eth_btc"]);
+        assert_eq!(derived.constituent_names(), vec!["coinbase_eth", "BTC-USD-INDEX", "kraken_eth_btc"]);
+    }
+
+    #[test]
+    fn test_invalid_derived_indices_are_rejected() {
+        let error = |index: &str| Config::from_toml(&format!("{}\n{}", FEEDS, index)).unwrap_err().to_string();
+
+        assert!(error(r#"
+            [[indices]]
+            name = "ETH-BTC-INDEX"
+            smoothing = "none"
+            expression = "ETH-USD-INDEX / BTC-USD-INDEX"
+        "#).contains("'ETH-USD-INDEX' in the expression of index 'ETH-BTC-INDEX' is neither an index nor a feed"));
+        assert!(error(r#"
+            [[indices]]
+            name = "ETH-BTC-INDEX"
+            smoothing = "none"
+            expression = "coinbase_eth / BTC-USD-INDEX"
+            feeds = [{ id = "kraken_eth_btc", weight = 100 }]
+        "#).contains("must have either feeds, component indices or an expression"));
+        assert!(error(r#"
+            [[indices]]
+            name = "ETH-BTC-INDEX"
+            smoothing = "none"
+            expression = "coinbase_eth / (BTC-USD-INDEX"
+        "#).contains("unclosed parenthesis"));
+    }
+}
+
+#[cfg(test)]
+mod transform_tests {
+    use super::*;
+
+    #[test]
+    fn test_inverse_index_prices_the_swapped_pair() {
+        let config = |name: &str| Config::from_toml(&format!(r#"
+            [feeds]
+            coinbase_btc = {{ exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }}
+
+            [[indices]]
+            name = "{}"
+            smoothing = "none"
+            transform = "inverse"
+            scale = 100000000
+            feeds = [{{ id = "coinbase_btc", weight = 100 }}]
+        "#, name));
+
+        let indices = config("USD-BTC-INDEX").unwrap().to_internal_model().unwrap();
+        assert_eq!(indices[0].transformed(40000.0), Some(2500.0));
+        assert_eq!(indices[0].denomination.quote_currency, "BTC");
+
+        let error = config("BTC-USD-INDEX").unwrap_err().to_string();
+        assert!(error.contains("cannot be used in index 'BTC-USD-INDEX' with base currency 'USD'"), "{}", error);
+    }
+}
+
+#[cfg(test)]
+mod database_tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_settings_default_and_are_validated() {
+        let config = |database: &str| Config::from_toml(&format!(r#"
+            [database]
+            {}
+
+            [feeds]
+            coinbase_btc = {{ exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }}
+
+            [[indices]]
+            name = "BTC-USD-INDEX"
+            smoothing = "none"
+            feeds = [{{ id = "coinbase_btc", weight = 100 }}]
+        "#, database));
+
+        let database = config("").unwrap().database;
+        assert_eq!((database.max_connections, database.min_connections, database.acquire_timeout_ms), (5, 0, 30_000));
+        assert_eq!(database.statement_timeout_ms, None);
+        assert_eq!(database.application_name, "crypto-index-collector");
+
+        let database = config("max_connections = 20\nstatement_timeout_ms = 5000\napplication_name = \"collector-eu\"").unwrap().database;
+        assert_eq!((database.max_connections, database.statement_timeout_ms), (20, Some(5000)));
+        assert_eq!(database.application_name, "collector-eu");
+
+        assert!(config("max_connections = 2\nmin_connections = 3").unwrap_err().to_string().contains("max_connections"));
+        assert!(config("statement_timeout_ms = 0").unwrap_err().to_string().contains("statement_timeout_ms"));
+    }
+}
+
+#[cfg(test)]
+mod websocket_tests {
+    use super::*;
+
+    #[test]
+    fn test_listeners_are_validated() {
+        let config = |listeners: &str| Config::from_toml(&format!(r#"
+            [websocket]
+            address = "127.0.0.1:9000"
+
+            {}
+
+            [feeds]
+            coinbase_btc = {{ exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }}
+
+            [[indices]]
+            name = "BTC-USD-INDEX"
+            smoothing = "none"
+            feeds = [{{ id = "coinbase_btc", weight = 100 }}]
+        "#, listeners));
+
+        let websocket = config(r#"
+            [[websocket.listeners]]
+            address = "0.0.0.0:9443"
+            tls = { cert_path = "/etc/collector/cert.pem", key_path = "/etc/collector/key.pem" }
+
+            [[websocket.listeners]]
+            unix_socket = "/run/collector/ws.sock"
+        "#).unwrap().websocket;
+        let listeners = websocket.all_listeners();
+        assert_eq!(listeners.len(), 3);
+        assert_eq!(listeners[0].address.as_deref(), Some("127.0.0.1:9000"));
+        assert!(listeners[1].tls.is_some());
+        assert_eq!(listeners[2].unix_socket.as_deref(), Some("/run/collector/ws.sock"));
+
+        let error = config("[[websocket.listeners]]\ntls = { cert_path = \"c\", key_path = \"k\" }").unwrap_err().to_string();
+        assert!(error.contains("either an address or a unix_socket"), "{}", error);
+        let error = config("[[websocket.listeners]]\naddress = \"0.0.0.0:9001\"\nunix_socket = \"/tmp/ws.sock\"").unwrap_err().to_string();
+        assert!(error.contains("either an address or a unix_socket"), "{}", error);
+        let error = config("[[websocket.listeners]]\nunix_socket = \"/tmp/ws.sock\"\ntls = { cert_path = \"c\", key_path = \"k\" }").unwrap_err().to_string();
+        assert!(error.contains("tls is only supported"), "{}", error);
+    }
+}
+
+#[cfg(test)]
+mod storage_tests {
+    use super::*;
+    use super::super::{KafkaFormat, KafkaIndexKey};
+
+    #[test]
+    fn test_kafka_index_producer_is_configured_separately() {
+        let config = |storage: &str| Config::from_toml(&format!(r#"
+            {}
+
+            [feeds]
+            coinbase_btc = {{ exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }}
+
+            [[indices]]
+            name = "BTC-USD-INDEX"
+            smoothing = "none"
+            feeds = [{{ id = "coinbase_btc", weight = 100 }}]
+        "#, storage));
+
+        let storage = config(r#"
+            [storage.kafka_indices]
+            brokers = ["kafka-1:9092"]
+            topic = "index-levels"
+            format = "avro"
+            schema_id = 12
+            key = "quote_currency"
+        "#).unwrap().storage;
+        assert!(storage.kafka.is_none());
+        let kafka = storage.kafka_indices.unwrap();
+        assert_eq!((kafka.producer.topic.as_str(), kafka.producer.format, kafka.producer.schema_id), ("index-levels", KafkaFormat::Avro, Some(12)));
+        assert_eq!(kafka.key, KafkaIndexKey::QuoteCurrency);
+
+        let kafka = config("[storage.kafka_indices]\nbrokers = [\"kafka-1:9092\"]\ntopic = \"index-levels\"").unwrap().storage.kafka_indices.unwrap();
+        assert_eq!(kafka.key, KafkaIndexKey::Index);
+
+        let error = config("[storage.kafka_indices]\nbrokers = [\"kafka-1:9092\"]\ntopic = \"index-levels\"\nformat = \"avro\"").unwrap_err().to_string();
+        assert!(error.contains("storage.kafka_indices.schema_id"), "{}", error);
+
+        let kafka = config("[storage.kafka]\nbrokers = [\"kafka-1:9093\"]\ntopic = \"prices\"\nsasl_password_env = \"KAFKA_PASSWORD\"\n[storage.kafka.properties]\n\"security.protocol\" = \"sasl_ssl\"\n\"sasl.mechanism\" = \"SCRAM-SHA-512\"")
+            .unwrap().storage.kafka.unwrap();
+        assert_eq!(kafka.properties["sasl.mechanism"], "SCRAM-SHA-512");
+        assert_eq!(kafka.sasl_password_env.as_deref(), Some("KAFKA_PASSWORD"));
+        let error = config("[storage.kafka]\nbrokers = [\"kafka-1:9092\"]\ntopic = \"prices\"\nproperties = { acks = \"0\" }").unwrap_err().to_string();
+        assert!(error.contains("storage.kafka.properties cannot set acks"), "{}", error);
+    }
+
+    #[test]
+    fn test_redis_broadcast_defaults_and_validation() {
+        let config = |redis: &str| Config::from_toml(&format!(r#"
+            [redis]
+            {}
+
+            [feeds]
+            coinbase_btc = {{ exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }}
+
+            [[indices]]
+            name = "BTC-USD-INDEX"
+            smoothing = "none"
+            feeds = [{{ id = "coinbase_btc", weight = 100 }}]
+        "#, redis));
+
+        let redis = config("url = \"redis://127.0.0.1\"").unwrap().redis.unwrap();
+        assert_eq!(redis.channel, "crypto-index:indices");
+        assert_eq!((redis.stream, redis.stream_max_len), (None, None));
+
+        let redis = config("url = \"redis://127.0.0.1\"\nstream = \"index-updates\"\nstream_max_len = 10000").unwrap().redis.unwrap();
+        assert_eq!((redis.stream.as_deref(), redis.stream_max_len), (Some("index-updates"), Some(10000)));
+
+        assert!(config("url = \"127.0.0.1:6379\"").unwrap_err().to_string().contains("redis.url"));
+        assert!(config("url = \"redis://127.0.0.1\"\nstream_max_len = 0").unwrap_err().to_string().contains("stream_max_len"));
+    }
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use super::*;
+
+    #[test]
+    fn test_webhooks_are_validated() {
+        let config = |webhook: &str| Config::from_toml(&format!(r#"
+            [[webhooks]]
+            {}
+
+            [feeds]
+            coinbase_btc = {{ exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }}
+
+            [[indices]]
+            name = "BTC-USD-INDEX"
+            smoothing = "none"
+            feeds = [{{ id = "coinbase_btc", weight = 100 }}]
+        "#, webhook));
+
+        let webhooks = config("url = \"https://example.com/hook\"\nindices = [\"BTC-USD-INDEX\"]\nthresholds = [65000.0]").unwrap().webhooks;
+        assert_eq!(webhooks[0].thresholds, [65000.0]);
+        assert_eq!((webhooks[0].timeout_ms, webhooks[0].queue_capacity, webhooks[0].secret_env.as_deref()), (5_000, 1_000, None));
+
+        assert!(config("url = \"ftp://example.com\"").unwrap_err().to_string().contains("webhooks.url"));
+        assert!(config("url = \"https://example.com/hook\"\nindices = [\"ETH-USD-INDEX\"]").unwrap_err().to_string().contains("unknown index ETH-USD-INDEX"));
+    }
+}
+
+#[cfg(test)]
+mod telemetry_tests {
+    use super::*;
+
+    #[test]
+    fn test_telemetry_is_validated() {
+        let config = |telemetry: &str| Config::from_toml(&format!(r#"
+            [telemetry]
+            {}
+
+            [feeds]
+            coinbase_btc = {{ exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }}
+
+            [[indices]]
+            name = "BTC-USD-INDEX"
+            smoothing = "none"
+            feeds = [{{ id = "coinbase_btc", weight = 100 }}]
+        "#, telemetry));
+
+        let telemetry = config("").unwrap().telemetry;
+        assert_eq!((telemetry.otlp_endpoint, telemetry.sample_ratio, telemetry.metrics_interval_secs), (None, 1.0, 60));
+        let telemetry = config("otlp_endpoint = \"http://collector:4318\"\nsample_ratio = 0.1").unwrap().telemetry;
+        assert_eq!((telemetry.otlp_endpoint.as_deref(), telemetry.sample_ratio), (Some("http://collector:4318"), 0.1));
+
+        assert!(config("otlp_endpoint = \"collector:4318\"").unwrap_err().to_string().contains("telemetry.otlp_endpoint"));
+        assert!(config("sample_ratio = 1.5").unwrap_err().to_string().contains("sample_ratio"));
+        assert!(config("metrics_interval_secs = 0").unwrap_err().to_string().contains("metrics_interval_secs"));
+    }
+}
+
+#[cfg(test)]
+mod drill_tests {
+    use super::*;
+
+    #[test]
+    fn test_drills_are_only_allowed_in_staging() {
+        let config = |settings: &str| Config::from_toml(&format!(r#"
+            {}
+
+            [feeds]
+            coinbase_btc = {{ exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }}
+
+            [[indices]]
+            name = "BTC-USD-INDEX"
+            smoothing = "none"
+            feeds = [{{ id = "coinbase_btc", weight = 100 }}]
+        "#, settings));
+
+        assert!(config("[drill]\nenabled = true").unwrap_err().to_string().contains("environment = \"staging\""));
+        assert!(config("environment = \"production\"\n[drill]\nenabled = true").is_err());
+        let staging = config("environment = \"staging\"\n[drill]\nenabled = true").unwrap();
+        assert!(staging.drill.enabled);
+        assert!(config("[drill]\nenabled = false").is_ok());
+    }
+}
+
+#[cfg(test)]
+mod multicast_tests {
+    use super::*;
+
+    fn config(index_name: &str, group: &str) -> Result<Config, String> {
+        Config::from_toml(&format!(r#"
+            [feeds]
+            coinbase_btc = {{ exchange = "coinbase", base_currency = "BTC", quote_currency = "USD" }}
+
+            [[indices]]
+            name = "{}"
+            smoothing = "none"
+            feeds = [{{ id = "coinbase_btc", weight = 100 }}]
+
+            [multicast]
+            group = "{}"
+        "#, index_name, group)).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_group_must_be_a_multicast_address() {
+        assert!(config("BTC-USD-INDEX", "239.10.20.30:5000").is_ok());
+        for group in ["10.0.0.5:5000", "255.255.255.255:5000", "239.10.20.30", "[ff02::1]:5000"] {
+            let error = config("BTC-USD-INDEX", group).unwrap_err();
+            assert!(error.contains("multicast.group must be an IPv4 multicast address"), "{}: {}", group, error);
+        }
+    }
+
+    #[test]
+    fn test_index_names_must_fit_an_update() {
+        let name = format!("BTC-USD-{}", "X".repeat(247));
+        assert_eq!(name.len(), 255);
+        assert!(config(&name, "239.10.20.30:5000").is_ok());
+
+        let error = config(&format!("{}X", name), "239.10.20.30:5000").unwrap_err();
+        assert!(error.contains("longer than the 255 bytes a multicast update carries"), "{}", error);
+    }
+}
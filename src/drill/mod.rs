@@ -0,0 +1,6 @@
+mod scheduler;
+
+#[cfg(test)]
+mod tests;
+
+pub use scheduler::{run_drill, run_drills, CheckOutcome, Drill, DrillCheck, DrillReport, DrillSink, DrillState, DrillTarget};
@@ -0,0 +1,298 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::seq::SliceRandom;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::config::DrillConfig;
+use crate::error::AppResult;
+use crate::exchange::FetchMetric;
+use crate::index::IndexResult;
+use crate::metrics::metrics;
+use crate::models::FeedData;
+use crate::notification::{NotificationQueue, Severity};
+use crate::storage::StorageSink;
+
+/// Component deliberately taken down during a drill
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DrillTarget {
+    /// A single price feed
+    Feed(String),
+    /// The database connection
+    Database,
+}
+
+impl fmt::Display for DrillTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrillTarget::Feed(feed_id) => write!(f, "feed '{}'", feed_id),
+            DrillTarget::Database => write!(f, "database"),
+        }
+    }
+}
+
+/// Failure handling a drill verifies, observed as a counter of the metrics registry increasing
+/// during the outage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrillCheck {
+    /// The feed switched to its backup source
+    Failover,
+    /// The feed was excluded from its indices and the weights of the others renormalized
+    Renormalization,
+    /// Database writes failed and were kept buffered or in the write-ahead log
+    Spooling,
+    /// A notification went out
+    Alert,
+}
+
+impl DrillCheck {
+    /// Counter increasing when the failure handling happens
+    pub fn counter(self) -> &'static str {
+        match self {
+            DrillCheck::Failover => "feeds.failovers",
+            DrillCheck::Renormalization => "index.stale_feed_exclusions",
+            DrillCheck::Spooling => "database.price_write_failures",
+            DrillCheck::Alert => "notifications.sent",
+        }
+    }
+}
+
+impl fmt::Display for DrillCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrillCheck::Failover => write!(f, "failover"),
+            DrillCheck::Renormalization => write!(f, "renormalization"),
+            DrillCheck::Spooling => write!(f, "spooling"),
+            DrillCheck::Alert => write!(f, "alert"),
+        }
+    }
+}
+
+/// A drill target along with the failure handling its outage must trigger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drill {
+    pub target: DrillTarget,
+    pub checks: Vec<DrillCheck>,
+}
+
+impl Drill {
+    /// Outage of a feed: it fails over to its backup if it has one; otherwise it goes down,
+    /// which is alerted, and drops out of its indices
+    pub fn feed(feed_id: impl Into<String>, has_backup: bool) -> Self {
+        let checks = if has_backup {
+            vec![DrillCheck::Failover]
+        } else {
+            vec![DrillCheck::Renormalization, DrillCheck::Alert]
+        };
+        Self { target: DrillTarget::Feed(feed_id.into()), checks }
+    }
+
+    /// Outage of the database: writes are spooled, and alerted once the outage lasts longer than
+    /// `notify_after`
+    pub fn database(duration: Duration, notify_after: Duration) -> Self {
+        let mut checks = vec![DrillCheck::Spooling];
+        if duration > notify_after {
+            checks.push(DrillCheck::Alert);
+        }
+        Self { target: DrillTarget::Database, checks }
+    }
+}
+
+/// Shared drill state consulted by the fetch loops and the database writer
+#[derive(Debug, Default)]
+pub struct DrillState {
+    active: RwLock<Option<DrillTarget>>,
+    simulated_failures: AtomicU64,
+}
+
+impl DrillState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the feed is currently taken down by a drill
+    pub fn is_feed_disabled(&self, feed_id: &str) -> bool {
+        matches!(&*self.active.read().unwrap(), Some(DrillTarget::Feed(id)) if id == feed_id)
+    }
+
+    /// Whether the database is currently taken down by a drill
+    pub fn is_database_disabled(&self) -> bool {
+        matches!(&*self.active.read().unwrap(), Some(DrillTarget::Database))
+    }
+
+    /// Record that a component observed the simulated outage
+    pub fn record_simulated_failure(&self) {
+        self.simulated_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn start(&self, target: DrillTarget) {
+        self.simulated_failures.store(0, Ordering::Relaxed);
+        *self.active.write().unwrap() = Some(target);
+    }
+
+    pub(super) fn stop(&self) -> u64 {
+        *self.active.write().unwrap() = None;
+        self.simulated_failures.load(Ordering::Relaxed)
+    }
+}
+
+/// Storage sink failing all writes while a drill takes down the database, so the outage goes
+/// through the same buffering, write-ahead log and notification as a real one
+pub struct DrillSink {
+    inner: Arc<dyn StorageSink>,
+    state: Arc<DrillState>,
+}
+
+impl DrillSink {
+    pub fn new(inner: Arc<dyn StorageSink>, state: Arc<DrillState>) -> Self {
+        Self { inner, state }
+    }
+
+    fn check(&self) -> AppResult<()> {
+        if self.state.is_database_disabled() {
+            self.state.record_simulated_failure();
+            return Err("Simulated outage (failover drill)".into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StorageSink for DrillSink {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn save_prices(&self, prices: &[FeedData]) -> AppResult<()> {
+        self.check()?;
+        self.inner.save_prices(prices).await
+    }
+
+    async fn save_index_values(&self, values: &[IndexResult]) -> AppResult<()> {
+        self.check()?;
+        self.inner.save_index_values(values).await
+    }
+
+    async fn save_fetch_metrics(&self, fetches: &[FetchMetric]) -> AppResult<()> {
+        self.check()?;
+        self.inner.save_fetch_metrics(fetches).await
+    }
+}
+
+/// How often the failure handling a drill checks was observed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckOutcome {
+    pub check: DrillCheck,
+    pub observed: u64,
+}
+
+/// Outcome of a single drill
+#[derive(Debug, Clone)]
+pub struct DrillReport {
+    pub target: DrillTarget,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Number of times the outage was observed by the affected component
+    pub simulated_failures: u64,
+    pub checks: Vec<CheckOutcome>,
+}
+
+impl DrillReport {
+    /// A drill passes when the affected component went through its failure path and all of
+    /// the failure handling expected of the outage was observed
+    pub fn passed(&self) -> bool {
+        self.simulated_failures > 0 && self.checks.iter().all(|outcome| outcome.observed > 0)
+    }
+}
+
+impl fmt::Display for DrillReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Drill on {} from {} to {}: {} simulated failures observed",
+            self.target, self.started_at, self.finished_at, self.simulated_failures
+        )?;
+        for outcome in &self.checks {
+            match outcome.observed {
+                0 => write!(f, ", {}: missing", outcome.check)?,
+                observed => write!(f, ", {}: ok ({})", outcome.check, observed)?,
+            }
+        }
+        write!(f, ", result: {}", if self.passed() { "PASSED" } else { "FAILED" })
+    }
+}
+
+/// Counters of the checks of a drill as it starts
+fn counters(checks: &[DrillCheck]) -> Vec<u64> {
+    checks.iter().map(|check| metrics().counter(check.counter())).collect()
+}
+
+/// Report of a drill, with the increase of every check's counter since `baseline`
+fn report(drill: Drill, started_at: DateTime<Utc>, simulated_failures: u64, baseline: &[u64]) -> DrillReport {
+    let checks = drill.checks.iter().zip(baseline)
+        .map(|(&check, &before)| CheckOutcome { check, observed: metrics().counter(check.counter()).saturating_sub(before) })
+        .collect();
+    DrillReport { target: drill.target, started_at, finished_at: Utc::now(), simulated_failures, checks }
+}
+
+/// Take down the component of a drill for `duration` and report how the collector coped;
+/// `None` when interrupted by shutdown
+pub async fn run_drill(
+    drill: Drill,
+    duration: Duration,
+    state: &DrillState,
+    shutdown: &mut broadcast::Receiver<()>,
+) -> Option<DrillReport> {
+    let started_at = Utc::now();
+    let baseline = counters(&drill.checks);
+    warn!("[DRILL] Starting drill: simulating outage of {} for {}s", drill.target, duration.as_secs());
+    state.start(drill.target.clone());
+
+    let interrupted = tokio::select! {
+        _ = tokio::time::sleep(duration) => false,
+        _ = shutdown.recv() => true,
+    };
+
+    let simulated_failures = state.stop();
+    if interrupted {
+        info!("[DRILL] Drill on {} interrupted by shutdown", drill.target);
+        return None;
+    }
+    Some(report(drill, started_at, simulated_failures, &baseline))
+}
+
+/// Periodically take down a random feed or the database and report how the collector coped
+pub async fn run_drills(
+    config: DrillConfig,
+    state: Arc<DrillState>,
+    drills: Vec<Drill>,
+    notifier: NotificationQueue,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    if drills.is_empty() {
+        warn!("[DRILL] No drill targets available, drills disabled");
+        return;
+    }
+
+    info!("[DRILL] Failover drills scheduled every {}s for {}s", config.interval_secs, config.duration_secs);
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(config.interval_secs)) => {}
+            _ = shutdown.recv() => return,
+        }
+
+        let drill = drills.choose(&mut rand::thread_rng()).cloned().unwrap();
+        let Some(report) = run_drill(drill, Duration::from_secs(config.duration_secs), &state, &mut shutdown).await else {
+            return;
+        };
+
+        info!("[DRILL] {}", report);
+        let severity = if report.passed() { Severity::Info } else { Severity::Warning };
+        notifier.send(severity, report.to_string());
+    }
+}
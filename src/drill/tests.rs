@@ -0,0 +1,140 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::sync::broadcast;
+
+use crate::error::AppResult;
+use crate::index::IndexResult;
+use crate::metrics::metrics;
+use crate::models::FeedData;
+use crate::storage::StorageSink;
+use super::*;
+
+fn report(simulated_failures: u64, checks: &[(DrillCheck, u64)]) -> DrillReport {
+    DrillReport {
+        target: DrillTarget::Feed("coinbase_btc".to_string()),
+        started_at: Utc::now(),
+        finished_at: Utc::now(),
+        simulated_failures,
+        checks: checks.iter().map(|&(check, observed)| CheckOutcome { check, observed }).collect(),
+    }
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::*;
+
+    #[test]
+    fn test_feed_drills_expect_failover_or_renormalization() {
+        assert_eq!(Drill::feed("coinbase_btc", true).checks, [DrillCheck::Failover]);
+        assert_eq!(Drill::feed("coinbase_btc", false).checks, [DrillCheck::Renormalization, DrillCheck::Alert]);
+    }
+
+    #[test]
+    fn test_database_drills_expect_an_alert_only_beyond_the_notification_threshold() {
+        let short = Drill::database(Duration::from_secs(30), Duration::from_secs(60));
+        assert_eq!(short.checks, [DrillCheck::Spooling]);
+        let long = Drill::database(Duration::from_secs(120), Duration::from_secs(60));
+        assert_eq!(long.checks, [DrillCheck::Spooling, DrillCheck::Alert]);
+    }
+
+    #[test]
+    fn test_drill_fails_when_failure_handling_is_missing() {
+        let report = report(12, &[(DrillCheck::Renormalization, 3), (DrillCheck::Alert, 0)]);
+        assert!(!report.passed());
+        let text = report.to_string();
+        assert!(text.contains("renormalization: ok (3)"), "{}", text);
+        assert!(text.contains("alert: missing"), "{}", text);
+        assert!(text.ends_with("result: FAILED"), "{}", text);
+    }
+
+    #[test]
+    fn test_drill_fails_when_the_outage_was_never_observed() {
+        assert!(!report(0, &[(DrillCheck::Failover, 1)]).passed());
+        assert!(report(4, &[(DrillCheck::Failover, 1)]).passed());
+    }
+
+    #[tokio::test]
+    async fn test_drill_observes_the_failure_handling_during_the_outage() {
+        let state = Arc::new(DrillState::new());
+        let (_shutdown_tx, mut shutdown) = broadcast::channel(1);
+        let feed = {
+            let state = state.clone();
+            tokio::spawn(async move {
+                // A fetch loop failing over once it sees its feed taken down
+                while !state.is_feed_disabled("drill_test_feed") {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+                state.record_simulated_failure();
+                metrics().increment("feeds.failovers");
+            })
+        };
+
+        let report = run_drill(Drill::feed("drill_test_feed", true), Duration::from_millis(200), &state, &mut shutdown).await.unwrap();
+        feed.await.unwrap();
+        assert_eq!(report.simulated_failures, 1);
+        assert!(report.checks[0].observed >= 1);
+        assert!(report.passed(), "{}", report);
+        assert!(!state.is_feed_disabled("drill_test_feed"));
+    }
+
+    #[tokio::test]
+    async fn test_drill_interrupted_by_shutdown_has_no_report() {
+        let state = DrillState::new();
+        let (shutdown_tx, mut shutdown) = broadcast::channel(1);
+        shutdown_tx.send(()).unwrap();
+        assert!(run_drill(Drill::feed("coinbase_btc", false), Duration::from_secs(60), &state, &mut shutdown).await.is_none());
+        assert!(!state.is_feed_disabled("coinbase_btc"));
+    }
+}
+
+#[cfg(test)]
+mod sink_tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingSink {
+        saved: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl StorageSink for CountingSink {
+        fn name(&self) -> &'static str {
+            "database"
+        }
+
+        async fn save_prices(&self, prices: &[FeedData]) -> AppResult<()> {
+            *self.saved.lock().unwrap() += prices.len();
+            Ok(())
+        }
+
+        async fn save_index_values(&self, _values: &[IndexResult]) -> AppResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_database_drill_fails_writes_until_it_ends() {
+        let inner = Arc::new(CountingSink::default());
+        let state = Arc::new(DrillState::new());
+        let sink = DrillSink::new(inner.clone(), state.clone());
+        let price = FeedData {
+            feed_id: "coinbase_btc".to_string(),
+            timestamp: Utc::now(),
+            price: 64000.0,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        };
+
+        sink.save_prices(std::slice::from_ref(&price)).await.unwrap();
+        state.start(DrillTarget::Database);
+        assert!(sink.save_prices(std::slice::from_ref(&price)).await.is_err());
+        assert_eq!(state.stop(), 1);
+        sink.save_prices(std::slice::from_ref(&price)).await.unwrap();
+
+        assert_eq!(*inner.saved.lock().unwrap(), 2);
+        assert_eq!(sink.name(), "database");
+    }
+}
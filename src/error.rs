@@ -14,6 +14,8 @@ pub enum AppError {
     WebSocket(String),
     /// Index calculation error
     IndexCalculation(String),
+    /// Serialization error
+    Serialization(String),
     /// I/O error
     Io(std::io::Error),
     /// Generic error
@@ -28,6 +30,7 @@ impl fmt::Display for AppError {
             AppError::Exchange(msg) => write!(f, "Exchange error: {}", msg),
             AppError::WebSocket(msg) => write!(f, "WebSocket error: {}", msg),
             AppError::IndexCalculation(msg) => write!(f, "Index calculation error: {}", msg),
+            AppError::Serialization(msg) => write!(f, "Serialization error: {}", msg),
             AppError::Io(err) => write!(f, "I/O error: {}", err),
             AppError::Other(msg) => write!(f, "Error: {}", msg),
         }
@@ -54,6 +57,12 @@ impl From<sqlx::Error> for AppError {
     }
 }
 
+impl From<sqlx::migrate::MigrateError> for AppError {
+    fn from(err: sqlx::migrate::MigrateError) -> Self {
+        AppError::Database(err.to_string())
+    }
+}
+
 impl From<tokio_tungstenite::tungstenite::Error> for AppError {
     fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
         AppError::WebSocket(err.to_string())
@@ -66,6 +75,24 @@ impl From<reqwest::Error> for AppError {
     }
 }
 
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        AppError::Serialization(err.to_string())
+    }
+}
+
+impl From<parquet::errors::ParquetError> for AppError {
+    fn from(err: parquet::errors::ParquetError) -> Self {
+        AppError::Serialization(err.to_string())
+    }
+}
+
+impl From<arrow_schema::ArrowError> for AppError {
+    fn from(err: arrow_schema::ArrowError) -> Self {
+        AppError::Serialization(err.to_string())
+    }
+}
+
 impl From<std::num::ParseFloatError> for AppError {
     fn from(err: std::num::ParseFloatError) -> Self {
         AppError::Exchange(format!("Failed to parse price: {}", err))
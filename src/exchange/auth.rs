@@ -0,0 +1,148 @@
+use std::fmt;
+use std::fs;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hmac::{Hmac, Mac};
+use reqwest::{Method, RequestBuilder, Url};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::config::CredentialsConfig;
+use crate::error::{AppError, AppResult};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// API credentials of an exchange account
+#[derive(Clone)]
+pub struct ApiCredentials {
+    pub api_key: String,
+    pub api_secret: String,
+    pub passphrase: Option<String>,
+}
+
+// Never print secrets, not even in debug logs
+impl fmt::Debug for ApiCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiCredentials")
+            .field("api_key", &"<redacted>")
+            .field("api_secret", &"<redacted>")
+            .field("passphrase", &self.passphrase.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl ApiCredentials {
+    /// Resolve credentials from the secrets file and/or environment variables
+    pub fn load(config: &CredentialsConfig) -> AppResult<Self> {
+        #[derive(Default, Deserialize)]
+        struct SecretsFile {
+            api_key: Option<String>,
+            api_secret: Option<String>,
+            passphrase: Option<String>,
+        }
+
+        let file = match &config.secrets_file {
+            Some(path) => {
+                let content = fs::read_to_string(path)
+                    .map_err(|e| AppError::Config(format!("Failed to read secrets file '{}': {}", path, e)))?;
+                toml::from_str(&content)?
+            }
+            None => SecretsFile::default(),
+        };
+
+        let from_env = |name: &Option<String>| name.as_ref().and_then(|name| std::env::var(name).ok());
+
+        let api_key = file.api_key.or_else(|| from_env(&config.api_key_env))
+            .ok_or_else(|| AppError::Config("API key not found in secrets file or environment".to_string()))?;
+        let api_secret = file.api_secret.or_else(|| from_env(&config.api_secret_env))
+            .ok_or_else(|| AppError::Config("API secret not found in secrets file or environment".to_string()))?;
+        let passphrase = file.passphrase.or_else(|| from_env(&config.passphrase_env));
+
+        Ok(Self { api_key, api_secret, passphrase })
+    }
+}
+
+/// Adds exchange-specific authentication to outgoing requests
+pub trait RequestSigner: Send + Sync + fmt::Debug {
+    /// Authenticate a request; called for every attempt so signatures carry a fresh timestamp
+    fn sign(&self, method: &Method, url: &Url, request: RequestBuilder) -> AppResult<RequestBuilder>;
+}
+
+/// Binance authentication: the API key header, plus an HMAC-SHA256 query signature for signed endpoints
+#[derive(Debug)]
+pub struct BinanceSigner {
+    credentials: ApiCredentials,
+}
+
+impl BinanceSigner {
+    pub fn new(credentials: ApiCredentials) -> Self {
+        Self { credentials }
+    }
+
+    /// Hex HMAC-SHA256 signature of a query string, as required by Binance's SIGNED endpoints
+    pub fn signature(&self, query: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(self.credentials.api_secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(query.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+impl RequestSigner for BinanceSigner {
+    fn sign(&self, _method: &Method, url: &Url, request: RequestBuilder) -> AppResult<RequestBuilder> {
+        let request = request.header("X-MBX-APIKEY", &self.credentials.api_key);
+
+        // Market data endpoints only need the key; SIGNED endpoints carry a timestamp parameter
+        // and additionally need the query signed
+        if url.query_pairs().any(|(key, _)| key == "timestamp") {
+            let signature = self.signature(url.query().unwrap_or_default());
+            return Ok(request.query(&[("signature", signature)]));
+        }
+
+        Ok(request)
+    }
+}
+
+/// Coinbase Exchange authentication: HMAC-SHA256 of timestamp, method and path with the base64 secret
+#[derive(Debug)]
+pub struct CoinbaseSigner {
+    credentials: ApiCredentials,
+    secret: Vec<u8>,
+}
+
+impl CoinbaseSigner {
+    pub fn new(credentials: ApiCredentials) -> AppResult<Self> {
+        let secret = BASE64.decode(&credentials.api_secret)
+            .map_err(|e| AppError::Config(format!("Coinbase API secret is not valid base64: {}", e)))?;
+        Ok(Self { credentials, secret })
+    }
+
+    /// Base64 signature over `timestamp + method + request path` (GET requests have no body)
+    pub fn signature(&self, timestamp: &str, method: &Method, path_and_query: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(timestamp.as_bytes());
+        mac.update(method.as_str().as_bytes());
+        mac.update(path_and_query.as_bytes());
+        BASE64.encode(mac.finalize().into_bytes())
+    }
+}
+
+impl RequestSigner for CoinbaseSigner {
+    fn sign(&self, method: &Method, url: &Url, request: RequestBuilder) -> AppResult<RequestBuilder> {
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let path_and_query = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+
+        let mut request = request
+            .header("CB-ACCESS-KEY", &self.credentials.api_key)
+            .header("CB-ACCESS-SIGN", self.signature(&timestamp, method, &path_and_query))
+            .header("CB-ACCESS-TIMESTAMP", timestamp);
+        if let Some(passphrase) = &self.credentials.passphrase {
+            request = request.header("CB-ACCESS-PASSPHRASE", passphrase);
+        }
+
+        Ok(request)
+    }
+}
@@ -1,13 +1,15 @@
 use async_trait::async_trait;
-use reqwest::Client;
 use serde::Deserialize;
 use tracing::debug;
+use crate::config::ExchangeConfig;
 use crate::error::AppResult;
 
 use super::Exchange;
+use super::auth::{ApiCredentials, BinanceSigner};
+use super::http::ExchangeClient;
 
 pub struct BinanceExchange {
-    client: Client,
+    client: ExchangeClient,
 }
 
 #[derive(Debug, Deserialize)]
@@ -17,9 +19,18 @@ struct BinanceTickerResponse {
 
 impl BinanceExchange {
     pub fn new() -> Self {
-        Self {
-            client: Client::new(),
+        Self::with_config(&ExchangeConfig::default()).expect("Failed to build HTTP client")
+    }
+
+    /// Create an adapter applying the given exchange settings, authenticated when credentials are configured
+    pub fn with_config(config: &ExchangeConfig) -> AppResult<Self> {
+        let mut client = ExchangeClient::new(config)?;
+        if let Some(credentials_config) = &config.credentials {
+            let credentials = ApiCredentials::load(credentials_config)?;
+            client = client.with_signer(Box::new(BinanceSigner::new(credentials)));
         }
+
+        Ok(Self { client })
     }
 }
 
@@ -36,15 +47,42 @@ impl Exchange for BinanceExchange {
 
         debug!("Fetching price from Binance for {}", symbol);
 
-        let response = self.client.get(&url).send().await?;
+        let response = self.client.get_cached(&url).await?;
 
         if !response.status().is_success() {
             return Err(format!("Binance API error: {}", response.status()).into());
         }
 
-        let data: BinanceTickerResponse = response.json().await?;
+        let data: BinanceTickerResponse = response.json()?;
         let price = data.price.parse::<f64>()?;
 
         Ok(price)
     }
+
+    async fn fetch_recent_closes(&self, symbol: &str, limit: usize) -> AppResult<Vec<f64>> {
+        let url = format!("https://api.binance.com/api/v3/klines?symbol={}&interval=1m&limit={}", symbol, limit);
+
+        debug!("Fetching {} candles from Binance for {}", limit, symbol);
+
+        let response = self.client.get(&url).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Binance API error: {}", response.status()).into());
+        }
+
+        parse_kline_closes(symbol, &response.text().await?)
+    }
+}
+
+/// Closes of a klines response, oldest first
+pub(super) fn parse_kline_closes(symbol: &str, body: &str) -> AppResult<Vec<f64>> {
+    // Klines are arrays of [open time, open, high, low, close, ...], oldest first
+    let klines: Vec<Vec<serde_json::Value>> = serde_json::from_str(body)?;
+    klines.iter()
+        .map(|kline| {
+            let close = kline.get(4).and_then(|v| v.as_str())
+                .ok_or_else(|| format!("Malformed Binance kline for {}", symbol))?;
+            Ok(close.parse::<f64>()?)
+        })
+        .collect()
 }
\ No newline at end of file
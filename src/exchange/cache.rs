@@ -0,0 +1,145 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use reqwest::header::{HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use tokio::time::Instant;
+
+use crate::config::ResponseCacheConfig;
+use crate::error::AppResult;
+
+/// Status and body of a GET response, cheap to clone
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    status: StatusCode,
+    body: Arc<[u8]>,
+}
+
+impl CachedResponse {
+    pub fn new(status: StatusCode, body: &[u8]) -> Self {
+        Self { status, body: Arc::from(body) }
+    }
+
+    /// Read the whole body of a response
+    pub async fn read(response: Response) -> AppResult<Self> {
+        let status = response.status();
+        let body = response.bytes().await?;
+        Ok(Self::new(status, &body))
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> AppResult<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// A cached successful response with the validators to revalidate it
+#[derive(Debug)]
+pub struct CacheEntry {
+    pub response: CachedResponse,
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    fetched_at: Instant,
+}
+
+impl CacheEntry {
+    /// Cache a response, keeping the validators of its headers
+    pub fn new(response: CachedResponse, headers: &HeaderMap) -> Self {
+        Self {
+            response,
+            etag: headers.get(ETAG).cloned(),
+            last_modified: headers.get(LAST_MODIFIED).cloned(),
+            fetched_at: Instant::now(),
+        }
+    }
+
+    /// Headers making the next request conditional on the resource having changed
+    pub fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = &self.etag {
+            headers.insert(IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        }
+        headers
+    }
+
+    /// The server confirmed the cached response is still current
+    pub fn revalidated(&mut self) {
+        self.fetched_at = Instant::now();
+    }
+
+    fn age(&self) -> Duration {
+        self.fetched_at.elapsed()
+    }
+}
+
+/// Entry of one URL, locked while it is being fetched so concurrent requests wait for the result
+pub type CacheSlot = Arc<tokio::sync::Mutex<Option<CacheEntry>>>;
+
+/// Small cache of GET responses shared by all feeds of one exchange
+#[derive(Debug)]
+pub struct ResponseCache {
+    max_age: Duration,
+    max_entries: usize,
+    slots: Mutex<CacheSlots>,
+}
+
+#[derive(Debug, Default)]
+struct CacheSlots {
+    by_url: HashMap<String, CacheSlot>,
+    /// URLs in insertion order, for evicting the oldest
+    order: VecDeque<String>,
+}
+
+impl ResponseCache {
+    pub fn new(max_age: Duration, max_entries: usize) -> Self {
+        Self {
+            max_age,
+            max_entries: max_entries.max(1),
+            slots: Mutex::new(CacheSlots::default()),
+        }
+    }
+
+    /// Cache configured from the exchange settings, `None` if disabled
+    pub fn from_config(config: &ResponseCacheConfig) -> Option<Self> {
+        config.enabled.then(|| Self::new(Duration::from_millis(config.max_age_ms), config.max_entries))
+    }
+
+    /// Slot of a URL, creating it (and evicting the oldest one if full) if needed
+    pub fn slot(&self, url: &str) -> CacheSlot {
+        let mut slots = self.slots.lock().unwrap();
+        if let Some(slot) = slots.by_url.get(url) {
+            return slot.clone();
+        }
+
+        if slots.by_url.len() >= self.max_entries {
+            if let Some(oldest) = slots.order.pop_front() {
+                slots.by_url.remove(&oldest);
+            }
+        }
+
+        let slot = CacheSlot::default();
+        slots.by_url.insert(url.to_string(), slot.clone());
+        slots.order.push_back(url.to_string());
+        slot
+    }
+
+    /// Whether an entry can be served without asking the server
+    pub fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        entry.age() < self.max_age
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.lock().unwrap().by_url.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
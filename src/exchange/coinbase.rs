@@ -1,13 +1,15 @@
 use async_trait::async_trait;
-use reqwest::Client;
 use serde::Deserialize;
 use tracing::debug;
+use crate::config::ExchangeConfig;
 use crate::error::AppResult;
 
 use super::Exchange;
+use super::auth::{ApiCredentials, CoinbaseSigner};
+use super::http::ExchangeClient;
 
 pub struct CoinbaseExchange {
-    client: Client,
+    client: ExchangeClient,
 }
 
 #[derive(Debug, Deserialize)]
@@ -22,9 +24,18 @@ struct CoinbaseData {
 
 impl CoinbaseExchange {
     pub fn new() -> Self {
-        Self {
-            client: Client::new(),
+        Self::with_config(&ExchangeConfig::default()).expect("Failed to build HTTP client")
+    }
+
+    /// Create an adapter applying the given exchange settings, authenticated when credentials are configured
+    pub fn with_config(config: &ExchangeConfig) -> AppResult<Self> {
+        let mut client = ExchangeClient::new(config)?;
+        if let Some(credentials_config) = &config.credentials {
+            let credentials = ApiCredentials::load(credentials_config)?;
+            client = client.with_signer(Box::new(CoinbaseSigner::new(credentials)?));
         }
+
+        Ok(Self { client })
     }
 }
 
@@ -41,15 +52,39 @@ impl Exchange for CoinbaseExchange {
 
         debug!("Fetching price from Coinbase for {}", symbol);
 
-        let response = self.client.get(&url).send().await?;
+        let response = self.client.get_cached(&url).await?;
 
         if !response.status().is_success() {
             return Err(format!("Coinbase API error: {}", response.status()).into());
         }
 
-        let data: CoinbaseResponse = response.json().await?;
+        let data: CoinbaseResponse = response.json()?;
         let price = data.data.amount.parse::<f64>()?;
 
         Ok(price)
     }
+
+    async fn fetch_recent_closes(&self, symbol: &str, limit: usize) -> AppResult<Vec<f64>> {
+        let url = format!("https://api.exchange.coinbase.com/products/{}/candles?granularity=60", symbol);
+
+        debug!("Fetching {} candles from Coinbase for {}", limit, symbol);
+
+        let response = self.client.get(&url).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Coinbase API error: {}", response.status()).into());
+        }
+
+        parse_candle_closes(&response.text().await?, limit)
+    }
+}
+
+/// Closes of the newest `limit` candles of a candles response, oldest first
+pub(super) fn parse_candle_closes(body: &str, limit: usize) -> AppResult<Vec<f64>> {
+    // Candles are [time, low, high, open, close, volume], newest first
+    let candles: Vec<[f64; 6]> = serde_json::from_str(body)?;
+    let mut closes: Vec<f64> = candles.iter().take(limit).map(|candle| candle[4]).collect();
+    closes.reverse();
+
+    Ok(closes)
 }
\ No newline at end of file
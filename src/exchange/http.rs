@@ -0,0 +1,163 @@
+use std::time::Duration;
+use chrono::Utc;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Method, Proxy, Response, StatusCode, Url};
+use tracing::{debug, warn};
+use crate::config::ExchangeConfig;
+use crate::error::{AppError, AppResult};
+use crate::metrics::metrics;
+
+use super::auth::RequestSigner;
+use super::cache::{CacheEntry, CachedResponse, ResponseCache};
+use super::rate_limit::RateLimiter;
+use super::retry::{self, RetryPolicy};
+use super::telemetry;
+
+/// HTTP client used by the exchange adapters, applying the exchange's request policies
+#[derive(Debug)]
+pub struct ExchangeClient {
+    client: Client,
+    rate_limiter: Option<RateLimiter>,
+    retry_policy: RetryPolicy,
+    signer: Option<Box<dyn RequestSigner>>,
+    cache: Option<ResponseCache>,
+}
+
+impl ExchangeClient {
+    /// Create a client configured from the exchange settings
+    pub fn new(config: &ExchangeConfig) -> AppResult<Self> {
+        let mut builder = Client::builder()
+            .connect_timeout(Duration::from_millis(config.http.connect_timeout_ms))
+            .timeout(Duration::from_millis(config.http.request_timeout_ms))
+            .user_agent(config.http.user_agent.clone())
+            .pool_idle_timeout(Duration::from_secs(config.http.pool_idle_timeout_secs));
+
+        if let Some(keepalive) = config.http.tcp_keepalive_secs {
+            builder = builder.tcp_keepalive(Duration::from_secs(keepalive));
+        }
+
+        if let Some(proxy_config) = &config.http.proxy {
+            let mut proxy = Proxy::all(&proxy_config.url)?;
+            if let Some(username) = &proxy_config.username {
+                proxy = proxy.basic_auth(username, proxy_config.password.as_deref().unwrap_or_default());
+            }
+            builder = builder.proxy(proxy);
+        }
+
+        Ok(Self {
+            client: builder.build()?,
+            rate_limiter: config.rate_limit.as_ref().map(RateLimiter::from_config),
+            retry_policy: RetryPolicy::from_config(&config.retry),
+            signer: None,
+            cache: ResponseCache::from_config(&config.http.cache),
+        })
+    }
+
+    /// Authenticate every request with the given signer
+    pub fn with_signer(mut self, signer: Box<dyn RequestSigner>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Send a GET request, retrying transient failures according to the retry policy.
+    ///
+    /// Every attempt goes through the rate limiter. Once attempts are exhausted, the last
+    /// response (even a 5xx one) or error is returned to the adapter.
+    pub async fn get(&self, url: &str) -> AppResult<Response> {
+        self.send_get(url, &HeaderMap::new()).await
+    }
+
+    /// Send a GET request through the response cache.
+    ///
+    /// Responses younger than the cache's max age are served without a request (concurrent
+    /// requests for the same URL wait for the first one), older ones are revalidated with
+    /// `If-None-Match`/`If-Modified-Since` and reused on `304 Not Modified`. Only successful
+    /// responses are cached.
+    pub async fn get_cached(&self, url: &str) -> AppResult<CachedResponse> {
+        let Some(cache) = &self.cache else {
+            return CachedResponse::read(self.get(url).await?).await;
+        };
+
+        let slot = cache.slot(url);
+        let mut entry = slot.lock().await;
+
+        if let Some(cached) = entry.as_ref().filter(|cached| cache.is_fresh(cached)) {
+            metrics().increment("exchange.cache.hits");
+            return Ok(cached.response.clone());
+        }
+
+        let headers = entry.as_ref().map(CacheEntry::conditional_headers).unwrap_or_default();
+        let response = self.send_get(url, &headers).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = entry.as_mut() {
+                debug!("[CACHE] {} not modified, reusing cached response", url);
+                metrics().increment("exchange.cache.not_modified");
+                cached.revalidated();
+                return Ok(cached.response.clone());
+            }
+        }
+
+        metrics().increment("exchange.cache.misses");
+        let response_headers = response.headers().clone();
+        let response = CachedResponse::read(response).await?;
+        if response.status().is_success() {
+            *entry = Some(CacheEntry::new(response.clone(), &response_headers));
+        }
+
+        Ok(response)
+    }
+
+    async fn send_get(&self, url: &str, headers: &HeaderMap) -> AppResult<Response> {
+        let parsed_url = Url::parse(url).map_err(|e| AppError::Exchange(format!("Invalid URL {}: {}", url, e)))?;
+        let mut attempt = 1;
+
+        loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
+
+            // Sign each attempt separately so retries carry a fresh timestamp
+            let mut request = self.client.get(parsed_url.clone()).headers(headers.clone());
+            if let Some(signer) = &self.signer {
+                request = signer.sign(&Method::GET, &parsed_url, request)?;
+            }
+
+            let sent = request.send().await;
+            match &sent {
+                Ok(response) => telemetry::record_response(response.status()),
+                Err(e) => telemetry::record_error(e),
+            }
+            let (retry_reason, delay) = match sent {
+                Ok(response) if RetryPolicy::is_retryable_status(response.status()) => {
+                    if attempt >= self.retry_policy.max_attempts() {
+                        return Ok(response);
+                    }
+                    let delay = if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                        // Retrying before the server allows it only prolongs the rate limiting
+                        let retry_after = retry::retry_after(response.headers(), Utc::now());
+                        if let Some(retry_after) = retry_after.filter(|retry_after| *retry_after > retry::MAX_RETRY_AFTER) {
+                            warn!("[RETRY] Request to {} rate limited for {:?}, not retrying", url, retry_after);
+                            return Ok(response);
+                        }
+                        self.retry_policy.rate_limited_delay(attempt, retry_after)
+                    } else {
+                        self.retry_policy.delay(attempt)
+                    };
+                    (format!("status {}", response.status()), delay)
+                }
+                Ok(response) => return Ok(response),
+                Err(e) if RetryPolicy::is_retryable_error(&e) && attempt < self.retry_policy.max_attempts() => {
+                    (e.to_string(), self.retry_policy.delay(attempt))
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            warn!("[RETRY] Request to {} failed ({}), retrying in {:?} (attempt {}/{})",
+                  url, retry_reason, delay, attempt + 1, self.retry_policy.max_attempts());
+            tokio::time::sleep(delay).await;
+            telemetry::record_retry();
+            attempt += 1;
+        }
+    }
+}
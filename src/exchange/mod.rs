@@ -5,15 +5,28 @@ pub use crate::models::PriceFeed;
 pub mod coinbase;
 pub mod binance;
 pub mod traits;
+pub mod http;
+pub mod cache;
+pub mod rate_limit;
+pub mod retry;
+pub mod auth;
+pub mod telemetry;
+
+#[cfg(test)]
+mod tests;
+
+use crate::config::ExchangeConfig;
+use crate::error::{AppError, AppResult};
 
 // Re-export the Exchange trait
 pub use traits::Exchange;
+pub use telemetry::{FetchErrorClass, FetchMetric, FetchStats};
 
 // Factory function to create exchange instances
-pub fn create_exchange(name: &str) -> Option<Box<dyn Exchange>> {
+pub fn create_exchange(name: &str, config: &ExchangeConfig) -> AppResult<Box<dyn Exchange>> {
     match name.to_lowercase().as_str() {
-        "coinbase" => Some(Box::new(coinbase::CoinbaseExchange::new())),
-        "binance" => Some(Box::new(binance::BinanceExchange::new())),
-        _ => None,
+        "coinbase" => Ok(Box::new(coinbase::CoinbaseExchange::with_config(config)?)),
+        "binance" => Ok(Box::new(binance::BinanceExchange::with_config(config)?)),
+        _ => Err(AppError::Config(format!("Unsupported exchange: {}", name))),
     }
 }
\ No newline at end of file
@@ -0,0 +1,64 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::config::RateLimitConfig;
+
+/// Token bucket rate limiter shared by all requests to one exchange
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Create a limiter allowing `rate` requests per second with bursts of up to `burst` requests
+    pub fn new(rate: f64, burst: u32) -> Self {
+        let capacity = burst.max(1) as f64;
+        Self {
+            rate,
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        Self::new(config.requests_per_second, config.burst)
+    }
+
+    /// Take a token if one is available, otherwise return how long to wait for the next one
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.capacity);
+        state.last_refill = now;
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - state.tokens) / self.rate))
+        }
+    }
+
+    /// Wait until a token is available and take it
+    pub async fn acquire(&self) {
+        while let Err(wait) = self.try_acquire() {
+            debug!("[RATE LIMIT] Waiting {:?} for a request slot", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+
+use crate::config::RetryConfig;
+
+/// Longest `Retry-After` waited for within a request; beyond it the rate-limited response is
+/// returned and the next poll tries again
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Factor of the usual backoff after a `429 Too Many Requests` without `Retry-After`
+const RATE_LIMITED_BACKOFF_FACTOR: u32 = 4;
+
+/// Retry policy with exponential backoff and jitter for exchange requests
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration, max_delay: Duration, jitter: f64) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+            max_delay,
+            jitter: jitter.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn from_config(config: &RetryConfig) -> Self {
+        Self::new(
+            config.max_attempts,
+            Duration::from_millis(config.base_delay_ms),
+            Duration::from_millis(config.max_delay_ms),
+            config.jitter,
+        )
+    }
+
+    /// Total number of attempts, including the first one
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Backoff before retry number `attempt` (starting at 1), with `sample` in `[0, 1)` scaling the jitter.
+    ///
+    /// The exponential delay is reduced by up to `jitter` of itself so that feeds retrying
+    /// after the same outage don't hit the exchange in lockstep.
+    pub fn delay_with_sample(&self, attempt: u32, sample: f64) -> Duration {
+        let exponential = self.base_delay.saturating_mul(1 << attempt.saturating_sub(1).min(16));
+        let capped = exponential.min(self.max_delay);
+        capped.mul_f64(1.0 - self.jitter * sample)
+    }
+
+    /// Backoff before retry number `attempt` (starting at 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.delay_with_sample(attempt, rand::thread_rng().gen::<f64>())
+    }
+
+    /// Backoff before retry number `attempt` after a `429 Too Many Requests`: the `Retry-After`
+    /// the server asked for, otherwise a multiple of the usual backoff
+    pub fn rate_limited_delay_with_sample(&self, attempt: u32, retry_after: Option<Duration>, sample: f64) -> Duration {
+        retry_after.unwrap_or_else(|| self.delay_with_sample(attempt, sample).saturating_mul(RATE_LIMITED_BACKOFF_FACTOR))
+    }
+
+    /// Backoff before retry number `attempt` after a `429 Too Many Requests`
+    pub fn rate_limited_delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        self.rate_limited_delay_with_sample(attempt, retry_after, rand::thread_rng().gen::<f64>())
+    }
+
+    /// Whether an HTTP status is worth retrying
+    pub fn is_retryable_status(status: StatusCode) -> bool {
+        status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+    }
+
+    /// Whether a transport error is worth retrying: failed connections and timeouts, not
+    /// errors building the request or reading a response, which a retry would repeat
+    pub fn is_retryable_error(err: &reqwest::Error) -> bool {
+        err.is_timeout() || err.is_connect()
+    }
+}
+
+/// Wait a response asks for in its `Retry-After` header, given in seconds or as an HTTP date
+pub fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((at - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::from_config(&RetryConfig::default())
+    }
+}
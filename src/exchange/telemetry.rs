@@ -0,0 +1,146 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+tokio::task_local! {
+    static FETCH: RefCell<FetchStats>;
+}
+
+/// Why a fetch failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchErrorClass {
+    /// The request timed out
+    Timeout,
+    /// The exchange could not be connected to
+    Connect,
+    /// Any other transport error
+    Transport,
+    /// 429 Too Many Requests
+    RateLimited,
+    /// 5xx status
+    ServerError,
+    /// Other 4xx status
+    ClientError,
+    /// A successful response without a usable price
+    InvalidResponse,
+}
+
+impl FetchErrorClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FetchErrorClass::Timeout => "timeout",
+            FetchErrorClass::Connect => "connect",
+            FetchErrorClass::Transport => "transport",
+            FetchErrorClass::RateLimited => "rate_limited",
+            FetchErrorClass::ServerError => "server_error",
+            FetchErrorClass::ClientError => "client_error",
+            FetchErrorClass::InvalidResponse => "invalid_response",
+        }
+    }
+}
+
+/// Requests made while [`measure`]-ing a fetch, recorded by the exchange client
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FetchStats {
+    /// Requests sent, retries included; `0` when served from the response cache
+    pub requests: u32,
+    pub retries: u32,
+    /// Status of the last response
+    pub status: Option<u16>,
+    /// Transport error of the last request, if it got no response
+    pub transport_error: Option<FetchErrorClass>,
+    /// Time the whole fetch took, retries and rate limiting included
+    pub latency: Duration,
+}
+
+impl FetchStats {
+    /// Class of the error a fetch failed with, from its last request
+    pub fn error_class(&self, failed: bool) -> Option<FetchErrorClass> {
+        if !failed {
+            return None;
+        }
+        Some(self.transport_error.unwrap_or(match self.status {
+            Some(429) => FetchErrorClass::RateLimited,
+            Some(status) if status >= 500 => FetchErrorClass::ServerError,
+            Some(status) if status >= 400 => FetchErrorClass::ClientError,
+            _ => FetchErrorClass::InvalidResponse,
+        }))
+    }
+}
+
+/// Run a fetch, collecting the requests the exchange client makes for it
+pub async fn measure<F: Future>(fetch: F) -> (F::Output, FetchStats) {
+    let started = Instant::now();
+    FETCH.scope(RefCell::new(FetchStats::default()), async move {
+        let output = fetch.await;
+        let mut stats = FETCH.with(RefCell::take);
+        stats.latency = started.elapsed();
+        (output, stats)
+    }).await
+}
+
+/// Record a response of a request, if a fetch is being measured
+pub(super) fn record_response(status: StatusCode) {
+    let _ = FETCH.try_with(|stats| {
+        let mut stats = stats.borrow_mut();
+        stats.requests += 1;
+        stats.status = Some(status.as_u16());
+        stats.transport_error = None;
+    });
+}
+
+/// Record a request that got no response, if a fetch is being measured
+pub(super) fn record_error(error: &reqwest::Error) {
+    let class = if error.is_timeout() {
+        FetchErrorClass::Timeout
+    } else if error.is_connect() {
+        FetchErrorClass::Connect
+    } else {
+        FetchErrorClass::Transport
+    };
+    let _ = FETCH.try_with(|stats| {
+        let mut stats = stats.borrow_mut();
+        stats.requests += 1;
+        stats.status = None;
+        stats.transport_error = Some(class);
+    });
+}
+
+/// Record a retry, if a fetch is being measured
+pub(super) fn record_retry() {
+    let _ = FETCH.try_with(|stats| stats.borrow_mut().retries += 1);
+}
+
+/// Telemetry of one fetch of a feed's price, stored to analyse exchange latency and reliability
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FetchMetric {
+    pub feed_id: String,
+    pub exchange: String,
+    /// Start of the fetch
+    pub timestamp: DateTime<Utc>,
+    pub latency_ms: f64,
+    /// Status of the last response, absent without one
+    pub status: Option<u16>,
+    pub retries: u32,
+    /// Absent for successful fetches
+    pub error_class: Option<FetchErrorClass>,
+}
+
+impl FetchMetric {
+    pub fn new(feed_id: &str, exchange: &str, timestamp: DateTime<Utc>, stats: &FetchStats, failed: bool) -> Self {
+        Self {
+            feed_id: feed_id.to_string(),
+            exchange: exchange.to_string(),
+            timestamp,
+            latency_ms: stats.latency.as_secs_f64() * 1000.0,
+            status: stats.status,
+            retries: stats.retries,
+            error_class: stats.error_class(failed),
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use super::{auth::{ApiCredentials, BinanceSigner}, rate_limit::RateLimiter, retry::{retry_after, RetryPolicy}};
+use super::cache::{CacheEntry, CachedResponse, ResponseCache};
+use super::telemetry::{self, FetchErrorClass, FetchStats};
+use super::{binance::parse_kline_closes, coinbase::parse_candle_closes};
+
+#[cfg(test)]
+mod rate_limit_tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_is_allowed_then_limited() {
+        let limiter = RateLimiter::new(1.0, 3);
+
+        // The full burst is available immediately
+        for _ in 0..3 {
+            assert!(limiter.try_acquire().is_ok());
+        }
+
+        // The next request has to wait for a refill of roughly one second
+        let wait = limiter.try_acquire().unwrap_err();
+        assert!(wait.as_secs_f64() > 0.9 && wait.as_secs_f64() <= 1.0);
+    }
+
+    #[test]
+    fn test_zero_burst_is_treated_as_one() {
+        let limiter = RateLimiter::new(10.0, 0);
+
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_refill() {
+        let limiter = RateLimiter::new(50.0, 1);
+        let start = std::time::Instant::now();
+
+        limiter.acquire().await;
+        limiter.acquire().await;
+
+        // The second request needs 1/50s worth of refill
+        assert!(start.elapsed().as_millis() >= 15);
+    }
+}
+
+#[cfg(test)]
+mod retry_tests {
+    use super::*;
+    use std::time::Duration;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_exponential_backoff_is_capped() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100), Duration::from_millis(500), 0.0);
+
+        assert_eq!(policy.delay_with_sample(1, 0.5), Duration::from_millis(100));
+        assert_eq!(policy.delay_with_sample(2, 0.5), Duration::from_millis(200));
+        assert_eq!(policy.delay_with_sample(3, 0.5), Duration::from_millis(400));
+        assert_eq!(policy.delay_with_sample(4, 0.5), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_jitter_reduces_delay() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(200), Duration::from_secs(2), 0.5);
+
+        // No jitter applied with a zero sample, at most half the delay removed otherwise
+        assert_eq!(policy.delay_with_sample(1, 0.0), Duration::from_millis(200));
+        assert_eq!(policy.delay_with_sample(1, 1.0), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        assert!(RetryPolicy::is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(RetryPolicy::is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!RetryPolicy::is_retryable_status(StatusCode::BAD_REQUEST));
+        assert!(!RetryPolicy::is_retryable_status(StatusCode::OK));
+    }
+
+    #[test]
+    fn test_rate_limited_retries_wait_longer_or_as_asked() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100), Duration::from_millis(500), 0.0);
+
+        assert_eq!(policy.rate_limited_delay_with_sample(1, None, 0.5), Duration::from_millis(400));
+        assert_eq!(policy.rate_limited_delay_with_sample(4, None, 0.5), Duration::from_millis(2000));
+        assert_eq!(policy.rate_limited_delay_with_sample(1, Some(Duration::from_secs(3)), 0.5), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_retry_after_in_seconds_or_as_a_date() {
+        let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z").unwrap().with_timezone(&chrono::Utc);
+        let headers = |value: &str| {
+            let mut headers = reqwest::header::HeaderMap::new();
+            headers.insert(reqwest::header::RETRY_AFTER, value.parse().unwrap());
+            headers
+        };
+
+        assert_eq!(retry_after(&headers("120"), now), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(&headers("Wed, 21 Oct 2015 07:28:30 GMT"), now), Some(Duration::from_secs(30)));
+        assert_eq!(retry_after(&headers("Wed, 21 Oct 2015 07:27:00 GMT"), now), Some(Duration::ZERO));
+        assert_eq!(retry_after(&headers("soon"), now), None);
+        assert_eq!(retry_after(&reqwest::header::HeaderMap::new(), now), None);
+    }
+
+    #[tokio::test]
+    async fn test_only_connect_errors_and_timeouts_are_retried() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let refused = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+        let error = reqwest::get(&refused).await.unwrap_err();
+        assert!(RetryPolicy::is_retryable_error(&error), "{:?}", error);
+
+        // A server answering with garbage fails the request, but would do so again
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let garbled = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            socket.write_all(b"not http\r\n\r\n").await.unwrap();
+        });
+        let error = reqwest::get(&garbled).await.unwrap_err();
+        assert!(!RetryPolicy::is_retryable_error(&error), "{:?}", error);
+    }
+}
+
+#[cfg(test)]
+mod auth_tests {
+    use super::*;
+
+    #[test]
+    fn test_binance_signature_matches_documented_example() {
+        let signer = BinanceSigner::new(ApiCredentials {
+            api_key: "vmPUZE6mv9SD5VNHk4HlWFsOr6aKE2zvsw0MuIgwCIPy6utIco14y7Ju91duEh8A".to_string(),
+            api_secret: "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j".to_string(),
+            passphrase: None,
+        });
+
+        let query = "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000&timestamp=1499827319559";
+        assert_eq!(signer.signature(query), "c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71");
+    }
+
+    #[test]
+    fn test_debug_output_redacts_secrets() {
+        let credentials = ApiCredentials {
+            api_key: "my-key".to_string(),
+            api_secret: "my-secret".to_string(),
+            passphrase: Some("my-passphrase".to_string()),
+        };
+
+        let output = format!("{:?}", credentials);
+        assert!(!output.contains("my-key") && !output.contains("my-secret") && !output.contains("my-passphrase"));
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+    use std::time::Duration;
+    use reqwest::header::{HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_same_url_shares_a_slot_and_oldest_is_evicted() {
+        let cache = ResponseCache::new(Duration::from_secs(1), 2);
+
+        let first = cache.slot("https://a");
+        assert!(std::sync::Arc::ptr_eq(&first, &cache.slot("https://a")));
+
+        cache.slot("https://b");
+        cache.slot("https://c");
+        assert_eq!(cache.len(), 2);
+        assert!(!std::sync::Arc::ptr_eq(&first, &cache.slot("https://a")));
+    }
+
+    #[test]
+    fn test_entry_revalidates_with_its_validators() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        headers.insert(LAST_MODIFIED, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        let entry = CacheEntry::new(CachedResponse::new(StatusCode::OK, br#"{"price":"1.5"}"#), &headers);
+
+        let conditional = entry.conditional_headers();
+        assert_eq!(conditional[IF_NONE_MATCH], "\"v1\"");
+        assert_eq!(conditional[IF_MODIFIED_SINCE], "Wed, 21 Oct 2015 07:28:00 GMT");
+
+        let body: serde_json::Value = entry.response.json().unwrap();
+        assert_eq!(body["price"], "1.5");
+
+        assert!(ResponseCache::new(Duration::from_secs(60), 1).is_fresh(&entry));
+        assert!(!ResponseCache::new(Duration::ZERO, 1).is_fresh(&entry));
+    }
+}
+
+#[cfg(test)]
+mod telemetry_tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use crate::config::ExchangeConfig;
+    use crate::exchange::http::ExchangeClient;
+
+    #[test]
+    fn test_failed_fetches_are_classified_by_their_last_request() {
+        let stats = |status, transport_error| FetchStats { status, transport_error, ..FetchStats::default() };
+
+        assert_eq!(stats(Some(200), None).error_class(false), None);
+        assert_eq!(stats(Some(200), None).error_class(true), Some(FetchErrorClass::InvalidResponse));
+        assert_eq!(stats(Some(429), None).error_class(true), Some(FetchErrorClass::RateLimited));
+        assert_eq!(stats(Some(503), None).error_class(true), Some(FetchErrorClass::ServerError));
+        assert_eq!(stats(Some(404), None).error_class(true), Some(FetchErrorClass::ClientError));
+        assert_eq!(stats(None, Some(FetchErrorClass::Timeout)).error_class(true), Some(FetchErrorClass::Timeout));
+    }
+
+    #[tokio::test]
+    async fn test_measured_fetches_count_requests_and_retries() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/price", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            for status in ["503 Service Unavailable", "200 OK"] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0; 1024];
+                let _ = socket.read(&mut request).await.unwrap();
+                let response = format!("HTTP/1.1 {}\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{{}}", status);
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        let mut config = ExchangeConfig::default();
+        config.retry.base_delay_ms = 1;
+        let client = ExchangeClient::new(&config).unwrap();
+
+        let (response, stats) = telemetry::measure(client.get(&url)).await;
+
+        assert!(response.unwrap().status().is_success());
+        assert_eq!((stats.requests, stats.retries, stats.status), (2, 1, Some(200)));
+        assert!(stats.latency > std::time::Duration::ZERO);
+    }
+}
+
+#[cfg(test)]
+mod candle_tests {
+    use super::*;
+
+    #[test]
+    fn test_binance_kline_closes_are_parsed_oldest_first() {
+        let body = r#"[
+            [1714564800000, "64000.0", "64100.0", "63900.0", "64050.5", "12.3", 1714564859999, "0", 10, "0", "0", "0"],
+            [1714564860000, "64050.5", "64200.0", "64000.0", "64150.25", "8.1", 1714564919999, "0", 7, "0", "0", "0"]
+        ]"#;
+
+        assert_eq!(parse_kline_closes("BTCUSDT", body).unwrap(), [64050.5, 64150.25]);
+        assert!(parse_kline_closes("BTCUSDT", "[]").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_malformed_binance_klines_are_rejected() {
+        let error = parse_kline_closes("BTCUSDT", r#"[[1714564800000, "64000.0", "64100.0"]]"#).unwrap_err();
+        assert!(error.to_string().contains("Malformed Binance kline for BTCUSDT"), "{}", error);
+        // A close that is not a string, or not a number
+        assert!(parse_kline_closes("BTCUSDT", r#"[[1714564800000, "1", "1", "1", 64050.5]]"#).is_err());
+        assert!(parse_kline_closes("BTCUSDT", r#"[[1714564800000, "1", "1", "1", "not a price"]]"#).is_err());
+        assert!(parse_kline_closes("BTCUSDT", r#"{"code": -1121, "msg": "Invalid symbol."}"#).is_err());
+    }
+
+    #[test]
+    fn test_coinbase_candle_closes_are_the_newest_oldest_first() {
+        let body = "[
+            [1714564920, 63900.0, 64300.0, 64150.0, 64250.0, 3.2],
+            [1714564860, 63950.0, 64200.0, 64050.0, 64150.0, 4.1],
+            [1714564800, 63900.0, 64100.0, 64000.0, 64050.0, 5.0]
+        ]";
+
+        assert_eq!(parse_candle_closes(body, 2).unwrap(), [64150.0, 64250.0]);
+        assert_eq!(parse_candle_closes(body, 10).unwrap(), [64050.0, 64150.0, 64250.0]);
+    }
+
+    #[test]
+    fn test_malformed_coinbase_candles_are_rejected() {
+        assert!(parse_candle_closes("[[1714564800, 63900.0, 64100.0]]", 10).is_err());
+        assert!(parse_candle_closes(r#"{"message": "NotFound"}"#, 10).is_err());
+    }
+}
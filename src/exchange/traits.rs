@@ -1,9 +1,14 @@
 use async_trait::async_trait;
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
 
 /// Trait for cryptocurrency exchange APIs
 #[async_trait]
 pub trait Exchange: Send + Sync {
     /// Fetch the current price for a symbol
     async fn fetch_price(&self, symbol: &str) -> AppResult<f64>;
+
+    /// Fetch the closing prices of the most recent one-minute candles, oldest first
+    async fn fetch_recent_closes(&self, _symbol: &str, _limit: usize) -> AppResult<Vec<f64>> {
+        Err(AppError::Exchange("Candles are not supported by this exchange".to_string()))
+    }
 }
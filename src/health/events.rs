@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::index::IndexQuality;
+use super::FeedHealth;
+
+/// A health transition of a feed or an index, streamed to monitoring consumers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HealthEvent {
+    /// A feed changed health, e.g. went stale or down after repeated failures
+    FeedHealth {
+        feed_id: String,
+        previous: FeedHealth,
+        current: FeedHealth,
+        timestamp: DateTime<Utc>,
+    },
+    /// An index could not be calculated and is no longer published
+    IndexSuppressed {
+        index: String,
+        reason: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// A suppressed index is published again
+    IndexResumed {
+        index: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// An index is published without some of its constituents, or with some served by a backup
+    QualityDegraded {
+        index: String,
+        quality: IndexQuality,
+        timestamp: DateTime<Utc>,
+    },
+    /// An index is published from all of its primary constituents again
+    QualityRestored {
+        index: String,
+        timestamp: DateTime<Utc>,
+    },
+}
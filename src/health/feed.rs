@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::metrics::metrics;
+use crate::notification::{NotificationQueue, Severity};
+use super::HealthEvent;
+
+/// Consecutive failed polls after which a feed is considered down
+const DOWN_AFTER_FAILURES: u32 = 5;
+
+/// Health of a single feed, as maintained by its fetch loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedHealth {
+    /// Polling its primary exchange successfully
+    Healthy,
+    /// Served by its backup source, or failing but not yet down
+    Degraded,
+    /// Failed too many times in a row
+    Down,
+    /// Not failing, but no successful update for too long (e.g. paused or not yet started)
+    Stale,
+}
+
+impl fmt::Display for FeedHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FeedHealth::Healthy => "healthy",
+            FeedHealth::Degraded => "degraded",
+            FeedHealth::Down => "down",
+            FeedHealth::Stale => "stale",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Exported status of a feed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedStatus {
+    pub feed_id: String,
+    pub health: FeedHealth,
+    /// When the feed entered its current health state
+    pub since: DateTime<Utc>,
+    pub consecutive_failures: u32,
+    pub last_success: Option<DateTime<Utc>>,
+    /// Whether the latest price came from the backup source
+    pub on_backup: bool,
+}
+
+/// Health of all feeds, updated by the fetch loops and read by the servers and notifier
+pub struct FeedHealthRegistry {
+    feeds: RwLock<HashMap<String, FeedStatus>>,
+    stale_after: Duration,
+    notifier: Option<NotificationQueue>,
+    events: Option<broadcast::Sender<HealthEvent>>,
+}
+
+impl FeedHealthRegistry {
+    /// Create a registry treating feeds without a successful update for `stale_after_secs` as stale
+    pub fn new(stale_after_secs: u64) -> Self {
+        Self {
+            feeds: RwLock::new(HashMap::new()),
+            stale_after: Duration::seconds(stale_after_secs as i64),
+            notifier: None,
+            events: None,
+        }
+    }
+
+    /// Send health transitions of feeds to the given notifier
+    pub fn with_notifier(mut self, notifier: NotificationQueue) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Stream health transitions of feeds as events to subscribers
+    pub fn with_events(mut self, events: broadcast::Sender<HealthEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Start tracking a feed; it is stale until its first successful update
+    pub fn register(&self, feed_id: &str) {
+        self.feeds.write().unwrap().entry(feed_id.to_string()).or_insert_with(|| FeedStatus {
+            feed_id: feed_id.to_string(),
+            health: FeedHealth::Stale,
+            since: Utc::now(),
+            consecutive_failures: 0,
+            last_success: None,
+            on_backup: false,
+        });
+        self.update_gauges();
+    }
+
+    /// Record a successful poll, from the primary exchange or the backup source
+    pub fn record_success(&self, feed_id: &str, on_backup: bool) {
+        let health = if on_backup { FeedHealth::Degraded } else { FeedHealth::Healthy };
+        self.update(feed_id, |status, now| {
+            status.consecutive_failures = 0;
+            status.last_success = Some(now);
+            status.on_backup = on_backup;
+            health
+        });
+    }
+
+    /// Record a poll that produced no price
+    pub fn record_failure(&self, feed_id: &str) {
+        self.update(feed_id, |status, _| {
+            status.consecutive_failures += 1;
+            if status.consecutive_failures >= DOWN_AFTER_FAILURES {
+                FeedHealth::Down
+            } else {
+                FeedHealth::Degraded
+            }
+        });
+    }
+
+    /// Mark the feed stale if it has not updated recently, e.g. while its polling is paused
+    pub fn check_stale(&self, feed_id: &str) {
+        let stale_after = self.stale_after;
+        self.update(feed_id, |status, now| {
+            let outdated = status.last_success.is_none_or(|last| now - last > stale_after);
+            if outdated && status.health != FeedHealth::Down {
+                FeedHealth::Stale
+            } else {
+                status.health
+            }
+        });
+    }
+
+    /// Current status of a feed
+    pub fn status(&self, feed_id: &str) -> Option<FeedStatus> {
+        self.feeds.read().unwrap().get(feed_id).cloned()
+    }
+
+    /// Current status of all feeds, ordered by feed id
+    pub fn snapshot(&self) -> Vec<FeedStatus> {
+        let mut statuses: Vec<FeedStatus> = self.feeds.read().unwrap().values().cloned().collect();
+        statuses.sort_by(|a, b| a.feed_id.cmp(&b.feed_id));
+        statuses
+    }
+
+    /// Apply a state change and report the transition, if any
+    fn update(&self, feed_id: &str, change: impl FnOnce(&mut FeedStatus, DateTime<Utc>) -> FeedHealth) {
+        let now = Utc::now();
+        let transition = {
+            let mut feeds = self.feeds.write().unwrap();
+            let status = feeds.entry(feed_id.to_string()).or_insert_with(|| FeedStatus {
+                feed_id: feed_id.to_string(),
+                health: FeedHealth::Stale,
+                since: now,
+                consecutive_failures: 0,
+                last_success: None,
+                on_backup: false,
+            });
+
+            let previous = status.health;
+            status.health = change(status, now);
+            if status.health == previous {
+                None
+            } else {
+                status.since = now;
+                Some((previous, status.health))
+            }
+        };
+
+        if let Some((previous, current)) = transition {
+            self.report_transition(feed_id, previous, current);
+            if let Some(events) = &self.events {
+                // Nobody subscribed is fine
+                let _ = events.send(HealthEvent::FeedHealth {
+                    feed_id: feed_id.to_string(),
+                    previous,
+                    current,
+                    timestamp: now,
+                });
+            }
+            self.update_gauges();
+        }
+    }
+
+    fn report_transition(&self, feed_id: &str, previous: FeedHealth, current: FeedHealth) {
+        let message = format!("Feed {} changed from {} to {}", feed_id, previous, current);
+        let severity = match current {
+            FeedHealth::Down => Severity::Error,
+            FeedHealth::Stale => Severity::Warning,
+            FeedHealth::Healthy | FeedHealth::Degraded => Severity::Info,
+        };
+
+        match severity {
+            Severity::Info => info!("[HEALTH] {}", message),
+            _ => warn!("[HEALTH] {}", message),
+        }
+        metrics().increment("feeds.health_transitions");
+
+        // Only outages and recoveries from them are worth an alert, flapping between
+        // healthy and degraded is visible in the logs and status
+        let alert = matches!(current, FeedHealth::Down | FeedHealth::Stale)
+            || matches!(previous, FeedHealth::Down | FeedHealth::Stale) && current == FeedHealth::Healthy;
+        if let (true, Some(notifier)) = (alert, &self.notifier) {
+            notifier.send(severity, message);
+        }
+    }
+
+    fn update_gauges(&self) {
+        let feeds = self.feeds.read().unwrap();
+        for health in [FeedHealth::Healthy, FeedHealth::Degraded, FeedHealth::Down, FeedHealth::Stale] {
+            let count = feeds.values().filter(|status| status.health == health).count();
+            metrics().set_gauge(&format!("feeds.{}", health), count as f64);
+        }
+    }
+}
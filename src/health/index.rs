@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// Health of an index, as of its latest calculation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexHealth {
+    /// Not calculated since the collector started
+    Pending,
+    /// Published from all of its primary constituents
+    Healthy,
+    /// Published without some of its constituents, or with some served by a backup
+    Degraded,
+    /// Could not be calculated and is not published
+    Suppressed,
+}
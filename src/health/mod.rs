@@ -0,0 +1,10 @@
+mod events;
+mod feed;
+mod index;
+
+#[cfg(test)]
+mod tests;
+
+pub use events::HealthEvent;
+pub use feed::{FeedHealth, FeedHealthRegistry, FeedStatus};
+pub use index::IndexHealth;
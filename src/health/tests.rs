@@ -0,0 +1,64 @@
+use super::*;
+
+#[cfg(test)]
+mod feed_health_tests {
+    use super::*;
+
+    #[test]
+    fn test_feed_is_stale_until_first_success() {
+        let registry = FeedHealthRegistry::new(60);
+        registry.register("a");
+        assert_eq!(registry.status("a").unwrap().health, FeedHealth::Stale);
+
+        registry.record_success("a", false);
+        assert_eq!(registry.status("a").unwrap().health, FeedHealth::Healthy);
+    }
+
+    #[test]
+    fn test_failures_degrade_then_take_feed_down() {
+        let registry = FeedHealthRegistry::new(60);
+        registry.record_success("a", false);
+
+        registry.record_failure("a");
+        assert_eq!(registry.status("a").unwrap().health, FeedHealth::Degraded);
+
+        for _ in 0..4 {
+            registry.record_failure("a");
+        }
+        let status = registry.status("a").unwrap();
+        assert_eq!(status.health, FeedHealth::Down);
+        assert_eq!(status.consecutive_failures, 5);
+
+        // Staleness does not mask an outage
+        registry.check_stale("a");
+        assert_eq!(registry.status("a").unwrap().health, FeedHealth::Down);
+    }
+
+    #[test]
+    fn test_backup_source_is_degraded() {
+        let registry = FeedHealthRegistry::new(60);
+        registry.record_success("a", true);
+
+        let status = registry.status("a").unwrap();
+        assert_eq!(status.health, FeedHealth::Degraded);
+        assert!(status.on_backup);
+    }
+
+    #[test]
+    fn test_transitions_are_streamed_as_events() {
+        let (sender, mut events) = tokio::sync::broadcast::channel(16);
+        let registry = FeedHealthRegistry::new(60).with_events(sender);
+        registry.register("a");
+        registry.record_success("a", false);
+        registry.record_success("a", false);
+
+        match events.try_recv().unwrap() {
+            HealthEvent::FeedHealth { feed_id, previous, current, .. } => {
+                assert_eq!(feed_id, "a");
+                assert_eq!((previous, current), (FeedHealth::Stale, FeedHealth::Healthy));
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(events.try_recv().is_err());
+    }
+}
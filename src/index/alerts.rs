@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use tracing::{info, warn};
+
+use crate::metrics::metrics;
+use crate::models::{AlertCondition, AlertRule};
+use crate::notification::Severity;
+use super::models::IndexResult;
+
+/// Raising or clearing of an alert
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub severity: Severity,
+    /// `true` when the alert was raised, `false` when the index is back in line
+    pub raised: bool,
+    pub message: String,
+}
+
+/// Evaluation state of one rule
+#[derive(Debug)]
+struct RuleState {
+    rule: AlertRule,
+    consecutive_breaches: u32,
+    firing: bool,
+    /// Latest (index, reference) values, newest last; only kept for correlation rules
+    samples: VecDeque<(f64, f64)>,
+}
+
+/// Evaluates alert rules comparing published indices with their reference prices
+#[derive(Debug)]
+pub struct AlertMonitor {
+    rules: Vec<RuleState>,
+}
+
+impl AlertMonitor {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        let rules = rules.into_iter()
+            .map(|rule| RuleState { rule, consecutive_breaches: 0, firing: false, samples: VecDeque::new() })
+            .collect();
+        Self { rules }
+    }
+
+    /// Evaluate the rules of the indices in one calculation's results.
+    ///
+    /// `reference_price` returns the current value of a rule's reference, or `None` if it has none
+    /// or it is stale. Rules whose index or reference has no value are skipped without changing
+    /// their state.
+    pub fn evaluate(
+        &mut self,
+        results: &[IndexResult],
+        reference_price: impl Fn(&AlertRule) -> Option<f64>,
+    ) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+
+        for state in &mut self.rules {
+            let Some(value) = results.iter().find(|result| result.name == state.rule.index).map(|result| result.value) else {
+                continue;
+            };
+            let Some(reference) = reference_price(&state.rule).filter(|price| *price > 0.0) else {
+                continue;
+            };
+
+            let breach = match state.rule.condition {
+                AlertCondition::Divergence { max_pct } => {
+                    let divergence = (value - reference) / reference * 100.0;
+                    (divergence.abs() > max_pct)
+                        .then(|| format!("diverges {:.3}% from its reference (limit {}%)", divergence, max_pct))
+                }
+                AlertCondition::Correlation { min, window } => {
+                    state.samples.push_back((value, reference));
+                    if state.samples.len() > window + 1 {
+                        state.samples.pop_front();
+                    }
+                    returns_correlation(&state.samples)
+                        .filter(|correlation| state.samples.len() > window && *correlation < min)
+                        .map(|correlation| format!("return correlation with its reference fell to {:.3} (minimum {})", correlation, min))
+                }
+            };
+
+            match breach {
+                Some(reason) => {
+                    state.consecutive_breaches += 1;
+                    if !state.firing && state.consecutive_breaches >= state.rule.breaches {
+                        state.firing = true;
+                        let message = format!("Alert {}: index {} {}", state.rule.name, state.rule.index, reason);
+                        warn!("[ALERT] {}", message);
+                        metrics().increment("alerts.raised");
+                        events.push(AlertEvent { rule: state.rule.name.clone(), severity: state.rule.severity, raised: true, message });
+                    }
+                }
+                None => {
+                    state.consecutive_breaches = 0;
+                    if state.firing {
+                        state.firing = false;
+                        let message = format!("Alert {}: index {} is back in line with its reference", state.rule.name, state.rule.index);
+                        info!("[ALERT] {}", message);
+                        events.push(AlertEvent { rule: state.rule.name.clone(), severity: Severity::Info, raised: false, message });
+                    }
+                }
+            }
+        }
+
+        events
+    }
+}
+
+/// Pearson correlation of the simple returns of two series, `None` if either series is flat
+fn returns_correlation(samples: &VecDeque<(f64, f64)>) -> Option<f64> {
+    let returns: Vec<(f64, f64)> = samples.iter().zip(samples.iter().skip(1))
+        .map(|((a0, b0), (a1, b1))| (a1 / a0 - 1.0, b1 / b0 - 1.0))
+        .collect();
+    if returns.len() < 2 {
+        return None;
+    }
+
+    let n = returns.len() as f64;
+    let mean_a = returns.iter().map(|(a, _)| a).sum::<f64>() / n;
+    let mean_b = returns.iter().map(|(_, b)| b).sum::<f64>() / n;
+
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (a, b) in &returns {
+        covariance += (a - mean_a) * (b - mean_b);
+        variance_a += (a - mean_a).powi(2);
+        variance_b += (b - mean_b).powi(2);
+    }
+
+    if variance_a == 0.0 || variance_b == 0.0 {
+        return None;
+    }
+
+    Some(covariance / (variance_a.sqrt() * variance_b.sqrt()))
+}
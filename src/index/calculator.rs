@@ -1,22 +1,85 @@
-use std::collections::{HashMap, VecDeque};
-use chrono::Utc;
-use tokio::sync::mpsc;
-use tracing::{error, info, debug};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use chrono::{DateTime, Duration, Utc};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{error, info, debug, warn};
 
-use crate::models::{FeedData, IndexDefinition};
-use crate::smoothing;
-use crate::error::AppResult;
-use super::models::IndexResult;
+use crate::models::{
+    AlertReference, AlertRule, FeedData, IndexDefinition, Methodology, MissingFeeds, PriceFeed, PublishSchedule, WarmUp, Weighting,
+};
+use crate::aggregation::{self, median, weighted_mean};
+use crate::smoothing::{self, SmoothingState};
+use crate::error::{AppError, AppResult};
+use crate::health::{HealthEvent, IndexHealth};
+use crate::sketch::{Distribution, RollingQuantiles};
+use crate::logging::{record_stage, PipelineStage};
+use crate::metrics::metrics;
+use crate::notification::{NotificationQueue, Severity};
+use super::alerts::AlertMonitor;
+use super::change::ChangeTracker;
+use super::decimal::Arithmetic;
+use super::divisor::{market_value, DivisorState};
+use super::returns::ReturnState;
+use super::models::{CalculatorState, IndexQuality, IndexResult, IndexSnapshot, SmoothingSnapshot};
 
-const MAX_HISTORY_SIZE: usize = 20;
+/// Latest values kept per feed and per index
+pub const MAX_HISTORY_SIZE: usize = 20;
+
+/// Constituents of an index that can be used for a calculation, and those left out
+struct Selection<'a> {
+    fresh_feeds: Vec<&'a PriceFeed>,
+    stale_feeds: Vec<&'a PriceFeed>,
+    outlier_feeds: Vec<String>,
+    /// Feeds without a price yet, left out of an index that reweights around them
+    missing_feeds: Vec<String>,
+    /// Feeds updated too long before the most recently updated one
+    lagging_feeds: Vec<String>,
+}
 
 /// Calculator for cryptocurrency indices
-#[derive(Debug)]
 pub struct IndexCalculator {
     indices: Vec<IndexDefinition>,
     feed_values: HashMap<String, f64>,
+    /// Time of each feed's last successful update
+    feed_updated_at: HashMap<String, DateTime<Utc>>,
     feed_history: HashMap<String, VecDeque<f64>>,
     index_history: HashMap<String, VecDeque<f64>>,
+    /// Incremental smoothing state of each index, created once at startup
+    smoothers: HashMap<String, Box<dyn SmoothingState>>,
+    /// Feeds whose latest price came from their backup source
+    feeds_on_backup: HashSet<String>,
+    /// Feeds currently excluded for staleness, to log only the transitions
+    stale_feeds: HashSet<String>,
+    /// Feeds currently dropped as outliers, to log and notify only the transitions
+    outlier_feeds: HashSet<String>,
+    notifier: Option<NotificationQueue>,
+    alerts: AlertMonitor,
+    /// Indices that could not be calculated, to report only the transitions
+    suppressed_indices: HashSet<String>,
+    /// Quality of the indices published with degraded quality, to report only changes
+    degraded_indices: HashMap<String, IndexQuality>,
+    health_events: Option<broadcast::Sender<HealthEvent>>,
+    /// Current weights of the market-cap weighted indices, by index name and feed id
+    market_cap_weights: HashMap<String, HashMap<String, f64>>,
+    /// Weights of rebalanced indices replacing the configured ones, by index name and feed id
+    rebalanced_weights: HashMap<String, HashMap<String, f64>>,
+    /// When each index was last published, to leave stale components out of composites
+    index_published_at: HashMap<String, DateTime<Utc>>,
+    /// Published values of each index over its change windows
+    changes: HashMap<String, ChangeTracker>,
+    /// Divisors of divisor-based indices, and those changed since they were last saved
+    divisors: HashMap<String, DivisorState>,
+    unsaved_divisors: HashSet<String>,
+    /// Levels of log-return indices
+    returns: HashMap<String, ReturnState>,
+    /// Methodology version of every index, while versions are tracked
+    methodology_versions: HashMap<String, u32>,
+    /// Rolling price distribution of each feed
+    tick_distributions: HashMap<String, RollingQuantiles>,
+    distribution_window: Duration,
+    distribution_compression: f64,
+    /// Number of calculation passes so far, every pass reads one consistent set of feed values
+    epoch: u64,
+    arithmetic: Arithmetic,
     receiver: mpsc::Receiver<FeedData>,
 }
 
@@ -26,15 +89,25 @@ impl IndexCalculator {
         indices: Vec<IndexDefinition>,
         receiver: mpsc::Receiver<FeedData>,
     ) -> Self {
+        // Composites are calculated after the indices they are built from
+        let indices = dependency_order(indices);
         let mut feed_values = HashMap::new();
         let mut feed_history = HashMap::new();
         let mut index_history = HashMap::new();
+        let mut smoothers = HashMap::new();
+        let mut changes = HashMap::new();
 
         // Initialize data structures
         for index in &indices {
             index_history.insert(index.name.clone(), VecDeque::with_capacity(MAX_HISTORY_SIZE));
+            // Configurations with an unregistered algorithm are rejected, so this only skips
+            // smoothing of hand-built definitions
+            if let Some(algorithm) = smoothing::create_algorithm(&index.smoothing, index.smoothing_period, index.smoothing_factor) {
+                smoothers.insert(index.name.clone(), algorithm.start());
+            }
+            changes.insert(index.name.clone(), ChangeTracker::new(&index.change_windows_secs));
 
-            for feed in &index.feeds {
+            for feed in index.feeds.iter().chain(&index.conversion_feeds) {
                 feed_values.insert(feed.id.clone(), 0.0);
                 feed_history.insert(feed.id.clone(), VecDeque::with_capacity(MAX_HISTORY_SIZE));
             }
@@ -43,79 +116,889 @@ impl IndexCalculator {
         Self {
             indices,
             feed_values,
+            feed_updated_at: HashMap::new(),
             feed_history,
             index_history,
+            smoothers,
+            feeds_on_backup: HashSet::new(),
+            stale_feeds: HashSet::new(),
+            outlier_feeds: HashSet::new(),
+            notifier: None,
+            alerts: AlertMonitor::new(Vec::new()),
+            suppressed_indices: HashSet::new(),
+            degraded_indices: HashMap::new(),
+            health_events: None,
+            market_cap_weights: HashMap::new(),
+            rebalanced_weights: HashMap::new(),
+            index_published_at: HashMap::new(),
+            changes,
+            divisors: HashMap::new(),
+            unsaved_divisors: HashSet::new(),
+            returns: HashMap::new(),
+            methodology_versions: HashMap::new(),
+            tick_distributions: HashMap::new(),
+            distribution_window: Duration::hours(1),
+            distribution_compression: 100.0,
+            epoch: 0,
+            arithmetic: Arithmetic::Float,
             receiver,
         }
     }
 
+    /// Send alerts (e.g. about dropped outlier feeds) to the given notifier
+    pub fn with_notifier(mut self, notifier: NotificationQueue) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Stream suppression and quality transitions of the indices as health events
+    pub fn with_health_events(mut self, events: broadcast::Sender<HealthEvent>) -> Self {
+        self.health_events = Some(events);
+        self
+    }
+
+    /// Keep the price distribution of each feed over a rolling `window` in a quantile sketch
+    /// with the given compression (more centroids, more accurate)
+    pub fn with_tick_distributions(mut self, window: Duration, compression: f64) -> Self {
+        self.distribution_window = window;
+        self.distribution_compression = compression;
+        self
+    }
+
+    /// Make results exactly reproducible: indices are calculated in name order (composites still
+    /// after their components), constituents in feed id order, and prices aggregated in decimal
+    /// instead of floating-point arithmetic
+    pub fn with_determinism(mut self) -> Self {
+        self.indices.sort_by(|a, b| a.name.cmp(&b.name));
+        self.indices = dependency_order(std::mem::take(&mut self.indices));
+        for index_def in &mut self.indices {
+            index_def.feeds.sort_by(|a, b| a.id.cmp(&b.id));
+            index_def.conversion_feeds.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+        self.arithmetic = Arithmetic::Decimal;
+        self
+    }
+
+    /// Evaluate the given alert rules after every calculation, notifying about raised and cleared alerts
+    pub fn with_alerts(mut self, rules: Vec<AlertRule>) -> Self {
+        self.alerts = AlertMonitor::new(rules);
+        self
+    }
+
+    /// Distinct publication schedules of the indices
+    pub fn publish_schedules(&self) -> Vec<PublishSchedule> {
+        let mut schedules: Vec<PublishSchedule> = Vec::new();
+        for index_def in &self.indices {
+            if !schedules.contains(&index_def.publish) {
+                schedules.push(index_def.publish);
+            }
+        }
+        schedules
+    }
+
     /// Calculate all indices
     pub fn calculate_indices(&mut self) -> AppResult<Vec<IndexResult>> {
+        self.calculate_matching(|_| true, Utc::now())
+    }
+
+    /// Calculate the indices published on the given schedule
+    pub fn calculate_scheduled(&mut self, schedule: &PublishSchedule) -> AppResult<Vec<IndexResult>> {
+        self.calculate_scheduled_at(schedule, Utc::now())
+    }
+
+    /// Calculate the indices published on the given schedule as of `timestamp`, e.g. when
+    /// replaying recorded ticks
+    pub fn calculate_scheduled_at(&mut self, schedule: &PublishSchedule, timestamp: DateTime<Utc>) -> AppResult<Vec<IndexResult>> {
+        self.calculate_matching(|index_def| index_def.publish == *schedule, timestamp)
+    }
+
+    fn calculate_matching(
+        &mut self,
+        include: impl Fn(&IndexDefinition) -> bool,
+        timestamp: DateTime<Utc>,
+    ) -> AppResult<Vec<IndexResult>> {
         // Process any new feed updates
         self.process_feed_updates()?;
 
         let mut results = Vec::new();
-        let timestamp = Utc::now();
+        self.epoch += 1;
+        let mut suppressed = Vec::new();
 
-        for index_def in &self.indices {
-            let mut weighted_sum = 0.0;
-            let mut total_weights = 0;
-            let mut missing_feeds = false;
-
-            for feed in &index_def.feeds {
-                if let Some(&price) = self.feed_values.get(&feed.id) {
-                    if price > 0.0 {
-                        weighted_sum += price * (feed.weight as f64 / 100.0);
-                        total_weights += feed.weight;
-                    } else {
-                        missing_feeds = true;
-                        break;
+        for index_def in self.indices.iter().filter(|index_def| include(index_def)) {
+            let selection = self.select_feeds(index_def, timestamp);
+
+            for feed in &selection.stale_feeds {
+                if self.stale_feeds.insert(feed.id.clone()) {
+                    warn!("[STALENESS] Feed {} has not updated for over {}s, excluding it from index {}",
+                          feed.id, index_def.max_staleness_secs, index_def.name);
+                    metrics().increment("index.stale_feed_exclusions");
+                }
+            }
+            for feed in &selection.fresh_feeds {
+                if self.stale_feeds.remove(&feed.id) {
+                    info!("[STALENESS] Feed {} is updating again, including it in index {}", feed.id, index_def.name);
+                }
+            }
+
+            if !selection.lagging_feeds.is_empty() {
+                debug!("[ALIGNMENT] Index: {}, leaving out feeds lagging over {}ms: {}",
+                       index_def.name, index_def.alignment_window_ms.unwrap_or_default(), selection.lagging_feeds.join(", "));
+                metrics().increment("index.lagging_feed_exclusions");
+            }
+
+            for feed_id in &selection.outlier_feeds {
+                if self.outlier_feeds.insert(feed_id.clone()) {
+                    let message = format!("Feed {} deviates more than {}% from the median of index {}, dropping it",
+                                          feed_id, index_def.max_deviation_pct.unwrap_or_default(), index_def.name);
+                    warn!("[OUTLIER] {}", message);
+                    metrics().increment("index.outlier_feed_exclusions");
+                    if let Some(notifier) = &self.notifier {
+                        notifier.send(Severity::Warning, message);
                     }
-                } else {
-                    missing_feeds = true;
-                    break;
+                }
+            }
+            for feed in &selection.fresh_feeds {
+                if self.outlier_feeds.remove(&feed.id) {
+                    info!("[OUTLIER] Feed {} is back in line with index {}", feed.id, index_def.name);
                 }
             }
 
-            if missing_feeds || total_weights == 0 {
-                continue;
+            let (composite_value, stale_components) = self.composite_value(index_def, timestamp, &HashMap::new());
+
+            // An index of one or two surviving feeds would be misleading
+            if let Some(min_feeds) = index_def.min_feeds {
+                let available = if index_def.is_composite() {
+                    index_def.constituent_names().len() - stale_components.len()
+                } else {
+                    selection.fresh_feeds.iter().filter(|feed| converted_price(&self.feed_values, feed).is_some_and(|price| price > 0.0)).count()
+                };
+                if available < min_feeds {
+                    if !self.suppressed_indices.contains(&index_def.name) {
+                        let message = format!("Index {} withheld: only {} of the required {} constituents are fresh",
+                                              index_def.name, available, min_feeds);
+                        warn!("[QUORUM] {}", message);
+                        metrics().increment("index.quorum_failures");
+                        if let Some(notifier) = &self.notifier {
+                            notifier.send(Severity::Warning, message);
+                        }
+                    }
+                    suppressed.push((index_def.name.clone(), "constituent quorum not met"));
+                    continue;
+                }
             }
 
-            let raw_index_value = weighted_sum / (total_weights as f64 / 100.0);
+            let raw_index_value = match index_def.methodology {
+                _ if index_def.is_composite() => composite_value,
+                Methodology::Average => self.raw_value(index_def, &selection.fresh_feeds),
+                Methodology::Divisor => self.divisor_state(index_def, &selection.fresh_feeds).map(|state| {
+                    if let Some(previous) = self.divisors.get(&index_def.name).filter(|previous| previous.divisor != state.divisor) {
+                        info!("[DIVISOR] Index: {}, constituents or weights changed, divisor {} -> {} at level {}",
+                              index_def.name, previous.divisor, state.divisor, state.level);
+                        metrics().increment("index.divisor_adjustments");
+                    }
+                    let level = state.level;
+                    self.divisors.insert(index_def.name.clone(), state);
+                    self.unsaved_divisors.insert(index_def.name.clone());
+                    level
+                }),
+                Methodology::LogReturn => self.return_state(index_def, &selection.fresh_feeds, timestamp).map(|state| {
+                    if !self.returns.contains_key(&index_def.name) {
+                        info!("[ANCHOR] Index: {}, anchored at level {}", index_def.name, state.level);
+                    }
+                    let level = state.level;
+                    self.returns.insert(index_def.name.clone(), state);
+                    level
+                }),
+            };
+            let Some(raw_index_value) = raw_index_value.and_then(|value| index_def.transformed(value)) else {
+                let reason = if index_def.expression.is_some() {
+                    "expression references without a fresh value"
+                } else if !index_def.components.is_empty() {
+                    "no fresh component indices"
+                } else if selection.fresh_feeds.is_empty() {
+                    "no fresh constituent feeds"
+                } else {
+                    "missing constituent prices"
+                };
+                suppressed.push((index_def.name.clone(), reason));
+                continue;
+            };
             
             // Log raw index value before smoothing
             debug!("[CALCULATION] Index: {}, Raw Value: {}", index_def.name, raw_index_value);
             
-            // Apply smoothing algorithm
-            let smoothing_algo = smoothing::create_algorithm(&index_def.smoothing);
-            let index_history = self.index_history.entry(index_def.name.clone()).or_default();
-            let smoothed_value = smoothing_algo.apply(index_history, raw_index_value);
+            // Apply smoothing algorithm to the value clamped to the index's band
+            let smoothing_input = self.winsorized(index_def, raw_index_value);
+            if smoothing_input != raw_index_value {
+                debug!("[WINSORIZE] Index: {}, Raw: {} clamped to {}", index_def.name, raw_index_value, smoothing_input);
+                metrics().increment("index.winsorized_values");
+            }
+            let (smoothed_value, warming_up) = match self.smoothers.get_mut(&index_def.name) {
+                Some(smoother) => (smoother.update(smoothing_input), !smoother.is_warm()),
+                None => (smoothing_input, false),
+            };
             
             // Log the smoothing effect
             info!("[SMOOTHING] Index: {}, Algorithm: {:?}, Raw: {}, Smoothed: {}, Diff: {:.4}%", 
                  index_def.name, index_def.smoothing, raw_index_value, smoothed_value, 
                  (smoothed_value - raw_index_value) / raw_index_value * 100.0);
 
+            if warming_up && index_def.warm_up == WarmUp::Withhold {
+                suppressed.push((index_def.name.clone(), "smoothing warming up"));
+                continue;
+            }
+
             // Update history
+            let index_history = self.index_history.entry(index_def.name.clone()).or_default();
             index_history.push_front(smoothed_value);
             if index_history.len() > MAX_HISTORY_SIZE {
                 index_history.pop_back();
             }
 
-            results.push(IndexResult {
-                name: index_def.name.clone(),
-                timestamp,
-                value: smoothed_value,
-            });
+            self.index_published_at.insert(index_def.name.clone(), timestamp);
+            let mut result = self.result(index_def, timestamp, smoothed_value, selection, stale_components);
+            result.raw_value = Some(raw_index_value);
+            result.warming_up = warming_up;
+            if let Some(changes) = self.changes.get_mut(&index_def.name) {
+                result.change = changes.change(timestamp, smoothed_value);
+                changes.record(timestamp, smoothed_value);
+            }
+            results.push(result);
         }
 
         if results.is_empty() {
             error!("Failed to calculate any indices - missing price data");
         }
 
+        self.track_index_health(&results, &suppressed, timestamp);
+
+        // Compare indices with their reference prices, ignoring reference feeds that stopped updating.
+        // A reference index may be published on another schedule, so its latest value is used.
+        let reference_price = |rule: &AlertRule| match &rule.reference {
+            AlertReference::Feed(feed) => {
+                let max_staleness = self.indices.iter().find(|index| index.name == rule.index)?.max_staleness_secs;
+                let updated_at = self.feed_updated_at.get(&feed.id)?;
+                if timestamp - *updated_at > Duration::seconds(max_staleness as i64) {
+                    return None;
+                }
+                self.feed_values.get(&feed.id).copied()
+            }
+            AlertReference::Index(name) => self.index_history.get(name)?.front().copied(),
+        };
+        for event in self.alerts.evaluate(&results, reference_price) {
+            if let Some(notifier) = &self.notifier {
+                notifier.send(event.severity, event.message);
+            }
+        }
+
         Ok(results)
     }
 
+    /// Report indices that stopped or resumed publishing, or whose quality changed
+    fn track_index_health(&mut self, results: &[IndexResult], suppressed: &[(String, &str)], timestamp: DateTime<Utc>) {
+        let mut events = Vec::new();
+
+        for (index, reason) in suppressed {
+            if self.suppressed_indices.insert(index.clone()) {
+                warn!("[HEALTH] Index {} is suppressed: {}", index, reason);
+                events.push(HealthEvent::IndexSuppressed { index: index.clone(), reason: reason.to_string(), timestamp });
+            }
+        }
+
+        for result in results {
+            if self.suppressed_indices.remove(&result.name) {
+                info!("[HEALTH] Index {} is published again", result.name);
+                events.push(HealthEvent::IndexResumed { index: result.name.clone(), timestamp });
+            }
+
+            let quality = &result.quality;
+            let degraded = quality.is_degraded();
+            let reported = self.degraded_indices.get(&result.name).is_some_and(|previous| previous.same_exclusions(quality));
+            if degraded && !reported {
+                info!("[HEALTH] Index {} quality degraded: {:?}", result.name, quality);
+                self.degraded_indices.insert(result.name.clone(), quality.clone());
+                events.push(HealthEvent::QualityDegraded { index: result.name.clone(), quality: quality.clone(), timestamp });
+            } else if !degraded && self.degraded_indices.remove(&result.name).is_some() {
+                info!("[HEALTH] Index {} quality restored", result.name);
+                events.push(HealthEvent::QualityRestored { index: result.name.clone(), timestamp });
+            }
+        }
+
+        if let Some(sender) = &self.health_events {
+            for event in events {
+                // Nobody subscribed is fine
+                let _ = sender.send(event);
+            }
+        }
+    }
+
+    /// Health of an index as of its latest calculation
+    pub fn index_health(&self, index: &str) -> IndexHealth {
+        if self.suppressed_indices.contains(index) {
+            IndexHealth::Suppressed
+        } else if self.degraded_indices.contains_key(index) {
+            IndexHealth::Degraded
+        } else if self.index_published_at.contains_key(index) {
+            IndexHealth::Healthy
+        } else {
+            IndexHealth::Pending
+        }
+    }
+
+    /// Price distributions of all feeds over their rolling window, ordered by feed id
+    pub fn feed_distributions(&self) -> Vec<Distribution> {
+        let mut distributions: Vec<Distribution> = self.tick_distributions.iter()
+            .filter_map(|(feed_id, distribution)| distribution.distribution(feed_id))
+            .collect();
+        distributions.sort_by(|a, b| a.feed_id.cmp(&b.feed_id));
+        distributions
+    }
+
+    /// Calculate every index against the same feed values in one calculation epoch, e.g. for
+    /// cross-index ratios at an instant.
+    ///
+    /// Nothing is published: smoothing state, history and staleness/outlier tracking are left as
+    /// they are, each value is what its index would publish if it were calculated now.
+    pub fn snapshot(&mut self) -> AppResult<IndexSnapshot> {
+        self.process_feed_updates()?;
+
+        let timestamp = Utc::now();
+        self.epoch += 1;
+
+        // Composites are built from the values of this snapshot, not the last published ones
+        let mut values = HashMap::new();
+        let mut indices = Vec::new();
+        for index_def in &self.indices {
+            let selection = self.select_feeds(index_def, timestamp);
+            let (composite_value, stale_components) = self.composite_value(index_def, timestamp, &values);
+            let raw_index_value = match index_def.methodology {
+                _ if index_def.is_composite() => composite_value,
+                Methodology::Average => self.raw_value(index_def, &selection.fresh_feeds),
+                Methodology::Divisor => self.divisor_state(index_def, &selection.fresh_feeds).map(|state| state.level),
+                Methodology::LogReturn => self.return_state(index_def, &selection.fresh_feeds, timestamp).map(|state| state.level),
+            };
+            let Some(raw_index_value) = raw_index_value.and_then(|value| index_def.transformed(value)) else {
+                continue;
+            };
+            let smoothing_input = self.winsorized(index_def, raw_index_value);
+            let (value, warming_up) = match self.smoothers.get(&index_def.name) {
+                Some(smoother) => (smoother.preview(smoothing_input), !smoother.is_warm()),
+                None => (smoothing_input, false),
+            };
+            if warming_up && index_def.warm_up == WarmUp::Withhold {
+                continue;
+            }
+            values.insert(index_def.name.clone(), value);
+            let mut result = self.result(index_def, timestamp, value, selection, stale_components);
+            result.raw_value = Some(raw_index_value);
+            result.warming_up = warming_up;
+            if let Some(changes) = self.changes.get(&index_def.name) {
+                result.change = changes.change(timestamp, value);
+            }
+            indices.push(result);
+        }
+
+        Ok(IndexSnapshot { epoch: self.epoch, timestamp, indices })
+    }
+
+    /// Split the constituents of an index into fresh feeds, feeds (or conversion rates) that
+    /// stopped updating, feeds lagging outside the alignment window, feeds printing prices far
+    /// away from the other exchanges and, if the index reweights around them, feeds without a
+    /// price
+    fn select_feeds<'a>(&self, index_def: &'a IndexDefinition, timestamp: DateTime<Utc>) -> Selection<'a> {
+        let max_staleness = Duration::seconds(index_def.max_staleness_secs as i64);
+        let is_stale = |feed_id: &String| {
+            self.feed_updated_at.get(feed_id).is_some_and(|updated_at| timestamp - *updated_at > max_staleness)
+        };
+        let (mut fresh_feeds, stale_feeds): (Vec<&PriceFeed>, Vec<&PriceFeed>) = index_def.feeds.iter()
+            .partition(|feed| !is_stale(&feed.id) && !feed.conversion.as_ref().is_some_and(|c| is_stale(&c.feed_id)));
+
+        let mut missing_feeds = Vec::new();
+        if index_def.missing_feeds == MissingFeeds::Reweight {
+            fresh_feeds.retain(|feed| {
+                let priced = converted_price(&self.feed_values, feed).is_some_and(|price| price > 0.0);
+                if !priced {
+                    missing_feeds.push(feed.id.clone());
+                }
+                priced
+            });
+        }
+
+        // Mixing a print from a moment ago with one from seconds ago skews the index in fast moves
+        let mut lagging_feeds = Vec::new();
+        if let Some(window_ms) = index_def.alignment_window_ms {
+            let newest = fresh_feeds.iter().filter_map(|feed| self.feed_updated_at.get(&feed.id)).max().copied();
+            if let Some(newest) = newest {
+                let aligned_since = newest - Duration::milliseconds(window_ms as i64);
+                fresh_feeds.retain(|feed| {
+                    let aligned = self.feed_updated_at.get(&feed.id).is_none_or(|updated_at| *updated_at >= aligned_since);
+                    if !aligned {
+                        lagging_feeds.push(feed.id.clone());
+                    }
+                    aligned
+                });
+            }
+        }
+
+        let mut outlier_feeds = Vec::new();
+        if let Some(max_deviation_pct) = index_def.max_deviation_pct {
+            let prices: Vec<(&str, f64)> = fresh_feeds.iter()
+                .filter_map(|feed| converted_price(&self.feed_values, feed).map(|price| (feed.id.as_str(), price)))
+                .filter(|(_, price)| *price > 0.0)
+                .collect();
+            outlier_feeds = find_outliers(&prices, max_deviation_pct);
+            fresh_feeds.retain(|feed| !outlier_feeds.contains(&feed.id));
+        }
+
+        Selection { fresh_feeds, stale_feeds, outlier_feeds, missing_feeds, lagging_feeds }
+    }
+
+    /// Index value of the selected feeds, before smoothing
+    fn raw_value(&self, index_def: &IndexDefinition, feeds: &[&PriceFeed]) -> Option<f64> {
+        aggregate(
+            index_def,
+            feeds.iter().copied(),
+            |feed| self.weight_of(index_def, feed),
+            |feed| converted_price(&self.feed_values, feed),
+            self.arithmetic,
+        )
+    }
+
+    /// Value of a composite index from the latest values of its component indices, renormalizing
+    /// the weights of components not published within the index's max staleness, which are
+    /// returned as well. `current` holds values of this calculation that are not published yet.
+    ///
+    /// A derived index evaluates its expression instead, which has no value while any index or
+    /// feed it references is stale.
+    fn composite_value(
+        &self,
+        index_def: &IndexDefinition,
+        timestamp: DateTime<Utc>,
+        current: &HashMap<String, f64>,
+    ) -> (Option<f64>, Vec<String>) {
+        let max_staleness = Duration::seconds(index_def.max_staleness_secs as i64);
+        let index_value = |name: &str| current.get(name).copied().or_else(|| {
+            let published_at = self.index_published_at.get(name)?;
+            if timestamp - *published_at > max_staleness {
+                return None;
+            }
+            self.index_history.get(name)?.front().copied()
+        });
+        let mut stale = Vec::new();
+
+        if let Some(expression) = &index_def.expression {
+            let is_index = |name: &str| self.indices.iter().any(|other| other.name == name);
+            let feed_value = |name: &str| {
+                let updated_at = self.feed_updated_at.get(name)?;
+                if timestamp - *updated_at > max_staleness {
+                    return None;
+                }
+                self.feed_values.get(name).copied().filter(|price| *price > 0.0)
+            };
+            let value = expression.eval(&mut |name| {
+                let value = if is_index(name) { index_value(name) } else { feed_value(name) };
+                if value.is_none() && !stale.iter().any(|stale| stale == name) {
+                    stale.push(name.to_string());
+                }
+                value
+            });
+            return (value, stale);
+        }
+
+        let mut values = Vec::new();
+        for component in &index_def.components {
+            match index_value(&component.index) {
+                Some(value) => values.push((value, component.weight as f64)),
+                None => stale.push(component.index.clone()),
+            }
+        }
+
+        (weighted_mean(&values, self.arithmetic), stale)
+    }
+
+    /// State of a log-return index moved to the current prices of the selected feeds, anchored
+    /// at its base level if it has none yet. `None` if a feed has no price.
+    fn return_state(&self, index_def: &IndexDefinition, feeds: &[&PriceFeed], timestamp: DateTime<Utc>) -> Option<ReturnState> {
+        let mut prices = BTreeMap::new();
+        let mut weights = BTreeMap::new();
+        for feed in feeds {
+            let price = converted_price(&self.feed_values, feed).filter(|price| *price > 0.0)?;
+            prices.insert(feed.id.clone(), price);
+            weights.insert(feed.id.clone(), self.weight_of(index_def, feed));
+        }
+        if prices.is_empty() {
+            return None;
+        }
+
+        Some(match self.returns.get(&index_def.name) {
+            Some(state) => state.update(prices, &weights),
+            None => ReturnState::new(prices, index_def.base_level, timestamp),
+        })
+    }
+
+    /// Divisor state of a divisor-based index for the selected feeds at current prices, with the
+    /// divisor adjusted if constituents or weights changed. `None` if a feed has no price.
+    fn divisor_state(&self, index_def: &IndexDefinition, feeds: &[&PriceFeed]) -> Option<DivisorState> {
+        let price_of = |feed_id: &str| {
+            let feed = index_def.feeds.iter().find(|feed| feed.id == feed_id)?;
+            converted_price(&self.feed_values, feed)
+        };
+        let constituents: BTreeMap<String, f64> = feeds.iter()
+            .map(|feed| (feed.id.clone(), self.weight_of(index_def, feed)))
+            .collect();
+        let value = market_value(&constituents, price_of)?;
+
+        Some(match self.divisors.get(&index_def.name) {
+            Some(state) => state.update(constituents, value, market_value(&state.constituents, price_of)),
+            None => DivisorState::new(constituents, value, index_def.base_level),
+        })
+    }
+
+    /// Restore the divisors of divisor-based indices, e.g. saved before a restart
+    pub fn set_divisors(&mut self, divisors: HashMap<String, DivisorState>) {
+        for (index, state) in divisors {
+            let Some(index_def) = self.indices.iter().find(|index_def| index_def.name == index) else {
+                continue;
+            };
+            if index_def.methodology != Methodology::Divisor {
+                continue;
+            }
+            info!("[DIVISOR] Index: {}, restored divisor {} at level {}", index, state.divisor, state.level);
+            self.divisors.insert(index, state);
+        }
+    }
+
+    /// Stamp the values of every index with its methodology version, see
+    /// [`crate::storage::Database::register_methodologies`]
+    pub fn set_methodology_versions(&mut self, versions: HashMap<String, u32>) {
+        self.methodology_versions = versions;
+    }
+
+    /// Smoothing state, latest values, divisors and return levels of every index and the latest
+    /// prices of every feed
+    pub fn state(&self) -> CalculatorState {
+        let smoothing = self.indices.iter()
+            .filter_map(|index_def| {
+                let smoother = self.smoothers.get(&index_def.name)?;
+                let snapshot = SmoothingSnapshot {
+                    smoothing: index_def.smoothing.clone(),
+                    state: smoother.save(),
+                    history: self.index_history.get(&index_def.name).map(|history| history.iter().copied().collect()).unwrap_or_default(),
+                };
+                Some((index_def.name.clone(), snapshot))
+            })
+            .collect();
+        let divisors = self.divisors.iter().map(|(index, state)| (index.clone(), state.clone())).collect();
+        let feed_history = self.feed_history.iter()
+            .filter(|(_, history)| !history.is_empty())
+            .map(|(feed_id, history)| (feed_id.clone(), history.iter().copied().collect()))
+            .collect();
+
+        let returns = self.returns.iter().map(|(index, state)| (index.clone(), state.clone())).collect();
+
+        CalculatorState { smoothing, divisors, feed_history, returns, index_history: BTreeMap::new() }
+    }
+
+    /// Continue from a state taken with [`IndexCalculator::state`], e.g. by another collector.
+    ///
+    /// Smoothing states of unknown indices, or of indices whose smoothing algorithm changed since,
+    /// are skipped, as are feeds no index uses anymore.
+    pub fn restore_state(&mut self, state: CalculatorState) {
+        let mut restored = HashSet::new();
+        for (index, snapshot) in state.smoothing {
+            let Some(index_def) = self.indices.iter().find(|index_def| index_def.name == index) else {
+                continue;
+            };
+            if index_def.smoothing != snapshot.smoothing {
+                warn!("[RESTORE] Index: {}, smoothing changed from {:?} to {:?}, starting afresh",
+                      index, snapshot.smoothing, index_def.smoothing);
+                continue;
+            }
+            let Some(smoother) = self.smoothers.get_mut(&index) else {
+                continue;
+            };
+            smoother.restore(&snapshot.state);
+            self.index_history.insert(index.clone(), snapshot.history.into_iter().take(MAX_HISTORY_SIZE).collect());
+            info!("[RESTORE] Index: {}, restored smoothing state", index);
+            restored.insert(index);
+        }
+
+        // Without a state to continue from, smoothing is warmed up by replaying the stored values
+        for (index, values) in state.index_history {
+            if restored.contains(&index) || values.is_empty() {
+                continue;
+            }
+            let Some(smoother) = self.smoothers.get_mut(&index) else {
+                continue;
+            };
+            for stored in values.iter().rev() {
+                smoother.update(stored.raw_value.unwrap_or(stored.value));
+            }
+            self.index_history.insert(index.clone(), values.iter().take(MAX_HISTORY_SIZE).map(|stored| stored.value).collect());
+            info!("[RESTORE] Index: {}, warmed up smoothing from {} stored values", index, values.len());
+        }
+
+        for (feed_id, history) in state.feed_history {
+            if let Some(feed_history) = self.feed_history.get_mut(&feed_id) {
+                *feed_history = history.into_iter().take(MAX_HISTORY_SIZE).collect();
+            }
+        }
+
+        for (index, state) in state.returns {
+            let log_return = self.indices.iter().find(|index_def| index_def.name == index && index_def.methodology == Methodology::LogReturn);
+            if let Some(index_def) = log_return {
+                info!("[ANCHOR] Index: {}, restored level {} anchored at {} (log return {:.6})",
+                      index, state.level, state.anchored_at, state.log_return(index_def.base_level));
+                self.returns.insert(index, state);
+            }
+        }
+
+        self.set_divisors(state.divisors.into_iter().collect());
+    }
+
+    /// Raw value clamped to within the index's `smoothing_band_pct` of its previous smoothed
+    /// value; unchanged without a band or a previous value
+    fn winsorized(&self, index_def: &IndexDefinition, raw_value: f64) -> f64 {
+        let previous = self.index_history.get(&index_def.name).and_then(|history| history.front());
+        let (Some(band_pct), Some(previous)) = (index_def.smoothing_band_pct, previous) else {
+            return raw_value;
+        };
+        let band = previous.abs() * band_pct / 100.0;
+        raw_value.clamp(previous - band, previous + band)
+    }
+
+    /// Divisors changed since the last call, to be saved
+    pub fn take_unsaved_divisors(&mut self) -> Vec<(String, DivisorState)> {
+        self.unsaved_divisors.drain()
+            .filter_map(|index| self.divisors.get(&index).map(|state| (index, state.clone())))
+            .collect()
+    }
+
+    /// Current weight of a constituent, in percent. Market-cap weighted constituents weigh
+    /// nothing until the first market caps arrive.
+    fn weight_of(&self, index_def: &IndexDefinition, feed: &PriceFeed) -> f64 {
+        match index_def.weighting {
+            Weighting::Static => self.rebalanced_weights.get(&index_def.name)
+                .and_then(|weights| weights.get(&feed.id))
+                .copied()
+                .unwrap_or_else(|| static_weight(feed)),
+            Weighting::MarketCap => self.market_cap_weights.get(&index_def.name)
+                .and_then(|weights| weights.get(&feed.id))
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Reweight the market-cap weighted indices with fresh market caps by asset id. An index
+    /// keeps its previous weights if the cap of one of its assets is missing.
+    pub fn set_market_caps(&mut self, market_caps: &HashMap<String, f64>) {
+        for index_def in self.indices.iter().filter(|index_def| index_def.weighting == Weighting::MarketCap) {
+            match market_cap_weights(&index_def.feeds, market_caps) {
+                Some(weights) => {
+                    let mut summary: Vec<String> = weights.iter()
+                        .map(|(feed_id, weight)| format!("{}={:.2}%", feed_id, weight))
+                        .collect();
+                    summary.sort();
+                    info!("[MARKET CAP] Index: {}, reweighted: {}", index_def.name, summary.join(", "));
+                    self.market_cap_weights.insert(index_def.name.clone(), weights);
+                }
+                None => warn!("[MARKET CAP] Index: {}, missing market caps, keeping the previous weights", index_def.name),
+            }
+        }
+    }
+
+    /// Rebalance a statically weighted index to new weights of its feeds, in percent. The
+    /// divisor of a divisor-based index is adjusted at current prices in the same step, so the
+    /// level does not jump.
+    pub fn rebalance(&mut self, index: &str, weights: HashMap<String, f64>) -> AppResult<()> {
+        let index_def = self.indices.iter()
+            .find(|index_def| index_def.name == index && index_def.weighting == Weighting::Static && !index_def.is_composite())
+            .ok_or_else(|| AppError::IndexCalculation(format!("{} is not a statically weighted index of feeds", index)))?;
+        if let Some(feed_id) = weights.keys().find(|feed_id| !index_def.feeds.iter().any(|feed| &feed.id == *feed_id)) {
+            return Err(AppError::IndexCalculation(format!("Feed {} is not a constituent of index {}", feed_id, index)));
+        }
+        let total_weight: f64 = weights.values().sum();
+        if weights.len() != index_def.feeds.len() || (total_weight - 100.0).abs() > 1e-9 {
+            return Err(AppError::IndexCalculation(format!(
+                "Weights of index {} must cover all {} feeds and sum to 100, got {} summing to {}",
+                index, index_def.feeds.len(), weights.len(), total_weight
+            )));
+        }
+
+        if let Some(state) = self.divisors.get(index) {
+            let price_of = |feed_id: &str| {
+                let feed = index_def.feeds.iter().find(|feed| feed.id == feed_id)?;
+                converted_price(&self.feed_values, feed)
+            };
+            let constituents: BTreeMap<String, f64> = state.constituents.keys()
+                .map(|feed_id| (feed_id.clone(), weights[feed_id]))
+                .collect();
+            match market_value(&constituents, price_of) {
+                Some(value) => {
+                    let adjusted = state.update(constituents, value, market_value(&state.constituents, price_of));
+                    info!("[REBALANCE] Index: {}, divisor {} -> {} at level {}", index, state.divisor, adjusted.divisor, adjusted.level);
+                    self.divisors.insert(index.to_string(), adjusted);
+                    self.unsaved_divisors.insert(index.to_string());
+                }
+                // Adjusted against the latest level once prices are back
+                None => warn!("[REBALANCE] Index: {}, constituents without a price, adjusting the divisor on the next calculation", index),
+            }
+        }
+
+        let mut summary: Vec<String> = weights.iter()
+            .map(|(feed_id, weight)| format!("{}={}%", feed_id, weight))
+            .collect();
+        summary.sort();
+        info!("[REBALANCE] Index: {}, reweighted: {}", index, summary.join(", "));
+        metrics().increment("index.rebalances");
+        self.rebalanced_weights.insert(index.to_string(), weights);
+        Ok(())
+    }
+
+    fn result(
+        &self,
+        index_def: &IndexDefinition,
+        timestamp: DateTime<Utc>,
+        value: f64,
+        selection: Selection,
+        stale_components: Vec<String>,
+    ) -> IndexResult {
+        let failover_feeds = index_def.feeds.iter()
+            .filter(|feed| self.feeds_on_backup.contains(&feed.id))
+            .map(|feed| feed.id.clone())
+            .collect();
+
+        let age_secs = |updated_at: Option<&DateTime<Utc>>| {
+            updated_at.map_or(0.0, |updated_at| ((timestamp - *updated_at).num_milliseconds() as f64 / 1000.0).max(0.0))
+        };
+        let (contributors, max_age_secs, dispersion_pct) = if !index_def.is_composite() {
+            let prices: Vec<f64> = selection.fresh_feeds.iter()
+                .filter_map(|feed| converted_price(&self.feed_values, feed).filter(|price| *price > 0.0))
+                .collect();
+            let max_age_secs = selection.fresh_feeds.iter()
+                .map(|feed| age_secs(self.feed_updated_at.get(&feed.id)))
+                .fold(0.0, f64::max);
+            (prices.len(), max_age_secs, dispersion_pct(&prices))
+        } else {
+            // Components are different assets, so their values do not disperse around a price
+            let fresh_constituents: Vec<&str> = index_def.constituent_names().into_iter()
+                .filter(|name| !stale_components.iter().any(|stale| stale == name))
+                .collect();
+            let max_age_secs = fresh_constituents.iter()
+                .map(|name| age_secs(self.index_published_at.get(*name).or_else(|| self.feed_updated_at.get(*name))))
+                .fold(0.0, f64::max);
+            (fresh_constituents.len(), max_age_secs, None)
+        };
+        let constituents = index_def.feeds.len() + index_def.constituent_names().len();
+
+        IndexResult {
+            name: index_def.name.clone(),
+            timestamp,
+            value,
+            raw_value: None,
+            epoch: self.epoch,
+            denomination: index_def.denomination.clone(),
+            change: Default::default(),
+            methodology_version: self.methodology_versions.get(&index_def.name).copied().unwrap_or_default(),
+            warming_up: false,
+            quality: IndexQuality {
+                failover_feeds,
+                stale_feeds: selection.stale_feeds.iter().map(|feed| feed.id.clone()).chain(stale_components).collect(),
+                outlier_feeds: selection.outlier_feeds,
+                missing_feeds: selection.missing_feeds,
+                lagging_feeds: selection.lagging_feeds,
+                contributors,
+                max_age_secs,
+                dispersion_pct,
+                confidence: confidence(contributors, constituents, max_age_secs, index_def.max_staleness_secs, dispersion_pct),
+            },
+        }
+    }
+
+    /// Seed feed and index histories from historical closes (oldest first, keyed by feed id).
+    ///
+    /// The series of an index's feeds are aligned on their most recent close and replayed through
+    /// the index's smoothing algorithm, so smoothing starts warm instead of converging over the
+    /// first published values.
+    pub fn bootstrap_history(&mut self, closes: &HashMap<String, Vec<f64>>) {
+        for (feed_id, series) in closes {
+            if let Some(history) = self.feed_history.get_mut(feed_id) {
+                for &price in series {
+                    history.push_front(price);
+                    if history.len() > MAX_HISTORY_SIZE {
+                        history.pop_back();
+                    }
+                }
+            }
+        }
+
+        for index_def in &self.indices {
+            let feed_ids: Vec<&String> = index_def.feeds.iter().chain(&index_def.conversion_feeds)
+                .map(|feed| &feed.id)
+                .collect();
+
+            let Some(steps) = feed_ids.iter().map(|id| closes.get(*id).map(Vec::len)).min().flatten() else {
+                debug!("[BOOTSTRAP] Index: {}, no historical closes for all feeds, skipping", index_def.name);
+                continue;
+            };
+
+            // Divisor-based levels can only be replayed with a divisor restored from a previous run
+            let divisor = match index_def.methodology {
+                Methodology::Average => None,
+                Methodology::Divisor => match self.divisors.get(&index_def.name) {
+                    Some(state) => Some(state.divisor),
+                    None => {
+                        debug!("[BOOTSTRAP] Index: {}, no divisor yet, skipping", index_def.name);
+                        continue;
+                    }
+                },
+                // Replayed closes would move the level away from its anchor
+                Methodology::LogReturn => {
+                    debug!("[BOOTSTRAP] Index: {}, log-return levels are not bootstrapped, skipping", index_def.name);
+                    continue;
+                }
+            };
+
+            let weights: HashMap<&str, f64> = index_def.feeds.iter()
+                .map(|feed| (feed.id.as_str(), self.weight_of(index_def, feed)))
+                .collect();
+            let constituents: BTreeMap<String, f64> = weights.iter()
+                .map(|(feed_id, weight)| (feed_id.to_string(), *weight))
+                .collect();
+            let index_history = self.index_history.entry(index_def.name.clone()).or_default();
+            let Some(smoother) = self.smoothers.get_mut(&index_def.name) else {
+                continue;
+            };
+
+            for step in 0..steps {
+                let values: HashMap<String, f64> = feed_ids.iter()
+                    .map(|id| {
+                        let series = &closes[*id];
+                        ((*id).clone(), series[series.len() - steps + step])
+                    })
+                    .collect();
+
+                let weight_of = |feed: &PriceFeed| weights[feed.id.as_str()];
+                let price_of = |feed: &PriceFeed| converted_price(&values, feed);
+                let raw_value = match divisor {
+                    None => aggregate(index_def, &index_def.feeds, weight_of, price_of, self.arithmetic),
+                    Some(divisor) => market_value(&constituents, |feed_id| {
+                        index_def.feeds.iter().find(|feed| feed.id == feed_id).and_then(price_of)
+                    }).map(|value| value / divisor),
+                };
+                if let Some(raw_value) = raw_value.and_then(|value| index_def.transformed(value)) {
+                    let smoothed_value = smoother.update(raw_value);
+                    index_history.push_front(smoothed_value);
+                    if index_history.len() > MAX_HISTORY_SIZE {
+                        index_history.pop_back();
+                    }
+                }
+            }
+
+            info!("[BOOTSTRAP] Index: {}, seeded smoothing history with {} historical values", index_def.name, steps);
+        }
+    }
+
     /// Process feed updates from the receiver
     fn process_feed_updates(&mut self) -> AppResult<()> {
         // Process all available updates without blocking
@@ -123,12 +1006,32 @@ impl IndexCalculator {
         
         while let Ok(feed_data) = self.receiver.try_recv() {
             updates_count += 1;
+            let waited = Utc::now() - feed_data.timestamp;
+            record_stage(PipelineStage::Channel, waited.num_microseconds().unwrap_or(i64::MAX) as f64 / 1000.0);
             debug!("[PROCESSING] Feed: {}, Price: {}, Time: {}", 
                   feed_data.feed_id, feed_data.price, feed_data.timestamp);
             
             // Update current value
             self.feed_values.insert(feed_data.feed_id.clone(), feed_data.price);
+            self.feed_updated_at.insert(feed_data.feed_id.clone(), feed_data.timestamp);
+            if feed_data.backup_feed.is_some() {
+                self.feeds_on_backup.insert(feed_data.feed_id.clone());
+            } else {
+                self.feeds_on_backup.remove(&feed_data.feed_id);
+            }
             
+            // A heartbeat repeats the last price only to show the feed is alive
+            if feed_data.heartbeat {
+                continue;
+            }
+
+            // Track the price distribution, exporting its quantiles whenever the window moves on
+            let distribution = self.tick_distributions.entry(feed_data.feed_id.clone())
+                .or_insert_with(|| RollingQuantiles::new(self.distribution_window, self.distribution_compression));
+            if distribution.add(feed_data.price, feed_data.timestamp) {
+                export_distribution(distribution, &feed_data.feed_id);
+            }
+
             // Update history
             let history = self.feed_history.entry(feed_data.feed_id.clone()).or_default();
             history.push_front(feed_data.price);
@@ -144,3 +1047,134 @@ impl IndexCalculator {
         Ok(())
     }
 }
+
+/// Indices ordered so that every composite or derived index comes after the indices it is built
+/// from, otherwise keeping their order. Indices in a dependency cycle (rejected by the
+/// configuration) go last.
+pub fn dependency_order(indices: Vec<IndexDefinition>) -> Vec<IndexDefinition> {
+    let mut remaining = indices;
+    let mut ordered: Vec<IndexDefinition> = Vec::with_capacity(remaining.len());
+
+    while let Some(position) = remaining.iter().position(|index_def| {
+        index_def.constituent_names().into_iter().all(|name| {
+            ordered.iter().any(|done| done.name == name)
+                || !remaining.iter().any(|pending| pending.name == name)
+        })
+    }) {
+        ordered.push(remaining.remove(position));
+    }
+
+    ordered.extend(remaining);
+    ordered
+}
+
+/// Price of a feed expressed in the index quote currency, looked up in `feed_values`
+pub(crate) fn converted_price(feed_values: &HashMap<String, f64>, feed: &PriceFeed) -> Option<f64> {
+    let price = *feed_values.get(&feed.id)?;
+
+    match &feed.conversion {
+        Some(conversion) => {
+            let rate = *feed_values.get(&conversion.feed_id)?;
+            if rate > 0.0 {
+                Some(conversion.apply(price, rate))
+            } else {
+                None
+            }
+        }
+        None => Some(price),
+    }
+}
+
+/// Ids of the feeds whose price deviates from the median by more than `max_deviation_pct`.
+///
+/// Needs at least three prices: with two, neither can be told apart as the outlier.
+pub fn find_outliers(prices: &[(&str, f64)], max_deviation_pct: f64) -> Vec<String> {
+    if prices.len() < 3 {
+        return Vec::new();
+    }
+
+    let values: Vec<f64> = prices.iter().map(|(_, price)| *price).collect();
+    let Some(median) = median(&values) else {
+        return Vec::new();
+    };
+
+    prices.iter()
+        .filter(|(_, price)| ((price - median) / median).abs() * 100.0 > max_deviation_pct)
+        .map(|(id, _)| id.to_string())
+        .collect()
+}
+
+/// Export the quantiles of a feed's price distribution as `feeds.<id>.price_<quantile>` gauges
+fn export_distribution(distribution: &RollingQuantiles, feed_id: &str) {
+    let Some(distribution) = distribution.distribution(feed_id) else {
+        return;
+    };
+    for (label, value) in &distribution.quantiles {
+        metrics().set_gauge(&format!("feeds.{}.price_{}", feed_id, label), *value);
+    }
+}
+
+/// Index value of the given constituents under the index's aggregation, or `None` if any of
+/// them has no price or the aggregation is not registered
+pub(crate) fn aggregate<'a>(
+    index_def: &IndexDefinition,
+    feeds: impl IntoIterator<Item = &'a PriceFeed>,
+    weight_of: impl Fn(&PriceFeed) -> f64,
+    price_of: impl Fn(&PriceFeed) -> Option<f64>,
+    arithmetic: Arithmetic,
+) -> Option<f64> {
+    let values: Vec<(f64, f64)> = feeds.into_iter()
+        .map(|feed| price_of(feed).filter(|price| *price > 0.0).map(|price| (price, weight_of(feed))))
+        .collect::<Option<_>>()?;
+    aggregation::create_strategy(&index_def.aggregation, index_def.trim_pct)?.aggregate(&values, arithmetic)
+}
+
+/// Spread between the highest and the lowest price in percent of their median, `None` for
+/// fewer than two prices
+pub fn dispersion_pct(prices: &[f64]) -> Option<f64> {
+    if prices.len() < 2 {
+        return None;
+    }
+    let (min, max) = prices.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), price| (min.min(*price), max.max(*price)));
+    Some((max - min) / median(prices)? * 100.0)
+}
+
+/// Confidence in an index value between 0 and 1: the share of constituents contributing,
+/// times how far the oldest contributing price is from going stale, times the agreement of
+/// the prices, which halves at a dispersion of 1%
+pub fn confidence(contributors: usize, constituents: usize, max_age_secs: f64, max_staleness_secs: u64, dispersion_pct: Option<f64>) -> f64 {
+    if constituents == 0 {
+        return 0.0;
+    }
+    let coverage = contributors as f64 / constituents as f64;
+    let freshness = (1.0 - max_age_secs / max_staleness_secs.max(1) as f64).clamp(0.0, 1.0);
+    let agreement = 1.0 / (1.0 + dispersion_pct.unwrap_or(0.0));
+    coverage * freshness * agreement
+}
+
+/// Static weight of a constituent, in percent
+pub(crate) fn static_weight(feed: &PriceFeed) -> f64 {
+    feed.weight as f64
+}
+
+/// Weights in percent of market-cap weighted constituents by feed id: each asset weighs its share
+/// of the total market cap, split evenly between the feeds quoting it. `None` if a cap is missing.
+pub(crate) fn market_cap_weights(feeds: &[PriceFeed], market_caps: &HashMap<String, f64>) -> Option<HashMap<String, f64>> {
+    // Ordered, so that the total is summed in the same order on every run
+    let mut feeds_per_asset: BTreeMap<&str, usize> = BTreeMap::new();
+    for feed in feeds {
+        *feeds_per_asset.entry(feed.market_cap_id.as_deref()?).or_default() += 1;
+    }
+
+    let total_market_cap: f64 = feeds_per_asset.keys()
+        .map(|asset| market_caps.get(*asset).copied().filter(|cap| *cap > 0.0))
+        .sum::<Option<f64>>()?;
+
+    feeds.iter()
+        .map(|feed| {
+            let asset = feed.market_cap_id.as_deref()?;
+            let weight = market_caps[asset] / total_market_cap * 100.0 / feeds_per_asset[asset] as f64;
+            Some((feed.id.clone(), weight))
+        })
+        .collect()
+}
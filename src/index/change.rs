@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use chrono::{DateTime, Duration, Utc};
+
+use super::models::IndexChange;
+
+/// Samples kept per change window; a window starts from the latest sample at or before its
+/// start, which is at most 1/120th of the window older than the value published there
+const SAMPLES_PER_WINDOW: i64 = 120;
+
+/// Published values of one index over its change windows
+#[derive(Debug, Clone)]
+pub struct ChangeTracker {
+    windows: Vec<ChangeWindow>,
+    previous: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+struct ChangeWindow {
+    length: Duration,
+    label: String,
+    /// Sampled published values, oldest first
+    samples: VecDeque<(DateTime<Utc>, f64)>,
+}
+
+impl ChangeTracker {
+    pub fn new(windows_secs: &[u64]) -> Self {
+        Self {
+            windows: windows_secs.iter()
+                .map(|secs| ChangeWindow {
+                    length: Duration::seconds(*secs as i64),
+                    label: window_label(*secs),
+                    samples: VecDeque::new(),
+                })
+                .collect(),
+            previous: None,
+        }
+    }
+
+    /// Change of `value` at `timestamp` since the previous value and over every window the
+    /// index has been published for, in percent of the value the window starts from
+    pub fn change(&self, timestamp: DateTime<Utc>, value: f64) -> IndexChange {
+        let percent = |from: f64| (from != 0.0).then(|| (value - from) / from * 100.0);
+
+        IndexChange {
+            absolute: self.previous.map(|previous| value - previous),
+            percent: self.previous.and_then(percent),
+            windows: self.windows.iter()
+                .filter_map(|window| {
+                    let start = timestamp - window.length;
+                    let (_, from) = window.samples.iter().rev().find(|(sampled_at, _)| *sampled_at <= start)?;
+                    Some((window.label.clone(), percent(*from)?))
+                })
+                .collect(),
+        }
+    }
+
+    /// Record a published value
+    pub fn record(&mut self, timestamp: DateTime<Utc>, value: f64) {
+        self.previous = Some(value);
+
+        for window in &mut self.windows {
+            let resolution = window.length / SAMPLES_PER_WINDOW as i32;
+            if window.samples.back().is_none_or(|(sampled_at, _)| timestamp - *sampled_at >= resolution) {
+                window.samples.push_back((timestamp, value));
+            }
+
+            // Keep the newest sample at or before the start of the window
+            let start = timestamp - window.length;
+            while window.samples.get(1).is_some_and(|(sampled_at, _)| *sampled_at <= start) {
+                window.samples.pop_front();
+            }
+        }
+    }
+}
+
+/// Short label of a window length, e.g. `1m`, `1h` or `24h`
+pub fn window_label(secs: u64) -> String {
+    if secs >= 3600 && secs.is_multiple_of(3600) {
+        format!("{}h", secs / 3600)
+    } else if secs >= 60 && secs.is_multiple_of(60) {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
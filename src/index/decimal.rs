@@ -0,0 +1,76 @@
+/// Number type index values are aggregated in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Arithmetic {
+    /// Native floating point
+    #[default]
+    Float,
+    /// Fixed-point decimals; sums do not depend on the order of their terms, so results are
+    /// exactly reproducible
+    Decimal,
+}
+
+/// Decimal places of a `Decimal`
+const DECIMALS: u32 = 12;
+
+const SCALE: i128 = 10i128.pow(DECIMALS);
+
+/// Fixed-point decimal number with 12 decimal places
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Decimal(i128);
+
+impl Decimal {
+    pub const ZERO: Decimal = Decimal(0);
+
+    /// Nearest decimal to `value`, `None` if it is not finite or out of range
+    pub fn from_f64(value: f64) -> Option<Self> {
+        let scaled = (value * SCALE as f64).round();
+        (scaled.is_finite() && scaled.abs() < i128::MAX as f64).then_some(Decimal(scaled as i128))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / SCALE as f64
+    }
+
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        self.0.checked_add(other.0).map(Decimal)
+    }
+
+    /// Product, rounded half away from zero to 12 decimal places
+    pub fn checked_mul(self, other: Decimal) -> Option<Decimal> {
+        Some(Decimal(div_round(self.0.checked_mul(other.0)?, SCALE)))
+    }
+
+    /// Quotient, rounded half away from zero to 12 decimal places; `None` when dividing by zero
+    pub fn checked_div(self, other: Decimal) -> Option<Decimal> {
+        if other.0 == 0 {
+            return None;
+        }
+        Some(Decimal(div_round(self.0.checked_mul(SCALE)?, other.0)))
+    }
+}
+
+/// Integer division rounding halves away from zero
+fn div_round(numerator: i128, denominator: i128) -> i128 {
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    if remainder.abs() * 2 >= denominator.abs() {
+        quotient + numerator.signum() * denominator.signum()
+    } else {
+        quotient
+    }
+}
+
+/// Weighted mean of `(value, weight)` pairs in decimal arithmetic, `None` if a number is out of
+/// range or the weights sum to zero
+pub fn weighted_mean(values: impl IntoIterator<Item = (f64, f64)>) -> Option<f64> {
+    let mut weighted_sum = Decimal::ZERO;
+    let mut total_weight = Decimal::ZERO;
+
+    for (value, weight) in values {
+        let weight = Decimal::from_f64(weight)?;
+        weighted_sum = weighted_sum.checked_add(Decimal::from_f64(value)?.checked_mul(weight)?)?;
+        total_weight = total_weight.checked_add(weight)?;
+    }
+
+    Some(weighted_sum.checked_div(total_weight)?.to_f64())
+}
@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+/// Divisor of a divisor-based index and the composition it was set for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DivisorState {
+    pub divisor: f64,
+    /// Weight of every constituent by feed id
+    pub constituents: BTreeMap<String, f64>,
+    /// Latest level, used as the anchor of the next adjustment if the previous composition
+    /// can no longer be priced
+    pub level: f64,
+}
+
+impl DivisorState {
+    /// Start an index with the given composition and market value at `base_level`
+    pub fn new(constituents: BTreeMap<String, f64>, market_value: f64, base_level: f64) -> Self {
+        Self {
+            divisor: market_value / base_level,
+            constituents,
+            level: base_level,
+        }
+    }
+
+    /// State for the given composition and its market value at current prices.
+    ///
+    /// If constituents or weights changed, the divisor is adjusted so that the new composition
+    /// has the level the previous one has at current prices (`previous_market_value`), or the
+    /// latest level if the previous composition cannot be priced anymore.
+    pub fn update(&self, constituents: BTreeMap<String, f64>, market_value: f64, previous_market_value: Option<f64>) -> Self {
+        if constituents == self.constituents {
+            return Self {
+                divisor: self.divisor,
+                constituents,
+                level: market_value / self.divisor,
+            };
+        }
+
+        let level = previous_market_value.map_or(self.level, |value| value / self.divisor);
+        Self {
+            divisor: market_value / level,
+            constituents,
+            level,
+        }
+    }
+}
+
+/// Market value (sum of weight times price) of a composition, or `None` if a constituent has
+/// no price or the value is not positive
+pub fn market_value(constituents: &BTreeMap<String, f64>, price_of: impl Fn(&str) -> Option<f64>) -> Option<f64> {
+    let value = constituents.iter()
+        .map(|(feed_id, weight)| price_of(feed_id).filter(|price| *price > 0.0).map(|price| price * weight))
+        .sum::<Option<f64>>()?;
+    (value > 0.0).then_some(value)
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+use serde::Deserialize;
+
+/// Arithmetic expression over feeds and indices referenced by name, e.g.
+/// `ETH-USD-INDEX / BTC-USD-INDEX`.
+///
+/// Supports numbers, `+`, `-`, `*`, `/` and parentheses. Names may contain `-` and `.`, so a
+/// subtraction needs spaces around its operator.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Reference(String),
+    Negate(Box<Node>),
+    Binary(Box<Node>, Operator, Box<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(Operator),
+    Open,
+    Close,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens: &tokens, position: 0 };
+        let root = parser.sum()?;
+        if parser.position < tokens.len() {
+            return Err(format!("Unexpected {:?} in expression '{}'", tokens[parser.position], source));
+        }
+        Ok(Self { source: source.trim().to_string(), root })
+    }
+
+    /// Names of the feeds and indices the expression references, in order of appearance
+    pub fn references(&self) -> Vec<&str> {
+        let mut references = Vec::new();
+        self.root.collect_references(&mut references);
+        references
+    }
+
+    /// Value of the expression with `value_of` resolving references; `None` if a reference has
+    /// no value or the result is not finite, e.g. after a division by zero
+    pub fn eval(&self, value_of: &mut impl FnMut(&str) -> Option<f64>) -> Option<f64> {
+        self.root.eval(value_of).filter(|value| value.is_finite())
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Self::parse(&source)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Node {
+    fn collect_references<'a>(&'a self, references: &mut Vec<&'a str>) {
+        match self {
+            Node::Number(_) => {}
+            Node::Reference(name) => {
+                if !references.contains(&name.as_str()) {
+                    references.push(name);
+                }
+            }
+            Node::Negate(operand) => operand.collect_references(references),
+            Node::Binary(left, _, right) => {
+                left.collect_references(references);
+                right.collect_references(references);
+            }
+        }
+    }
+
+    fn eval(&self, value_of: &mut impl FnMut(&str) -> Option<f64>) -> Option<f64> {
+        Some(match self {
+            Node::Number(value) => *value,
+            Node::Reference(name) => value_of(name)?,
+            Node::Negate(operand) => -operand.eval(value_of)?,
+            Node::Binary(left, operator, right) => {
+                let (left, right) = (left.eval(value_of)?, right.eval(value_of)?);
+                match operator {
+                    Operator::Add => left + right,
+                    Operator::Subtract => left - right,
+                    Operator::Multiply => left * right,
+                    Operator::Divide => left / right,
+                }
+            }
+        })
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            _ if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '+' => Token::Operator(Operator::Add),
+            '-' => Token::Operator(Operator::Subtract),
+            '*' => Token::Operator(Operator::Multiply),
+            '/' => Token::Operator(Operator::Divide),
+            _ if c.is_ascii_digit() || c == '.' => {
+                let mut end = start + c.len_utf8();
+                while let Some((position, _)) = chars.next_if(|(_, c)| c.is_ascii_digit() || *c == '.') {
+                    end = position + 1;
+                }
+                let number = &source[start..end];
+                Token::Number(number.parse().map_err(|_| format!("Invalid number '{}' in expression '{}'", number, source))?)
+            }
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = start + 1;
+                while let Some((position, _)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+                    end = position + 1;
+                }
+                Token::Name(source[start..end].to_string())
+            }
+            _ => return Err(format!("Unexpected character '{}' in expression '{}'", c, source)),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser: sums of products of (possibly negated) numbers, names and
+/// parenthesized sums
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn next_if(&mut self, accept: impl Fn(&Token) -> bool) -> Option<&Token> {
+        let token = self.tokens.get(self.position).filter(|token| accept(token))?;
+        self.position += 1;
+        Some(token)
+    }
+
+    fn operator(&mut self, operators: [Operator; 2]) -> Option<Operator> {
+        match self.next_if(|token| matches!(token, Token::Operator(operator) if operators.contains(operator))) {
+            Some(Token::Operator(operator)) => Some(*operator),
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<Node, String> {
+        let mut node = self.product()?;
+        while let Some(operator) = self.operator([Operator::Add, Operator::Subtract]) {
+            node = Node::Binary(Box::new(node), operator, Box::new(self.product()?));
+        }
+        Ok(node)
+    }
+
+    fn product(&mut self) -> Result<Node, String> {
+        let mut node = self.operand()?;
+        while let Some(operator) = self.operator([Operator::Multiply, Operator::Divide]) {
+            node = Node::Binary(Box::new(node), operator, Box::new(self.operand()?));
+        }
+        Ok(node)
+    }
+
+    fn operand(&mut self) -> Result<Node, String> {
+        let token = self.tokens.get(self.position).cloned().ok_or("Expression ends unexpectedly")?;
+        self.position += 1;
+        match token {
+            Token::Number(value) => Ok(Node::Number(value)),
+            Token::Name(name) => Ok(Node::Reference(name)),
+            Token::Operator(Operator::Subtract) => Ok(Node::Negate(Box::new(self.operand()?))),
+            Token::Open => {
+                let node = self.sum()?;
+                self.next_if(|token| *token == Token::Close).ok_or("Expression has an unclosed parenthesis")?;
+                Ok(node)
+            }
+            token => Err(format!("Unexpected {:?} in expression", token)),
+        }
+    }
+}
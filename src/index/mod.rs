@@ -1,5 +1,28 @@
+pub mod alerts;
 pub mod calculator;
+pub mod change;
+pub mod decimal;
+pub mod divisor;
+pub mod expression;
 pub mod models;
+pub mod persistence;
+pub mod publisher;
+pub mod rebalance;
+pub mod replay;
+pub mod returns;
+pub mod simulation;
 
+#[cfg(test)]
+mod tests;
+
+pub use alerts::{AlertEvent, AlertMonitor};
 pub use calculator::IndexCalculator;
-pub use models::IndexResult;
+pub use divisor::DivisorState;
+pub use expression::Expression;
+pub use persistence::{read_checkpoint, run_checkpointing, run_state_persistence, write_checkpoint};
+pub use publisher::run_publisher;
+pub use rebalance::run_rebalancing;
+pub use replay::{replay, replay_from, with_constituent_indices, SeriesChecksum};
+pub use returns::ReturnState;
+pub use models::{CalculatorState, StoredValue, IndexCatalogEntry, IndexChange, IndexResult, IndexQuality, IndexSnapshot, SmoothingSettings, SmoothingSnapshot};
+pub use simulation::{simulate, WhatIfPoint, WhatIfRequest, WhatIfResult};
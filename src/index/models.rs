@@ -1,7 +1,16 @@
+use std::collections::BTreeMap;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
-/// Result of an index calculation
-#[derive(Debug, Clone)]
+use crate::models::{Denomination, IndexDefinition, Lifecycle, SmoothingType};
+use super::divisor::DivisorState;
+use super::returns::ReturnState;
+
+/// Result of an index calculation.
+///
+/// Published to clients and serialized in a [`crate::serialization::Envelope`]; new fields
+/// must be `#[serde(default)]` so older payloads keep decoding.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IndexResult {
     /// Name of the index
     pub name: String,
@@ -9,4 +18,197 @@ pub struct IndexResult {
     pub timestamp: DateTime<Utc>,
     /// Calculated index value
     pub value: f64,
+    /// Index value before smoothing; absent on values read back from storage without it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_value: Option<f64>,
+    /// Calculation epoch; results sharing an epoch were calculated from the same feed values
+    #[serde(default)]
+    pub epoch: u64,
+    /// Currencies and precision of the value
+    #[serde(default)]
+    pub denomination: Denomination,
+    /// How the value was obtained
+    #[serde(default)]
+    pub quality: IndexQuality,
+    /// How the value moved since the previous one and over the index's change windows
+    #[serde(default)]
+    pub change: IndexChange,
+    /// Version of the index definition the value was calculated with, bumped whenever the
+    /// definition changes; `0` while versions are not tracked (without a database)
+    #[serde(default)]
+    pub methodology_version: u32,
+    /// The smoothing has not seen a full window of values yet, e.g. after a restart, so the
+    /// value is close to unsmoothed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub warming_up: bool,
+}
+
+/// Change of an index value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexChange {
+    /// Change since the previous published value, absent for the first one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub absolute: Option<f64>,
+    /// Change since the previous published value in percent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+    /// Change in percent over each change window by label (e.g. `1h`), for the windows the
+    /// index has been published for
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub windows: BTreeMap<String, f64>,
+}
+
+/// All indices calculated in one calculation epoch, see [`crate::index::IndexCalculator::snapshot`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexSnapshot {
+    pub epoch: u64,
+    pub timestamp: DateTime<Utc>,
+    pub indices: Vec<IndexResult>,
+}
+
+/// Quality metadata of an index value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexQuality {
+    /// Constituent feeds currently served by their backup source
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failover_feeds: Vec<String>,
+    /// Constituent feeds excluded because they stopped updating
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stale_feeds: Vec<String>,
+    /// Constituent feeds dropped for deviating too far from the other feeds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outlier_feeds: Vec<String>,
+    /// Constituent feeds without a price, left out of an index reweighting around them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_feeds: Vec<String>,
+    /// Constituent feeds left out for lagging behind the most recently updated feed by more
+    /// than the alignment window. Routine during fast markets, so not counted as degradation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lagging_feeds: Vec<String>,
+    /// Constituents the value was calculated from: feeds with a price, or fresh components
+    #[serde(default)]
+    pub contributors: usize,
+    /// Age of the oldest contributing price (or component value) at calculation time
+    #[serde(default)]
+    pub max_age_secs: f64,
+    /// Spread between the highest and lowest contributing price in percent of their median;
+    /// absent for a single price and for composite indices
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispersion_pct: Option<f64>,
+    /// Confidence in the value between 0 and 1, see [`crate::index::calculator::confidence`]
+    #[serde(default)]
+    pub confidence: f64,
+}
+
+impl IndexQuality {
+    /// Whether constituents were left out of the value or are served by their backup
+    pub fn is_degraded(&self) -> bool {
+        !self.failover_feeds.is_empty() || !self.stale_feeds.is_empty()
+            || !self.outlier_feeds.is_empty() || !self.missing_feeds.is_empty()
+    }
+
+    /// Whether the same constituents are left out of both values or served by their backup
+    pub fn same_exclusions(&self, other: &IndexQuality) -> bool {
+        self.failover_feeds == other.failover_feeds && self.stale_feeds == other.stale_feeds
+            && self.outlier_feeds == other.outlier_feeds && self.missing_feeds == other.missing_feeds
+    }
+}
+
+/// Catalog listing of an index, for consumers discovering the available indices
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexCatalogEntry {
+    pub name: String,
+    #[serde(default)]
+    pub family: Option<String>,
+    #[serde(default)]
+    pub lifecycle: Lifecycle,
+    /// Currencies and precision of the index values
+    pub denomination: Denomination,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    pub publish_interval_ms: u64,
+    pub smoothing: SmoothingSettings,
+    /// Ids of the constituent feeds, names of the component indices of a composite index, or the
+    /// indices and feeds referenced by a derived index
+    pub constituents: Vec<String>,
+    /// Expression a derived index is calculated with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<String>,
+}
+
+impl From<&IndexDefinition> for IndexCatalogEntry {
+    fn from(index: &IndexDefinition) -> Self {
+        Self {
+            name: index.name.clone(),
+            family: index.family.clone(),
+            lifecycle: index.lifecycle,
+            denomination: index.denomination.clone(),
+            labels: index.labels.clone(),
+            publish_interval_ms: index.publish.interval_ms,
+            smoothing: SmoothingSettings {
+                algorithm: index.smoothing.clone(),
+                period: index.smoothing_period,
+                factor: index.smoothing_factor,
+                band_pct: index.smoothing_band_pct,
+            },
+            constituents: index.feeds.iter().map(|feed| feed.id.as_str())
+                .chain(index.constituent_names())
+                .map(str::to_string)
+                .collect(),
+            expression: index.expression.as_ref().map(ToString::to_string),
+        }
+    }
+}
+
+/// Smoothing of an index as listed in the catalog
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmoothingSettings {
+    pub algorithm: SmoothingType,
+    /// Number of values averaged over
+    pub period: usize,
+    /// Smoothing factor of the EMA-based algorithms
+    pub factor: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub band_pct: Option<f64>,
+}
+
+/// State the calculation of the indices continues from, to carry it over to another collector,
+/// see [`crate::index::IndexCalculator::state`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CalculatorState {
+    /// Smoothing state by index name
+    #[serde(default)]
+    pub smoothing: BTreeMap<String, SmoothingSnapshot>,
+    /// Divisors of divisor-based indices by index name
+    #[serde(default)]
+    pub divisors: BTreeMap<String, DivisorState>,
+    /// Latest prices by feed id, newest first
+    #[serde(default)]
+    pub feed_history: BTreeMap<String, Vec<f64>>,
+    /// Levels of log-return indices by index name
+    #[serde(default)]
+    pub returns: BTreeMap<String, ReturnState>,
+    /// Latest stored values of indices by index name, newest first, smoothing is warmed up
+    /// from where it has no state to continue from
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub index_history: BTreeMap<String, Vec<StoredValue>>,
+}
+
+/// Published value of an index and the value it was smoothed from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StoredValue {
+    pub value: f64,
+    /// Absent on values stored before raw values were recorded
+    pub raw_value: Option<f64>,
+}
+
+/// Smoothing state and latest values of one index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmoothingSnapshot {
+    /// Algorithm the state belongs to; a state is not restored into another algorithm
+    pub smoothing: SmoothingType,
+    /// Values of the smoothing state, see [`crate::smoothing::SmoothingState::save`]
+    pub state: Vec<f64>,
+    /// Latest published values, newest first
+    pub history: Vec<f64>,
 }
@@ -0,0 +1,110 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{error, info};
+
+use crate::error::AppResult;
+use crate::metrics::metrics;
+use crate::serialization::WireFormat;
+use crate::storage::Database;
+use super::calculator::IndexCalculator;
+use super::models::CalculatorState;
+
+/// How often divisors, return levels and smoothing states are saved
+pub const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Save the divisors of divisor-based indices, the levels of log-return indices and the
+/// smoothing state of every index periodically and once more on shutdown, so a restarted
+/// collector continues from them
+pub async fn run_state_persistence(
+    index_calc: Arc<RwLock<IndexCalculator>>,
+    database: Database,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(STATE_SAVE_INTERVAL);
+
+    loop {
+        let stopping = tokio::select! {
+            _ = interval.tick() => false,
+            _ = shutdown.recv() => true,
+        };
+
+        let (divisors, state) = {
+            let mut calculator = index_calc.write().await;
+            (calculator.take_unsaved_divisors(), calculator.state())
+        };
+        for (index, state) in divisors {
+            if let Err(e) = database.save_divisor(&index, &state).await {
+                // The next calculation marks the divisor unsaved again
+                error!("[DIVISOR] Failed to save divisor of index {}: {}", index, e);
+                metrics().increment("index.divisor_save_failures");
+            }
+        }
+        for (index, level) in state.returns {
+            if let Err(e) = database.save_return_state(&index, &level).await {
+                error!("[ANCHOR] Failed to save level of index {}: {}", index, e);
+                metrics().increment("index.return_save_failures");
+            }
+        }
+        for (index, snapshot) in state.smoothing {
+            if let Err(e) = database.save_smoothing_state(&index, &snapshot).await {
+                error!("[SMOOTHING] Failed to save smoothing state of index {}: {}", index, e);
+                metrics().increment("index.smoothing_save_failures");
+            }
+        }
+
+        if stopping {
+            info!("[SHUTDOWN] Saved index divisors, return levels and smoothing states");
+            return;
+        }
+    }
+}
+
+/// Write a checkpoint of the calculation state to `path`, replacing the previous one only once
+/// it is complete
+pub fn write_checkpoint(path: &Path, state: &CalculatorState) -> AppResult<()> {
+    let partial = path.with_extension("partial");
+    fs::write(&partial, WireFormat::Json.encode(state)?)?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Calculation state of a checkpoint written by [`write_checkpoint`], `None` if there is none yet
+pub fn read_checkpoint(path: &Path) -> AppResult<Option<CalculatorState>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(WireFormat::Json.decode(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Checkpoint the calculation state to a local file every `interval` and once more on
+/// shutdown, so a restarted collector continues from it without a database
+pub async fn run_checkpointing(
+    index_calc: Arc<RwLock<IndexCalculator>>,
+    path: PathBuf,
+    interval: Duration,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        let stopping = tokio::select! {
+            _ = interval.tick() => false,
+            _ = shutdown.recv() => true,
+        };
+
+        let state = index_calc.read().await.state();
+        if let Err(e) = write_checkpoint(&path, &state) {
+            error!("[CHECKPOINT] Failed to write checkpoint {}: {}", path.display(), e);
+            metrics().increment("index.checkpoint_failures");
+        }
+
+        if stopping {
+            info!("[SHUTDOWN] Wrote checkpoint {}", path.display());
+            return;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, RwLock};
+use tokio::time::{Instant, Interval};
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::logging::{record_stage, PipelineStage};
+use crate::metrics::metrics;
+use crate::models::{MissedTicks, PublishSchedule};
+use super::calculator::IndexCalculator;
+use super::models::IndexResult;
+
+/// Calculate the indices on their publication schedules and broadcast the results to every
+/// consumer (WebSocket clients, stdout export, ...), so indices are calculated once regardless
+/// of the number of consumers.
+pub async fn run_publisher(
+    index_calc: Arc<RwLock<IndexCalculator>>,
+    updates: broadcast::Sender<IndexResult>,
+    shutdown: broadcast::Receiver<()>,
+) {
+    let schedules = index_calc.read().await.publish_schedules();
+
+    let handles: Vec<_> = schedules.into_iter()
+        .map(|schedule| tokio::spawn(publish_on_schedule(
+            index_calc.clone(), updates.clone(), schedule, shutdown.resubscribe(),
+        )))
+        .collect();
+
+    for handle in handles {
+        if let Err(e) = handle.await {
+            error!("Index publisher task failed: {}", e);
+        }
+    }
+}
+
+/// Publish the indices of one schedule until shutdown
+async fn publish_on_schedule(
+    index_calc: Arc<RwLock<IndexCalculator>>,
+    updates: broadcast::Sender<IndexResult>,
+    schedule: PublishSchedule,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let period = Duration::from_millis(schedule.interval_ms);
+    let mut interval = aligned_interval(period);
+    interval.set_missed_tick_behavior(schedule.missed_ticks.behavior());
+
+    loop {
+        tokio::select! {
+            scheduled = interval.tick() => {
+                record_missed_ticks(&schedule, period, scheduled);
+
+                let span = info_span!("index.calculate", schedule_ms = schedule.interval_ms, indices = tracing::field::Empty);
+                let calculated = async {
+                    let started = Instant::now();
+                    let calculated = index_calc.write().await.calculate_scheduled(&schedule);
+                    record_stage(PipelineStage::Calculation, started.elapsed().as_secs_f64() * 1000.0);
+                    calculated
+                }.instrument(span.clone()).await;
+                match calculated {
+                    Ok(results) => {
+                        span.record("indices", results.len());
+                        let _entered = info_span!(parent: &span, "index.publish", receivers = updates.receiver_count()).entered();
+                        for result in results {
+                            // Nobody listening is fine, results are simply dropped
+                            let _ = updates.send(result);
+                        }
+                    }
+                    Err(e) => error!("Failed to calculate indices: {}", e),
+                }
+            }
+
+            _ = shutdown.recv() => {
+                info!("[SHUTDOWN] Stopping index publisher ({}ms schedule)", schedule.interval_ms);
+                return;
+            }
+        }
+    }
+}
+
+/// Interval whose ticks fall on wall-clock multiples of `period`
+fn aligned_interval(period: Duration) -> Interval {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let into_period = Duration::from_nanos((since_epoch.as_nanos() % period.as_nanos()) as u64);
+    tokio::time::interval_at(Instant::now() + (period - into_period), period)
+}
+
+/// Count the ticks that passed while the publisher was held up and will not be published
+fn record_missed_ticks(schedule: &PublishSchedule, period: Duration, scheduled: Instant) {
+    let lateness = scheduled.elapsed();
+    metrics().set_gauge("index.publish.lateness_ms", lateness.as_secs_f64() * 1000.0);
+
+    // Bursting publishes every missed tick late instead of dropping it
+    let missed = (lateness.as_nanos() / period.as_nanos()) as u64;
+    if missed > 0 && schedule.missed_ticks != MissedTicks::Burst {
+        warn!("[PUBLISH] Publisher fell {}ms behind its {}ms schedule, skipping {} missed ticks",
+              lateness.as_millis(), schedule.interval_ms, missed);
+        metrics().increment_by("index.publish.missed_ticks", missed);
+    }
+}
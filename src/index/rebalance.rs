@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use chrono::{DateTime, Datelike, Days, Utc, Weekday};
+use tokio::sync::{broadcast, RwLock};
+use tracing::{error, info};
+
+use crate::config::{Config, RebalanceConfig, RebalanceSchedule};
+use crate::error::{AppError, AppResult};
+use crate::metrics::metrics;
+use super::calculator::IndexCalculator;
+
+/// First rebalancing time of the schedule after `after`
+pub fn next_rebalance(schedule: RebalanceSchedule, after: DateTime<Utc>) -> DateTime<Utc> {
+    let mut day = after.date_naive();
+    loop {
+        day = day + Days::new(1);
+        let due = match schedule {
+            RebalanceSchedule::Daily => true,
+            RebalanceSchedule::Weekly => day.weekday() == Weekday::Mon,
+            RebalanceSchedule::Monthly => day.day() == 1,
+        };
+        if due {
+            return day.and_time(chrono::NaiveTime::MIN).and_utc();
+        }
+    }
+}
+
+/// Weights of the feeds of an index by feed id, in percent, from its weights file or else
+/// from the index in the configuration file at `config_path`
+pub fn read_weights(index: &str, rebalance: &RebalanceConfig, config_path: &Path) -> AppResult<HashMap<String, f64>> {
+    let weights: HashMap<String, u32> = match &rebalance.weights_file {
+        Some(path) => {
+            let mut files: HashMap<String, HashMap<String, u32>> = toml::from_str(&fs::read_to_string(path)?)?;
+            files.remove(index)
+                .ok_or_else(|| AppError::Config(format!("Weights file {} has no weights for index {}", path, index)))?
+        }
+        None => {
+            let config = Config::from_file(config_path).map_err(|e| AppError::Config(e.to_string()))?;
+            let index_config = config.indices.into_iter()
+                .find(|index_config| index_config.name == index)
+                .ok_or_else(|| AppError::Config(format!("Index {} is no longer configured", index)))?;
+            index_config.feeds.into_iter().map(|feed| (feed.id, feed.weight)).collect()
+        }
+    };
+    Ok(weights.into_iter().map(|(feed_id, weight)| (feed_id, weight as f64)).collect())
+}
+
+/// Rebalance every index with a schedule: once on startup, so the latest weights survive a
+/// restart, and then on every scheduled rebalancing. An index whose weights cannot be read or
+/// are invalid keeps its previous weights until the next rebalancing.
+pub async fn run_rebalancing(
+    index_calc: Arc<RwLock<IndexCalculator>>,
+    rebalances: Vec<(String, RebalanceConfig)>,
+    config_path: PathBuf,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let now = Utc::now();
+    let mut next: Vec<DateTime<Utc>> = rebalances.iter().map(|_| now).collect();
+
+    loop {
+        let Some(due) = next.iter().min().copied() else {
+            return;
+        };
+        let delay = (due - Utc::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.recv() => {
+                info!("[SHUTDOWN] Stopping index rebalancing");
+                return;
+            }
+        }
+
+        let now = Utc::now();
+        for ((index, rebalance), next) in rebalances.iter().zip(&mut next) {
+            if *next > now {
+                continue;
+            }
+            let result = match read_weights(index, rebalance, &config_path) {
+                Ok(weights) => index_calc.write().await.rebalance(index, weights),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                error!("[REBALANCE] Index: {}, keeping the previous weights: {}", index, e);
+                metrics().increment("index.rebalance_failures");
+            }
+            *next = next_rebalance(rebalance.schedule, now);
+            info!("[REBALANCE] Index: {}, next rebalancing at {}", index, next);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{FeedData, IndexDefinition};
+use super::calculator::IndexCalculator;
+use super::models::{CalculatorState, IndexResult};
+
+/// Replay recorded ticks through the given indices on their publication schedules, returning
+/// every value the indices would have published, in publication order.
+///
+/// Publications fall on multiples of each schedule's interval, like the live publisher, from
+/// the first tick up to the first publication at or after the last tick. Ticks with the same
+/// timestamp are applied in feed id order. With `deterministic`, the calculator runs with
+/// [`IndexCalculator::with_determinism`], so the series is exactly reproducible.
+pub fn replay(indices: Vec<IndexDefinition>, ticks: Vec<FeedData>, deterministic: bool) -> AppResult<Vec<IndexResult>> {
+    replay_from(CalculatorState::default(), indices, ticks, deterministic).map(|(results, _)| results)
+}
+
+/// [`replay`], continuing from `state` (e.g. stored divisors) and returning the state the
+/// calculation ends in along with the values
+pub fn replay_from(
+    state: CalculatorState,
+    indices: Vec<IndexDefinition>,
+    mut ticks: Vec<FeedData>,
+    deterministic: bool,
+) -> AppResult<(Vec<IndexResult>, CalculatorState)> {
+    ticks.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.feed_id.cmp(&b.feed_id)));
+
+    let (sender, receiver) = mpsc::channel(ticks.len().max(1));
+    let mut calculator = IndexCalculator::new(indices, receiver);
+    if deterministic {
+        calculator = calculator.with_determinism();
+    }
+    calculator.restore_state(state);
+
+    let (Some(start), Some(end)) = (ticks.first().map(|tick| tick.timestamp), ticks.last().map(|tick| tick.timestamp)) else {
+        return Ok((Vec::new(), calculator.state()));
+    };
+
+    // Every publication of every schedule, in time order; schedules publishing at the same
+    // instant keep the order of their first index
+    let schedules = calculator.publish_schedules();
+    let mut publications: Vec<(DateTime<Utc>, usize)> = Vec::new();
+    for (position, schedule) in schedules.iter().enumerate() {
+        let interval = Duration::milliseconds(schedule.interval_ms as i64);
+        let mut step = align_up(start, schedule.interval_ms);
+        let last = align_up(end, schedule.interval_ms);
+        while step <= last {
+            publications.push((step, position));
+            step += interval;
+        }
+    }
+    publications.sort();
+
+    let mut remaining = ticks.into_iter().peekable();
+    let mut results = Vec::new();
+    for (step, position) in publications {
+        while let Some(tick) = remaining.next_if(|tick| tick.timestamp <= step) {
+            sender.try_send(tick).map_err(|e| AppError::IndexCalculation(format!("Failed to replay tick: {}", e)))?;
+        }
+        results.extend(calculator.calculate_scheduled_at(&schedules[position], step)?);
+    }
+
+    Ok((results, calculator.state()))
+}
+
+/// The index named `name` and every index it is built from, directly or through other composite
+/// or derived indices; empty if there is no such index
+pub fn with_constituent_indices(indices: &[IndexDefinition], name: &str) -> Vec<IndexDefinition> {
+    let mut selected: Vec<IndexDefinition> = Vec::new();
+    let mut pending = vec![name];
+    while let Some(name) = pending.pop() {
+        if selected.iter().any(|index| index.name == name) {
+            continue;
+        }
+        if let Some(index) = indices.iter().find(|index| index.name == name) {
+            pending.extend(index.constituent_names());
+            selected.push(index.clone());
+        }
+    }
+    selected
+}
+
+/// First multiple of `interval_ms` since the Unix epoch at or after `timestamp`
+fn align_up(timestamp: DateTime<Utc>, interval_ms: u64) -> DateTime<Utc> {
+    let interval_ms = interval_ms as i64;
+    let into_interval = timestamp.timestamp_millis().rem_euclid(interval_ms);
+    let aligned = timestamp.timestamp_millis() - into_interval + if into_interval > 0 { interval_ms } else { 0 };
+    DateTime::from_timestamp_millis(aligned).unwrap_or(timestamp)
+}
+
+/// SHA-256 checksum of a series of index values, over each value's index name, timestamp and
+/// exact bit pattern, to compare replays across runs and platforms
+#[derive(Default)]
+pub struct SeriesChecksum {
+    hasher: Sha256,
+    count: usize,
+}
+
+impl SeriesChecksum {
+    pub fn update(&mut self, result: &IndexResult) {
+        self.hasher.update((result.name.len() as u64).to_be_bytes());
+        self.hasher.update(result.name.as_bytes());
+        self.hasher.update(result.timestamp.timestamp_millis().to_be_bytes());
+        self.hasher.update(result.value.to_bits().to_be_bytes());
+        self.count += 1;
+    }
+
+    /// Number of values in the checksum
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Hex-encoded checksum
+    pub fn finish(self) -> String {
+        hex::encode(self.hasher.finalize())
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Level of a log-return index: its base level at the anchor, compounded by the weighted log
+/// returns of its constituents since
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReturnState {
+    pub level: f64,
+    /// Constituent prices the next returns are measured from, by feed id
+    pub prices: BTreeMap<String, f64>,
+    /// When the index was at its base level
+    pub anchored_at: DateTime<Utc>,
+}
+
+impl ReturnState {
+    /// Anchor an index at `base_level` with the given constituent prices
+    pub fn new(prices: BTreeMap<String, f64>, base_level: f64, anchored_at: DateTime<Utc>) -> Self {
+        Self { level: base_level, prices, anchored_at }
+    }
+
+    /// State after moving to `prices`: the level grows by the weighted mean log return of the
+    /// constituents priced both before and now, so constituents joining or leaving do not move it
+    pub fn update(&self, prices: BTreeMap<String, f64>, weights: &BTreeMap<String, f64>) -> Self {
+        let (weighted_return, total_weight) = prices.iter()
+            .filter_map(|(feed_id, price)| {
+                let previous = self.prices.get(feed_id)?;
+                let weight = weights.get(feed_id).copied().unwrap_or_default();
+                Some((weight * (price / previous).ln(), weight))
+            })
+            .fold((0.0, 0.0), |(returns, weights), (weighted, weight)| (returns + weighted, weights + weight));
+        let log_return = if total_weight > 0.0 { weighted_return / total_weight } else { 0.0 };
+
+        Self {
+            level: self.level * log_return.exp(),
+            prices,
+            anchored_at: self.anchored_at,
+        }
+    }
+
+    /// Cumulative log return since the anchor
+    pub fn log_return(&self, base_level: f64) -> f64 {
+        (self.level / base_level).ln()
+    }
+}
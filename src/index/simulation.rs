@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{FeedData, IndexDefinition, SmoothingType, Weighting, Aggregation, Methodology};
+use crate::smoothing::{self, SmoothingState};
+use super::calculator::{aggregate, converted_price, static_weight};
+use super::decimal::Arithmetic;
+
+/// Longest period a simulation may cover
+pub const MAX_WHAT_IF_HOURS: u64 = 7 * 24;
+
+/// Most points a simulated series may have
+pub const MAX_WHAT_IF_POINTS: i64 = 10_000;
+
+/// Hypothetical methodology change of an existing index, previewed against stored raw data
+#[derive(Debug, Clone, Deserialize)]
+pub struct WhatIfRequest {
+    pub index: String,
+    /// New weights of constituents by feed id; unlisted feeds keep their weight and a weight
+    /// of 0 drops a feed. The resulting weights must still sum to 100.
+    #[serde(default)]
+    pub weights: HashMap<String, u32>,
+    #[serde(default)]
+    pub smoothing: Option<SmoothingType>,
+    /// How many hours back from now to simulate
+    #[serde(default = "default_what_if_hours")]
+    pub hours: u64,
+    /// Spacing of the simulated values
+    #[serde(default = "default_what_if_resolution_secs")]
+    pub resolution_secs: u64,
+}
+
+fn default_what_if_hours() -> u64 {
+    24
+}
+
+fn default_what_if_resolution_secs() -> u64 {
+    60
+}
+
+impl WhatIfRequest {
+    /// The definition of the index with the overrides applied
+    pub fn apply(&self, current: &IndexDefinition) -> AppResult<IndexDefinition> {
+        // Historical market caps are not stored, so past weights are unknown
+        if current.weighting == Weighting::MarketCap {
+            return Err(AppError::IndexCalculation(format!(
+                "What-if simulations are not supported for market-cap weighted index {}", current.name
+            )));
+        }
+        if current.is_composite() {
+            return Err(AppError::IndexCalculation(format!(
+                "What-if simulations are not supported for composite or derived index {}", current.name
+            )));
+        }
+        // Past divisor adjustments and return anchors are not stored either
+        if current.methodology != Methodology::Average {
+            return Err(AppError::IndexCalculation(format!(
+                "What-if simulations are not supported for {:?} index {}", current.methodology, current.name
+            )));
+        }
+        if self.hours == 0 || self.hours > MAX_WHAT_IF_HOURS {
+            return Err(AppError::IndexCalculation(format!("hours must be between 1 and {}", MAX_WHAT_IF_HOURS)));
+        }
+        if self.resolution_secs == 0 || (self.hours * 3600 / self.resolution_secs) as i64 > MAX_WHAT_IF_POINTS {
+            return Err(AppError::IndexCalculation(format!(
+                "resolution_secs must be positive and yield at most {} points", MAX_WHAT_IF_POINTS
+            )));
+        }
+
+        let mut proposed = current.clone();
+        for (feed_id, weight) in &self.weights {
+            let feed = proposed.feeds.iter_mut().find(|feed| &feed.id == feed_id)
+                .ok_or_else(|| AppError::IndexCalculation(format!("Feed {} is not a constituent of index {}", feed_id, current.name)))?;
+            feed.weight = *weight;
+        }
+        proposed.feeds.retain(|feed| feed.weight > 0);
+
+        let total_weight: u32 = proposed.feeds.iter().map(|feed| feed.weight).sum();
+        if proposed.aggregation == Aggregation::WeightedMean && total_weight != 100 {
+            return Err(AppError::IndexCalculation(format!("Proposed weights must sum to 100, got {}", total_weight)));
+        }
+
+        if let Some(smoothing) = &self.smoothing {
+            proposed.smoothing = smoothing.clone();
+        }
+
+        Ok(proposed)
+    }
+
+    /// Time range covered by the simulation, ending now
+    pub fn range(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let to = Utc::now();
+        (to - Duration::hours(self.hours as i64), to)
+    }
+}
+
+/// Current and proposed value of an index at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WhatIfPoint {
+    pub timestamp: DateTime<Utc>,
+    pub current: f64,
+    pub proposed: f64,
+}
+
+/// Recomputed series of an index under its current and a proposed methodology
+#[derive(Debug, Clone, Serialize)]
+pub struct WhatIfResult {
+    pub index: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub points: Vec<WhatIfPoint>,
+    /// Largest difference between proposed and current value, in percent of the current value
+    pub max_difference_pct: f64,
+}
+
+/// Replay stored raw prices (oldest first) through the current and the proposed definition.
+///
+/// Every `resolution` step uses the latest stored price of each feed, so feeds keep their last
+/// price across gaps. Steps before all constituents have a price are left out.
+pub fn simulate(
+    current: &IndexDefinition,
+    request: &WhatIfRequest,
+    ticks: &[FeedData],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> AppResult<WhatIfResult> {
+    let proposed = request.apply(current)?;
+    let resolution = Duration::seconds(request.resolution_secs as i64);
+
+    let mut current_smoother = start_smoothing(current)?;
+    let mut proposed_smoother = start_smoothing(&proposed)?;
+    let mut feed_values = HashMap::new();
+    let mut remaining = ticks.iter().peekable();
+    let mut points = Vec::new();
+
+    let mut step = from;
+    while step <= to {
+        while let Some(tick) = remaining.next_if(|tick| tick.timestamp <= step) {
+            feed_values.insert(tick.feed_id.clone(), tick.price);
+        }
+
+        let price_of = |feed: &_| converted_price(&feed_values, feed);
+        if let (Some(current_value), Some(proposed_value)) =
+            (aggregate(current, &current.feeds, static_weight, price_of, Arithmetic::Float),
+             aggregate(&proposed, &proposed.feeds, static_weight, price_of, Arithmetic::Float))
+        {
+            points.push(WhatIfPoint {
+                timestamp: step,
+                current: current_smoother.update(current_value),
+                proposed: proposed_smoother.update(proposed_value),
+            });
+        }
+
+        step += resolution;
+    }
+
+    let max_difference_pct = points.iter()
+        .map(|point| ((point.proposed - point.current) / point.current * 100.0).abs())
+        .fold(0.0, f64::max);
+
+    Ok(WhatIfResult { index: current.name.clone(), from, to, points, max_difference_pct })
+}
+
+fn start_smoothing(index: &IndexDefinition) -> AppResult<Box<dyn SmoothingState>> {
+    smoothing::create_algorithm(&index.smoothing, index.smoothing_period, index.smoothing_factor)
+        .map(|algorithm| algorithm.start())
+        .ok_or_else(|| AppError::IndexCalculation(format!("Smoothing {:?} is not registered", index.smoothing)))
+}
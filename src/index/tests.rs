@@ -0,0 +1,875 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::Utc;
+use tokio::sync::{broadcast, mpsc};
+
+use super::calculator::find_outliers;
+use crate::aggregation::{median, trimmed_mean};
+use super::change::{window_label, ChangeTracker};
+use super::{read_checkpoint, write_checkpoint, AlertMonitor, CalculatorState, StoredValue, Expression, IndexCalculator, IndexCatalogEntry, IndexResult};
+use crate::models::{
+    Aggregation, AlertCondition, AlertReference, AlertRule, FeedData, Lifecycle, MissedTicks, PublishSchedule, SmoothingType,
+};
+use crate::health::{HealthEvent, IndexHealth};
+use crate::notification::Severity;
+use crate::test_support::{assert_values_close, IndexDefinitionBuilder, IndexHarness};
+
+#[cfg(test)]
+mod calculator_tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_average_of_fresh_feeds() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50).build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 100.0).push("b", 200.0);
+
+        let results = harness.calculate();
+        assert_eq!(results[0].value, 150.0);
+        assert!(results[0].quality.stale_feeds.is_empty());
+    }
+
+    #[test]
+    fn test_stale_feed_is_excluded_and_weights_renormalized() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 25).feed("b", 75).build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push_aged("a", 100.0, 120).push("b", 200.0);
+
+        let results = harness.calculate();
+        assert_eq!(results[0].value, 200.0);
+        assert_eq!(results[0].quality.stale_feeds, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn test_all_feeds_stale_publishes_nothing() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100).build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push_aged("a", 100.0, 120);
+
+        assert!(harness.calculate().is_empty());
+    }
+
+    #[test]
+    fn test_heartbeat_keeps_unchanged_feed_fresh() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50).build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push_aged("a", 100.0, 120).push_heartbeat("a", 100.0).push("b", 200.0);
+
+        let results = harness.calculate();
+        assert_eq!(results[0].value, 150.0);
+        assert!(results[0].quality.stale_feeds.is_empty());
+    }
+
+    #[test]
+    fn test_scheduled_calculation_only_covers_its_indices() {
+        let fast = PublishSchedule { interval_ms: 100, missed_ticks: MissedTicks::Skip };
+        let mut harness = IndexHarness::new(vec![
+            IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100).publish(fast).build(),
+            IndexDefinitionBuilder::new("ETH-USD-INDEX").feed("b", 100).build(),
+        ]);
+        harness.push("a", 100.0).push("b", 10.0);
+
+        let calculator = harness.calculator();
+        assert_eq!(calculator.publish_schedules(), vec![fast, PublishSchedule::default()]);
+        let results = calculator.calculate_scheduled(&fast).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "BTC-USD-INDEX");
+    }
+
+    #[test]
+    fn test_snapshot_shares_one_epoch_without_advancing_smoothing() {
+        let fast = PublishSchedule { interval_ms: 100, missed_ticks: MissedTicks::Skip };
+        let mut harness = IndexHarness::new(vec![
+            IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100)
+                .smoothing(SmoothingType::Sma)
+                .publish(fast)
+                .build(),
+            IndexDefinitionBuilder::new("ETH-USD-INDEX").feed("b", 100).build(),
+        ]);
+        harness.push("a", 100.0).push("b", 10.0);
+        harness.calculator().calculate_scheduled(&fast).unwrap();
+        harness.push("a", 200.0);
+
+        let snapshot = harness.calculator().snapshot().unwrap();
+        assert_eq!(snapshot.indices.len(), 2);
+        assert!(snapshot.indices.iter().all(|index| index.epoch == snapshot.epoch));
+
+        // The next published value is what the snapshot showed
+        let results = harness.calculator().calculate_scheduled(&fast).unwrap();
+        assert_eq!(results[0].value, snapshot.indices[0].value);
+        assert!(snapshot.indices[0].value > 100.0 && snapshot.indices[0].value < 200.0);
+        assert!(results[0].epoch > snapshot.epoch);
+    }
+
+    #[test]
+    fn test_market_cap_weights_follow_market_caps() {
+        let index = IndexDefinitionBuilder::new("TOP2-USD-INDEX")
+            .feed("coinbase_btc", 0).feed("binance_btc", 0).feed("coinbase_eth", 0)
+            .market_cap_weighted(&[("coinbase_btc", "bitcoin"), ("binance_btc", "bitcoin"), ("coinbase_eth", "ethereum")])
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("coinbase_btc", 60000.0).push("binance_btc", 60000.0).push("coinbase_eth", 3000.0);
+
+        // Nothing to publish before the first market caps arrive
+        assert!(harness.calculate().is_empty());
+
+        let market_caps = HashMap::from([("bitcoin".to_string(), 3.0e12), ("ethereum".to_string(), 1.0e12)]);
+        harness.calculator().set_market_caps(&market_caps);
+        assert_values_close(&[harness.calculate()[0].value], &[0.75 * 60000.0 + 0.25 * 3000.0], 1e-6);
+
+        // A missing cap keeps the previous weights
+        harness.calculator().set_market_caps(&HashMap::from([("bitcoin".to_string(), 1.0e12)]));
+        harness.push("coinbase_eth", 3000.0);
+        assert_values_close(&[harness.calculate()[0].value], &[0.75 * 60000.0 + 0.25 * 3000.0], 1e-6);
+    }
+
+    #[test]
+    fn test_median_aggregation_ignores_a_bad_venue() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX")
+            .feed("a", 50).feed("b", 30).feed("c", 20)
+            .aggregation(Aggregation::Median)
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 100.0).push("b", 101.0).push("c", 5000.0);
+
+        assert_eq!(harness.calculate()[0].value, 101.0);
+    }
+
+    #[test]
+    fn test_suppression_and_quality_transitions_are_streamed() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50).build();
+        let (sender, receiver) = mpsc::channel(16);
+        let (events_tx, mut events) = broadcast::channel(16);
+        let mut calculator = IndexCalculator::new(vec![index], receiver).with_health_events(events_tx);
+        assert_eq!(calculator.index_health("BTC-USD-INDEX"), IndexHealth::Pending);
+        let update = |feed_id: &str, age_secs: i64| FeedData {
+            feed_id: feed_id.to_string(),
+            timestamp: Utc::now() - chrono::Duration::seconds(age_secs),
+            price: 100.0,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        };
+
+        sender.try_send(update("a", 120)).unwrap();
+        sender.try_send(update("b", 120)).unwrap();
+        calculator.calculate_indices().unwrap();
+        assert!(matches!(events.try_recv().unwrap(), HealthEvent::IndexSuppressed { .. }));
+        assert_eq!(calculator.index_health("BTC-USD-INDEX"), IndexHealth::Suppressed);
+
+        sender.try_send(update("a", 0)).unwrap();
+        calculator.calculate_indices().unwrap();
+        assert!(matches!(events.try_recv().unwrap(), HealthEvent::IndexResumed { .. }));
+        assert!(matches!(events.try_recv().unwrap(), HealthEvent::QualityDegraded { quality, .. } if quality.stale_feeds == ["b"]));
+        assert_eq!(calculator.index_health("BTC-USD-INDEX"), IndexHealth::Degraded);
+
+        sender.try_send(update("b", 0)).unwrap();
+        calculator.calculate_indices().unwrap();
+        assert!(matches!(events.try_recv().unwrap(), HealthEvent::QualityRestored { .. }));
+        assert_eq!(calculator.index_health("BTC-USD-INDEX"), IndexHealth::Healthy);
+    }
+
+    #[test]
+    fn test_feeds_lagging_outside_the_alignment_window_are_left_out() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50).alignment_window_ms(2000).build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 100.0).push_aged("b", 90.0, 3);
+
+        let result = &harness.calculate()[0];
+        assert_eq!(result.value, 100.0);
+        assert_eq!(result.quality.lagging_feeds, vec!["b"]);
+        assert!(!result.quality.is_degraded());
+
+        harness.push("b", 102.0);
+        let result = &harness.calculate()[0];
+        assert_eq!(result.value, 101.0);
+        assert!(result.quality.lagging_feeds.is_empty());
+    }
+
+    #[test]
+    fn test_index_is_withheld_below_its_feed_quorum() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 40).feed("b", 30).feed("c", 30)
+            .min_feeds(2)
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 100.0).push_aged("b", 101.0, 120).push_aged("c", 102.0, 120);
+        assert!(harness.calculate().is_empty());
+
+        harness.push("b", 101.0);
+        assert_values_close(&[harness.calculate()[0].value], &[(0.4 * 100.0 + 0.3 * 101.0) / 0.7], 1e-9);
+    }
+
+    #[test]
+    fn test_feeds_never_priced_do_not_count_towards_the_quorum() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 40).feed("b", 30).feed("c", 30)
+            .min_feeds(2)
+            .build();
+        let (sender, receiver) = mpsc::channel(16);
+        let (events_tx, mut events) = broadcast::channel(16);
+        let mut calculator = IndexCalculator::new(vec![index], receiver).with_health_events(events_tx);
+        sender.try_send(FeedData {
+            feed_id: "a".to_string(),
+            timestamp: Utc::now(),
+            price: 100.0,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        }).unwrap();
+
+        // b and c never updated, so they are not stale, but they have no price either
+        assert!(calculator.calculate_indices().unwrap().is_empty());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            HealthEvent::IndexSuppressed { reason, .. } if reason == "constituent quorum not met"
+        ));
+    }
+
+    #[test]
+    fn test_missing_feed_is_reweighted_and_flagged() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 60).feed("b", 40);
+        let mut harness = IndexHarness::new(vec![index.clone().build()]);
+        harness.push("a", 100.0);
+        assert!(harness.calculate().is_empty());
+
+        let mut harness = IndexHarness::new(vec![index.reweight_missing_feeds().build()]);
+        harness.push("a", 100.0);
+        let results = harness.calculate();
+        assert_eq!(results[0].value, 100.0);
+        assert_eq!(results[0].quality.missing_feeds, vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn test_quality_reports_contributors_dispersion_and_confidence() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 25).feed("c", 25).build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 100.0).push("b", 101.0).push_aged("c", 102.0, 120);
+
+        let quality = harness.calculate().remove(0).quality;
+        assert_eq!(quality.contributors, 2);
+        assert_values_close(&[quality.dispersion_pct.unwrap()], &[1.0 / 100.5 * 100.0], 1e-9);
+        assert!(quality.max_age_secs < 5.0);
+        assert!(quality.confidence > 0.3 && quality.confidence < 2.0 / 3.0);
+    }
+
+    #[test]
+    fn test_trimmed_mean_drops_both_ends() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX")
+            .feed("a", 20).feed("b", 20).feed("c", 20).feed("d", 20).feed("e", 20)
+            .trimmed_mean(20.0)
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 1.0).push("b", 100.0).push("c", 101.0).push("d", 102.0).push("e", 5000.0);
+
+        assert_eq!(harness.calculate()[0].value, 101.0);
+        assert_eq!(trimmed_mean(&[1.0, 2.0, 3.0], 10.0), Some(2.0));
+    }
+
+    #[test]
+    fn test_divisor_keeps_level_continuous_when_a_constituent_drops_out() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50).divisor(1000.0).build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 100.0).push("b", 200.0);
+        assert_eq!(harness.calculate()[0].value, 1000.0);
+
+        // An average would fall to 100 without b; the divisor absorbs the change instead
+        harness.push_aged("b", 200.0, 120);
+        assert_values_close(&[harness.calculate()[0].value], &[1000.0], 1e-9);
+
+        harness.push("a", 110.0);
+        assert_values_close(&[harness.calculate()[0].value], &[1100.0], 1e-9);
+
+        let saved = harness.calculator().take_unsaved_divisors();
+        assert_eq!(saved.len(), 1);
+        assert_values_close(&[saved[0].1.divisor], &[5.0], 1e-9);
+        assert!(harness.calculator().take_unsaved_divisors().is_empty());
+    }
+
+    #[test]
+    fn test_rebalancing_adjusts_the_divisor_without_a_level_jump() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50).divisor(1000.0).build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 100.0).push("b", 200.0);
+        assert_eq!(harness.calculate()[0].value, 1000.0);
+
+        let weights = |a: f64, b: f64| HashMap::from([("a".to_string(), a), ("b".to_string(), b)]);
+        assert!(harness.calculator().rebalance("BTC-USD-INDEX", weights(80.0, 30.0)).is_err());
+        harness.calculator().rebalance("BTC-USD-INDEX", weights(80.0, 20.0)).unwrap();
+        assert_values_close(&[harness.calculator().take_unsaved_divisors()[0].1.divisor], &[12.0], 1e-9);
+        assert_values_close(&[harness.calculate()[0].value], &[1000.0], 1e-9);
+
+        // a now weighs 80%: (80 * 110 + 20 * 200) / 12
+        harness.push("a", 110.0);
+        assert_values_close(&[harness.calculate()[0].value], &[3200.0 / 3.0], 1e-9);
+    }
+
+    #[test]
+    fn test_log_return_level_compounds_returns_across_constituent_changes() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50).log_returns(100.0).build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 100.0).push("b", 200.0);
+        assert_eq!(harness.calculate()[0].value, 100.0);
+
+        // Both constituents up 10%
+        harness.push("a", 110.0).push("b", 220.0);
+        assert_values_close(&[harness.calculate()[0].value], &[110.0], 1e-9);
+
+        // b stops updating; the level follows a alone, without jumping on b's exit or return
+        harness.push("a", 121.0).push_aged("b", 220.0, 120);
+        assert_values_close(&[harness.calculate()[0].value], &[121.0], 1e-9);
+        harness.push("b", 220.0);
+        assert_values_close(&[harness.calculate()[0].value], &[121.0], 1e-9);
+
+        let state = harness.calculator().state();
+        assert_values_close(&[state.returns["BTC-USD-INDEX"].log_return(100.0)], &[1.21f64.ln()], 1e-9);
+    }
+
+    #[test]
+    fn test_composite_is_calculated_after_its_components() {
+        let composite = IndexDefinitionBuilder::new("BLUECHIP-USD-INDEX").component("BTC-USD-INDEX", 60).component("ETH-USD-INDEX", 40).build();
+        let btc = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("btc", 100).build();
+        let eth = IndexDefinitionBuilder::new("ETH-USD-INDEX").feed("eth", 100).build();
+        let mut harness = IndexHarness::new(vec![composite, btc, eth]);
+        harness.push("btc", 60000.0).push("eth", 3000.0);
+
+        let results = harness.calculate();
+        let names: Vec<&str> = results.iter().map(|result| result.name.as_str()).collect();
+        assert_eq!(names, vec!["BTC-USD-INDEX", "ETH-USD-INDEX", "BLUECHIP-USD-INDEX"]);
+        assert_values_close(&[results[2].value], &[0.6 * 60000.0 + 0.4 * 3000.0], 1e-9);
+
+        // Two minutes later only BTC is still updating and the last ETH value is too old to be used
+        harness.push_aged("btc", 60000.0, -120);
+        let later = Utc::now() + chrono::Duration::seconds(120);
+        let results = harness.calculator().calculate_scheduled_at(&PublishSchedule::default(), later).unwrap();
+        assert_eq!(results[1].name, "BLUECHIP-USD-INDEX");
+        assert_eq!(results[1].value, 60000.0);
+        assert_eq!(results[1].quality.stale_feeds, vec!["ETH-USD-INDEX".to_string()]);
+    }
+
+    #[test]
+    fn test_derived_index_divides_index_values() {
+        let cross = IndexDefinitionBuilder::new("ETH-BTC-INDEX").expression("ETH-USD-INDEX / BTC-USD-INDEX").build();
+        let btc = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("btc", 100).build();
+        let eth = IndexDefinitionBuilder::new("ETH-USD-INDEX").feed("eth", 100).build();
+        let mut harness = IndexHarness::new(vec![cross, btc, eth]);
+        harness.push("btc", 60000.0).push("eth", 3000.0);
+
+        let results = harness.calculate();
+        assert_eq!(results[2].name, "ETH-BTC-INDEX");
+        assert_values_close(&[results[2].value], &[0.05], 1e-12);
+        assert_eq!(results[2].quality.contributors, 2);
+
+        // Without a fresh BTC value there is no cross rate
+        harness.push_aged("eth", 3000.0, -120);
+        let later = Utc::now() + chrono::Duration::seconds(120);
+        let results = harness.calculator().calculate_scheduled_at(&PublishSchedule::default(), later).unwrap();
+        assert!(results.iter().all(|result| result.name != "ETH-BTC-INDEX"));
+    }
+
+    #[test]
+    fn test_inverse_index_is_transformed_before_smoothing() {
+        let index = IndexDefinitionBuilder::new("USD-BTC-INDEX").feed("a", 50).feed("b", 50)
+            .inverse(1e8)
+            .smoothing(SmoothingType::Sma)
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("a", 40000.0).push("b", 60000.0);
+        harness.calculate();
+        harness.push("a", 100000.0).push("b", 100000.0);
+
+        // The mean of the inverted aggregates, not the inverse of the mean price
+        let results = harness.calculate();
+        assert_values_close(&[results[0].value], &[(1e8 / 50000.0 + 1e8 / 100000.0) / 2.0], 1e-9);
+    }
+
+    #[test]
+    fn test_restored_state_continues_smoothing_after_a_restart() {
+        let index = || IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100).smoothing(SmoothingType::Ema).build();
+        let mut running = IndexHarness::new(vec![index()]);
+        running.replay("a", &[100.0, 110.0, 120.0]);
+
+        let mut restarted = IndexHarness::new(vec![index()]);
+        restarted.calculator().restore_state(running.calculator().state());
+        assert_eq!(restarted.calculator().state(), running.calculator().state());
+
+        let expected = running.replay("a", &[130.0]);
+        let continued = restarted.replay("a", &[130.0]);
+        assert_eq!(continued[0].value, expected[0].value);
+    }
+
+    #[test]
+    fn test_results_carry_the_raw_value_next_to_the_smoothed_one() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100)
+            .smoothing(SmoothingType::Sma)
+            .smoothing_params(2, 2.0)
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+
+        let results = harness.replay("a", &[100.0, 120.0]);
+        assert_eq!((results[1].value, results[1].raw_value), (110.0, Some(120.0)));
+        harness.push("a", 140.0);
+        let snapshot = harness.calculator().snapshot().unwrap();
+        assert_eq!((snapshot.indices[0].value, snapshot.indices[0].raw_value), (130.0, Some(140.0)));
+    }
+
+    #[test]
+    fn test_winsorized_smoothing_bounds_a_single_anomalous_tick() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100)
+            .smoothing(SmoothingType::Sma)
+            .smoothing_params(2, 2.0)
+            .smoothing_band_pct(5.0)
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+
+        // The 200 print is smoothed as 105, 5% above the previous smoothed value
+        let results = harness.replay("a", &[100.0, 200.0, 100.0]);
+        assert_values_close(&results.iter().map(|result| result.value).collect::<Vec<_>>(), &[100.0, 102.5, 102.5], 1e-9);
+        assert_eq!(results[1].raw_value, Some(200.0));
+    }
+
+    #[test]
+    fn test_values_are_flagged_until_smoothing_is_warm() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100)
+            .smoothing(SmoothingType::Sma)
+            .smoothing_params(3, 2.0)
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+
+        let results = harness.replay("a", &[100.0, 110.0, 120.0, 130.0]);
+        let flags: Vec<bool> = results.iter().map(|result| result.warming_up).collect();
+        assert_eq!(flags, [true, true, false, false]);
+    }
+
+    #[test]
+    fn test_warm_up_can_be_withheld() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100)
+            .smoothing(SmoothingType::Sma)
+            .smoothing_params(3, 2.0)
+            .withhold_warm_up()
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.replay("a", &[100.0, 110.0, 120.0, 130.0]);
+
+        assert_values_close(&harness.published_values("BTC-USD-INDEX"), &[110.0, 120.0], 1e-9);
+    }
+
+    #[test]
+    fn test_smoothing_is_warmed_up_from_stored_values_without_a_state() {
+        let index = || IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100)
+            .smoothing(SmoothingType::Sma)
+            .smoothing_params(3, 2.0)
+            .build();
+        let mut running = IndexHarness::new(vec![index()]);
+        let published = running.replay("a", &[100.0, 110.0, 120.0]);
+
+        // Stored values, newest first, but no smoothing state
+        let stored = published.iter().rev()
+            .map(|result| StoredValue { value: result.value, raw_value: result.raw_value })
+            .collect();
+        let mut restarted = IndexHarness::new(vec![index()]);
+        restarted.calculator().restore_state(CalculatorState {
+            index_history: BTreeMap::from([("BTC-USD-INDEX".to_string(), stored)]),
+            ..Default::default()
+        });
+
+        let expected = running.replay("a", &[130.0]);
+        let continued = restarted.replay("a", &[130.0]);
+        assert_eq!(continued[0].value, expected[0].value);
+        assert!(!continued[0].warming_up);
+    }
+
+    #[test]
+    fn test_checkpoint_continues_smoothing_after_a_restart() {
+        let index = || IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100).smoothing(SmoothingType::Ema).build();
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.json", std::process::id()));
+        let mut running = IndexHarness::new(vec![index()]);
+        assert_eq!(read_checkpoint(&path).unwrap(), None);
+        running.replay("a", &[100.0, 110.0, 120.0]);
+        write_checkpoint(&path, &running.calculator().state()).unwrap();
+
+        let mut restarted = IndexHarness::new(vec![index()]);
+        restarted.calculator().restore_state(read_checkpoint(&path).unwrap().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let expected = running.replay("a", &[130.0]);
+        let continued = restarted.replay("a", &[130.0]);
+        assert_eq!(continued[0].value, expected[0].value);
+    }
+
+    #[test]
+    fn test_published_sequence_follows_updates() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50)
+            .smoothing(SmoothingType::None)
+            .build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.push("b", 100.0);
+        harness.replay("a", &[100.0, 200.0, 300.0]);
+
+        assert_values_close(&harness.published_values("BTC-USD-INDEX"), &[100.0, 150.0, 200.0], 1e-9);
+    }
+}
+
+#[cfg(test)]
+mod expression_tests {
+    use super::*;
+
+    fn eval(source: &str, values: &[(&str, f64)]) -> Option<f64> {
+        Expression::parse(source).unwrap()
+            .eval(&mut |name| values.iter().find(|(id, _)| *id == name).map(|(_, value)| *value))
+    }
+
+    #[test]
+    fn test_expressions_follow_operator_precedence() {
+        assert_eq!(eval("1 + 2 * 3", &[]), Some(7.0));
+        assert_eq!(eval("(1 + 2) * 3", &[]), Some(9.0));
+        assert_eq!(eval("-2 * 3 - 1", &[]), Some(-7.0));
+        assert_eq!(eval("ETH-USD-INDEX / BTC-USD-INDEX", &[("ETH-USD-INDEX", 3000.0), ("BTC-USD-INDEX", 60000.0)]), Some(0.05));
+        assert_eq!(eval("A-INDEX - B-INDEX", &[("A-INDEX", 5.0), ("B-INDEX", 2.0)]), Some(3.0));
+    }
+
+    #[test]
+    fn test_expressions_without_a_value() {
+        assert_eq!(eval("a / b", &[("a", 1.0), ("b", 0.0)]), None);
+        assert_eq!(eval("a / b", &[("a", 1.0)]), None);
+        assert!(Expression::parse("a /").is_err());
+        assert!(Expression::parse("(a + b").is_err());
+        assert!(Expression::parse("a $ b").is_err());
+        assert_eq!(Expression::parse("a * (b + a)").unwrap().references(), vec!["a", "b"]);
+    }
+}
+
+#[cfg(test)]
+mod outlier_tests {
+    use super::*;
+
+    #[test]
+    fn test_median_of_odd_and_even_sets() {
+        assert_eq!(median(&[3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(&[4.0, 1.0, 3.0, 2.0]), Some(2.5));
+        assert_eq!(median(&[]), None);
+    }
+
+    #[test]
+    fn test_fat_finger_price_is_rejected() {
+        let prices = [("a", 100.0), ("b", 100.5), ("c", 99.8), ("d", 1.0)];
+
+        assert_eq!(find_outliers(&prices, 5.0), vec!["d".to_string()]);
+    }
+
+    #[test]
+    fn test_two_feeds_are_never_outliers() {
+        let prices = [("a", 100.0), ("b", 1.0)];
+
+        assert!(find_outliers(&prices, 5.0).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod alert_tests {
+    use super::*;
+
+    fn rule(condition: AlertCondition, breaches: u32) -> AlertRule {
+        AlertRule {
+            name: "benchmark".to_string(),
+            index: "BTC-USD-INDEX".to_string(),
+            reference: AlertReference::Index("BTC-USD-BENCHMARK".to_string()),
+            condition,
+            breaches,
+            severity: Severity::Warning,
+        }
+    }
+
+    fn results(value: f64) -> Vec<IndexResult> {
+        vec![IndexResult { name: "BTC-USD-INDEX".to_string(), timestamp: Utc::now(), value, raw_value: None, epoch: 1, denomination: Default::default(), quality: Default::default(), change: Default::default(), methodology_version: 0, warming_up: false }]
+    }
+
+    #[test]
+    fn test_divergence_alert_raised_after_consecutive_breaches_and_cleared() {
+        let mut monitor = AlertMonitor::new(vec![rule(AlertCondition::Divergence { max_pct: 1.0 }, 2)]);
+        let mut evaluate = |value| monitor.evaluate(&results(value), |_| Some(100.0));
+
+        assert!(evaluate(102.0).is_empty());
+        let raised = evaluate(102.0);
+        assert!(raised.len() == 1 && raised[0].raised && raised[0].severity == Severity::Warning);
+        assert!(evaluate(103.0).is_empty());
+
+        let cleared = evaluate(100.5);
+        assert!(cleared.len() == 1 && !cleared[0].raised);
+    }
+
+    #[test]
+    fn test_correlation_alert_on_opposite_moves() {
+        let mut monitor = AlertMonitor::new(vec![rule(AlertCondition::Correlation { min: 0.5, window: 3 }, 1)]);
+
+        let events: Vec<_> = [(100.0, 100.0), (101.0, 99.0), (100.0, 100.0), (102.0, 98.0)].into_iter()
+            .flat_map(|(value, reference)| monitor.evaluate(&results(value), |_| Some(reference)))
+            .collect();
+
+        assert_eq!(events.len(), 1);
+        assert!(events[0].raised);
+    }
+}
+
+#[cfg(test)]
+mod simulation_tests {
+    use super::*;
+    use std::collections::HashMap;
+    use chrono::Duration;
+    use crate::index::{simulate, WhatIfRequest};
+    use crate::models::FeedData;
+
+    fn request(weights: &[(&str, u32)]) -> WhatIfRequest {
+        WhatIfRequest {
+            index: "BTC-USD-INDEX".to_string(),
+            weights: weights.iter().map(|(id, weight)| (id.to_string(), *weight)).collect::<HashMap<_, _>>(),
+            smoothing: None,
+            hours: 1,
+            resolution_secs: 60,
+        }
+    }
+
+    fn tick(feed_id: &str, timestamp: chrono::DateTime<Utc>, price: f64) -> FeedData {
+        FeedData { feed_id: feed_id.to_string(), timestamp, price, backup_feed: None, heartbeat: false, denomination: None }
+    }
+
+    #[test]
+    fn test_reweighted_series_is_replayed_next_to_the_current_one() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50).build();
+        let from = Utc::now() - Duration::minutes(3);
+        let ticks = vec![
+            tick("a", from, 100.0),
+            tick("b", from + Duration::seconds(30), 200.0),
+            tick("a", from + Duration::seconds(90), 120.0),
+        ];
+
+        let result = simulate(&index, &request(&[("a", 75), ("b", 25)]), &ticks, from, from + Duration::minutes(2)).unwrap();
+
+        // No value until both feeds have a price
+        let values: Vec<(f64, f64)> = result.points.iter().map(|point| (point.current, point.proposed)).collect();
+        assert_eq!(values, vec![(150.0, 125.0), (160.0, 140.0)]);
+        assert!((result.max_difference_pct - 100.0 / 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_invalid_overrides_are_rejected() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50).build();
+
+        assert!(request(&[("a", 60)]).apply(&index).is_err());
+        assert!(request(&[("c", 0)]).apply(&index).is_err());
+        assert_eq!(request(&[("a", 100), ("b", 0)]).apply(&index).unwrap().feeds.len(), 1);
+    }
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use super::*;
+    use chrono::{DateTime, Duration};
+    use crate::index::{decimal, replay, with_constituent_indices, SeriesChecksum};
+
+    fn tick(feed_id: &str, timestamp: DateTime<Utc>, price: f64) -> FeedData {
+        FeedData { feed_id: feed_id.to_string(), timestamp, price, backup_feed: None, heartbeat: false, denomination: None }
+    }
+
+    fn checksum(results: &[IndexResult]) -> String {
+        let mut checksum = SeriesChecksum::default();
+        results.iter().for_each(|result| checksum.update(result));
+        checksum.finish()
+    }
+
+    #[test]
+    fn test_deterministic_replay_does_not_depend_on_feed_or_tick_order() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let ticks = vec![
+            tick("a", start, 0.1),
+            tick("b", start, 0.2),
+            tick("c", start, 0.3),
+            tick("b", start + Duration::milliseconds(1500), 0.25),
+        ];
+        let forward = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 20).feed("b", 30).feed("c", 50).build();
+        let backward = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("c", 50).feed("b", 30).feed("a", 20).build();
+        let mut reversed = ticks.clone();
+        reversed.reverse();
+
+        let first = replay(vec![forward], ticks, true).unwrap();
+        let second = replay(vec![backward], reversed, true).unwrap();
+
+        assert_eq!(first.iter().map(|result| result.value).collect::<Vec<_>>(), vec![0.23, 0.23, 0.245]);
+        assert_eq!(checksum(&first), checksum(&second));
+    }
+
+    #[test]
+    fn test_recomputed_index_brings_its_constituent_indices() {
+        let indices = vec![
+            IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 100).build(),
+            IndexDefinitionBuilder::new("ETH-USD-INDEX").feed("b", 100).build(),
+            IndexDefinitionBuilder::new("SOL-USD-INDEX").feed("c", 100).build(),
+            IndexDefinitionBuilder::new("ETH-BTC-INDEX").expression("ETH-USD-INDEX / BTC-USD-INDEX").build(),
+        ];
+
+        let mut names: Vec<String> = with_constituent_indices(&indices, "ETH-BTC-INDEX").into_iter().map(|index| index.name).collect();
+        names.sort();
+
+        assert_eq!(names, vec!["BTC-USD-INDEX", "ETH-BTC-INDEX", "ETH-USD-INDEX"]);
+        assert!(with_constituent_indices(&indices, "MISSING-INDEX").is_empty());
+    }
+
+    #[test]
+    fn test_decimal_weighted_mean_is_exact() {
+        assert_eq!(decimal::weighted_mean([(0.1, 1.0), (0.2, 1.0)]), Some(0.15));
+        assert_eq!(decimal::weighted_mean([(0.1, 0.0)]), None);
+    }
+}
+
+#[cfg(test)]
+mod catalog_tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_entry_lists_metadata_and_constituents() {
+        let index = IndexDefinitionBuilder::new("BLUECHIP-USD-INDEX").component("BTC-USD-INDEX", 70).component("ETH-USD-INDEX", 30)
+            .family("majors", Lifecycle::Preview)
+            .build();
+
+        let entry = IndexCatalogEntry::from(&index);
+        assert_eq!(entry.family.as_deref(), Some("majors"));
+        assert_eq!(entry.lifecycle, Lifecycle::Preview);
+        assert_eq!(entry.publish_interval_ms, 1000);
+        assert_eq!(entry.constituents, vec!["BTC-USD-INDEX".to_string(), "ETH-USD-INDEX".to_string()]);
+        assert_eq!(entry.smoothing.algorithm, index.smoothing);
+        assert_eq!(entry.smoothing.period, index.smoothing_period);
+
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(json["lifecycle"], "preview");
+        assert!(json.get("labels").is_none());
+        assert!(json["smoothing"].get("band_pct").is_none());
+    }
+}
+
+#[cfg(test)]
+mod methodology_tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_follows_the_calculation_only() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 60).feed("b", 40);
+        let fingerprint = index.clone().build().methodology_fingerprint();
+
+        let republished = index.clone().publish(PublishSchedule { interval_ms: 250, ..Default::default() }).build();
+        assert_eq!(republished.methodology_fingerprint(), fingerprint);
+
+        let reweighted = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("b", 50).build();
+        assert_ne!(reweighted.methodology_fingerprint(), fingerprint);
+        let resmoothed = index.smoothing(SmoothingType::Ema).build();
+        assert_ne!(resmoothed.methodology_fingerprint(), fingerprint);
+        assert!(resmoothed.methodology_description().contains("smoothing = Ema"));
+    }
+}
+
+#[cfg(test)]
+mod change_tests {
+    use super::*;
+
+    #[test]
+    fn test_change_since_previous_value_and_over_windows() {
+        let start = Utc::now();
+        let at = |secs: i64| start + chrono::Duration::seconds(secs);
+        let mut tracker = ChangeTracker::new(&[60, 3600]);
+
+        assert_eq!(tracker.change(at(0), 100.0), Default::default());
+        for secs in 0..=90 {
+            tracker.record(at(secs), 100.0 + secs as f64);
+        }
+
+        let change = tracker.change(at(91), 200.0);
+        assert_eq!(change.absolute, Some(10.0));
+        assert_values_close(&[change.percent.unwrap()], &[10.0 / 190.0 * 100.0], 1e-9);
+        // The value published 60s earlier, at 31s, is the start of the 1m window
+        assert_values_close(&[change.windows["1m"]], &[(200.0 - 131.0) / 131.0 * 100.0], 1e-9);
+        // Not published for an hour yet
+        assert!(!change.windows.contains_key("1h"));
+    }
+
+    #[test]
+    fn test_window_labels() {
+        assert_eq!(window_label(60), "1m");
+        assert_eq!(window_label(86400), "24h");
+        assert_eq!(window_label(90), "90s");
+    }
+}
+
+#[cfg(test)]
+mod rebalance_tests {
+    use chrono::DateTime;
+    use crate::config::RebalanceSchedule;
+    use crate::index::rebalance::next_rebalance;
+
+    #[test]
+    fn test_next_rebalancing_is_at_midnight_of_the_next_scheduled_day() {
+        let at = |timestamp: &str| DateTime::parse_from_rfc3339(timestamp).unwrap().to_utc();
+        // A Wednesday
+        let now = at("2024-01-31T00:00:00Z");
+
+        assert_eq!(next_rebalance(RebalanceSchedule::Daily, now), at("2024-02-01T00:00:00Z"));
+        assert_eq!(next_rebalance(RebalanceSchedule::Weekly, now), at("2024-02-05T00:00:00Z"));
+        assert_eq!(next_rebalance(RebalanceSchedule::Monthly, now), at("2024-02-01T00:00:00Z"));
+        assert_eq!(next_rebalance(RebalanceSchedule::Monthly, at("2024-02-01T00:00:00Z")), at("2024-03-01T00:00:00Z"));
+    }
+}
+
+#[cfg(test)]
+mod bootstrap_tests {
+    use super::*;
+
+    fn closes(series: &[(&str, &[f64])]) -> HashMap<String, Vec<f64>> {
+        series.iter().map(|(feed_id, closes)| (feed_id.to_string(), closes.to_vec())).collect()
+    }
+
+    fn sma_index(name: &str) -> IndexDefinitionBuilder {
+        IndexDefinitionBuilder::new(name).feed("a", 50).feed("b", 50).smoothing(SmoothingType::Sma).smoothing_params(3, 0.0)
+    }
+
+    fn bootstrapped_history(harness: &mut IndexHarness, index_name: &str) -> Vec<f64> {
+        harness.calculator().state().smoothing.get(index_name)
+            .map(|snapshot| snapshot.history.clone())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_smoothing_is_warm_after_bootstrapping() {
+        let mut harness = IndexHarness::new(vec![sma_index("BTC-USD-INDEX").build()]);
+        harness.calculator().bootstrap_history(&closes(&[("a", &[100.0, 110.0, 120.0]), ("b", &[200.0, 210.0, 220.0])]));
+        harness.push("a", 130.0).push("b", 230.0);
+
+        // Averages the replayed 160 and 170 with the live 180 instead of starting at 180
+        assert_eq!(harness.calculate()[0].value, 170.0);
+    }
+
+    #[test]
+    fn test_series_of_unequal_length_are_aligned_on_the_newest_close() {
+        let mut harness = IndexHarness::new(vec![sma_index("BTC-USD-INDEX").build()]);
+        harness.calculator().bootstrap_history(&closes(&[("a", &[1000.0, 100.0, 110.0, 120.0]), ("b", &[210.0, 220.0])]));
+
+        // Only the two closes both feeds have are replayed, newest first
+        assert_values_close(&bootstrapped_history(&mut harness, "BTC-USD-INDEX"), &[165.0, 160.0], 1e-9);
+    }
+
+    #[test]
+    fn test_index_missing_closes_for_a_feed_is_skipped() {
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("a", 50).feed("c", 50)
+            .smoothing(SmoothingType::Sma).smoothing_params(3, 0.0).build();
+        let mut harness = IndexHarness::new(vec![index]);
+        harness.calculator().bootstrap_history(&closes(&[("a", &[100.0, 110.0, 120.0])]));
+        assert!(bootstrapped_history(&mut harness, "BTC-USD-INDEX").is_empty());
+
+        // Smoothing starts cold
+        harness.push("a", 130.0).push("c", 230.0);
+        assert_eq!(harness.calculate()[0].value, 180.0);
+    }
+
+    #[test]
+    fn test_divisor_index_without_restored_divisor_is_skipped() {
+        let mut harness = IndexHarness::new(vec![sma_index("BTC-USD-INDEX").divisor(1000.0).build()]);
+        harness.calculator().bootstrap_history(&closes(&[("a", &[100.0, 110.0, 120.0]), ("b", &[200.0, 210.0, 220.0])]));
+
+        assert!(bootstrapped_history(&mut harness, "BTC-USD-INDEX").is_empty());
+    }
+}
@@ -2,16 +2,29 @@
 pub mod config;
 pub mod exchange;
 pub mod index;
+pub mod aggregation;
 pub mod storage;
 pub mod smoothing;
 pub mod websocket;
+pub mod api;
+pub mod multicast;
 pub mod notification;
 pub mod logging;
+pub mod drill;
+pub mod metrics;
+pub mod limits;
+pub mod health;
+pub mod market_cap;
+pub mod serialization;
+pub mod sketch;
+pub mod bundle;
 pub mod models;
 pub mod error;
+#[cfg(any(test, feature = "test_support"))]
+pub mod test_support;
 
 // Export commonly used types for convenience
-pub use models::{FeedData, PriceFeed, IndexDefinition, SmoothingType};
+pub use models::{FeedData, PriceFeed, IndexDefinition, SmoothingType, FeedPriority};
 pub use index::calculator::IndexCalculator;
 pub use index::models::IndexResult;
 pub use exchange::traits::Exchange;
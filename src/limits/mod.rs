@@ -0,0 +1,6 @@
+mod monitor;
+
+#[cfg(test)]
+mod tests;
+
+pub use monitor::{ConnectionPermit, ConnectionRefusal, ResourceGuard};
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::config::LimitsConfig;
+use crate::metrics::metrics;
+use crate::models::FeedPriority;
+
+/// Enforces the configured resource limits and tracks whether the collector runs degraded.
+///
+/// While degraded, new WebSocket connections are refused, one existing client is shed per
+/// check interval and low-priority feeds stop polling.
+#[derive(Debug)]
+pub struct ResourceGuard {
+    config: LimitsConfig,
+    connections: AtomicUsize,
+    /// Open connections by client address
+    connections_per_ip: Mutex<HashMap<IpAddr, usize>>,
+    degraded: AtomicBool,
+    pending_sheds: AtomicUsize,
+}
+
+/// Why a WebSocket connection was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionRefusal {
+    /// The collector is over one of its limits
+    Degraded,
+    /// `max_connections` are open
+    ConnectionLimit,
+    /// `max_connections_per_ip` are open from the client's address
+    AddressLimit,
+}
+
+impl std::fmt::Display for ConnectionRefusal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ConnectionRefusal::Degraded => "collector degraded",
+            ConnectionRefusal::ConnectionLimit => "connection limit reached",
+            ConnectionRefusal::AddressLimit => "connection limit of the client address reached",
+        })
+    }
+}
+
+/// Slot held by a WebSocket connection for as long as it is open
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    guard: Arc<ResourceGuard>,
+    ip: Option<IpAddr>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.guard.connections.fetch_sub(1, Ordering::SeqCst);
+        let Some(ip) = self.ip else {
+            return;
+        };
+        let mut per_ip = self.guard.connections_per_ip.lock().unwrap();
+        if let Some(count) = per_ip.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                per_ip.remove(&ip);
+            }
+        }
+    }
+}
+
+impl ResourceGuard {
+    pub fn new(config: LimitsConfig) -> Self {
+        Self {
+            config,
+            connections: AtomicUsize::new(0),
+            connections_per_ip: Mutex::new(HashMap::new()),
+            degraded: AtomicBool::new(false),
+            pending_sheds: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reserve a connection slot for a client at `ip`, or why the connection has to be refused;
+    /// clients without an address, such as those of a Unix domain socket, only count towards
+    /// `max_connections`
+    pub fn try_open_connection(self: &Arc<Self>, ip: Option<IpAddr>) -> Result<ConnectionPermit, ConnectionRefusal> {
+        if self.is_degraded() {
+            metrics().increment("limits.websocket_rejected");
+            return Err(ConnectionRefusal::Degraded);
+        }
+
+        // Held while reserving, so concurrent connections from one address can't overshoot
+        let mut per_ip = self.connections_per_ip.lock().unwrap();
+        if let (Some(ip), Some(max_per_ip)) = (ip, self.config.max_connections_per_ip) {
+            if per_ip.get(&ip).copied().unwrap_or_default() >= max_per_ip {
+                metrics().increment("limits.websocket_rejected_per_ip");
+                return Err(ConnectionRefusal::AddressLimit);
+            }
+        }
+
+        let max = self.config.max_connections.unwrap_or(usize::MAX);
+        let reserved = self.connections.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+            (current < max).then_some(current + 1)
+        });
+
+        match reserved {
+            Ok(_) => {
+                if let Some(ip) = ip {
+                    *per_ip.entry(ip).or_default() += 1;
+                }
+                metrics().increment("websocket.connections_opened");
+                Ok(ConnectionPermit { guard: self.clone(), ip })
+            }
+            Err(_) => {
+                metrics().increment("limits.websocket_rejected");
+                Err(ConnectionRefusal::ConnectionLimit)
+            }
+        }
+    }
+
+    /// Open WebSocket connections of a client address
+    pub fn connections_from(&self, ip: IpAddr) -> usize {
+        self.connections_per_ip.lock().unwrap().get(&ip).copied().unwrap_or_default()
+    }
+
+    /// Number of open WebSocket connections
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Whether the collector is currently over one of its limits
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::SeqCst)
+    }
+
+    /// Whether a feed with the given priority should skip polling
+    pub fn should_pause(&self, priority: FeedPriority) -> bool {
+        priority == FeedPriority::Low && self.is_degraded()
+    }
+
+    /// How long a paused feed waits before checking again whether it may poll
+    pub fn pause_interval(&self) -> Duration {
+        Duration::from_secs(self.config.paused_poll_interval_secs.max(1))
+    }
+
+    /// Claim a pending shed request; the connection that gets it should disconnect
+    pub fn take_shed_request(&self) -> bool {
+        let taken = self.pending_sheds
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |pending| pending.checked_sub(1))
+            .is_ok();
+        if taken {
+            metrics().increment("limits.websocket_shed");
+        }
+        taken
+    }
+
+    /// Sample resource usage periodically and switch in and out of degraded mode
+    pub async fn run(self: Arc<Self>, mut shutdown: broadcast::Receiver<()>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.check_interval_secs.max(1)));
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => self.check(),
+                _ = shutdown.recv() => break,
+            }
+        }
+    }
+
+    pub(super) fn check(&self) {
+        let mut exceeded = Vec::new();
+
+        let rss = current_rss_bytes();
+        if let Some(rss) = rss {
+            metrics().set_gauge("process.rss_bytes", rss as f64);
+        }
+        if let Some(max_rss_mb) = self.config.max_rss_mb {
+            metrics().set_gauge("limits.max_rss_bytes", (max_rss_mb * 1024 * 1024) as f64);
+            if rss.is_some_and(|rss| rss > max_rss_mb * 1024 * 1024) {
+                exceeded.push("memory");
+            }
+        }
+
+        let tasks = tokio::runtime::Handle::current().metrics().num_alive_tasks();
+        metrics().set_gauge("runtime.alive_tasks", tasks as f64);
+        if let Some(max_tasks) = self.config.max_tasks {
+            metrics().set_gauge("limits.max_tasks", max_tasks as f64);
+            if tasks > max_tasks {
+                exceeded.push("tasks");
+            }
+        }
+
+        let connections = self.connections();
+        metrics().set_gauge("websocket.connections", connections as f64);
+        if let Some(max_connections) = self.config.max_connections {
+            metrics().set_gauge("limits.max_connections", max_connections as f64);
+        }
+        metrics().set_gauge("websocket.client_addresses", self.connections_per_ip.lock().unwrap().len() as f64);
+
+        self.update(&exceeded, connections);
+    }
+
+    /// Enter or stay in degraded mode while any limit is exceeded, requesting one of the open
+    /// `connections` to be shed per check; leave it once none is
+    pub(super) fn update(&self, exceeded: &[&str], connections: usize) {
+        if !exceeded.is_empty() {
+            if !self.degraded.swap(true, Ordering::SeqCst) {
+                warn!("[LIMITS] Resource limits exceeded ({}), degrading: refusing clients and pausing low-priority feeds",
+                      exceeded.join(", "));
+            }
+            metrics().increment("limits.degraded_checks");
+            if connections > 0 {
+                self.pending_sheds.fetch_add(1, Ordering::SeqCst);
+            }
+        } else if self.degraded.swap(false, Ordering::SeqCst) {
+            info!("[LIMITS] Resource usage back within limits, leaving degraded mode");
+            self.pending_sheds.store(0, Ordering::SeqCst);
+        }
+
+        metrics().set_gauge("limits.degraded", if self.is_degraded() { 1.0 } else { 0.0 });
+    }
+}
+
+/// Resident set size of the current process, where the platform exposes it
+fn current_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
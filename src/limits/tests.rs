@@ -0,0 +1,121 @@
+use super::*;
+use crate::config::LimitsConfig;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+#[cfg(test)]
+mod connection_tests {
+    use super::*;
+
+    fn guard(max_connections: Option<usize>, max_connections_per_ip: Option<usize>) -> Arc<ResourceGuard> {
+        Arc::new(ResourceGuard::new(LimitsConfig { max_connections, max_connections_per_ip, ..Default::default() }))
+    }
+
+    #[test]
+    fn test_connection_limit() {
+        let guard = guard(Some(2), None);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+
+        let first = guard.try_open_connection(Some(ip)).unwrap();
+        let _second = guard.try_open_connection(Some(ip)).unwrap();
+        assert_eq!(guard.try_open_connection(Some("10.0.0.2".parse().unwrap())).unwrap_err(), ConnectionRefusal::ConnectionLimit);
+        assert_eq!(guard.connections(), 2);
+
+        drop(first);
+        assert!(guard.try_open_connection(Some(ip)).is_ok());
+    }
+
+    #[test]
+    fn test_per_ip_connection_limit() {
+        let guard = guard(Some(10), Some(2));
+        let farm: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let permits: Vec<_> = (0..2).map(|_| guard.try_open_connection(Some(farm)).unwrap()).collect();
+        assert_eq!(guard.try_open_connection(Some(farm)).unwrap_err(), ConnectionRefusal::AddressLimit);
+        assert_eq!(guard.connections_from(farm), 2);
+        let _other = guard.try_open_connection(Some(other)).unwrap();
+        assert_eq!(guard.connections(), 3);
+
+        drop(permits);
+        assert_eq!(guard.connections_from(farm), 0);
+        assert_eq!(guard.connections(), 1);
+        assert!(guard.try_open_connection(Some(farm)).is_ok());
+    }
+
+    #[test]
+    fn test_connections_without_address_skip_per_ip_limit() {
+        let guard = guard(Some(3), Some(1));
+
+        let permits: Vec<_> = (0..3).map(|_| guard.try_open_connection(None).unwrap()).collect();
+        assert_eq!(guard.try_open_connection(None).unwrap_err(), ConnectionRefusal::ConnectionLimit);
+
+        drop(permits);
+        assert_eq!(guard.connections(), 0);
+    }
+}
+
+#[cfg(test)]
+mod degraded_tests {
+    use super::*;
+    use crate::models::FeedPriority;
+    use std::time::Duration;
+
+    #[test]
+    fn test_exceeded_limit_degrades_until_usage_recovers() {
+        let guard = Arc::new(ResourceGuard::new(LimitsConfig::default()));
+        let _permit = guard.try_open_connection(None).unwrap();
+        assert!(!guard.should_pause(FeedPriority::Low));
+
+        guard.update(&["memory"], guard.connections());
+        assert!(guard.is_degraded());
+        assert!(guard.should_pause(FeedPriority::Low));
+        assert!(!guard.should_pause(FeedPriority::Normal));
+        assert_eq!(guard.try_open_connection(None).unwrap_err(), ConnectionRefusal::Degraded);
+
+        guard.update(&[], guard.connections());
+        assert!(!guard.is_degraded());
+        assert!(!guard.should_pause(FeedPriority::Low));
+        assert!(guard.try_open_connection(None).is_ok());
+    }
+
+    #[test]
+    fn test_one_connection_is_shed_per_degraded_check() {
+        let guard = Arc::new(ResourceGuard::new(LimitsConfig::default()));
+
+        guard.update(&["tasks"], 0);
+        assert!(!guard.take_shed_request());
+
+        guard.update(&["tasks"], 3);
+        guard.update(&["tasks", "memory"], 3);
+        assert!(guard.take_shed_request());
+        assert!(guard.take_shed_request());
+        assert!(!guard.take_shed_request());
+
+        // Leaving degraded mode drops the sheds not claimed yet
+        guard.update(&["tasks"], 3);
+        guard.update(&[], 3);
+        assert!(!guard.take_shed_request());
+    }
+
+    #[tokio::test]
+    async fn test_task_limit_is_sampled_from_the_runtime() {
+        let guard = Arc::new(ResourceGuard::new(LimitsConfig { max_tasks: Some(0), ..Default::default() }));
+        let task = tokio::spawn(std::future::pending::<()>());
+
+        guard.check();
+        assert!(guard.is_degraded());
+
+        task.abort();
+        let _ = task.await;
+        guard.check();
+        assert!(!guard.is_degraded());
+    }
+
+    #[test]
+    fn test_paused_feeds_wait_the_configured_interval() {
+        let guard = ResourceGuard::new(LimitsConfig { paused_poll_interval_secs: 2, ..Default::default() });
+        assert_eq!(guard.pause_interval(), Duration::from_secs(2));
+        assert_eq!(ResourceGuard::new(LimitsConfig::default()).pause_interval(), Duration::from_secs(5));
+    }
+}
@@ -1,13 +1,55 @@
 use tracing::Level;
-use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, Layer, Registry};
+use crate::config::TelemetryConfig;
 use crate::error::AppResult;
+use super::telemetry::Telemetry;
+
+/// Layer exporting spans, absent until telemetry is enabled
+type TelemetryLayer = Option<Box<dyn Layer<Registry> + Send + Sync>>;
+
+/// Handle of the installed logging, to enable telemetry export once the configuration is known
+pub struct LoggingHandle {
+    telemetry: reload::Handle<TelemetryLayer, Registry>,
+}
+
+impl LoggingHandle {
+    /// Export spans and metrics over OTLP as configured; `None` without an endpoint
+    pub fn enable_telemetry(&self, config: &TelemetryConfig) -> AppResult<Option<Telemetry>> {
+        let Some(endpoint) = &config.otlp_endpoint else {
+            return Ok(None);
+        };
+        let telemetry = Telemetry::start(endpoint, config)?;
+        self.telemetry.reload(Some(telemetry.layer()))
+            .map_err(|e| format!("Failed to enable telemetry: {}", e))?;
+        Ok(Some(telemetry))
+    }
+}
+
+/// Install logging to `writer`. Log lines are events of level INFO and above; spans only go to
+/// the telemetry export, along with the events within them.
+fn install<W>(writer: W) -> AppResult<LoggingHandle>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let (telemetry, handle) = reload::Layer::new(None);
+    let subscriber = tracing_subscriber::registry()
+        .with(telemetry.with_filter(filter_fn(|metadata| metadata.is_span() || *metadata.level() <= Level::INFO)))
+        .with(fmt::layer().with_writer(writer).with_filter(filter_fn(|metadata| metadata.is_event() && *metadata.level() <= Level::INFO)));
 
-/// Set up structured logging for the application
-pub fn setup_logging() -> AppResult<()> {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .finish();
-    
     tracing::subscriber::set_global_default(subscriber)
-        .map_err(|e| format!("Failed to set up logging: {}", e).into())
+        .map_err(|e| format!("Failed to set up logging: {}", e))?;
+    Ok(LoggingHandle { telemetry: handle })
+}
+
+/// Set up structured logging for the application
+pub fn setup_logging() -> AppResult<LoggingHandle> {
+    install(std::io::stdout)
+}
+
+/// Set up logging to stderr, keeping stdout free for data output
+pub fn setup_logging_to_stderr() -> AppResult<LoggingHandle> {
+    install(std::io::stderr)
 }
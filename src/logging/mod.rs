@@ -1,3 +1,8 @@
 mod formatter;
+mod telemetry;
 
-pub use formatter::setup_logging;
+#[cfg(test)]
+mod tests;
+
+pub use formatter::{setup_logging, setup_logging_to_stderr, LoggingHandle};
+pub use telemetry::{export_name, record_stage, PipelineStage, Telemetry};
@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use std::time::Duration;
+use opentelemetry::metrics::{Histogram, Meter, MeterProvider as _};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use tokio::task::JoinHandle;
+use tracing::info;
+use tracing_subscriber::{Layer, Registry};
+
+use crate::config::TelemetryConfig;
+use crate::error::{AppError, AppResult};
+use crate::metrics::metrics;
+
+/// Name the spans and instruments of the collector are exported under
+const INSTRUMENTATION_SCOPE: &str = "crypto-index-collector";
+
+/// Stage of the fetch → calculate → publish pipeline whose latency is recorded, so the
+/// end-to-end latency of an index value can be attributed to the stages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineStage {
+    /// Request of a price from an exchange
+    Fetch,
+    /// Wait of a fetched price in the channel until the calculator takes it in
+    Channel,
+    /// Calculation of the indices of a publication schedule
+    Calculation,
+    /// From calculating an index value to writing it to a WebSocket client
+    Delivery,
+}
+
+impl PipelineStage {
+    const ALL: [PipelineStage; 4] = [Self::Fetch, Self::Channel, Self::Calculation, Self::Delivery];
+
+    /// Name of the stage's latency histogram
+    pub fn metric_name(self) -> &'static str {
+        match self {
+            Self::Fetch => "pipeline.fetch.duration",
+            Self::Channel => "pipeline.channel.wait",
+            Self::Calculation => "pipeline.calculation.duration",
+            Self::Delivery => "pipeline.delivery.latency",
+        }
+    }
+}
+
+/// Latency histograms of the pipeline stages, in the order of [`PipelineStage::ALL`]; unset
+/// while telemetry is not exported
+static STAGE_LATENCIES: OnceLock<Vec<Histogram<f64>>> = OnceLock::new();
+
+/// Record how long a stage of the pipeline took, in milliseconds
+pub fn record_stage(stage: PipelineStage, latency_ms: f64) {
+    if let Some(histograms) = STAGE_LATENCIES.get() {
+        histograms[stage as usize].record(latency_ms.max(0.0), &[]);
+    }
+}
+
+/// Metric name of the registry as accepted by OpenTelemetry, e.g. `broadcast.index.http_latest.skipped`
+/// for `broadcast.index.http:latest.skipped`
+pub fn export_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '/') { c } else { '_' })
+        .collect()
+}
+
+/// Export of the pipeline's spans and of the metrics to an OTLP/HTTP receiver
+pub struct Telemetry {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+    registry_export: JoinHandle<()>,
+}
+
+impl Telemetry {
+    /// Start exporting to `<endpoint>/v1/traces` and `<endpoint>/v1/metrics`
+    pub fn start(endpoint: &str, config: &TelemetryConfig) -> AppResult<Self> {
+        let endpoint = endpoint.trim_end_matches('/');
+        let resource = Resource::builder().with_service_name(config.service_name.clone()).build();
+
+        let span_exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/traces", endpoint))
+            .build()
+            .map_err(|e| AppError::Config(format!("Cannot export traces to {}: {}", endpoint, e)))?;
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_batch_exporter(span_exporter)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+            .with_resource(resource.clone())
+            .build();
+
+        let metric_exporter = MetricExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/metrics", endpoint))
+            .build()
+            .map_err(|e| AppError::Config(format!("Cannot export metrics to {}: {}", endpoint, e)))?;
+        let interval = Duration::from_secs(config.metrics_interval_secs);
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(metric_exporter).with_interval(interval).build())
+            .with_resource(resource)
+            .build();
+
+        let meter = meter_provider.meter(INSTRUMENTATION_SCOPE);
+        let _ = STAGE_LATENCIES.set(PipelineStage::ALL.iter()
+            .map(|stage| meter.f64_histogram(stage.metric_name()).with_unit("ms").build())
+            .collect());
+        let registry_export = tokio::spawn(export_registry(meter, interval));
+
+        info!("[TELEMETRY] Exporting traces and metrics to {}", endpoint);
+        Ok(Self { tracer_provider, meter_provider, registry_export })
+    }
+
+    /// Layer turning the spans of the pipeline into exported spans
+    pub(super) fn layer(&self) -> Box<dyn Layer<Registry> + Send + Sync> {
+        Box::new(tracing_opentelemetry::layer().with_tracer(self.tracer_provider.tracer(INSTRUMENTATION_SCOPE)))
+    }
+
+    /// Export what is pending and stop; blocks until the receiver answered or timed out
+    pub fn shutdown(self) {
+        self.registry_export.abort();
+        if let Err(e) = self.tracer_provider.shutdown() {
+            tracing::warn!("[TELEMETRY] Failed to export the last spans: {}", e);
+        }
+        if let Err(e) = self.meter_provider.shutdown() {
+            tracing::warn!("[TELEMETRY] Failed to export the last metrics: {}", e);
+        }
+    }
+}
+
+/// Export the counters and gauges of the registry, registering an observable instrument for
+/// every metric as it appears
+async fn export_registry(meter: Meter, interval: Duration) {
+    let mut counters = HashSet::new();
+    let mut gauges = HashSet::new();
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let snapshot = metrics().snapshot();
+        for name in snapshot.counters.into_keys() {
+            if !counters.insert(name.clone()) {
+                continue;
+            }
+            meter.u64_observable_counter(export_name(&name))
+                .with_callback(move |observer| observer.observe(metrics().counter(&name), &[]))
+                .build();
+        }
+        for name in snapshot.gauges.into_keys() {
+            if !gauges.insert(name.clone()) {
+                continue;
+            }
+            meter.f64_observable_gauge(export_name(&name))
+                .with_callback(move |observer| {
+                    if let Some(value) = metrics().gauge(&name) {
+                        observer.observe(value, &[]);
+                    }
+                })
+                .build();
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use super::*;
+
+#[cfg(test)]
+mod telemetry_tests {
+    use super::*;
+
+    #[test]
+    fn test_export_name_replaces_unsupported_characters() {
+        assert_eq!(export_name("broadcast.index.http:latest.skipped"), "broadcast.index.http_latest.skipped");
+        assert_eq!(export_name("exchange.fetch_errors/coinbase-pro"), "exchange.fetch_errors/coinbase-pro");
+        assert_eq!(export_name("feed.BTC USD"), "feed.BTC_USD");
+    }
+
+    #[test]
+    fn test_stage_metric_names() {
+        assert_eq!(PipelineStage::Fetch.metric_name(), "pipeline.fetch.duration");
+        assert_eq!(PipelineStage::Channel.metric_name(), "pipeline.channel.wait");
+        assert_eq!(PipelineStage::Calculation.metric_name(), "pipeline.calculation.duration");
+        assert_eq!(PipelineStage::Delivery.metric_name(), "pipeline.delivery.latency");
+    }
+
+    #[test]
+    fn test_recording_without_export_is_a_no_op() {
+        record_stage(PipelineStage::Delivery, -5.0);
+        record_stage(PipelineStage::Fetch, 12.5);
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::config::{ExchangeConfig, MarketCapConfig};
+use crate::error::AppResult;
+use crate::exchange::http::ExchangeClient;
+use super::MarketCapSource;
+
+/// Market caps from the CoinGecko `simple/price` endpoint
+pub struct CoinGeckoSource {
+    client: ExchangeClient,
+    url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinGeckoQuote {
+    usd_market_cap: Option<f64>,
+}
+
+impl CoinGeckoSource {
+    /// Create a source using the HTTP settings of `[exchanges.coingecko]`
+    pub fn new(config: &MarketCapConfig, http: &ExchangeConfig) -> AppResult<Self> {
+        let api_key = match &config.api_key_env {
+            Some(var) => Some(std::env::var(var)
+                .map_err(|_| format!("Environment variable {} with the CoinGecko API key is not set", var))?),
+            None => None,
+        };
+
+        Ok(Self {
+            client: ExchangeClient::new(http)?,
+            url: config.url.trim_end_matches('/').to_string(),
+            api_key,
+        })
+    }
+}
+
+#[async_trait]
+impl MarketCapSource for CoinGeckoSource {
+    async fn fetch_market_caps(&self, asset_ids: &[String]) -> AppResult<HashMap<String, f64>> {
+        let mut url = format!(
+            "{}/simple/price?ids={}&vs_currencies=usd&include_market_cap=true",
+            self.url, asset_ids.join(",")
+        );
+        if let Some(api_key) = &self.api_key {
+            url.push_str(&format!("&x_cg_demo_api_key={}", api_key));
+        }
+
+        debug!("Fetching market caps from CoinGecko for {}", asset_ids.join(","));
+
+        let response = self.client.get(&url).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("CoinGecko API error: {}", response.status()).into());
+        }
+
+        let quotes: HashMap<String, CoinGeckoQuote> = response.json().await?;
+        Ok(quotes.into_iter()
+            .filter_map(|(asset_id, quote)| Some((asset_id, quote.usd_market_cap?)))
+            .collect())
+    }
+}
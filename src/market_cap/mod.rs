@@ -0,0 +1,66 @@
+// Modules
+pub mod coingecko;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{error, info};
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::index::IndexCalculator;
+use crate::metrics::metrics;
+
+/// Delay before retrying a failed market cap refresh
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Provider of asset market caps
+#[async_trait]
+pub trait MarketCapSource: Send + Sync {
+    /// Fetch the current market caps of the given assets, by asset id
+    async fn fetch_market_caps(&self, asset_ids: &[String]) -> AppResult<HashMap<String, f64>>;
+}
+
+// Factory function to create the configured market cap source
+pub fn create_source(config: &Config) -> AppResult<Box<dyn MarketCapSource>> {
+    match config.market_cap.source.to_lowercase().as_str() {
+        "coingecko" => Ok(Box::new(coingecko::CoinGeckoSource::new(
+            &config.market_cap, &config.exchange_config("coingecko"),
+        )?)),
+        _ => Err(AppError::Config(format!("Unsupported market cap source: {}", config.market_cap.source))),
+    }
+}
+
+/// Refresh the market caps of the given assets every `refresh` and reweight the market-cap
+/// weighted indices, retrying failed refreshes sooner, until shutdown
+pub async fn run_market_cap_updates(
+    source: Box<dyn MarketCapSource>,
+    index_calc: Arc<RwLock<IndexCalculator>>,
+    asset_ids: Vec<String>,
+    refresh: Duration,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    loop {
+        let delay = match source.fetch_market_caps(&asset_ids).await {
+            Ok(market_caps) => {
+                index_calc.write().await.set_market_caps(&market_caps);
+                refresh
+            }
+            Err(e) => {
+                error!("[MARKET CAP] Failed to fetch market caps: {}", e);
+                metrics().increment("market_cap.fetch_failures");
+                RETRY_DELAY.min(refresh)
+            }
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.recv() => {
+                info!("[SHUTDOWN] Stopping market cap updates");
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,8 @@
+mod registry;
+mod subscriber;
+
+#[cfg(test)]
+mod tests;
+
+pub use registry::{metrics, Metrics, MetricsSnapshot};
+pub use subscriber::{Subscriber, LAG_WINDOW, SLOW_CONSUMER_LAGS};
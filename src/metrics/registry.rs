@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref METRICS: Metrics = Metrics::new();
+}
+
+/// Process-wide metrics registry
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Named counters and gauges collected by the components of the collector
+#[derive(Debug, Default)]
+pub struct Metrics {
+    counters: Mutex<BTreeMap<String, u64>>,
+    gauges: Mutex<BTreeMap<String, f64>>,
+}
+
+/// Point-in-time copy of all metrics, ordered by name
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSnapshot {
+    pub counters: BTreeMap<String, u64>,
+    pub gauges: BTreeMap<String, f64>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Increase a counter by one
+    pub fn increment(&self, name: &str) {
+        self.increment_by(name, 1);
+    }
+
+    /// Increase a counter by `value`
+    pub fn increment_by(&self, name: &str, value: u64) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.entry(name.to_string()).or_default() += value;
+    }
+
+    /// Set a gauge to its current value
+    pub fn set_gauge(&self, name: &str, value: f64) {
+        self.gauges.lock().unwrap().insert(name.to_string(), value);
+    }
+
+    /// Current value of a counter
+    pub fn counter(&self, name: &str) -> u64 {
+        self.counters.lock().unwrap().get(name).copied().unwrap_or(0)
+    }
+
+    /// Current value of a gauge
+    pub fn gauge(&self, name: &str) -> Option<f64> {
+        self.gauges.lock().unwrap().get(name).copied()
+    }
+
+    /// Copy all metrics
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            counters: self.counters.lock().unwrap().clone(),
+            gauges: self.gauges.lock().unwrap().clone(),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::notification::{NotificationQueue, Severity};
+use super::registry::metrics;
+
+/// Window in which lags of a subscriber count as repeated
+pub const LAG_WINDOW: Duration = Duration::from_secs(60);
+
+/// Lags within [`LAG_WINDOW`] after which a subscriber is reported as a slow consumer
+pub const SLOW_CONSUMER_LAGS: usize = 3;
+
+/// Named subscriber of a broadcast channel (a WebSocket client, the stdout export, ...),
+/// recording how far behind it falls so a slow sink can be identified.
+///
+/// Per subscriber, `broadcast.<channel>.<subscriber>.skipped` counts the messages it lost to
+/// lagging and `broadcast.<channel>.<subscriber>.backlog` holds the messages still queued for
+/// it. A subscriber lagging repeatedly is logged and notified as a slow consumer.
+pub struct Subscriber<T> {
+    receiver: broadcast::Receiver<T>,
+    channel: &'static str,
+    name: String,
+    lags: VecDeque<Instant>,
+    reported_at: Option<Instant>,
+    notifier: Option<NotificationQueue>,
+}
+
+impl<T: Clone> Subscriber<T> {
+    pub fn new(receiver: broadcast::Receiver<T>, channel: &'static str, name: impl Into<String>) -> Self {
+        Self {
+            receiver,
+            channel,
+            name: name.into(),
+            lags: VecDeque::new(),
+            reported_at: None,
+            notifier: None,
+        }
+    }
+
+    /// Notify when the subscriber keeps falling behind
+    pub fn with_notifier(mut self, notifier: NotificationQueue) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Next message, skipping the messages lost while lagging; `None` once the channel is closed.
+    ///
+    /// Cancel safe, like [`broadcast::Receiver::recv`].
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(message) => {
+                    metrics().set_gauge(&self.metric("backlog"), self.receiver.len() as f64);
+                    return Some(message);
+                }
+                Err(RecvError::Lagged(skipped)) => self.record_lag(skipped),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    fn record_lag(&mut self, skipped: u64) {
+        warn!("[SLOW CONSUMER] {} fell behind on {} updates, skipped {}", self.name, self.channel, skipped);
+        metrics().increment_by(&self.metric("skipped"), skipped);
+        metrics().increment(&format!("broadcast.{}.lags", self.channel));
+
+        let now = Instant::now();
+        self.lags.push_back(now);
+        while self.lags.front().is_some_and(|lagged_at| now.duration_since(*lagged_at) > LAG_WINDOW) {
+            self.lags.pop_front();
+        }
+
+        // Reported at most once per window
+        let reported_recently = self.reported_at.is_some_and(|reported_at| now.duration_since(reported_at) <= LAG_WINDOW);
+        if self.lags.len() >= SLOW_CONSUMER_LAGS && !reported_recently {
+            self.reported_at = Some(now);
+            let message = format!("{} is a slow consumer of {} updates: lagged {} times within {}s",
+                                  self.name, self.channel, self.lags.len(), LAG_WINDOW.as_secs());
+            warn!("[SLOW CONSUMER] {}", message);
+            metrics().increment(&format!("broadcast.{}.slow_consumers", self.channel));
+            if let Some(notifier) = &self.notifier {
+                notifier.send(Severity::Warning, message);
+            }
+        }
+    }
+
+    fn metric(&self, name: &str) -> String {
+        format!("broadcast.{}.{}.{}", self.channel, self.name, name)
+    }
+}
@@ -0,0 +1,25 @@
+use tokio::sync::broadcast;
+
+use super::{metrics, Subscriber};
+
+#[cfg(test)]
+mod subscriber_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_skips_ahead_and_counts_skipped_messages() {
+        let (sender, receiver) = broadcast::channel(2);
+        let mut subscriber = Subscriber::new(receiver, "test_index", "slow_client");
+        for value in 0..5 {
+            sender.send(value).unwrap();
+        }
+
+        assert_eq!(subscriber.recv().await, Some(3));
+        assert_eq!(metrics().counter("broadcast.test_index.slow_client.skipped"), 3);
+        assert_eq!(metrics().gauge("broadcast.test_index.slow_client.backlog"), Some(1.0));
+
+        assert_eq!(subscriber.recv().await, Some(4));
+        drop(sender);
+        assert_eq!(subscriber.recv().await, None);
+    }
+}
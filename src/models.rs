@@ -1,11 +1,371 @@
+use std::collections::BTreeMap;
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::index::expression::Expression;
+use crate::notification::Severity;
+
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct IndexDefinition {
     pub name: String,
     pub feeds: Vec<PriceFeed>,
+    /// Other indices a composite index is built from, instead of feeds
+    #[serde(default)]
+    pub components: Vec<IndexComponent>,
+    /// Arithmetic over other feeds and indices a derived index is calculated from, instead of
+    /// feeds or components
+    #[serde(default)]
+    pub expression: Option<Expression>,
     pub smoothing: SmoothingType,
+    /// Number of values the smoothing averages over
+    #[serde(default = "default_smoothing_period")]
+    pub smoothing_period: usize,
+    /// Smoothing factor of the EMA-based smoothing algorithms
+    #[serde(default = "default_smoothing_factor")]
+    pub smoothing_factor: f64,
+    /// Raw values are clamped to within this percentage of the previous smoothed value before
+    /// smoothing
+    #[serde(default)]
+    pub smoothing_band_pct: Option<f64>,
+    /// Feeds that only provide cross rates for constituents quoted in another currency
+    #[serde(default)]
+    pub conversion_feeds: Vec<PriceFeed>,
+    /// Feeds without an update for longer than this are left out of the index
+    #[serde(default = "default_max_staleness_secs")]
+    pub max_staleness_secs: u64,
+    /// Feeds deviating from the median of the index's feeds by more than this percentage are dropped
+    #[serde(default)]
+    pub max_deviation_pct: Option<f64>,
+    /// Feeds updated more than this long before the most recently updated feed of the index
+    /// are left out of the calculation
+    #[serde(default)]
+    pub alignment_window_ms: Option<u64>,
+    /// Fewer fresh constituents than this withhold the index
+    #[serde(default)]
+    pub min_feeds: Option<usize>,
+    #[serde(default)]
+    pub missing_feeds: MissingFeeds,
+    #[serde(default)]
+    pub warm_up: WarmUp,
+    #[serde(default)]
+    pub publish: PublishSchedule,
+    /// Lookback windows the change of every value is published for
+    #[serde(default = "default_change_windows_secs")]
+    pub change_windows_secs: Vec<u64>,
+    #[serde(default)]
+    pub weighting: Weighting,
+    #[serde(default)]
+    pub aggregation: Aggregation,
+    /// Percentage of the highest and of the lowest prices dropped by a trimmed mean
+    #[serde(default = "default_trim_pct")]
+    pub trim_pct: f64,
+    #[serde(default)]
+    pub methodology: Methodology,
+    /// Level a divisor-based or log-return index starts at
+    #[serde(default = "default_base_level")]
+    pub base_level: f64,
+    #[serde(default)]
+    pub transform: Transform,
+    /// Factor the transformed value is multiplied with
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub denomination: Denomination,
+    /// Group of related indices the index belongs to, e.g. `majors`
+    #[serde(default)]
+    pub family: Option<String>,
+    #[serde(default)]
+    pub lifecycle: Lifecycle,
+    /// Free-form labels for consumers discovering the index
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl IndexDefinition {
+    /// Whether the index is calculated from component indices or an expression instead of its
+    /// own feeds
+    pub fn is_composite(&self) -> bool {
+        !self.components.is_empty() || self.expression.is_some()
+    }
+
+    /// Names of the component indices, or of the indices and feeds the expression references
+    pub fn constituent_names(&self) -> Vec<&str> {
+        match &self.expression {
+            Some(expression) => expression.references(),
+            None => self.components.iter().map(|component| component.index.as_str()).collect(),
+        }
+    }
+
+    /// Value published for an aggregated value, before smoothing; `None` if it has no inverse
+    pub fn transformed(&self, value: f64) -> Option<f64> {
+        let value = match self.transform {
+            Transform::None => value,
+            Transform::Inverse if value == 0.0 => return None,
+            Transform::Inverse => 1.0 / value,
+        };
+        Some(value * self.scale)
+    }
+
+    /// Everything that determines how the index value is calculated, one `key = value` per line.
+    /// Presentation settings (schedule, denomination, catalog metadata) are left out.
+    pub fn methodology_description(&self) -> String {
+        let feeds: Vec<String> = self.feeds.iter()
+            .map(|feed| match &feed.conversion {
+                Some(conversion) => format!("{}:{} via {}{}", feed.id, feed.weight,
+                                            if conversion.invert { "1/" } else { "" }, conversion.feed_id),
+                None => format!("{}:{}", feed.id, feed.weight),
+            })
+            .collect();
+        let components: Vec<String> = self.components.iter()
+            .map(|component| format!("{}:{}", component.index, component.weight))
+            .collect();
+
+        let mut lines = vec![
+            format!("feeds = {}", feeds.join(", ")),
+            format!("components = {}", components.join(", ")),
+            format!("expression = {}", self.expression.as_ref().map(ToString::to_string).unwrap_or_default()),
+            format!("smoothing = {:?}", self.smoothing),
+            format!("aggregation = {:?}", self.aggregation),
+            format!("trim_pct = {}", self.trim_pct),
+            format!("weighting = {:?}", self.weighting),
+            format!("methodology = {:?}", self.methodology),
+            format!("base_level = {}", self.base_level),
+            format!("transform = {:?}", self.transform),
+            format!("scale = {}", self.scale),
+            format!("max_staleness_secs = {}", self.max_staleness_secs),
+            format!("max_deviation_pct = {:?}", self.max_deviation_pct),
+            format!("min_feeds = {:?}", self.min_feeds),
+            format!("missing_feeds = {:?}", self.missing_feeds),
+        ];
+        // Only listed when set, so indices without them keep their fingerprint
+        if let Some(window) = self.alignment_window_ms {
+            lines.push(format!("alignment_window_ms = {}", window));
+        }
+        if (self.smoothing_period, self.smoothing_factor) != (default_smoothing_period(), default_smoothing_factor()) {
+            lines.push(format!("smoothing_params = {}, {}", self.smoothing_period, self.smoothing_factor));
+        }
+        if let Some(band) = self.smoothing_band_pct {
+            lines.push(format!("smoothing_band_pct = {}", band));
+        }
+        lines.join("\n")
+    }
+
+    /// Short hash of [`IndexDefinition::methodology_description`], changing whenever the
+    /// calculation of the index changes
+    pub fn methodology_fingerprint(&self) -> String {
+        let digest = Sha256::digest(self.methodology_description().as_bytes());
+        hex::encode(&digest[..8])
+    }
+}
+
+/// Publication stage of an index, advertised to consumers; it does not change how the index
+/// is calculated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lifecycle {
+    /// Production index
+    #[default]
+    Active,
+    /// New index whose methodology may still change
+    Preview,
+    /// Index scheduled for removal
+    Deprecated,
+}
+
+/// Index used as a constituent of a composite index
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IndexComponent {
+    /// Name of the component index
+    pub index: String,
+    pub weight: u32,
+}
+
+pub fn default_max_staleness_secs() -> u64 {
+    60
+}
+
+pub fn default_trim_pct() -> f64 {
+    10.0
+}
+
+pub fn default_change_windows_secs() -> Vec<u64> {
+    vec![60, 3600, 86400]
+}
+
+pub fn default_base_level() -> f64 {
+    1000.0
+}
+
+pub fn default_scale() -> f64 {
+    1.0
+}
+
+pub fn default_smoothing_period() -> usize {
+    20
+}
+
+pub fn default_smoothing_factor() -> f64 {
+    2.0
+}
+
+/// Currencies and precision of a feed's or an index's prices, sent along so consumers do not
+/// have to assume them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Denomination {
+    /// Asset being priced; for an index, the first part of its name
+    pub base_currency: String,
+    /// Currency the price is quoted in
+    pub quote_currency: String,
+    /// Decimal places the price is meaningful to
+    pub decimals: u32,
+}
+
+/// How the constituents of an index are weighted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weighting {
+    /// Fixed per-feed weights from the configuration
+    #[default]
+    Static,
+    /// Each asset weighs its share of the total market cap, refreshed periodically
+    MarketCap,
+}
+
+/// How the constituent prices of an index are combined into its value, see
+/// [`crate::aggregation::create_strategy`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum Aggregation {
+    /// Average of the prices weighted by the constituents' weights
+    #[default]
+    WeightedMean,
+    /// Median of the prices, ignoring weights
+    Median,
+    /// Average of the prices left after dropping the highest and lowest ones, ignoring weights
+    TrimmedMean,
+    /// Aggregation registered under this name with [`crate::aggregation::register_strategy`]
+    Custom(String),
+}
+
+impl From<String> for Aggregation {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "weighted_mean" => Aggregation::WeightedMean,
+            "median" => Aggregation::Median,
+            "trimmed_mean" => Aggregation::TrimmedMean,
+            _ => Aggregation::Custom(name),
+        }
+    }
+}
+
+/// How the index value is derived from the aggregated constituents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Methodology {
+    /// The aggregated price itself; dropping or reweighting a constituent moves the value
+    #[default]
+    Average,
+    /// Market value of the constituents (weight times price) divided by a divisor, which is
+    /// adjusted whenever constituents or weights change so that the level stays continuous
+    Divisor,
+    /// Base level compounded by the weighted log returns of the constituents since an anchor,
+    /// for performance indices
+    LogReturn,
+}
+
+impl Methodology {
+    /// Level an index starts at unless configured otherwise
+    pub fn default_base_level(self) -> f64 {
+        match self {
+            Methodology::LogReturn => 100.0,
+            Methodology::Average | Methodology::Divisor => default_base_level(),
+        }
+    }
+}
+
+/// Transformation of the aggregated index value, applied before smoothing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// The aggregated value itself
+    #[default]
+    None,
+    /// The reciprocal of the aggregated value, e.g. BTC per USD from BTC-USD feeds
+    Inverse,
+}
+
+/// What an index does while a constituent feed has no price yet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingFeeds {
+    /// Publish nothing until every constituent has a price
+    #[default]
+    Withhold,
+    /// Publish from the priced constituents, re-normalizing their weights, and flag the
+    /// missing feeds in the value's quality
+    Reweight,
+}
+
+/// What an index does while its smoothing has not seen a full window of values yet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarmUp {
+    /// Publish the values, flagged as warming up
+    #[default]
+    Publish,
+    /// Publish nothing until the smoothing is warm
+    Withhold,
+}
+
+/// When an index is calculated and published
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub struct PublishSchedule {
+    /// Publication interval; ticks are aligned to multiples of it in wall-clock time
+    #[serde(default = "default_publish_interval_ms")]
+    pub interval_ms: u64,
+    /// What to do about ticks missed while the publisher was held up
+    #[serde(default)]
+    pub missed_ticks: MissedTicks,
+}
+
+impl Default for PublishSchedule {
+    fn default() -> Self {
+        Self {
+            interval_ms: default_publish_interval_ms(),
+            missed_ticks: MissedTicks::default(),
+        }
+    }
+}
+
+fn default_publish_interval_ms() -> u64 {
+    1000
+}
+
+/// Catch-up policy for publication ticks missed after a stall (e.g. CPU starvation)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MissedTicks {
+    /// Publish one fresh value right away, then continue on the original schedule
+    #[default]
+    Skip,
+    /// Publish one fresh value right away and restart the schedule from there
+    Delay,
+    /// Publish every missed tick back to back until caught up
+    Burst,
+}
+
+impl MissedTicks {
+    pub fn behavior(self) -> tokio::time::MissedTickBehavior {
+        match self {
+            MissedTicks::Skip => tokio::time::MissedTickBehavior::Skip,
+            MissedTicks::Delay => tokio::time::MissedTickBehavior::Delay,
+            MissedTicks::Burst => tokio::time::MissedTickBehavior::Burst,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -14,19 +374,333 @@ pub struct PriceFeed {
     pub exchange: String,
     pub symbol: String,
     pub weight: u32,  // Percentage (1-100)
+    /// Cross rate applied when the feed is quoted in a different currency than the index
+    #[serde(default)]
+    pub conversion: Option<RateConversion>,
+    #[serde(default)]
+    pub priority: FeedPriority,
+    /// Source polled instead while this feed's own exchange keeps failing
+    #[serde(default)]
+    pub backup: Option<BackupSource>,
+    #[serde(default)]
+    pub bounds: PriceBounds,
+    /// Forward only changed prices, plus a heartbeat after this many seconds without a change
+    #[serde(default)]
+    pub heartbeat_secs: Option<u64>,
+    /// Other feed ids for the same exchange and symbol; they are not polled themselves but
+    /// receive this feed's prices under their own id
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Id of the feed's base asset at the market-cap source, for market-cap weighted indices
+    #[serde(default)]
+    pub market_cap_id: Option<String>,
+    #[serde(default)]
+    pub denomination: Denomination,
+}
+
+impl PriceFeed {
+    /// The feed's id followed by its aliases
+    pub fn ids(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.id).chain(&self.aliases)
+    }
 }
 
+/// Sanity bounds for fetched prices; prices outside them are rejected as failed polls
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct PriceBounds {
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    /// Maximum change against the previous accepted price, in percent
+    pub max_tick_change_pct: Option<f64>,
+}
+
+impl PriceBounds {
+    /// Check a price, returning why it is rejected. Non-finite and non-positive prices are always rejected.
+    pub fn check(&self, price: f64, previous: Option<f64>) -> Result<(), String> {
+        if !price.is_finite() || price <= 0.0 {
+            return Err("not a positive finite number".to_string());
+        }
+        if let Some(min_price) = self.min_price.filter(|min_price| price < *min_price) {
+            return Err(format!("below min_price {}", min_price));
+        }
+        if let Some(max_price) = self.max_price.filter(|max_price| price > *max_price) {
+            return Err(format!("above max_price {}", max_price));
+        }
+        if let (Some(max_change), Some(previous)) = (self.max_tick_change_pct, previous) {
+            let change = ((price - previous) / previous).abs() * 100.0;
+            if change > max_change {
+                return Err(format!("changed {:.2}% since the previous price {}, more than {}%", change, previous, max_change));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Backup source of a feed, taking over after repeated failures of the primary exchange
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackupSource {
+    /// Id of the feed definition the backup is taken from
+    pub feed_id: String,
+    pub exchange: String,
+    pub symbol: String,
+    /// Consecutive failures of the primary before switching to the backup
+    pub failover_after: u32,
+}
+
+/// Backup polls between two attempts of a failed-over feed's primary exchange
+pub const PRIMARY_PROBE_EVERY: u32 = 6;
+
+/// Source a feed's price is polled from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedSource {
+    Primary,
+    Backup,
+}
+
+/// Failover state of a feed between its primary exchange and its backup source.
+///
+/// Once failed over, the feed stays on its backup, which is polled straight away instead of after
+/// the primary's retries; the primary is only tried again every `PRIMARY_PROBE_EVERY` polls, or
+/// on the next poll after the backup failed, and the feed fails back as soon as it succeeds.
+#[derive(Debug, Clone, Default)]
+pub struct Failover {
+    failover_after: Option<u32>,
+    consecutive_failures: u32,
+    on_backup: bool,
+    /// Backup polls since the primary was last tried
+    backup_polls: u32,
+}
+
+impl Failover {
+    pub fn new(backup: Option<&BackupSource>) -> Self {
+        Self { failover_after: backup.map(|backup| backup.failover_after), ..Self::default() }
+    }
+
+    /// Consecutive failed polls of the primary
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    pub fn on_backup(&self) -> bool {
+        self.on_backup
+    }
+
+    /// Source to poll first
+    pub fn source(&self) -> FeedSource {
+        if self.on_backup && self.backup_polls < PRIMARY_PROBE_EVERY {
+            FeedSource::Backup
+        } else {
+            FeedSource::Primary
+        }
+    }
+
+    /// Record a successful poll of the primary, returning whether it ends a failover
+    pub fn primary_succeeded(&mut self) -> bool {
+        self.consecutive_failures = 0;
+        self.backup_polls = 0;
+        std::mem::replace(&mut self.on_backup, false)
+    }
+
+    /// Record a failed poll of the primary, returning whether the backup is polled instead
+    pub fn primary_failed(&mut self) -> bool {
+        self.consecutive_failures += 1;
+        self.backup_polls = 0;
+        self.failover_after.is_some_and(|failover_after| self.consecutive_failures >= failover_after)
+    }
+
+    /// Record a successful poll of the backup, returning whether it starts a failover
+    pub fn backup_succeeded(&mut self) -> bool {
+        self.backup_polls += 1;
+        !std::mem::replace(&mut self.on_backup, true)
+    }
+
+    /// Record a failed poll of the backup; the primary is tried again on the next poll
+    pub fn backup_failed(&mut self) {
+        self.backup_polls = PRIMARY_PROBE_EVERY;
+    }
+}
+
+/// Whether a polled price of a feed is passed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Forward {
+    Price,
+    /// Unchanged price, passed on only to show the feed is alive
+    Heartbeat,
+    Skip,
+}
+
+/// Decides which polled prices are forwarded for feeds with change-only forwarding. A price from
+/// another source than the last forwarded one counts as changed, so a switch to or from the
+/// backup is passed on even when both quote the same price.
+#[derive(Debug, Clone)]
+pub struct ChangeFilter {
+    heartbeat: Option<tokio::time::Duration>,
+    /// Last forwarded price, the backup feed it came from and when
+    last_forwarded: Option<(f64, Option<String>, tokio::time::Instant)>,
+}
+
+impl ChangeFilter {
+    pub fn new(heartbeat_secs: Option<u64>) -> Self {
+        Self {
+            heartbeat: heartbeat_secs.map(tokio::time::Duration::from_secs),
+            last_forwarded: None,
+        }
+    }
+
+    /// Classify a price polled at `now`, from the backup feed `backup_feed` if any
+    pub fn classify(&mut self, price: f64, backup_feed: Option<&str>, now: tokio::time::Instant) -> Forward {
+        let Some(heartbeat) = self.heartbeat else {
+            return Forward::Price;
+        };
+
+        let forward = match &self.last_forwarded {
+            Some((last_price, last_backup, _)) if *last_price != price || last_backup.as_deref() != backup_feed => Forward::Price,
+            Some((_, _, forwarded_at)) if now - *forwarded_at < heartbeat => return Forward::Skip,
+            Some(_) => Forward::Heartbeat,
+            None => Forward::Price,
+        };
+
+        self.last_forwarded = Some((price, backup_feed.map(str::to_string), now));
+        forward
+    }
+}
+
+/// Rule alerting when an index drifts away from a reference price, e.g. a market benchmark
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    /// Name of the monitored index
+    pub index: String,
+    pub reference: AlertReference,
+    pub condition: AlertCondition,
+    /// Consecutive calculations breaking the condition before the alert is raised
+    pub breaches: u32,
+    pub severity: Severity,
+}
+
+/// Price an index is compared with
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "lowercase")]
+pub enum AlertReference {
+    /// A feed polled for the alert only, such as an external benchmark
+    Feed(Box<PriceFeed>),
+    /// Another published index
+    Index(String),
+}
+
+impl AlertRule {
+    /// The reference feed, if the rule compares against a feed
+    pub fn reference_feed(&self) -> Option<&PriceFeed> {
+        match &self.reference {
+            AlertReference::Feed(feed) => Some(feed),
+            AlertReference::Index(_) => None,
+        }
+    }
+}
+
+/// Condition an index must keep against its reference
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AlertCondition {
+    /// Breaks when the index differs from the reference by more than `max_pct` percent
+    Divergence { max_pct: f64 },
+    /// Breaks when the correlation of the index's and the reference's returns over the last
+    /// `window` calculations drops below `min`
+    Correlation { min: f64, window: usize },
+}
+
+/// Polling priority of a feed, used to decide what to pause under resource pressure
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedPriority {
+    #[default]
+    Normal,
+    /// Paused first when the collector degrades to protect itself
+    Low,
+}
+
+/// Reference to a feed whose price converts a constituent into the index quote currency
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateConversion {
+    /// Id of the conversion rate feed
+    pub feed_id: String,
+    /// Divide by the rate instead of multiplying (the rate feed is quoted the other way round)
+    #[serde(default)]
+    pub invert: bool,
+}
+
+impl RateConversion {
+    /// Convert a price into the index quote currency using the given rate
+    pub fn apply(&self, price: f64, rate: f64) -> f64 {
+        if self.invert {
+            price / rate
+        } else {
+            price * rate
+        }
+    }
+}
+
+/// Smoothing algorithm of an index, see [`crate::smoothing::create_algorithm`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum SmoothingType {
     None,
     Sma,
     Ema,
+    Wma,
+    /// Double exponential moving average
+    Dema,
+    /// Triple exponential moving average
+    Tema,
+    /// Algorithm registered under this name with [`crate::smoothing::register_algorithm`]
+    Custom(String),
 }
 
-#[derive(Debug, Clone)]
+impl From<String> for SmoothingType {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "none" => SmoothingType::None,
+            "sma" => SmoothingType::Sma,
+            "ema" => SmoothingType::Ema,
+            "wma" => SmoothingType::Wma,
+            "dema" => SmoothingType::Dema,
+            "tema" => SmoothingType::Tema,
+            _ => SmoothingType::Custom(name),
+        }
+    }
+}
+
+impl From<SmoothingType> for String {
+    fn from(smoothing: SmoothingType) -> Self {
+        match smoothing {
+            SmoothingType::None => "none".to_string(),
+            SmoothingType::Sma => "sma".to_string(),
+            SmoothingType::Ema => "ema".to_string(),
+            SmoothingType::Wma => "wma".to_string(),
+            SmoothingType::Dema => "dema".to_string(),
+            SmoothingType::Tema => "tema".to_string(),
+            SmoothingType::Custom(name) => name,
+        }
+    }
+}
+
+/// A single price observation of a feed.
+///
+/// Crosses process boundaries (database, streams, spool files), so it is serialized in a
+/// [`crate::serialization::Envelope`]: fields added later must be `#[serde(default)]` and
+/// renamed fields keep their old name as an `alias`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FeedData {
     pub feed_id: String,
     pub timestamp: DateTime<Utc>,
     pub price: f64,
+    /// Id of the backup feed that supplied the price while the primary source is down
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_feed: Option<String>,
+    /// The price did not change since the last forwarded observation; only confirms the feed is alive
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub heartbeat: bool,
+    /// Currencies and precision of the price; not stored, so absent on prices read back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denomination: Option<Denomination>,
 }
\ No newline at end of file
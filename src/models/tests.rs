@@ -0,0 +1,166 @@
+use super::*;
+
+fn bounds(min_price: Option<f64>, max_price: Option<f64>, max_tick_change_pct: Option<f64>) -> PriceBounds {
+    PriceBounds { min_price, max_price, max_tick_change_pct }
+}
+
+#[cfg(test)]
+mod price_bounds_tests {
+    use super::*;
+
+    #[test]
+    fn test_prices_outside_min_and_max_are_rejected() {
+        let bounds = bounds(Some(1000.0), Some(200000.0), None);
+
+        assert!(bounds.check(1000.0, None).is_ok());
+        assert!(bounds.check(200000.0, None).is_ok());
+        assert_eq!(bounds.check(999.0, None).unwrap_err(), "below min_price 1000");
+        assert_eq!(bounds.check(200001.0, None).unwrap_err(), "above max_price 200000");
+    }
+
+    #[test]
+    fn test_tick_change_is_limited_against_the_previous_price() {
+        let bounds = bounds(None, None, Some(10.0));
+
+        assert!(bounds.check(110.0, Some(100.0)).is_ok());
+        assert!(bounds.check(90.0, Some(100.0)).is_ok());
+        let error = bounds.check(111.0, Some(100.0)).unwrap_err();
+        assert!(error.starts_with("changed 11.00% since the previous price 100"), "{}", error);
+        assert!(bounds.check(85.0, Some(100.0)).is_err());
+        // Nothing to compare the first price with
+        assert!(bounds.check(1000.0, None).is_ok());
+    }
+
+    #[test]
+    fn test_non_finite_and_non_positive_prices_are_always_rejected() {
+        for bounds in [PriceBounds::default(), bounds(Some(-10.0), None, Some(1000.0))] {
+            for price in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 0.0, -1.0] {
+                assert_eq!(bounds.check(price, Some(100.0)).unwrap_err(), "not a positive finite number", "{}", price);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod failover_tests {
+    use super::*;
+
+    fn backup(failover_after: u32) -> BackupSource {
+        BackupSource {
+            feed_id: "binance_btc_usdt".to_string(),
+            exchange: "binance".to_string(),
+            symbol: "BTCUSDT".to_string(),
+            failover_after,
+        }
+    }
+
+    #[test]
+    fn test_fails_over_after_consecutive_primary_failures() {
+        let mut failover = Failover::new(Some(&backup(3)));
+
+        assert!(!failover.primary_failed());
+        assert!(!failover.primary_failed());
+        assert!(failover.primary_failed());
+        assert!(failover.backup_succeeded());
+        assert!(failover.on_backup());
+        assert_eq!(failover.consecutive_failures(), 3);
+    }
+
+    #[test]
+    fn test_feed_without_backup_never_fails_over() {
+        let mut failover = Failover::new(None);
+
+        for _ in 0..10 {
+            assert!(!failover.primary_failed());
+            assert_eq!(failover.source(), FeedSource::Primary);
+        }
+    }
+
+    #[test]
+    fn test_stays_on_backup_and_probes_the_primary_periodically() {
+        let mut failover = Failover::new(Some(&backup(1)));
+        assert!(failover.primary_failed());
+        assert!(failover.backup_succeeded());
+
+        let mut sources = Vec::new();
+        for _ in 0..2 * PRIMARY_PROBE_EVERY {
+            let source = failover.source();
+            sources.push(source);
+            if source == FeedSource::Primary {
+                assert!(failover.primary_failed());
+            }
+            assert!(!failover.backup_succeeded());
+        }
+
+        let probes = sources.iter().filter(|&&source| source == FeedSource::Primary).count();
+        assert_eq!(probes, 2);
+        assert_eq!(sources[..PRIMARY_PROBE_EVERY as usize - 1], vec![FeedSource::Backup; PRIMARY_PROBE_EVERY as usize - 1][..]);
+        assert!(failover.on_backup());
+    }
+
+    #[test]
+    fn test_fails_back_once_the_primary_recovers() {
+        let mut failover = Failover::new(Some(&backup(1)));
+        assert!(failover.primary_failed());
+        assert!(failover.backup_succeeded());
+        while failover.source() == FeedSource::Backup {
+            failover.backup_succeeded();
+        }
+
+        assert!(failover.primary_succeeded());
+        assert!(!failover.on_backup());
+        assert_eq!(failover.consecutive_failures(), 0);
+        assert_eq!(failover.source(), FeedSource::Primary);
+        assert!(!failover.primary_succeeded());
+    }
+
+    #[test]
+    fn test_primary_is_tried_right_after_a_backup_failure() {
+        let mut failover = Failover::new(Some(&backup(1)));
+        assert!(failover.primary_failed());
+        assert!(failover.backup_succeeded());
+        assert_eq!(failover.source(), FeedSource::Backup);
+
+        failover.backup_failed();
+        assert_eq!(failover.source(), FeedSource::Primary);
+        assert!(failover.on_backup());
+    }
+}
+
+#[cfg(test)]
+mod change_filter_tests {
+    use super::*;
+    use tokio::time::{Duration, Instant};
+
+    #[test]
+    fn test_every_price_is_forwarded_without_change_only() {
+        let mut filter = ChangeFilter::new(None);
+        let now = Instant::now();
+
+        assert_eq!(filter.classify(100.0, None, now), Forward::Price);
+        assert_eq!(filter.classify(100.0, None, now), Forward::Price);
+    }
+
+    #[test]
+    fn test_unchanged_prices_are_skipped_until_the_heartbeat() {
+        let mut filter = ChangeFilter::new(Some(30));
+        let start = Instant::now();
+
+        assert_eq!(filter.classify(100.0, None, start), Forward::Price);
+        assert_eq!(filter.classify(100.0, None, start + Duration::from_secs(10)), Forward::Skip);
+        assert_eq!(filter.classify(100.0, None, start + Duration::from_secs(30)), Forward::Heartbeat);
+        assert_eq!(filter.classify(100.0, None, start + Duration::from_secs(40)), Forward::Skip);
+        assert_eq!(filter.classify(101.0, None, start + Duration::from_secs(41)), Forward::Price);
+    }
+
+    #[test]
+    fn test_same_price_from_another_source_is_forwarded() {
+        let mut filter = ChangeFilter::new(Some(30));
+        let start = Instant::now();
+
+        assert_eq!(filter.classify(100.0, None, start), Forward::Price);
+        assert_eq!(filter.classify(100.0, Some("binance_btc_usdt"), start + Duration::from_secs(5)), Forward::Price);
+        assert_eq!(filter.classify(100.0, Some("binance_btc_usdt"), start + Duration::from_secs(10)), Forward::Skip);
+        assert_eq!(filter.classify(100.0, None, start + Duration::from_secs(15)), Forward::Price);
+    }
+}
@@ -0,0 +1,8 @@
+mod packet;
+mod publisher;
+
+#[cfg(test)]
+mod tests;
+
+pub use packet::{decode_packet, encode_heartbeat, encode_update, Packet, PacketHeader, PacketUpdate, MAGIC, MAX_NAME_LEN, PROTOCOL_VERSION};
+pub use publisher::MulticastPublisher;
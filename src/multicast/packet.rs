@@ -0,0 +1,175 @@
+//! Compact binary datagrams of the multicast feed.
+//!
+//! Every datagram starts with the same header, all integers big-endian:
+//!
+//! | Bytes | Field |
+//! |-------|-------|
+//! | 2 | magic `CI` |
+//! | 1 | protocol version |
+//! | 1 | kind: `1` update, `2` heartbeat |
+//! | 4 | session, random per publisher start |
+//! | 8 | sequence number, per session, incremented by every datagram |
+//! | 8 | time sent, Unix microseconds |
+//!
+//! An update continues with the name length (1 byte) and name, the value's timestamp (8 bytes,
+//! Unix microseconds), value (8 bytes, IEEE 754), epoch (8 bytes), decimals (1 byte) and flags
+//! (1 byte: bit 0 degraded, bit 1 warming up). A heartbeat continues with the heartbeat interval
+//! in milliseconds (4 bytes).
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::error::{AppError, AppResult};
+use crate::index::IndexResult;
+
+pub const MAGIC: [u8; 2] = *b"CI";
+pub const PROTOCOL_VERSION: u8 = 1;
+/// Longest index name an update carries, in bytes
+pub const MAX_NAME_LEN: usize = u8::MAX as usize;
+
+const KIND_UPDATE: u8 = 1;
+const KIND_HEARTBEAT: u8 = 2;
+const HEADER_LEN: usize = 24;
+const FLAG_DEGRADED: u8 = 1;
+const FLAG_WARMING_UP: u8 = 2;
+
+/// Header of every datagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketHeader {
+    pub session: u32,
+    pub sequence: u64,
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Index value as carried by an update datagram
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketUpdate {
+    pub name: String,
+    pub timestamp: DateTime<Utc>,
+    pub value: f64,
+    pub epoch: u64,
+    pub decimals: u8,
+    pub degraded: bool,
+    pub warming_up: bool,
+}
+
+/// Decoded datagram
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    Update { header: PacketHeader, update: PacketUpdate },
+    Heartbeat { header: PacketHeader, interval_ms: u32 },
+}
+
+fn encode_header(buf: &mut Vec<u8>, kind: u8, header: &PacketHeader) {
+    buf.extend_from_slice(&MAGIC);
+    buf.push(PROTOCOL_VERSION);
+    buf.push(kind);
+    buf.extend_from_slice(&header.session.to_be_bytes());
+    buf.extend_from_slice(&header.sequence.to_be_bytes());
+    buf.extend_from_slice(&header.sent_at.timestamp_micros().to_be_bytes());
+}
+
+/// Update datagram of an index value; longer names are cut to `MAX_NAME_LEN` bytes, at a
+/// character boundary so they stay valid UTF-8
+pub fn encode_update(header: &PacketHeader, value: &IndexResult) -> Vec<u8> {
+    let mut name_len = value.name.len().min(MAX_NAME_LEN);
+    while !value.name.is_char_boundary(name_len) {
+        name_len -= 1;
+    }
+    let name = &value.name.as_bytes()[..name_len];
+    let mut buf = Vec::with_capacity(HEADER_LEN + 27 + name.len());
+    encode_header(&mut buf, KIND_UPDATE, header);
+    buf.push(name.len() as u8);
+    buf.extend_from_slice(name);
+    buf.extend_from_slice(&value.timestamp.timestamp_micros().to_be_bytes());
+    buf.extend_from_slice(&value.value.to_be_bytes());
+    buf.extend_from_slice(&value.epoch.to_be_bytes());
+    buf.push(value.denomination.decimals.min(u8::MAX as u32) as u8);
+    let mut flags = 0;
+    if value.quality.is_degraded() {
+        flags |= FLAG_DEGRADED;
+    }
+    if value.warming_up {
+        flags |= FLAG_WARMING_UP;
+    }
+    buf.push(flags);
+    buf
+}
+
+/// Heartbeat datagram, announcing when the next one is due at the latest
+pub fn encode_heartbeat(header: &PacketHeader, interval_ms: u32) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_LEN + 4);
+    encode_header(&mut buf, KIND_HEARTBEAT, header);
+    buf.extend_from_slice(&interval_ms.to_be_bytes());
+    buf
+}
+
+/// Reads big-endian fields off a datagram
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> AppResult<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(AppError::Other("Truncated multicast datagram".to_string()));
+        }
+        let (taken, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> AppResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> AppResult<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> AppResult<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn micros(&mut self) -> AppResult<DateTime<Utc>> {
+        let micros = self.u64()? as i64;
+        Utc.timestamp_micros(micros).single()
+            .ok_or_else(|| AppError::Other(format!("Invalid timestamp in multicast datagram: {}", micros)))
+    }
+}
+
+/// Decode a datagram, as a consumer would
+pub fn decode_packet(buf: &[u8]) -> AppResult<Packet> {
+    let mut reader = Reader { buf };
+    if reader.take(2)? != MAGIC {
+        return Err(AppError::Other("Not a multicast index datagram".to_string()));
+    }
+    let version = reader.u8()?;
+    if version != PROTOCOL_VERSION {
+        return Err(AppError::Other(format!("Unsupported multicast protocol version {}", version)));
+    }
+    let kind = reader.u8()?;
+    let header = PacketHeader { session: reader.u32()?, sequence: reader.u64()?, sent_at: reader.micros()? };
+    match kind {
+        KIND_UPDATE => {
+            let len = reader.u8()? as usize;
+            let name = String::from_utf8_lossy(reader.take(len)?).into_owned();
+            let timestamp = reader.micros()?;
+            let value = f64::from_bits(reader.u64()?);
+            let epoch = reader.u64()?;
+            let decimals = reader.u8()?;
+            let flags = reader.u8()?;
+            let update = PacketUpdate {
+                name,
+                timestamp,
+                value,
+                epoch,
+                decimals,
+                degraded: flags & FLAG_DEGRADED != 0,
+                warming_up: flags & FLAG_WARMING_UP != 0,
+            };
+            Ok(Packet::Update { header, update })
+        }
+        KIND_HEARTBEAT => Ok(Packet::Heartbeat { header, interval_ms: reader.u32()? }),
+        kind => Err(AppError::Other(format!("Unknown multicast datagram kind {}", kind))),
+    }
+}
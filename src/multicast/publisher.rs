@@ -0,0 +1,108 @@
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tokio::sync::broadcast;
+use tokio::time::{self, Instant};
+use tracing::{info, warn};
+
+use crate::config::MulticastConfig;
+use crate::error::{AppError, AppResult};
+use crate::index::IndexResult;
+use crate::metrics::{metrics, Subscriber};
+use super::packet::{encode_heartbeat, encode_update, PacketHeader};
+
+/// Publishes every index value as one datagram to a multicast group, for many consumers on one
+/// network segment.
+///
+/// Every datagram carries the next sequence number of the publisher's session, so consumers can
+/// detect lost datagrams; a heartbeat is sent whenever nothing else was for the heartbeat
+/// interval, so a lost last update is detected without waiting for the next one.
+pub struct MulticastPublisher {
+    socket: UdpSocket,
+    group: SocketAddr,
+    session: u32,
+    sequence: u64,
+    heartbeat_interval: Duration,
+}
+
+impl MulticastPublisher {
+    /// Open the sending socket, with the TTL, loopback and interface of the configuration
+    pub fn bind(config: &MulticastConfig) -> AppResult<Self> {
+        let group: SocketAddrV4 = config.group.parse()
+            .map_err(|e| AppError::Config(format!("Invalid multicast group {}: {}", config.group, e)))?;
+        let interface: Option<Ipv4Addr> = config.interface.as_deref()
+            .map(|interface| interface.parse()
+                .map_err(|e| AppError::Config(format!("Invalid multicast interface {}: {}", interface, e))))
+            .transpose()?;
+
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_multicast_ttl_v4(config.ttl)?;
+        socket.set_multicast_loop_v4(config.loopback)?;
+        if let Some(interface) = interface {
+            socket.set_multicast_if_v4(&interface)?;
+        }
+        socket.bind(&SocketAddr::from((interface.unwrap_or(Ipv4Addr::UNSPECIFIED), 0)).into())?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket: UdpSocket::from_std(socket.into())?,
+            group: group.into(),
+            session: rand::random(),
+            sequence: 0,
+            heartbeat_interval: Duration::from_millis(config.heartbeat_interval_ms),
+        })
+    }
+
+    /// Session of the publisher, changing with every start so consumers can tell a restart
+    /// (sequence numbers starting over) from lost datagrams
+    pub fn session(&self) -> u32 {
+        self.session
+    }
+
+    fn next_header(&mut self) -> PacketHeader {
+        self.sequence += 1;
+        PacketHeader { session: self.session, sequence: self.sequence, sent_at: chrono::Utc::now() }
+    }
+
+    /// Send an update datagram of an index value
+    pub async fn publish(&mut self, value: &IndexResult) -> AppResult<()> {
+        let header = self.next_header();
+        self.send(&encode_update(&header, value)).await
+    }
+
+    /// Send a heartbeat datagram
+    pub async fn heartbeat(&mut self) -> AppResult<()> {
+        let header = self.next_header();
+        let interval_ms = self.heartbeat_interval.as_millis().min(u32::MAX as u128) as u32;
+        self.send(&encode_heartbeat(&header, interval_ms)).await
+    }
+
+    async fn send(&self, datagram: &[u8]) -> AppResult<()> {
+        self.socket.send_to(datagram, self.group).await?;
+        metrics().increment("multicast.datagrams_sent");
+        Ok(())
+    }
+
+    /// Publish index values, and heartbeats while there are none, until shutdown
+    pub async fn run(mut self, mut updates: Subscriber<IndexResult>, mut shutdown: broadcast::Receiver<()>) {
+        info!("[MULTICAST] Publishing index values to {} (session {})", self.group, self.session);
+        let mut heartbeat_due = Instant::now();
+        loop {
+            let result = tokio::select! {
+                _ = shutdown.recv() => break,
+                _ = time::sleep_until(heartbeat_due) => self.heartbeat().await,
+                value = updates.recv() => match value {
+                    Some(value) => self.publish(&value).await,
+                    None => break,
+                },
+            };
+            if let Err(e) = result {
+                // The sequence number is spent all the same, so consumers see the gap
+                warn!("[MULTICAST] Failed to send datagram {} to {}: {}", self.sequence, self.group, e);
+                metrics().increment("multicast.send_errors");
+            }
+            heartbeat_due = Instant::now() + self.heartbeat_interval;
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use chrono::{TimeZone, Utc};
+
+use crate::index::IndexResult;
+use super::*;
+
+fn value(name: &str) -> IndexResult {
+    IndexResult {
+        name: name.to_string(),
+        timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+        value: 64123.45,
+        raw_value: None,
+        epoch: 42,
+        denomination: crate::models::Denomination {
+            base_currency: "BTC".to_string(),
+            quote_currency: "USD".to_string(),
+            decimals: 2,
+        },
+        quality: Default::default(),
+        change: Default::default(),
+        methodology_version: 0,
+        warming_up: true,
+    }
+}
+
+#[cfg(test)]
+mod packet_tests {
+    use super::*;
+
+    fn header(sequence: u64) -> PacketHeader {
+        PacketHeader { session: 7, sequence, sent_at: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 1).unwrap() }
+    }
+
+    #[test]
+    fn test_update_round_trip() {
+        let datagram = encode_update(&header(3), &value("BTC-USD-INDEX"));
+
+        assert_eq!(&datagram[..4], [b'C', b'I', PROTOCOL_VERSION, 1]);
+        assert_eq!(datagram.len(), 24 + 1 + 13 + 26);
+        assert_eq!(decode_packet(&datagram).unwrap(), Packet::Update {
+            header: header(3),
+            update: PacketUpdate {
+                name: "BTC-USD-INDEX".to_string(),
+                timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+                value: 64123.45,
+                epoch: 42,
+                decimals: 2,
+                degraded: false,
+                warming_up: true,
+            },
+        });
+    }
+
+    #[test]
+    fn test_long_names_are_cut_at_a_char_boundary() {
+        // 253 ASCII bytes leave no room for the whole of the 3-byte euro sign
+        let name = format!("{}€€", "A".repeat(253));
+        let datagram = encode_update(&header(1), &value(&name));
+
+        let Packet::Update { update, .. } = decode_packet(&datagram).unwrap() else {
+            panic!("not an update");
+        };
+        assert_eq!(update.name, "A".repeat(253));
+        assert_eq!(datagram[24] as usize, 253);
+    }
+
+    #[test]
+    fn test_heartbeat_round_trip() {
+        let datagram = encode_heartbeat(&header(4), 1000);
+
+        assert_eq!(datagram.len(), 28);
+        assert_eq!(decode_packet(&datagram).unwrap(), Packet::Heartbeat { header: header(4), interval_ms: 1000 });
+    }
+
+    #[test]
+    fn test_invalid_datagrams_are_rejected() {
+        let datagram = encode_update(&header(1), &value("BTC-USD-INDEX"));
+
+        assert!(decode_packet(&datagram[..datagram.len() - 1]).is_err());
+        assert!(decode_packet(b"XX").is_err());
+        let mut unknown = datagram.clone();
+        unknown[2] = PROTOCOL_VERSION + 1;
+        assert!(decode_packet(&unknown).is_err());
+    }
+}
+
+#[cfg(test)]
+mod publisher_tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+    use tokio::net::UdpSocket;
+    use tokio::sync::broadcast;
+    use crate::config::MulticastConfig;
+    use crate::metrics::Subscriber;
+
+    #[tokio::test]
+    async fn test_updates_and_heartbeats_share_the_sequence() {
+        let group = Ipv4Addr::new(239, 255, 42, 99);
+        let receiver = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.unwrap();
+        receiver.join_multicast_v4(group, Ipv4Addr::LOCALHOST).unwrap();
+        let config = MulticastConfig {
+            group: format!("{}:{}", group, receiver.local_addr().unwrap().port()),
+            interface: Some("127.0.0.1".to_string()),
+            ttl: 1,
+            loopback: true,
+            heartbeat_interval_ms: 500,
+        };
+        let publisher = MulticastPublisher::bind(&config).unwrap();
+        let session = publisher.session();
+
+        let (tx, rx) = broadcast::channel(16);
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let task = tokio::spawn(publisher.run(Subscriber::new(rx, "index", "multicast-test"), shutdown_rx));
+        let receive = || async {
+            let mut buf = [0; 1500];
+            let len = tokio::time::timeout(Duration::from_secs(5), receiver.recv(&mut buf)).await.unwrap().unwrap();
+            decode_packet(&buf[..len]).unwrap()
+        };
+
+        // A heartbeat announces the session right away
+        let Packet::Heartbeat { header, interval_ms } = receive().await else { panic!("expected a heartbeat") };
+        assert_eq!((header.session, header.sequence, interval_ms), (session, 1, 500));
+
+        tx.send(value("BTC-USD-INDEX")).unwrap();
+        let Packet::Update { header, update } = receive().await else { panic!("expected an update") };
+        assert_eq!((header.sequence, update.name.as_str()), (2, "BTC-USD-INDEX"));
+
+        let Packet::Heartbeat { header, .. } = receive().await else { panic!("expected a heartbeat") };
+        assert_eq!(header.sequence, 3);
+
+        shutdown_tx.send(()).unwrap();
+        task.await.unwrap();
+    }
+}
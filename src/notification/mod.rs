@@ -1,3 +1,10 @@
 pub mod sender;
+pub mod queue;
+pub mod webhook;
 
-pub use sender::{Notifier, ConsoleNotifier, ScriptNotifier, Severity};
+pub use sender::{Notifier, ConsoleNotifier, ScriptNotifier, RoutingNotifier, Severity};
+pub use queue::NotificationQueue;
+pub use webhook::WebhookDispatcher;
+
+#[cfg(test)]
+mod tests;
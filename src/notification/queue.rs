@@ -0,0 +1,131 @@
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+use tracing::{error, warn};
+
+use crate::config::NotificationDeliveryConfig;
+use crate::exchange::retry::RetryPolicy;
+use crate::metrics::metrics;
+use super::sender::{Notifier, Severity};
+
+/// Notification waiting for delivery
+#[derive(Debug, Clone)]
+struct Notification {
+    severity: Severity,
+    message: String,
+}
+
+/// Handle for sending notifications without waiting for their delivery.
+///
+/// Notifications are delivered by a background task which batches, retries and, if all else
+/// fails, dead-letters them, so a slow or failing notifier never holds up the caller.
+#[derive(Clone)]
+pub struct NotificationQueue {
+    sender: mpsc::Sender<Notification>,
+}
+
+impl NotificationQueue {
+    /// Start delivering queued notifications to `notifier`.
+    ///
+    /// The returned task finishes once every handle has been dropped and the queue is drained.
+    pub fn spawn(notifier: Box<dyn Notifier>, config: &NotificationDeliveryConfig) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        let worker = tokio::spawn(deliver_notifications(receiver, notifier, config.clone()));
+        (Self { sender }, worker)
+    }
+
+    /// Queue a notification, dead-lettering it if the queue is full
+    pub fn send(&self, severity: Severity, message: impl Into<String>) {
+        let notification = Notification { severity, message: message.into() };
+        match self.sender.try_send(notification) {
+            Ok(()) => metrics().increment("notifications.sent"),
+            Err(TrySendError::Full(notification)) => {
+                metrics().increment("notifications.dropped");
+                dead_letter(&notification, "notification queue is full");
+            }
+            Err(TrySendError::Closed(notification)) => {
+                metrics().increment("notifications.dropped");
+                dead_letter(&notification, "notification delivery has stopped");
+            }
+        }
+    }
+}
+
+/// Log a notification that could not be delivered so that it is not lost silently
+fn dead_letter(notification: &Notification, reason: &str) {
+    error!("[DEAD LETTER] Undeliverable {:?} notification ({}): {}", notification.severity, reason, notification.message);
+}
+
+/// Deliver queued notifications in batches until all senders are gone
+async fn deliver_notifications(
+    mut receiver: mpsc::Receiver<Notification>,
+    notifier: Box<dyn Notifier>,
+    config: NotificationDeliveryConfig,
+) {
+    let retry = RetryPolicy::from_config(&config.retry);
+    let window = Duration::from_millis(config.batch_window_ms);
+
+    while let Some(first) = receiver.recv().await {
+        let mut batch = vec![first];
+        let deadline = Instant::now() + window;
+        while batch.len() < config.max_batch {
+            match time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(notification)) => batch.push(notification),
+                _ => break,
+            }
+        }
+
+        for notification in combine(batch) {
+            deliver(notifier.as_ref(), &notification, &retry).await;
+        }
+    }
+}
+
+/// Merge a batch into one notification per severity, in the order the severities first appear
+fn combine(batch: Vec<Notification>) -> Vec<Notification> {
+    let mut grouped: Vec<(Severity, Vec<String>)> = Vec::new();
+    for notification in batch {
+        match grouped.iter_mut().find(|(severity, _)| *severity == notification.severity) {
+            Some((_, messages)) => messages.push(notification.message),
+            None => grouped.push((notification.severity, vec![notification.message])),
+        }
+    }
+
+    grouped.into_iter()
+        .map(|(severity, mut messages)| {
+            let message = if messages.len() == 1 {
+                messages.remove(0)
+            } else {
+                format!("{} notifications:\n{}", messages.len(), messages.join("\n"))
+            };
+            Notification { severity, message }
+        })
+        .collect()
+}
+
+/// Send one notification, retrying with backoff and dead-lettering it after the last attempt
+async fn deliver(notifier: &dyn Notifier, notification: &Notification, retry: &RetryPolicy) {
+    let mut attempt = 1;
+    loop {
+        match notifier.notify(notification.severity, &notification.message).await {
+            Ok(()) => {
+                metrics().increment("notifications.delivered");
+                return;
+            }
+            Err(e) if attempt < retry.max_attempts() => {
+                let delay = retry.delay(attempt);
+                warn!("[NOTIFICATION] Delivery attempt {}/{} failed: {}. Retrying in {:?}",
+                      attempt, retry.max_attempts(), e, delay);
+                metrics().increment("notifications.retries");
+                time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                metrics().increment("notifications.dead_letters");
+                dead_letter(notification, &format!("{} attempts failed, last error: {}", attempt, e));
+                return;
+            }
+        }
+    }
+}
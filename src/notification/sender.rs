@@ -1,9 +1,13 @@
-use std::process::Command;
+use std::collections::HashMap;
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::process::Command;
 use tracing::{info, error};
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
 
 /// Severity level for notifications
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     /// Informational message
     Info,
@@ -16,22 +20,29 @@ pub enum Severity {
 }
 
 /// Trait for notification senders
-pub trait Notifier {
-    /// Send a notification
-    fn notify(&self, severity: Severity, message: &str) -> AppResult<()>;
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Send a notification, failing if it could not be delivered
+    async fn notify(&self, severity: Severity, message: &str) -> AppResult<()>;
+}
+
+/// Log a notification to the console
+fn log_notification(severity: Severity, message: &str) {
+    match severity {
+        Severity::Info => info!("[NOTIFICATION] {}", message),
+        Severity::Warning => info!("[WARNING] {}", message),
+        Severity::Error => error!("[ERROR] {}", message),
+        Severity::Critical => error!("[CRITICAL] {}", message),
+    }
 }
 
 /// Console notifier that logs messages to the console
 pub struct ConsoleNotifier;
 
+#[async_trait]
 impl Notifier for ConsoleNotifier {
-    fn notify(&self, severity: Severity, message: &str) -> AppResult<()> {
-        match severity {
-            Severity::Info => info!("[NOTIFICATION] {}", message),
-            Severity::Warning => info!("[WARNING] {}", message),
-            Severity::Error => error!("[ERROR] {}", message),
-            Severity::Critical => error!("[CRITICAL] {}", message),
-        }
+    async fn notify(&self, severity: Severity, message: &str) -> AppResult<()> {
+        log_notification(severity, message);
         Ok(())
     }
 }
@@ -48,16 +59,12 @@ impl ScriptNotifier {
     }
 }
 
+#[async_trait]
 impl Notifier for ScriptNotifier {
-    fn notify(&self, severity: Severity, message: &str) -> AppResult<()> {
+    async fn notify(&self, severity: Severity, message: &str) -> AppResult<()> {
         // Log the notification message
-        match severity {
-            Severity::Info => info!("[NOTIFICATION] {}", message),
-            Severity::Warning => info!("[WARNING] {}", message),
-            Severity::Error => error!("[ERROR] {}", message),
-            Severity::Critical => error!("[CRITICAL] {}", message),
-        }
-        
+        log_notification(severity, message);
+
         // Format the message with severity prefix
         let prefixed_message = match severity {
             Severity::Info => format!("INFO: {}", message),
@@ -66,14 +73,47 @@ impl Notifier for ScriptNotifier {
             Severity::Critical => format!("CRITICAL: {}", message),
         };
         
-        // Execute the script
-        match Command::new(&self.script_path)
+        // Execute the script without blocking the runtime; a failing script counts as undelivered
+        let status = Command::new(&self.script_path)
             .arg(&prefixed_message)
-            .status() {
-                Ok(_) => info!("[NOTIFICATION] Script executed successfully"),
-                Err(e) => error!("[NOTIFICATION] Failed to execute script: {}", e),
-            }
-        
+            .status()
+            .await
+            .map_err(|e| AppError::Other(format!("Failed to execute notification script: {}", e)))?;
+
+        if !status.success() {
+            return Err(AppError::Other(format!("Notification script exited with {}", status)));
+        }
+
+        info!("[NOTIFICATION] Script executed successfully");
         Ok(())
     }
 }
+
+/// Notifier dispatching each notification to a target chosen by its severity
+pub struct RoutingNotifier {
+    routes: HashMap<Severity, Box<dyn Notifier>>,
+    fallback: Box<dyn Notifier>,
+}
+
+impl RoutingNotifier {
+    /// Create a router sending everything without a specific route to `fallback`
+    pub fn new(fallback: Box<dyn Notifier>) -> Self {
+        Self {
+            routes: HashMap::new(),
+            fallback,
+        }
+    }
+
+    /// Route notifications of the given severity to `notifier`
+    pub fn route(mut self, severity: Severity, notifier: Box<dyn Notifier>) -> Self {
+        self.routes.insert(severity, notifier);
+        self
+    }
+}
+
+#[async_trait]
+impl Notifier for RoutingNotifier {
+    async fn notify(&self, severity: Severity, message: &str) -> AppResult<()> {
+        self.routes.get(&severity).unwrap_or(&self.fallback).notify(severity, message).await
+    }
+}
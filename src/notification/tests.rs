@@ -0,0 +1,234 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use async_trait::async_trait;
+
+use crate::config::{NotificationDeliveryConfig, RetryConfig, WebhookConfig};
+use crate::error::{AppError, AppResult};
+use super::{NotificationQueue, Notifier, Severity};
+
+/// Notifier recording deliveries, failing the first `failures` attempts
+struct RecordingNotifier {
+    delivered: Arc<Mutex<Vec<(Severity, String)>>>,
+    failures: Mutex<u32>,
+}
+
+#[async_trait]
+impl Notifier for RecordingNotifier {
+    async fn notify(&self, severity: Severity, message: &str) -> AppResult<()> {
+        let mut failures = self.failures.lock().unwrap();
+        if *failures > 0 {
+            *failures -= 1;
+            return Err(AppError::Other("unavailable".to_string()));
+        }
+        self.delivered.lock().unwrap().push((severity, message.to_string()));
+        Ok(())
+    }
+}
+
+fn delivery_config(max_attempts: u32) -> NotificationDeliveryConfig {
+    NotificationDeliveryConfig {
+        batch_window_ms: 50,
+        retry: RetryConfig { max_attempts, base_delay_ms: 1, max_delay_ms: 1, jitter: 0.0 },
+        ..NotificationDeliveryConfig::default()
+    }
+}
+
+/// Queue the given notifications and wait until the queue has been drained
+async fn deliver(failures: u32, max_attempts: u32, notifications: &[(Severity, &str)]) -> Vec<(Severity, String)> {
+    let delivered = Arc::new(Mutex::new(Vec::new()));
+    let notifier = RecordingNotifier { delivered: delivered.clone(), failures: Mutex::new(failures) };
+    let (queue, worker) = NotificationQueue::spawn(Box::new(notifier), &delivery_config(max_attempts));
+
+    for (severity, message) in notifications {
+        queue.send(*severity, *message);
+    }
+    drop(queue);
+    tokio::time::timeout(Duration::from_secs(5), worker).await.unwrap().unwrap();
+
+    let delivered = delivered.lock().unwrap().clone();
+    delivered
+}
+
+#[cfg(test)]
+mod queue_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_notifications_are_batched_by_severity() {
+        let delivered = deliver(0, 1, &[
+            (Severity::Warning, "feed a is down"),
+            (Severity::Info, "drill passed"),
+            (Severity::Warning, "feed b is down"),
+        ]).await;
+
+        assert_eq!(delivered, vec![
+            (Severity::Warning, "2 notifications:\nfeed a is down\nfeed b is down".to_string()),
+            (Severity::Info, "drill passed".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries_are_retried() {
+        let delivered = deliver(2, 3, &[(Severity::Error, "collector crashed")]).await;
+
+        assert_eq!(delivered, vec![(Severity::Error, "collector crashed".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_undeliverable_notifications_are_dead_lettered() {
+        let delivered = deliver(3, 3, &[(Severity::Error, "collector crashed")]).await;
+
+        assert!(delivered.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use super::*;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use chrono::{TimeZone, Utc};
+    use tokio::sync::broadcast;
+    use crate::index::IndexResult;
+    use crate::metrics::{metrics, Subscriber};
+    use crate::notification::webhook::{crossings, sign, Direction, EventFilter, WebhookDispatcher, WebhookEvent, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+    fn webhook(url: &str, thresholds: &[f64]) -> WebhookConfig {
+        WebhookConfig {
+            url: url.to_string(),
+            secret_env: None,
+            indices: vec!["BTC-USD-INDEX".to_string()],
+            thresholds: thresholds.to_vec(),
+            timeout_ms: 1000,
+            queue_capacity: 10,
+            retry: RetryConfig { max_attempts: 3, base_delay_ms: 1, max_delay_ms: 1, jitter: 0.0 },
+            drain_timeout_secs: 10,
+        }
+    }
+
+    fn value(name: &str, value: f64) -> IndexResult {
+        IndexResult {
+            name: name.to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            value,
+            raw_value: None,
+            epoch: 0,
+            denomination: Default::default(),
+            quality: Default::default(),
+            change: Default::default(),
+            methodology_version: 0,
+            warming_up: false,
+        }
+    }
+
+    #[test]
+    fn test_crossings_are_ordered_by_when_they_were_crossed() {
+        let thresholds = [60000.0, 70000.0, 65000.0];
+
+        assert_eq!(crossings(&thresholds, 59000.0, 66000.0), [(60000.0, Direction::Up), (65000.0, Direction::Up)]);
+        assert_eq!(crossings(&thresholds, 71000.0, 65000.0), [(70000.0, Direction::Down), (65000.0, Direction::Down)]);
+        assert_eq!(crossings(&thresholds, 64000.0, 65000.0), [(65000.0, Direction::Up)]);
+        assert!(crossings(&thresholds, 65000.0, 66000.0).is_empty());
+        assert!(crossings(&thresholds, 61000.0, 61000.0).is_empty());
+    }
+
+    #[test]
+    fn test_filter_picks_the_webhooks_events() {
+        let mut updates = EventFilter::new(&webhook("http://127.0.0.1", &[]));
+        assert_eq!(updates.events(&value("BTC-USD-INDEX", 1.0)), [WebhookEvent::IndexUpdate { index: value("BTC-USD-INDEX", 1.0) }]);
+        assert!(updates.events(&value("ETH-USD-INDEX", 1.0)).is_empty());
+
+        let mut thresholds = EventFilter::new(&webhook("http://127.0.0.1", &[65000.0]));
+        assert!(thresholds.events(&value("BTC-USD-INDEX", 64000.0)).is_empty());
+        assert!(thresholds.events(&value("BTC-USD-INDEX", 64500.0)).is_empty());
+        assert_eq!(thresholds.events(&value("BTC-USD-INDEX", 65500.0)), [WebhookEvent::ThresholdCrossed {
+            threshold: 65000.0,
+            direction: Direction::Up,
+            previous: 64500.0,
+            index: value("BTC-USD-INDEX", 65500.0),
+        }]);
+    }
+
+    #[test]
+    fn test_signature_covers_timestamp_and_body() {
+        let signature = sign(b"secret", 1714564800, b"{}");
+
+        assert!(signature.starts_with("sha256=") && signature.len() == 7 + 64);
+        assert_ne!(signature, sign(b"secret", 1714564801, b"{}"));
+        assert_ne!(signature, sign(b"other", 1714564800, b"{}"));
+    }
+
+    #[tokio::test]
+    async fn test_deliveries_are_signed_and_retried() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let app = axum::Router::new().route("/hook", post({
+            let received = received.clone();
+            move |headers: HeaderMap, body: String| async move {
+                let mut received = received.lock().unwrap();
+                received.push((headers, body));
+                if received.len() == 1 { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK }
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        std::env::set_var("WEBHOOK_TESTS_SECRET", "s3cret");
+        let config = WebhookConfig { secret_env: Some("WEBHOOK_TESTS_SECRET".to_string()), ..webhook(&url, &[]) };
+        let (tx, rx) = broadcast::channel(16);
+        let (_shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        tx.send(value("BTC-USD-INDEX", 64000.0)).unwrap();
+        drop(tx);
+        let dispatcher = WebhookDispatcher::new(config).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), dispatcher.run(Subscriber::new(rx, "index", "webhook-test"), shutdown_rx)).await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let (headers, body) = &received[1];
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(headers[SIGNATURE_HEADER].to_str().unwrap(), sign(b"s3cret", timestamp, body.as_bytes()));
+        let event: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(event["event"], "index_update");
+        assert_eq!(event["index"]["value"], 64000.0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_dead_letters_events_left_after_the_drain_timeout() {
+        // An endpoint that never answers within the delivery timeout
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+
+        let config = WebhookConfig {
+            timeout_ms: 60_000,
+            drain_timeout_secs: 1,
+            retry: RetryConfig { max_attempts: 100, base_delay_ms: 1, max_delay_ms: 1, jitter: 0.0 },
+            ..webhook(&url, &[])
+        };
+        let (tx, rx) = broadcast::channel(16);
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let dead_letters = metrics().counter("webhooks.dead_letters");
+        let dispatcher = WebhookDispatcher::new(config).unwrap();
+        let run = tokio::spawn(dispatcher.run(Subscriber::new(rx, "index", "webhook-drain-test"), shutdown_rx));
+        for price in [64000.0, 64100.0, 64200.0] {
+            tx.send(value("BTC-USD-INDEX", price)).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        shutdown_tx.send(()).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), run).await.unwrap().unwrap();
+        assert!(metrics().counter("webhooks.dead_letters") >= dead_letters + 3);
+    }
+
+    #[test]
+    fn test_missing_secret_fails_startup() {
+        let config = WebhookConfig { secret_env: Some("WEBHOOK_TESTS_UNSET_SECRET".to_string()), ..webhook("http://127.0.0.1", &[]) };
+
+        assert!(WebhookDispatcher::new(config).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::oneshot;
+use tokio::time;
+use tracing::{error, warn};
+
+use crate::config::WebhookConfig;
+use crate::error::{AppError, AppResult};
+use crate::exchange::retry::RetryPolicy;
+use crate::index::IndexResult;
+use crate::metrics::{metrics, Subscriber};
+use crate::serialization::WireFormat;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header with the signature of a delivery, `sha256=<hex>`
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+/// Header with the Unix time a delivery was signed at
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+/// Signature of a delivery: hex HMAC-SHA256 of `<timestamp>.<body>`, so a captured delivery
+/// cannot be replayed with another timestamp
+pub fn sign(secret: &[u8], timestamp: i64, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Direction an index value crossed a threshold in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+/// Event delivered to a webhook
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A published index value
+    IndexUpdate { index: IndexResult },
+    /// A published index value crossed one of the webhook's thresholds
+    ThresholdCrossed { threshold: f64, direction: Direction, previous: f64, index: IndexResult },
+}
+
+/// Thresholds crossed moving from `previous` to `current`, in the order they were crossed.
+/// Reaching a threshold counts as crossing it, moving away from it again does not.
+pub fn crossings(thresholds: &[f64], previous: f64, current: f64) -> Vec<(f64, Direction)> {
+    let mut crossed: Vec<(f64, Direction)> = if current > previous {
+        thresholds.iter().filter(|&&t| previous < t && t <= current).map(|&t| (t, Direction::Up)).collect()
+    } else {
+        thresholds.iter().filter(|&&t| current <= t && t < previous).map(|&t| (t, Direction::Down)).collect()
+    };
+    crossed.sort_by(|(a, direction), (b, _)| match direction {
+        Direction::Up => a.total_cmp(b),
+        Direction::Down => b.total_cmp(a),
+    });
+    crossed
+}
+
+/// Picks the events of a webhook out of the published index values
+pub struct EventFilter {
+    indices: Vec<String>,
+    thresholds: Vec<f64>,
+    previous: HashMap<String, f64>,
+}
+
+impl EventFilter {
+    pub fn new(config: &WebhookConfig) -> Self {
+        Self { indices: config.indices.clone(), thresholds: config.thresholds.clone(), previous: HashMap::new() }
+    }
+
+    /// Every value of the webhook's indices without thresholds, otherwise one event per
+    /// threshold crossed since the index's previous value
+    pub fn events(&mut self, value: &IndexResult) -> Vec<WebhookEvent> {
+        if !self.indices.is_empty() && !self.indices.contains(&value.name) {
+            return Vec::new();
+        }
+        if self.thresholds.is_empty() {
+            return vec![WebhookEvent::IndexUpdate { index: value.clone() }];
+        }
+        let Some(previous) = self.previous.insert(value.name.clone(), value.value) else {
+            return Vec::new();
+        };
+        crossings(&self.thresholds, previous, value.value).into_iter()
+            .map(|(threshold, direction)| WebhookEvent::ThresholdCrossed { threshold, direction, previous, index: value.clone() })
+            .collect()
+    }
+}
+
+/// POSTs events to one webhook URL, signed when a secret is configured
+struct WebhookClient {
+    client: reqwest::Client,
+    url: String,
+    secret: Option<Vec<u8>>,
+    retry: RetryPolicy,
+}
+
+impl WebhookClient {
+    /// Post an event once; the error says whether trying again could help
+    async fn post(&self, body: &[u8]) -> Result<(), (bool, String)> {
+        let mut request = self.client.post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+        if let Some(secret) = &self.secret {
+            let timestamp = chrono::Utc::now().timestamp();
+            request = request
+                .header(TIMESTAMP_HEADER, timestamp)
+                .header(SIGNATURE_HEADER, sign(secret, timestamp, body));
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) => Err((RetryPolicy::is_retryable_status(response.status()), format!("HTTP {}", response.status()))),
+            Err(e) => Err((true, e.to_string())),
+        }
+    }
+
+    /// Deliver an event, retrying with backoff and dead-lettering it once retrying is exhausted
+    /// or cannot help
+    async fn deliver(&self, event: &WebhookEvent) {
+        let body = match WireFormat::Json.encode(event) {
+            Ok(body) => body,
+            Err(e) => return dead_letter(&self.url, event, &e.to_string()),
+        };
+        let mut attempt = 1;
+        loop {
+            match self.post(&body).await {
+                Ok(()) => {
+                    metrics().increment("webhooks.delivered");
+                    return;
+                }
+                Err((true, e)) if attempt < self.retry.max_attempts() => {
+                    let delay = self.retry.delay(attempt);
+                    warn!("[WEBHOOK] Delivery attempt {}/{} to {} failed: {}. Retrying in {:?}",
+                          attempt, self.retry.max_attempts(), self.url, e, delay);
+                    metrics().increment("webhooks.retries");
+                    time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err((_, e)) => {
+                    metrics().increment("webhooks.dead_letters");
+                    return dead_letter(&self.url, event, &format!("{} attempts failed, last error: {}", attempt, e));
+                }
+            }
+        }
+    }
+}
+
+/// Log an event that could not be delivered so that it is not lost silently
+fn dead_letter(url: &str, event: &WebhookEvent, reason: &str) {
+    error!("[DEAD LETTER] Undeliverable webhook event for {} ({}): {:?}", url, reason, event);
+}
+
+/// Delivers index updates, or only threshold crossings, to a webhook URL.
+///
+/// Events are queued for a background task which delivers them in order, so a slow or failing
+/// endpoint never holds up the index values; events beyond the queue's capacity are
+/// dead-lettered.
+pub struct WebhookDispatcher {
+    config: WebhookConfig,
+    client: WebhookClient,
+}
+
+impl WebhookDispatcher {
+    /// Fails when the environment variable of the secret is not set
+    pub fn new(config: WebhookConfig) -> AppResult<Self> {
+        let secret = config.secret_env.as_ref()
+            .map(|var| std::env::var(var)
+                .map(String::into_bytes)
+                .map_err(|_| AppError::Config(format!("Webhook secret variable {} is not set", var))))
+            .transpose()?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .map_err(|e| AppError::Config(format!("Cannot create webhook client: {}", e)))?;
+        let client = WebhookClient { client, url: config.url.clone(), secret, retry: RetryPolicy::from_config(&config.retry) };
+        Ok(Self { config, client })
+    }
+
+    /// Queue the events of published index values until shutdown, then deliver the events
+    /// still queued for up to `drain_timeout_secs` and dead-letter those left
+    pub async fn run(self, mut updates: Subscriber<IndexResult>, mut shutdown: broadcast::Receiver<()>) {
+        let (sender, mut receiver) = mpsc::channel::<WebhookEvent>(self.config.queue_capacity.max(1));
+        let (abandon_tx, mut abandon) = oneshot::channel::<()>();
+        let client = self.client;
+        let mut worker = tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                tokio::select! {
+                    biased;
+                    _ = &mut abandon => {
+                        metrics().increment("webhooks.dead_letters");
+                        dead_letter(&client.url, &event, "shutdown drain deadline passed");
+                        break;
+                    }
+                    _ = client.deliver(&event) => {}
+                }
+            }
+            while let Ok(event) = receiver.try_recv() {
+                metrics().increment("webhooks.dead_letters");
+                dead_letter(&client.url, &event, "shutdown drain deadline passed");
+            }
+        });
+
+        let mut filter = EventFilter::new(&self.config);
+        loop {
+            tokio::select! {
+                _ = shutdown.recv() => break,
+                value = updates.recv() => {
+                    let Some(value) = value else { break };
+                    for event in filter.events(&value) {
+                        if let Err(TrySendError::Full(event) | TrySendError::Closed(event)) = sender.try_send(event) {
+                            metrics().increment("webhooks.dropped");
+                            dead_letter(&self.config.url, &event, "delivery queue is full");
+                        }
+                    }
+                }
+            }
+        }
+
+        drop(sender);
+        let drain_timeout = Duration::from_secs(self.config.drain_timeout_secs);
+        let result = match time::timeout(drain_timeout, &mut worker).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!("[WEBHOOK] Events for {} still undelivered after {}s, dead-lettering them",
+                      self.config.url, self.config.drain_timeout_secs);
+                let _ = abandon_tx.send(());
+                worker.await
+            }
+        };
+        if let Err(e) = result {
+            error!("[WEBHOOK] Delivery to {} stopped: {}", self.config.url, e);
+        }
+    }
+}
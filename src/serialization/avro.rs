@@ -0,0 +1,108 @@
+use crate::index::IndexResult;
+use crate::models::FeedData;
+
+/// Avro schema of raw prices encoded with [`encode_feed_data`], to register with a schema
+/// registry
+pub const FEED_DATA_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "FeedData",
+  "namespace": "crypto_index",
+  "fields": [
+    {"name": "feed_id", "type": "string"},
+    {"name": "timestamp", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+    {"name": "price", "type": "double"},
+    {"name": "backup_feed", "type": ["null", "string"], "default": null},
+    {"name": "base_currency", "type": ["null", "string"], "default": null},
+    {"name": "quote_currency", "type": ["null", "string"], "default": null},
+    {"name": "decimals", "type": ["null", "int"], "default": null}
+  ]
+}"#;
+
+/// Avro schema of published index values encoded with [`encode_index_result`], to register
+/// with a schema registry
+pub const INDEX_RESULT_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "IndexResult",
+  "namespace": "crypto_index",
+  "fields": [
+    {"name": "name", "type": "string"},
+    {"name": "timestamp", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+    {"name": "value", "type": "double"},
+    {"name": "raw_value", "type": ["null", "double"], "default": null},
+    {"name": "epoch", "type": "long"},
+    {"name": "base_currency", "type": "string"},
+    {"name": "quote_currency", "type": "string"},
+    {"name": "decimals", "type": "int"},
+    {"name": "degraded", "type": "boolean"},
+    {"name": "warming_up", "type": "boolean"},
+    {"name": "methodology_version", "type": "int"}
+  ]
+}"#;
+
+/// Append a zigzag-encoded variable-length integer, as Avro encodes `int` and `long` (and
+/// Kafka its varints)
+pub(crate) fn write_long(buf: &mut Vec<u8>, value: i64) {
+    let mut zigzag = ((value << 1) ^ (value >> 63)) as u64;
+    while zigzag >= 0x80 {
+        buf.push((zigzag as u8 & 0x7f) | 0x80);
+        zigzag >>= 7;
+    }
+    buf.push(zigzag as u8);
+}
+
+fn write_string(buf: &mut Vec<u8>, value: &str) {
+    write_long(buf, value.len() as i64);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Union of `null` and a value
+fn write_optional<T>(buf: &mut Vec<u8>, value: Option<T>, write: impl FnOnce(&mut Vec<u8>, T)) {
+    match value {
+        None => write_long(buf, 0),
+        Some(value) => {
+            write_long(buf, 1);
+            write(buf, value);
+        }
+    }
+}
+
+/// Avro binary encoding of a raw price with [`FEED_DATA_SCHEMA`]
+pub fn encode_feed_data(price: &FeedData) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_string(&mut buf, &price.feed_id);
+    write_long(&mut buf, price.timestamp.timestamp_micros());
+    buf.extend_from_slice(&price.price.to_le_bytes());
+    write_optional(&mut buf, price.backup_feed.as_deref(), write_string);
+    let denomination = price.denomination.as_ref();
+    write_optional(&mut buf, denomination.map(|d| d.base_currency.as_str()), write_string);
+    write_optional(&mut buf, denomination.map(|d| d.quote_currency.as_str()), write_string);
+    write_optional(&mut buf, denomination.map(|d| d.decimals as i64), write_long);
+    buf
+}
+
+/// Avro binary encoding of a published index value with [`INDEX_RESULT_SCHEMA`]
+pub fn encode_index_result(value: &IndexResult) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_string(&mut buf, &value.name);
+    write_long(&mut buf, value.timestamp.timestamp_micros());
+    buf.extend_from_slice(&value.value.to_le_bytes());
+    write_optional(&mut buf, value.raw_value, |buf, raw| buf.extend_from_slice(&raw.to_le_bytes()));
+    write_long(&mut buf, value.epoch as i64);
+    write_string(&mut buf, &value.denomination.base_currency);
+    write_string(&mut buf, &value.denomination.quote_currency);
+    write_long(&mut buf, value.denomination.decimals as i64);
+    buf.push(value.quality.is_degraded() as u8);
+    buf.push(value.warming_up as u8);
+    write_long(&mut buf, value.methodology_version as i64);
+    buf
+}
+
+/// Confluent wire format: magic byte `0`, the big-endian schema id of the registry and the Avro
+/// payload
+pub fn confluent_framed(schema_id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(payload.len() + 5);
+    framed.push(0);
+    framed.extend_from_slice(&schema_id.to_be_bytes());
+    framed.extend_from_slice(payload);
+    framed
+}
@@ -0,0 +1,347 @@
+use serde_json::{Map, Number, Value};
+
+use crate::error::{AppError, AppResult};
+
+/// Nesting deeper than this is rejected when decoding, so a hostile payload can't overflow the
+/// stack
+const MAX_DEPTH: usize = 64;
+
+fn invalid(format: &str, reason: impl std::fmt::Display) -> AppError {
+    AppError::Serialization(format!("Invalid {} payload: {}", format, reason))
+}
+
+/// Reader of a binary payload
+struct Reader<'a> {
+    bytes: &'a [u8],
+    format: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> AppResult<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid(self.format, "truncated"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> AppResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Big-endian unsigned integer of `len` bytes
+    fn uint(&mut self, len: usize) -> AppResult<u64> {
+        Ok(self.take(len)?.iter().fold(0, |value, byte| value << 8 | *byte as u64))
+    }
+
+    fn string(&mut self, len: u64) -> AppResult<String> {
+        let bytes = self.take(self.len(len)?)?;
+        String::from_utf8(bytes.to_vec()).map_err(|e| invalid(self.format, e))
+    }
+
+    /// Length of a string or collection, which can't exceed the remaining bytes
+    fn len(&self, len: u64) -> AppResult<usize> {
+        if len > self.bytes.len() as u64 {
+            return Err(invalid(self.format, "truncated"));
+        }
+        Ok(len as usize)
+    }
+
+    fn finish(self, value: Value) -> AppResult<Value> {
+        if !self.bytes.is_empty() {
+            return Err(invalid(self.format, format!("{} trailing bytes", self.bytes.len())));
+        }
+        Ok(value)
+    }
+}
+
+fn float(value: f64) -> Value {
+    // Non-finite floats have no JSON representation
+    Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
+
+/// MessagePack encoding of a JSON value
+pub fn to_msgpack(value: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_msgpack(&mut buf, value);
+    buf
+}
+
+fn write_msgpack(buf: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => buf.push(0xc0),
+        Value::Bool(false) => buf.push(0xc2),
+        Value::Bool(true) => buf.push(0xc3),
+        Value::Number(number) => {
+            if let Some(n) = number.as_u64() {
+                match n {
+                    0..=0x7f => buf.push(n as u8),
+                    0x80..=0xff => buf.extend([0xcc, n as u8]),
+                    0x100..=0xffff => buf.extend(std::iter::once(0xcd).chain((n as u16).to_be_bytes())),
+                    0x1_0000..=0xffff_ffff => buf.extend(std::iter::once(0xce).chain((n as u32).to_be_bytes())),
+                    _ => buf.extend(std::iter::once(0xcf).chain(n.to_be_bytes())),
+                }
+            } else if let Some(n) = number.as_i64() {
+                match n {
+                    -32..=-1 => buf.push(n as u8),
+                    -0x80..=-33 => buf.extend([0xd0, n as u8]),
+                    -0x8000..=-0x81 => buf.extend(std::iter::once(0xd1).chain((n as i16).to_be_bytes())),
+                    -0x8000_0000..=-0x8001 => buf.extend(std::iter::once(0xd2).chain((n as i32).to_be_bytes())),
+                    _ => buf.extend(std::iter::once(0xd3).chain(n.to_be_bytes())),
+                }
+            } else {
+                buf.push(0xcb);
+                buf.extend(number.as_f64().unwrap_or_default().to_be_bytes());
+            }
+        }
+        Value::String(string) => {
+            write_msgpack_len(buf, string.len(), 0xa0, 32, [0xd9, 0xda, 0xdb]);
+            buf.extend_from_slice(string.as_bytes());
+        }
+        Value::Array(items) => {
+            write_msgpack_len(buf, items.len(), 0x90, 16, [0, 0xdc, 0xdd]);
+            for item in items {
+                write_msgpack(buf, item);
+            }
+        }
+        Value::Object(entries) => {
+            write_msgpack_len(buf, entries.len(), 0x80, 16, [0, 0xde, 0xdf]);
+            for (key, value) in entries {
+                write_msgpack(buf, &Value::String(key.clone()));
+                write_msgpack(buf, value);
+            }
+        }
+    }
+}
+
+/// Length with its fix-format `prefix` below `fix_limit`, else with the 8, 16 or 32-bit
+/// `markers` (collections have no 8-bit form)
+fn write_msgpack_len(buf: &mut Vec<u8>, len: usize, prefix: u8, fix_limit: usize, markers: [u8; 3]) {
+    if len < fix_limit {
+        buf.push(prefix | len as u8);
+    } else if len <= 0xff && markers[0] != 0 {
+        buf.extend([markers[0], len as u8]);
+    } else if len <= 0xffff {
+        buf.push(markers[1]);
+        buf.extend((len as u16).to_be_bytes());
+    } else {
+        buf.push(markers[2]);
+        buf.extend((len as u32).to_be_bytes());
+    }
+}
+
+/// JSON value of a MessagePack payload; binary and extension types are not supported
+pub fn from_msgpack(bytes: &[u8]) -> AppResult<Value> {
+    let mut reader = Reader { bytes, format: "MessagePack" };
+    let value = read_msgpack(&mut reader, 0)?;
+    reader.finish(value)
+}
+
+fn read_msgpack(reader: &mut Reader, depth: usize) -> AppResult<Value> {
+    if depth > MAX_DEPTH {
+        return Err(invalid(reader.format, "nested too deeply"));
+    }
+    let marker = reader.byte()?;
+    Ok(match marker {
+        0x00..=0x7f => Value::from(marker),
+        0x80..=0x8f => read_msgpack_map(reader, (marker & 0x0f) as u64, depth)?,
+        0x90..=0x9f => read_msgpack_array(reader, (marker & 0x0f) as u64, depth)?,
+        0xa0..=0xbf => Value::String(reader.string((marker & 0x1f) as u64)?),
+        0xc0 => Value::Null,
+        0xc2 => Value::Bool(false),
+        0xc3 => Value::Bool(true),
+        0xca => float(f32::from_bits(reader.uint(4)? as u32) as f64),
+        0xcb => float(f64::from_bits(reader.uint(8)?)),
+        0xcc => Value::from(reader.uint(1)?),
+        0xcd => Value::from(reader.uint(2)?),
+        0xce => Value::from(reader.uint(4)?),
+        0xcf => Value::from(reader.uint(8)?),
+        0xd0 => Value::from(reader.uint(1)? as u8 as i8),
+        0xd1 => Value::from(reader.uint(2)? as u16 as i16),
+        0xd2 => Value::from(reader.uint(4)? as u32 as i32),
+        0xd3 => Value::from(reader.uint(8)? as i64),
+        0xd9 => {
+            let len = reader.uint(1)?;
+            Value::String(reader.string(len)?)
+        }
+        0xda => {
+            let len = reader.uint(2)?;
+            Value::String(reader.string(len)?)
+        }
+        0xdb => {
+            let len = reader.uint(4)?;
+            Value::String(reader.string(len)?)
+        }
+        0xdc => {
+            let len = reader.uint(2)?;
+            read_msgpack_array(reader, len, depth)?
+        }
+        0xdd => {
+            let len = reader.uint(4)?;
+            read_msgpack_array(reader, len, depth)?
+        }
+        0xde => {
+            let len = reader.uint(2)?;
+            read_msgpack_map(reader, len, depth)?
+        }
+        0xdf => {
+            let len = reader.uint(4)?;
+            read_msgpack_map(reader, len, depth)?
+        }
+        0xe0..=0xff => Value::from(marker as i8),
+        _ => return Err(invalid(reader.format, format!("unsupported type 0x{:02x}", marker))),
+    })
+}
+
+fn read_msgpack_array(reader: &mut Reader, len: u64, depth: usize) -> AppResult<Value> {
+    // Every item takes at least a byte
+    let mut items = Vec::with_capacity(reader.len(len)?);
+    for _ in 0..len {
+        items.push(read_msgpack(reader, depth + 1)?);
+    }
+    Ok(Value::Array(items))
+}
+
+fn read_msgpack_map(reader: &mut Reader, len: u64, depth: usize) -> AppResult<Value> {
+    reader.len(len)?;
+    let mut entries = Map::new();
+    for _ in 0..len {
+        let Value::String(key) = read_msgpack(reader, depth + 1)? else {
+            return Err(invalid(reader.format, "map key is not a string"));
+        };
+        entries.insert(key, read_msgpack(reader, depth + 1)?);
+    }
+    Ok(Value::Object(entries))
+}
+
+/// CBOR encoding of a JSON value, with definite lengths
+pub fn to_cbor(value: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_cbor(&mut buf, value);
+    buf
+}
+
+/// Head of a CBOR data item: its major type and argument
+fn write_cbor_head(buf: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    match argument {
+        0..=23 => buf.push(major | argument as u8),
+        24..=0xff => buf.extend([major | 24, argument as u8]),
+        0x100..=0xffff => buf.extend(std::iter::once(major | 25).chain((argument as u16).to_be_bytes())),
+        0x1_0000..=0xffff_ffff => buf.extend(std::iter::once(major | 26).chain((argument as u32).to_be_bytes())),
+        _ => buf.extend(std::iter::once(major | 27).chain(argument.to_be_bytes())),
+    }
+}
+
+fn write_cbor(buf: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => buf.push(0xf6),
+        Value::Bool(false) => buf.push(0xf4),
+        Value::Bool(true) => buf.push(0xf5),
+        Value::Number(number) => {
+            if let Some(n) = number.as_u64() {
+                write_cbor_head(buf, 0, n);
+            } else if let Some(n) = number.as_i64() {
+                write_cbor_head(buf, 1, (-1 - n) as u64);
+            } else {
+                buf.push(0xfb);
+                buf.extend(number.as_f64().unwrap_or_default().to_be_bytes());
+            }
+        }
+        Value::String(string) => {
+            write_cbor_head(buf, 3, string.len() as u64);
+            buf.extend_from_slice(string.as_bytes());
+        }
+        Value::Array(items) => {
+            write_cbor_head(buf, 4, items.len() as u64);
+            for item in items {
+                write_cbor(buf, item);
+            }
+        }
+        Value::Object(entries) => {
+            write_cbor_head(buf, 5, entries.len() as u64);
+            for (key, value) in entries {
+                write_cbor_head(buf, 3, key.len() as u64);
+                buf.extend_from_slice(key.as_bytes());
+                write_cbor(buf, value);
+            }
+        }
+    }
+}
+
+/// JSON value of a CBOR payload; byte strings, indefinite lengths and non-string map keys are
+/// not supported, tags are skipped
+pub fn from_cbor(bytes: &[u8]) -> AppResult<Value> {
+    let mut reader = Reader { bytes, format: "CBOR" };
+    let value = read_cbor(&mut reader, 0)?;
+    reader.finish(value)
+}
+
+fn read_cbor(reader: &mut Reader, depth: usize) -> AppResult<Value> {
+    if depth > MAX_DEPTH {
+        return Err(invalid(reader.format, "nested too deeply"));
+    }
+    let initial = reader.byte()?;
+    let (major, info) = (initial >> 5, initial & 0x1f);
+    if major == 7 {
+        return Ok(match info {
+            20 => Value::Bool(false),
+            21 => Value::Bool(true),
+            22 | 23 => Value::Null,
+            25 => float(half_to_f64(reader.uint(2)? as u16)),
+            26 => float(f32::from_bits(reader.uint(4)? as u32) as f64),
+            27 => float(f64::from_bits(reader.uint(8)?)),
+            _ => return Err(invalid(reader.format, format!("unsupported simple value {}", info))),
+        });
+    }
+    let argument = match info {
+        0..=23 => info as u64,
+        24 => reader.uint(1)?,
+        25 => reader.uint(2)?,
+        26 => reader.uint(4)?,
+        27 => reader.uint(8)?,
+        _ => return Err(invalid(reader.format, "indefinite lengths are not supported")),
+    };
+    Ok(match major {
+        0 => Value::from(argument),
+        1 => match i64::try_from(argument) {
+            Ok(n) => Value::from(-1 - n),
+            Err(_) => float(-1.0 - argument as f64),
+        },
+        3 => Value::String(reader.string(argument)?),
+        4 => {
+            let mut items = Vec::with_capacity(reader.len(argument)?);
+            for _ in 0..argument {
+                items.push(read_cbor(reader, depth + 1)?);
+            }
+            Value::Array(items)
+        }
+        5 => {
+            reader.len(argument)?;
+            let mut entries = Map::new();
+            for _ in 0..argument {
+                let Value::String(key) = read_cbor(reader, depth + 1)? else {
+                    return Err(invalid(reader.format, "map key is not a string"));
+                };
+                entries.insert(key, read_cbor(reader, depth + 1)?);
+            }
+            Value::Object(entries)
+        }
+        6 => read_cbor(reader, depth + 1)?,
+        _ => return Err(invalid(reader.format, "byte strings are not supported")),
+    })
+}
+
+/// IEEE 754 half-precision float
+fn half_to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f64;
+    sign * match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15),
+    }
+}
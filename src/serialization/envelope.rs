@@ -0,0 +1,96 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::debug;
+
+use crate::error::AppResult;
+use super::binary;
+use crate::index::IndexResult;
+use crate::models::FeedData;
+
+/// Current schema version of everything the collector writes
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Version assumed for payloads written before they carried a version
+pub const LEGACY_SCHEMA_VERSION: u32 = 0;
+
+fn legacy_schema_version() -> u32 {
+    LEGACY_SCHEMA_VERSION
+}
+
+/// Versioned wrapper for data crossing process boundaries (database JSON columns, streams,
+/// WebSocket messages, spool files).
+///
+/// The payload is flattened, so a versioned `FeedData` is the plain object plus a
+/// `schema_version` field. Evolution rules for payload types:
+/// - new fields are `#[serde(default)]`, so older payloads still decode
+/// - unknown fields are ignored, so older consumers still decode newer payloads
+/// - renamed fields keep their old name as a `#[serde(alias = "...")]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    #[serde(default = "legacy_schema_version", alias = "version")]
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub payload: T,
+}
+
+impl<T> Envelope<T> {
+    /// Wrap a payload with the current schema version
+    pub fn new(payload: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            payload,
+        }
+    }
+
+    /// Unwrap the payload
+    pub fn into_payload(self) -> T {
+        self.payload
+    }
+}
+
+/// Encoding used on the wire or on disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    #[default]
+    Json,
+    /// MessagePack, the envelope encoded like its JSON form
+    #[serde(rename = "msgpack")]
+    MessagePack,
+    /// CBOR (RFC 8949), the envelope encoded like its JSON form
+    Cbor,
+}
+
+impl WireFormat {
+    /// Encode a payload in a versioned envelope
+    pub fn encode<T: Serialize>(&self, payload: &T) -> AppResult<Vec<u8>> {
+        match self {
+            WireFormat::Json => Ok(serde_json::to_vec(&Envelope::new(payload))?),
+            WireFormat::MessagePack => Ok(binary::to_msgpack(&serde_json::to_value(Envelope::new(payload))?)),
+            WireFormat::Cbor => Ok(binary::to_cbor(&serde_json::to_value(Envelope::new(payload))?)),
+        }
+    }
+
+    /// Decode a versioned (or legacy unversioned) payload
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> AppResult<T> {
+        let envelope: Envelope<T> = match self {
+            WireFormat::Json => serde_json::from_slice(bytes)?,
+            WireFormat::MessagePack => serde_json::from_value(binary::from_msgpack(bytes)?)?,
+            WireFormat::Cbor => serde_json::from_value(binary::from_cbor(bytes)?)?,
+        };
+
+        if envelope.schema_version > SCHEMA_VERSION {
+            debug!("Decoding payload with newer schema version {} (current: {})",
+                   envelope.schema_version, SCHEMA_VERSION);
+        }
+
+        Ok(envelope.into_payload())
+    }
+}
+
+/// Record of a mixed stream of index values and raw ticks, tagged with its `type`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StreamRecord {
+    Index(Box<IndexResult>),
+    Tick(FeedData),
+}
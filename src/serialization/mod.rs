@@ -0,0 +1,8 @@
+mod envelope;
+pub mod avro;
+pub mod binary;
+
+#[cfg(test)]
+mod tests;
+
+pub use envelope::{Envelope, StreamRecord, WireFormat, SCHEMA_VERSION, LEGACY_SCHEMA_VERSION};
@@ -0,0 +1,246 @@
+use super::*;
+use crate::index::IndexResult;
+use crate::models::{Denomination, FeedData};
+use chrono::{TimeZone, Utc};
+
+#[cfg(test)]
+mod envelope_tests {
+    use super::*;
+
+    fn feed_data() -> FeedData {
+        FeedData {
+            feed_id: "coinbase_btc_usd".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            price: 64123.45,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        }
+    }
+
+    #[test]
+    fn test_feed_data_round_trip() {
+        let bytes = WireFormat::Json.encode(&feed_data()).unwrap();
+        let decoded: FeedData = WireFormat::Json.decode(&bytes).unwrap();
+
+        assert_eq!(decoded, feed_data());
+    }
+
+    #[test]
+    fn test_index_result_round_trip() {
+        let result = IndexResult {
+            name: "BTC-USD-INDEX".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            value: 64000.0,
+            epoch: 7,
+            denomination: Denomination {
+                base_currency: "BTC".to_string(),
+                quote_currency: "USD".to_string(),
+                decimals: 2,
+            },
+            quality: Default::default(),
+            change: Default::default(),
+            methodology_version: 0,
+            raw_value: None,
+            warming_up: false,
+        };
+
+        let bytes = WireFormat::Json.encode(&result).unwrap();
+        let decoded: IndexResult = WireFormat::Json.decode(&bytes).unwrap();
+
+        assert_eq!(decoded, result);
+    }
+
+    #[test]
+    fn test_encoded_payload_carries_schema_version() {
+        let bytes = WireFormat::Json.encode(&feed_data()).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+        assert_eq!(value["feed_id"], "coinbase_btc_usd");
+    }
+
+    #[test]
+    fn test_legacy_payload_without_version_decodes() {
+        let legacy = br#"{"feed_id":"coinbase_btc_usd","timestamp":"2024-05-01T12:00:00Z","price":64123.45}"#;
+
+        let envelope: Envelope<FeedData> = serde_json::from_slice(legacy).unwrap();
+        assert_eq!(envelope.schema_version, LEGACY_SCHEMA_VERSION);
+        assert_eq!(envelope.payload, feed_data());
+    }
+
+    #[test]
+    fn test_newer_payload_with_unknown_fields_decodes() {
+        let newer = br#"{"schema_version":7,"feed_id":"coinbase_btc_usd","timestamp":"2024-05-01T12:00:00Z","price":64123.45,"volume":12.5}"#;
+
+        let decoded: FeedData = WireFormat::Json.decode(newer).unwrap();
+        assert_eq!(decoded, feed_data());
+    }
+
+    #[test]
+    fn test_stream_record_is_tagged() {
+        let bytes = WireFormat::Json.encode(&StreamRecord::Tick(feed_data())).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(value["type"], "tick");
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+
+        let decoded: StreamRecord = WireFormat::Json.decode(&bytes).unwrap();
+        assert_eq!(decoded, StreamRecord::Tick(feed_data()));
+    }
+}
+
+#[cfg(test)]
+mod avro_tests {
+    use super::*;
+    use super::avro::{confluent_framed, encode_feed_data, encode_index_result, write_long};
+
+    #[test]
+    fn test_longs_are_zigzag_varints() {
+        let encode = |value| {
+            let mut buf = Vec::new();
+            write_long(&mut buf, value);
+            buf
+        };
+
+        assert_eq!(encode(0), [0x00]);
+        assert_eq!(encode(-1), [0x01]);
+        assert_eq!(encode(1), [0x02]);
+        assert_eq!(encode(-64), [0x7f]);
+        assert_eq!(encode(64), [0x80, 0x01]);
+    }
+
+    #[test]
+    fn test_feed_data_encoding() {
+        let price = FeedData {
+            feed_id: "btc".to_string(),
+            timestamp: Utc.timestamp_opt(1, 0).unwrap(),
+            price: 1.5,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: Some(Denomination {
+                base_currency: "BTC".to_string(),
+                quote_currency: "USD".to_string(),
+                decimals: 2,
+            }),
+        };
+
+        let mut expected = vec![0x06, b'b', b't', b'c'];
+        // 1_000_000 microseconds, zigzag encoded
+        expected.extend_from_slice(&[0x80, 0x89, 0x7a]);
+        expected.extend_from_slice(&1.5f64.to_le_bytes());
+        expected.push(0x00);
+        expected.extend_from_slice(&[0x02, 0x06, b'B', b'T', b'C']);
+        expected.extend_from_slice(&[0x02, 0x06, b'U', b'S', b'D']);
+        expected.extend_from_slice(&[0x02, 0x04]);
+        assert_eq!(encode_feed_data(&price), expected);
+        assert_eq!(confluent_framed(7, &[0xaa]), [0, 0, 0, 0, 7, 0xaa]);
+    }
+
+    #[test]
+    fn test_index_result_encoding() {
+        let result = IndexResult {
+            name: "idx".to_string(),
+            timestamp: Utc.timestamp_opt(1, 0).unwrap(),
+            value: 2.5,
+            raw_value: Some(2.0),
+            epoch: 3,
+            denomination: Denomination {
+                base_currency: "BTC".to_string(),
+                quote_currency: "USD".to_string(),
+                decimals: 2,
+            },
+            quality: Default::default(),
+            change: Default::default(),
+            methodology_version: 1,
+            warming_up: true,
+        };
+
+        let mut expected = vec![0x06, b'i', b'd', b'x'];
+        expected.extend_from_slice(&[0x80, 0x89, 0x7a]);
+        expected.extend_from_slice(&2.5f64.to_le_bytes());
+        expected.push(0x02);
+        expected.extend_from_slice(&2.0f64.to_le_bytes());
+        expected.push(0x06);
+        expected.extend_from_slice(&[0x06, b'B', b'T', b'C']);
+        expected.extend_from_slice(&[0x06, b'U', b'S', b'D']);
+        expected.push(0x04);
+        // Not degraded, warming up, methodology version 1
+        expected.extend_from_slice(&[0x00, 0x01, 0x02]);
+        assert_eq!(encode_index_result(&result), expected);
+    }
+}
+
+#[cfg(test)]
+mod binary_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_msgpack_encoding() {
+        assert_eq!(binary::to_msgpack(&json!(null)), [0xc0]);
+        assert_eq!(binary::to_msgpack(&json!(true)), [0xc3]);
+        assert_eq!(binary::to_msgpack(&json!(127)), [0x7f]);
+        assert_eq!(binary::to_msgpack(&json!(200)), [0xcc, 0xc8]);
+        assert_eq!(binary::to_msgpack(&json!(70000)), [0xce, 0x00, 0x01, 0x11, 0x70]);
+        assert_eq!(binary::to_msgpack(&json!(-1)), [0xff]);
+        assert_eq!(binary::to_msgpack(&json!(-200)), [0xd1, 0xff, 0x38]);
+        assert_eq!(binary::to_msgpack(&json!(1.5)), [0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(binary::to_msgpack(&json!({"a": [1, "b"]})), [0x81, 0xa1, b'a', 0x92, 0x01, 0xa1, b'b']);
+        assert_eq!(&binary::to_msgpack(&json!("x".repeat(40)))[..2], [0xd9, 40]);
+    }
+
+    #[test]
+    fn test_cbor_encoding() {
+        assert_eq!(binary::to_cbor(&json!(null)), [0xf6]);
+        assert_eq!(binary::to_cbor(&json!(false)), [0xf4]);
+        assert_eq!(binary::to_cbor(&json!(23)), [0x17]);
+        assert_eq!(binary::to_cbor(&json!(1000)), [0x19, 0x03, 0xe8]);
+        assert_eq!(binary::to_cbor(&json!(-10)), [0x29]);
+        assert_eq!(binary::to_cbor(&json!(-1000)), [0x39, 0x03, 0xe7]);
+        assert_eq!(binary::to_cbor(&json!(1.5)), [0xfb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(binary::to_cbor(&json!({"a": [1, "b"]})), [0xa1, 0x61, b'a', 0x82, 0x01, 0x61, b'b']);
+        // Half-precision floats of other encoders
+        assert_eq!(binary::from_cbor(&[0xf9, 0x3e, 0x00]).unwrap(), json!(1.5));
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let value = json!({
+            "name": "BTC-USD-INDEX",
+            "values": [0, 255, 65536, u64::MAX, -33, -40000, i64::MIN, 64123.45, null, true],
+            "nested": {"text": "é".repeat(300), "empty": {}},
+        });
+        assert_eq!(binary::from_msgpack(&binary::to_msgpack(&value)).unwrap(), value);
+        assert_eq!(binary::from_cbor(&binary::to_cbor(&value)).unwrap(), value);
+    }
+
+    #[test]
+    fn test_invalid_binary_payloads() {
+        assert!(binary::from_msgpack(&[0x92, 0x01]).is_err());
+        assert!(binary::from_msgpack(&[0x01, 0x02]).is_err());
+        assert!(binary::from_msgpack(&[0xdd, 0xff, 0xff, 0xff, 0xff]).is_err());
+        assert!(binary::from_msgpack(&[0x81, 0x01, 0x01]).is_err());
+        assert!(binary::from_cbor(&[0x9f, 0x01, 0xff]).is_err());
+        assert!(binary::from_cbor(&[0x43, 1, 2, 3]).is_err());
+        assert!(binary::from_cbor(&[0x91; 100]).is_err());
+    }
+
+    #[test]
+    fn test_binary_wire_formats() {
+        let tick = FeedData {
+            feed_id: "coinbase_btc_usd".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            price: 64123.45,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        };
+        for format in [WireFormat::MessagePack, WireFormat::Cbor] {
+            let bytes = format.encode(&tick).unwrap();
+            assert!(bytes.len() < WireFormat::Json.encode(&tick).unwrap().len());
+            assert_eq!(format.decode::<FeedData>(&bytes).unwrap(), tick);
+        }
+        assert_eq!(serde_json::from_str::<WireFormat>(r#""msgpack""#).unwrap(), WireFormat::MessagePack);
+        assert_eq!(serde_json::from_str::<WireFormat>(r#""cbor""#).unwrap(), WireFormat::Cbor);
+    }
+}
@@ -0,0 +1,8 @@
+mod rolling;
+mod tdigest;
+
+#[cfg(test)]
+mod tests;
+
+pub use rolling::{Distribution, RollingQuantiles, REPORTED_QUANTILES};
+pub use tdigest::TDigest;
@@ -0,0 +1,78 @@
+use std::collections::{BTreeMap, VecDeque};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::TDigest;
+
+/// Quantiles reported for every feed, by label
+pub const REPORTED_QUANTILES: [(&str, f64); 5] = [("p01", 0.01), ("p05", 0.05), ("p50", 0.5), ("p95", 0.95), ("p99", 0.99)];
+
+/// The rolling window is covered by this many digests, the oldest one dropped at a time
+const BUCKETS_PER_WINDOW: i32 = 6;
+
+/// Price distribution of a feed over its rolling window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Distribution {
+    pub feed_id: String,
+    pub window_secs: i64,
+    pub count: u64,
+    pub min: f64,
+    pub max: f64,
+    /// Estimated quantiles by label, see [`REPORTED_QUANTILES`]
+    pub quantiles: BTreeMap<String, f64>,
+}
+
+/// Quantile sketch over a rolling time window, kept as a ring of t-digests per window slice
+#[derive(Debug, Clone)]
+pub struct RollingQuantiles {
+    buckets: VecDeque<(DateTime<Utc>, TDigest)>,
+    window: Duration,
+    bucket_span: Duration,
+    compression: f64,
+}
+
+impl RollingQuantiles {
+    pub fn new(window: Duration, compression: f64) -> Self {
+        Self {
+            buckets: VecDeque::new(),
+            window,
+            bucket_span: window / BUCKETS_PER_WINDOW,
+            compression,
+        }
+    }
+
+    /// Add a value observed at `timestamp`, returning whether it started a new window slice
+    pub fn add(&mut self, value: f64, timestamp: DateTime<Utc>) -> bool {
+        let rotate = self.buckets.back().is_none_or(|(start, _)| timestamp - *start >= self.bucket_span);
+        if rotate {
+            while self.buckets.front().is_some_and(|(start, _)| timestamp - *start >= self.window) {
+                self.buckets.pop_front();
+            }
+            self.buckets.push_back((timestamp, TDigest::new(self.compression)));
+        }
+
+        if let Some((_, digest)) = self.buckets.back_mut() {
+            digest.add(value);
+        }
+        rotate
+    }
+
+    /// Distribution of the values within the window, `None` before the first value
+    pub fn distribution(&self, feed_id: &str) -> Option<Distribution> {
+        let mut merged = TDigest::new(self.compression);
+        for (_, digest) in &self.buckets {
+            merged.merge(digest);
+        }
+
+        Some(Distribution {
+            feed_id: feed_id.to_string(),
+            window_secs: self.window.num_seconds(),
+            count: merged.count(),
+            min: merged.min()?,
+            max: merged.max()?,
+            quantiles: REPORTED_QUANTILES.iter()
+                .filter_map(|(label, q)| Some((label.to_string(), merged.quantile(*q)?)))
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,156 @@
+use std::f64::consts::PI;
+
+/// A cluster of nearby values summarized by their mean and count
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// Merging t-digest: a compact sketch of a value distribution, most accurate at the tails.
+///
+/// Values are buffered and merged into at most about `compression` centroids, so memory stays
+/// constant however many values are added.
+#[derive(Debug, Clone)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    buffer: Vec<Centroid>,
+    count: u64,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub fn new(compression: f64) -> Self {
+        Self {
+            compression: compression.max(10.0),
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Number of values added
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    pub fn add(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        self.buffer.push(Centroid { mean: value, weight: 1.0 });
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+
+        if self.buffer.len() as f64 >= self.compression * 5.0 {
+            self.compress();
+        }
+    }
+
+    /// Add all values summarized by another digest
+    pub fn merge(&mut self, other: &TDigest) {
+        if other.count == 0 {
+            return;
+        }
+        self.buffer.extend(other.centroids.iter().chain(&other.buffer));
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.compress();
+    }
+
+    /// Estimated value below which the fraction `q` of all values falls, `None` if empty
+    pub fn quantile(&mut self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        self.compress();
+
+        let q = q.clamp(0.0, 1.0);
+        if q == 0.0 {
+            return Some(self.min);
+        }
+        if q == 1.0 {
+            return Some(self.max);
+        }
+        let target = q * self.count as f64;
+
+        // Each centroid sits at the middle of the ranks it covers; interpolate between neighbours,
+        // and between the outermost centroids and the exact extremes
+        let mut previous = (0.0, self.min);
+        let mut rank = 0.0;
+        for centroid in &self.centroids {
+            let center = rank + centroid.weight / 2.0;
+            if target < center {
+                return Some(interpolate(previous, (center, centroid.mean), target));
+            }
+            previous = (center, centroid.mean);
+            rank += centroid.weight;
+        }
+        Some(interpolate(previous, (self.count as f64, self.max), target))
+    }
+
+    /// Merge buffered values into the centroids, keeping centroids small near the tails
+    fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let mut all = std::mem::take(&mut self.centroids);
+        all.append(&mut self.buffer);
+        all.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+
+        let total: f64 = all.iter().map(|c| c.weight).sum();
+        let mut merged = Vec::with_capacity(self.compression as usize);
+        let mut iter = all.into_iter();
+        let Some(mut current) = iter.next() else {
+            return;
+        };
+        let mut q_start = 0.0;
+        let mut q_limit = self.q_limit(q_start);
+
+        for next in iter {
+            if q_start + (current.weight + next.weight) / total <= q_limit {
+                current.mean += (next.mean - current.mean) * next.weight / (current.weight + next.weight);
+                current.weight += next.weight;
+            } else {
+                q_start += current.weight / total;
+                q_limit = self.q_limit(q_start);
+                merged.push(current);
+                current = next;
+            }
+        }
+        merged.push(current);
+
+        self.centroids = merged;
+    }
+
+    /// Highest quantile a centroid starting at quantile `q` may reach (arcsine scale function)
+    fn q_limit(&self, q: f64) -> f64 {
+        let k = self.compression / (2.0 * PI) * (2.0 * q.min(1.0) - 1.0).asin() + 1.0;
+        if k >= self.compression / 4.0 {
+            return 1.0;
+        }
+        ((k * 2.0 * PI / self.compression).sin() + 1.0) / 2.0
+    }
+}
+
+fn interpolate((x0, y0): (f64, f64), (x1, y1): (f64, f64), x: f64) -> f64 {
+    if x1 <= x0 {
+        return y1;
+    }
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+}
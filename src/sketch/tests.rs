@@ -0,0 +1,66 @@
+use chrono::{Duration, TimeZone, Utc};
+
+use super::*;
+
+#[cfg(test)]
+mod tdigest_tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles_of_uniform_values() {
+        let mut digest = TDigest::new(100.0);
+        for value in 1..=10_000 {
+            digest.add(value as f64);
+        }
+
+        assert_eq!(digest.count(), 10_000);
+        assert_eq!(digest.quantile(0.0), Some(1.0));
+        assert_eq!(digest.quantile(1.0), Some(10_000.0));
+        for (q, expected) in [(0.01, 100.0), (0.5, 5000.0), (0.99, 9900.0)] {
+            let estimate = digest.quantile(q).unwrap();
+            assert!((estimate - expected).abs() / expected < 0.01, "q{} estimated {}", q, estimate);
+        }
+    }
+
+    #[test]
+    fn test_merged_digests_match_one_digest() {
+        let (mut low, mut high) = (TDigest::new(100.0), TDigest::new(100.0));
+        for value in 0..500 {
+            low.add(value as f64);
+            high.add((value + 500) as f64);
+        }
+        low.merge(&high);
+
+        assert_eq!(low.count(), 1000);
+        assert!((low.quantile(0.5).unwrap() - 500.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn test_empty_digest_has_no_quantiles() {
+        assert_eq!(TDigest::new(100.0).quantile(0.5), None);
+    }
+}
+
+#[cfg(test)]
+mod rolling_tests {
+    use super::*;
+
+    #[test]
+    fn test_old_values_leave_the_window() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let mut quantiles = RollingQuantiles::new(Duration::minutes(60), 100.0);
+
+        for minute in 0..60 {
+            quantiles.add(100.0, start + Duration::minutes(minute));
+        }
+        assert_eq!(quantiles.distribution("a").unwrap().max, 100.0);
+
+        // An hour later only the new prices are left
+        for minute in 60..130 {
+            quantiles.add(200.0, start + Duration::minutes(minute));
+        }
+        let distribution = quantiles.distribution("a").unwrap();
+        assert_eq!(distribution.min, 200.0);
+        assert_eq!(distribution.quantiles["p50"], 200.0);
+    }
+}
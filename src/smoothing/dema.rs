@@ -0,0 +1,99 @@
+use super::{SmoothingState, SmoothingStrategy};
+
+/// Double (order 2) or triple (order 3) exponential moving average: EMAs chained on each
+/// other's output and combined to cancel most of the lag of a plain EMA, `2*EMA1 - EMA2` and
+/// `3*EMA1 - 3*EMA2 + EMA3` respectively
+pub struct ChainedExponentialMovingAverage {
+    order: usize,
+    n: usize,  // Number of samples
+    s: f64,    // Smoothing factor
+}
+
+impl ChainedExponentialMovingAverage {
+    pub fn double(n: usize, s: f64) -> Self {
+        Self::new(2, n, s)
+    }
+
+    pub fn triple(n: usize, s: f64) -> Self {
+        Self::new(3, n, s)
+    }
+
+    fn new(order: usize, n: usize, s: f64) -> Self {
+        // Same bounds as the plain EMA
+        Self { order, n: n.max(1), s: s.max(0.0) }
+    }
+
+    fn alpha(&self) -> f64 {
+        (self.s / (1.0 + self.n as f64)).clamp(0.0, 1.0)
+    }
+}
+
+impl SmoothingStrategy for ChainedExponentialMovingAverage {
+    /// Starts with an empty chain: the first raw value seeds every EMA, and the chain counts as
+    /// warm after `n` values
+    fn start(&self) -> Box<dyn SmoothingState> {
+        Box::new(ChainedEmaState { alpha: self.alpha(), emas: Vec::with_capacity(self.order), order: self.order, period: self.n, updates: 0 })
+    }
+}
+
+/// Value of every EMA of the chain, the first one smoothing the raw values
+struct ChainedEmaState {
+    alpha: f64,
+    emas: Vec<f64>,
+    order: usize,
+    /// Values after which the chain is considered warm, and values seen so far
+    period: usize,
+    updates: usize,
+}
+
+impl ChainedEmaState {
+    fn next_emas(&self, value: f64) -> Vec<f64> {
+        let mut input = value;
+        (0..self.order)
+            .map(|level| {
+                input = match self.emas.get(level) {
+                    Some(previous) => input * self.alpha + previous * (1.0 - self.alpha),
+                    None => input,
+                };
+                input
+            })
+            .collect()
+    }
+
+    /// Combination of the chained EMAs cancelling their lag
+    fn combine(&self, emas: &[f64]) -> f64 {
+        match emas {
+            [ema1, ema2] => 2.0 * ema1 - ema2,
+            [ema1, ema2, ema3] => 3.0 * ema1 - 3.0 * ema2 + ema3,
+            _ => emas.first().copied().unwrap_or_default(),
+        }
+    }
+}
+
+impl SmoothingState for ChainedEmaState {
+    fn update(&mut self, value: f64) -> f64 {
+        self.emas = self.next_emas(value);
+        self.updates += 1;
+        self.combine(&self.emas)
+    }
+
+    fn preview(&self, value: f64) -> f64 {
+        self.combine(&self.next_emas(value))
+    }
+
+    fn save(&self) -> Vec<f64> {
+        self.emas.clone()
+    }
+
+    fn restore(&mut self, saved: &[f64]) {
+        // A state of another order would combine into a different value
+        if saved.len() == self.order {
+            self.emas = saved.to_vec();
+            self.updates = self.period;
+        }
+    }
+
+    fn is_warm(&self) -> bool {
+        self.updates >= self.period
+    }
+}
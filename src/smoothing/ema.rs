@@ -1,5 +1,4 @@
-use std::collections::VecDeque;
-use super::SmoothingStrategy;
+use super::{SmoothingState, SmoothingStrategy};
 
 /// Exponential Moving Average smoothing algorithm
 pub struct ExponentialMovingAverage {
@@ -17,29 +16,53 @@ impl ExponentialMovingAverage {
     }
 }
 
+impl ExponentialMovingAverage {
+    fn alpha(&self) -> f64 {
+        self.s / (1.0 + self.n as f64)
+    }
+}
+
 impl SmoothingStrategy for ExponentialMovingAverage {
-    fn apply(&self, price_history: &VecDeque<f64>, current_price: f64) -> f64 {
-        // If history is empty, return current price
-        if price_history.is_empty() {
-            return current_price;
-        }
+    fn start(&self) -> Box<dyn SmoothingState> {
+        Box::new(EmaState { alpha: self.alpha().clamp(0.0, 1.0), previous: None, period: self.n, updates: 0 })
+    }
+}
 
-        // Calculate alpha (smoothing factor)
-        let a = self.s / (1.0 + self.n as f64);
+/// The previous EMA value, which is all an EMA needs
+struct EmaState {
+    alpha: f64,
+    previous: Option<f64>,
+    /// Values after which the EMA is considered warm, and values seen so far
+    period: usize,
+    updates: usize,
+}
+
+impl SmoothingState for EmaState {
+    fn update(&mut self, value: f64) -> f64 {
+        let ema = self.preview(value);
+        self.previous = Some(ema);
+        self.updates += 1;
+        ema
+    }
 
-        // Handle edge cases
-        if a >= 1.0 {
-            // If alpha is 1 or greater, just return current price
-            return current_price;
-        } else if a <= 0.0 {
-            // If alpha is 0 or negative, just return previous EMA
-            return price_history[0];
+    fn preview(&self, value: f64) -> f64 {
+        match self.previous {
+            Some(previous) => value * self.alpha + previous * (1.0 - self.alpha),
+            None => value,
         }
+    }
 
-        // Get the previous EMA (most recent price in history)
-        let previous_ema = price_history[0];
+    fn save(&self) -> Vec<f64> {
+        self.previous.into_iter().collect()
+    }
+
+    fn restore(&mut self, saved: &[f64]) {
+        self.previous = saved.last().copied();
+        // A restored EMA continues a series that was smoothed before
+        self.updates = if self.previous.is_some() { self.period } else { 0 };
+    }
 
-        // Calculate EMA: current_price * alpha + previous_ema * (1 - alpha)
-        current_price * a + previous_ema * (1.0 - a)
+    fn is_warm(&self) -> bool {
+        self.updates >= self.period
     }
 }
@@ -1,24 +1,78 @@
 mod none;
 mod sma;
 mod ema;
+mod wma;
+mod dema;
 
 #[cfg(test)]
 mod tests;
 
-use std::collections::VecDeque;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
 use crate::models::SmoothingType;
 
-/// Trait for smoothing algorithms
-pub trait SmoothingStrategy {
-    /// Apply the smoothing algorithm to the price history
-    fn apply(&self, price_history: &VecDeque<f64>, current_price: f64) -> f64;
+/// Trait for smoothing algorithms, holding their parameters
+pub trait SmoothingStrategy: Send + Sync {
+    /// Fresh incremental state for smoothing a series value by value. Every index owns one,
+    /// created once at startup, so an algorithm keeps whatever it needs between values (the
+    /// previous EMA, a running sum, a filter's estimate) apart from the published index history.
+    fn start(&self) -> Box<dyn SmoothingState>;
 }
 
-/// Factory function to create smoothing algorithm instances
-pub fn create_algorithm(smoothing_type: &SmoothingType) -> Box<dyn SmoothingStrategy> {
-    match smoothing_type {
-        SmoothingType::None => Box::new(none::NoSmoothing),
-        SmoothingType::Sma => Box::new(sma::SimpleMovingAverage::new(20)),
-        SmoothingType::Ema => Box::new(ema::ExponentialMovingAverage::new(20, 2.0)),
+/// Smoothing state of one series, updated in constant time with every raw value
+pub trait SmoothingState: Send + Sync {
+    /// Add the next raw value and return the smoothed value
+    fn update(&mut self, value: f64) -> f64;
+
+    /// Smoothed value `update` would return for the next raw value, without adding it
+    fn preview(&self, value: f64) -> f64;
+
+    /// Values the state is made of, oldest first, to carry it over to another process
+    fn save(&self) -> Vec<f64>;
+
+    /// Continue from values returned by `save` of a state of the same algorithm
+    fn restore(&mut self, saved: &[f64]);
+
+    /// Whether the state has seen enough values to smooth over its full window; before that,
+    /// smoothed values are close to the raw ones
+    fn is_warm(&self) -> bool {
+        true
     }
 }
+
+/// Creates a custom smoothing algorithm from an index's `smoothing_period` and `smoothing_factor`
+type AlgorithmFactory = Arc<dyn Fn(usize, f64) -> Box<dyn SmoothingStrategy> + Send + Sync>;
+
+fn registry() -> &'static RwLock<HashMap<String, AlgorithmFactory>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, AlgorithmFactory>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Register a custom smoothing algorithm, selected by indices with `smoothing = "<name>"` and
+/// created by `factory` from their `smoothing_period` and `smoothing_factor`. Must be called
+/// before the configuration is loaded; the built-in names cannot be overridden.
+pub fn register_algorithm<S: SmoothingStrategy + 'static>(
+    name: &str,
+    factory: impl Fn(usize, f64) -> S + Send + Sync + 'static,
+) {
+    let factory: AlgorithmFactory = Arc::new(move |period, factor| Box::new(factory(period, factor)));
+    registry().write().unwrap().insert(name.to_string(), factory);
+}
+
+/// Factory function to create smoothing algorithm instances, averaging over `period` values
+/// with the EMAs using smoothing factor `factor`; `None` for a custom algorithm that is not
+/// registered
+pub fn create_algorithm(smoothing_type: &SmoothingType, period: usize, factor: f64) -> Option<Box<dyn SmoothingStrategy>> {
+    Some(match smoothing_type {
+        SmoothingType::None => Box::new(none::NoSmoothing),
+        SmoothingType::Sma => Box::new(sma::SimpleMovingAverage::new(period)),
+        SmoothingType::Ema => Box::new(ema::ExponentialMovingAverage::new(period, factor)),
+        SmoothingType::Wma => Box::new(wma::WeightedMovingAverage::new(period)),
+        SmoothingType::Dema => Box::new(dema::ChainedExponentialMovingAverage::double(period, factor)),
+        SmoothingType::Tema => Box::new(dema::ChainedExponentialMovingAverage::triple(period, factor)),
+        SmoothingType::Custom(name) => {
+            let factory = registry().read().unwrap().get(name).cloned()?;
+            factory(period, factor)
+        }
+    })
+}
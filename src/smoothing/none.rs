@@ -1,11 +1,26 @@
-use std::collections::VecDeque;
-use super::SmoothingStrategy;
+use super::{SmoothingState, SmoothingStrategy};
 
 /// No smoothing - returns the raw price
 pub struct NoSmoothing;
 
 impl SmoothingStrategy for NoSmoothing {
-    fn apply(&self, _price_history: &VecDeque<f64>, current_price: f64) -> f64 {
-        current_price
+    fn start(&self) -> Box<dyn SmoothingState> {
+        Box::new(NoSmoothing)
     }
 }
+
+impl SmoothingState for NoSmoothing {
+    fn update(&mut self, value: f64) -> f64 {
+        value
+    }
+
+    fn preview(&self, value: f64) -> f64 {
+        value
+    }
+
+    fn save(&self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    fn restore(&mut self, _saved: &[f64]) {}
+}
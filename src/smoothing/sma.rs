@@ -1,5 +1,8 @@
 use std::collections::VecDeque;
-use super::SmoothingStrategy;
+use super::{SmoothingState, SmoothingStrategy};
+
+/// Updates after which the running sum is recomputed from the window, to drop accumulated rounding errors
+const RESUM_INTERVAL: usize = 1024;
 
 /// Simple Moving Average smoothing algorithm
 pub struct SimpleMovingAverage {
@@ -15,25 +18,60 @@ impl SimpleMovingAverage {
 }
 
 impl SmoothingStrategy for SimpleMovingAverage {
-    fn apply(&self, price_history: &VecDeque<f64>, current_price: f64) -> f64 {
-        // If window size is 1 or history is empty, just return current price
-        if self.window_size == 1 || price_history.is_empty() {
-            return current_price;
+    fn start(&self) -> Box<dyn SmoothingState> {
+        Box::new(SmaState {
+            window: VecDeque::with_capacity(self.window_size),
+            window_size: self.window_size,
+            sum: 0.0,
+            updates: 0,
+        })
+    }
+}
+
+/// Running sum over the last `window_size` raw values
+struct SmaState {
+    window: VecDeque<f64>,
+    window_size: usize,
+    sum: f64,
+    updates: usize,
+}
+
+impl SmoothingState for SmaState {
+    fn update(&mut self, value: f64) -> f64 {
+        self.window.push_back(value);
+        self.sum += value;
+        if self.window.len() > self.window_size {
+            self.sum -= self.window.pop_front().unwrap_or_default();
+        }
+
+        self.updates += 1;
+        if self.updates.is_multiple_of(RESUM_INTERVAL) {
+            self.sum = self.window.iter().sum();
         }
 
-        let mut sum = current_price;
-        let mut count = 1;
-
-        // Add prices from history up to window_size - 1
-        for (i, &price) in price_history.iter().enumerate() {
-            if i < self.window_size - 1 {
-                sum += price;
-                count += 1;
-            } else {
-                break;
-            }
+        self.sum / self.window.len() as f64
+    }
+
+    fn preview(&self, value: f64) -> f64 {
+        if self.window.len() >= self.window_size {
+            let oldest = self.window.front().copied().unwrap_or_default();
+            (self.sum - oldest + value) / self.window.len() as f64
+        } else {
+            (self.sum + value) / (self.window.len() + 1) as f64
         }
+    }
+
+    fn save(&self) -> Vec<f64> {
+        self.window.iter().copied().collect()
+    }
+
+    fn restore(&mut self, saved: &[f64]) {
+        let kept = &saved[saved.len().saturating_sub(self.window_size)..];
+        self.window = kept.iter().copied().collect();
+        self.sum = self.window.iter().sum();
+    }
 
-        sum / count as f64
+    fn is_warm(&self) -> bool {
+        self.window.len() >= self.window_size
     }
 }
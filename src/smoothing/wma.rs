@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use super::{SmoothingState, SmoothingStrategy};
+
+/// Updates after which the running sums are recomputed from the window, to drop accumulated rounding errors
+const RESUM_INTERVAL: usize = 1024;
+
+/// Linearly Weighted Moving Average smoothing algorithm: over a window of `window_size`
+/// values, the newest weighs `window_size`, the one before `window_size - 1` and so on, and
+/// values leaving the window stop counting
+pub struct WeightedMovingAverage {
+    window_size: usize,
+}
+
+impl WeightedMovingAverage {
+    pub fn new(window_size: usize) -> Self {
+        // Ensure window size is at least 1
+        let window_size = if window_size == 0 { 1 } else { window_size };
+        Self { window_size }
+    }
+}
+
+impl SmoothingStrategy for WeightedMovingAverage {
+    fn start(&self) -> Box<dyn SmoothingState> {
+        Box::new(WmaState {
+            window: VecDeque::with_capacity(self.window_size),
+            window_size: self.window_size,
+            sum: 0.0,
+            weighted_sum: 0.0,
+            updates: 0,
+        })
+    }
+}
+
+/// Sum of the weights 1 to n
+fn triangular(n: usize) -> f64 {
+    (n * (n + 1) / 2) as f64
+}
+
+/// Running plain and weighted sums over the last `window_size` raw values, oldest first. Adding
+/// a value to a full window lowers the weight of every other value by one, i.e. subtracts
+/// their plain sum from the weighted sum.
+struct WmaState {
+    window: VecDeque<f64>,
+    window_size: usize,
+    sum: f64,
+    weighted_sum: f64,
+    updates: usize,
+}
+
+impl WmaState {
+    /// Plain and weighted sums after adding `value`
+    fn next_sums(&self, value: f64) -> (f64, f64) {
+        if self.window.len() >= self.window_size {
+            let oldest = self.window.front().copied().unwrap_or_default();
+            (self.sum - oldest + value, self.weighted_sum - self.sum + self.window_size as f64 * value)
+        } else {
+            (self.sum + value, self.weighted_sum + (self.window.len() + 1) as f64 * value)
+        }
+    }
+
+    fn resum(&mut self) {
+        self.sum = self.window.iter().sum();
+        self.weighted_sum = self.window.iter().enumerate().map(|(i, value)| (i + 1) as f64 * value).sum();
+    }
+}
+
+impl SmoothingState for WmaState {
+    fn update(&mut self, value: f64) -> f64 {
+        (self.sum, self.weighted_sum) = self.next_sums(value);
+        self.window.push_back(value);
+        if self.window.len() > self.window_size {
+            self.window.pop_front();
+        }
+
+        self.updates += 1;
+        if self.updates.is_multiple_of(RESUM_INTERVAL) {
+            self.resum();
+        }
+
+        self.weighted_sum / triangular(self.window.len())
+    }
+
+    fn preview(&self, value: f64) -> f64 {
+        let (_, weighted_sum) = self.next_sums(value);
+        weighted_sum / triangular((self.window.len() + 1).min(self.window_size))
+    }
+
+    fn save(&self) -> Vec<f64> {
+        self.window.iter().copied().collect()
+    }
+
+    fn restore(&mut self, saved: &[f64]) {
+        let kept = &saved[saved.len().saturating_sub(self.window_size)..];
+        self.window = kept.iter().copied().collect();
+        self.resum();
+    }
+
+    fn is_warm(&self) -> bool {
+        self.window.len() >= self.window_size
+    }
+}
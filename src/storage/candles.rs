@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Bucket width of stored candles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CandleInterval {
+    #[serde(rename = "1m")]
+    Minute,
+    #[serde(rename = "1h")]
+    Hour,
+}
+
+impl CandleInterval {
+    pub const ALL: [CandleInterval; 2] = [CandleInterval::Minute, CandleInterval::Hour];
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            CandleInterval::Minute => "1m",
+            CandleInterval::Hour => "1h",
+        }
+    }
+}
+
+/// Series candles are aggregated from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CandleSeries {
+    /// Published values of an index, by index name
+    Index,
+    /// Raw prices of a feed, by feed id
+    Feed,
+}
+
+impl CandleSeries {
+    pub const ALL: [CandleSeries; 2] = [CandleSeries::Index, CandleSeries::Feed];
+
+    /// Name column of the candles' view
+    pub fn key_column(&self) -> &'static str {
+        match self {
+            CandleSeries::Index => "index_name",
+            CandleSeries::Feed => "feed_id",
+        }
+    }
+}
+
+/// Open, high, low and close of a series within one bucket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Candle {
+    /// Start of the bucket
+    pub bucket: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Number of values aggregated
+    pub samples: i64,
+}
+
+/// Continuous aggregate holding the candles of a series, e.g. `index_candles_1m`, created by
+/// the `0003_candles` migration
+pub fn candle_view(series: CandleSeries, interval: CandleInterval) -> String {
+    let prefix = match series {
+        CandleSeries::Index => "index",
+        CandleSeries::Feed => "price",
+    };
+    format!("{}_candles_{}", prefix, interval.suffix())
+}
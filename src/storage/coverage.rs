@@ -0,0 +1,111 @@
+use std::fmt;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::error::{AppError, AppResult};
+
+/// Why a stretch of input data is missing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapReason {
+    /// The range lies before the retention horizon, so the data has been dropped
+    BeyondRetention,
+    /// No observations were recorded for longer than the allowed gap
+    NoData,
+}
+
+/// A stretch of time without usable observations for a feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageGap {
+    pub feed_id: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub reason: GapReason,
+}
+
+/// Input coverage of a set of feeds over a requested time range
+#[derive(Debug, Clone)]
+pub struct CoverageReport {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub gaps: Vec<CoverageGap>,
+}
+
+impl CoverageReport {
+    /// Whether every feed has data over the whole range
+    pub fn is_complete(&self) -> bool {
+        self.gaps.is_empty()
+    }
+
+    /// Fail with the full list of gaps if the coverage is incomplete
+    pub fn ensure_complete(&self) -> AppResult<()> {
+        if self.is_complete() {
+            Ok(())
+        } else {
+            Err(AppError::IndexCalculation(self.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_complete() {
+            return write!(f, "Complete input coverage from {} to {}", self.from, self.to);
+        }
+
+        write!(f, "Incomplete input coverage from {} to {}:", self.from, self.to)?;
+        for gap in &self.gaps {
+            let reason = match gap.reason {
+                GapReason::BeyondRetention => "beyond retention",
+                GapReason::NoData => "no data",
+            };
+            write!(f, "\n  - {}: {} to {} ({})", gap.feed_id, gap.from, gap.to, reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the gaps in a feed's observations over `[from, to]`.
+///
+/// `timestamps` must be sorted ascending and may include archived observations from before
+/// `retention_horizon`. Stretches longer than `max_gap` without an observation are reported;
+/// the part of such a stretch before the horizon is reported as beyond retention.
+pub fn find_gaps(
+    feed_id: &str,
+    timestamps: &[DateTime<Utc>],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    max_gap: Duration,
+    retention_horizon: Option<DateTime<Utc>>,
+) -> Vec<CoverageGap> {
+    let mut gaps = Vec::new();
+    let mut cursor = from;
+
+    let within = timestamps.iter().copied().filter(|&t| t >= from && t <= to);
+    for end in within.chain(std::iter::once(to)) {
+        if end - cursor > max_gap {
+            push_gap(&mut gaps, feed_id, cursor, end, max_gap, retention_horizon);
+        }
+        cursor = end;
+    }
+
+    gaps
+}
+
+/// Report a stretch without observations, split at the retention horizon
+fn push_gap(
+    gaps: &mut Vec<CoverageGap>,
+    feed_id: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    max_gap: Duration,
+    retention_horizon: Option<DateTime<Utc>>,
+) {
+    let mut from = from;
+    if let Some(horizon) = retention_horizon.filter(|horizon| *horizon > from) {
+        let end = horizon.min(to);
+        gaps.push(CoverageGap { feed_id: feed_id.to_string(), from, to: end, reason: GapReason::BeyondRetention });
+        from = end;
+    }
+    if to - from > max_gap {
+        gaps.push(CoverageGap { feed_id: feed_id.to_string(), from, to, reason: GapReason::NoData });
+    }
+}
@@ -1,9 +1,25 @@
-use sqlx::{Pool, Postgres, postgres::PgPoolOptions, Row};
-use chrono::{DateTime, Utc};
-use tracing::info;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::hash::Hash;
+use std::str::FromStr;
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres, postgres::{PgConnectOptions, PgListener, PgPoolOptions, PgRow}, types::Json, Row};
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use tracing::{info, warn};
 
-use crate::models::FeedData;
+use crate::exchange::FetchMetric;
+use crate::index::divisor::DivisorState;
+use crate::index::{CalculatorState, IndexResult, ReturnState, SmoothingSnapshot, StoredValue};
+use crate::models::{FeedData, IndexDefinition};
+use crate::config::DatabaseConfig;
 use crate::error::AppResult;
+use super::candles::{self, Candle, CandleInterval, CandleSeries};
+use super::history::StoredIndexValue;
+use super::coverage::{self, CoverageReport};
+use super::sink::StorageSink;
+
+/// Channel notified with the feed id whenever the latest value of a feed changes
+pub const LATEST_PRICE_CHANNEL: &str = "latest_price_data";
 
 #[derive(Clone)]
 pub struct Database {
@@ -12,146 +28,631 @@ pub struct Database {
 }
 
 impl Database {
-    pub async fn new(db_url: &str, enabled: bool) -> AppResult<Self> {
-        if !enabled {
+    /// Pool of connections to the configured database, migrated to the current schema. A
+    /// disabled database never connects; all its operations are no-ops.
+    pub async fn new(config: &DatabaseConfig) -> AppResult<Self> {
+        let options = Self::pool_options(config);
+        let connect_options = Self::connect_options(config)?;
+        if !config.enabled {
             info!("[DATABASE] Persistence disabled in configuration");
             return Ok(Self {
-                pool: Pool::connect(db_url).await?,
+                pool: options.connect_lazy_with(connect_options),
                 enabled: false,
             });
         }
 
-        info!("[DATABASE] Connecting to database at {}", db_url);
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(db_url)
-            .await?;
+        info!("[DATABASE] Connecting to database at {} with up to {} connections", config.url, config.max_connections);
+        let pool = options.connect_with(connect_options).await?;
 
-        // Initialize the database schema
-        Self::init_schema(&pool).await?;
+        Self::migrate(&pool).await?;
 
         info!("[DATABASE] Connection established successfully");
 
         Ok(Self {
             pool,
-            enabled,
+            enabled: true,
         })
     }
 
-    async fn init_schema(pool: &Pool<Postgres>) -> AppResult<()> {
-        // First ensure the extension is available
-        sqlx::query("CREATE EXTENSION IF NOT EXISTS timescaledb CASCADE;")
-            .execute(pool)
-            .await?;
+    fn pool_options(config: &DatabaseConfig) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .acquire_timeout(std::time::Duration::from_millis(config.acquire_timeout_ms))
+    }
+
+    /// Connection options of the URL, with the application name and statement timeout set on
+    /// every connection
+    fn connect_options(config: &DatabaseConfig) -> AppResult<PgConnectOptions> {
+        let mut options = PgConnectOptions::from_str(&config.url)?.application_name(&config.application_name);
+        if let Some(timeout_ms) = config.statement_timeout_ms {
+            options = options.options([("statement_timeout", timeout_ms.to_string())]);
+        }
+        Ok(options)
+    }
+
+    /// Apply the migrations in `migrations/` not applied yet, in order. Migrations are embedded
+    /// at build time and recorded in `_sqlx_migrations`; they only ever create or alter, so a
+    /// database set up by a version predating migrations is adopted as is.
+    async fn migrate(pool: &Pool<Postgres>) -> AppResult<()> {
+        let migrator = sqlx::migrate!();
+        migrator.run(pool).await?;
+
+        let version = migrator.iter().map(|migration| migration.version).max().unwrap_or_default();
+        info!("[DATABASE] Schema migrated to version {}", version);
+        Ok(())
+    }
+
+    /// Store a batch of prices in one multi-row statement
+    pub async fn save_price_batch(&self, prices: &[FeedData]) -> AppResult<()> {
+        if !self.enabled || prices.is_empty() {
+            return Ok(());
+        }
 
-        // Drop the existing table if it exists but is not a hypertable
-        // This is needed because we can't convert an existing table with constraints to a hypertable
+        let prices = last_per_key(prices, |price| (price.feed_id.as_str(), price.timestamp));
+        let feed_ids: Vec<&str> = prices.iter().map(|price| price.feed_id.as_str()).collect();
+        let timestamps: Vec<DateTime<Utc>> = prices.iter().map(|price| price.timestamp).collect();
+        let values: Vec<f64> = prices.iter().map(|price| price.price).collect();
+
+        // Use ON CONFLICT to handle duplicates. The latest value of every feed is upserted in the
+        // same statement and only moves forward in time; listeners are notified once the
+        // transaction commits.
         sqlx::query(
             r#"
-                DO $$
-                BEGIN
-                    IF EXISTS (
-                        SELECT 1 FROM pg_tables WHERE tablename = 'raw_price_data'
-                    ) AND NOT EXISTS (
-                        SELECT 1 FROM timescaledb_information.hypertables WHERE hypertable_name = 'raw_price_data'
-                    ) THEN
-                        DROP TABLE raw_price_data CASCADE;
-                        RAISE NOTICE 'Dropped existing non-hypertable to recreate as hypertable';
-                    END IF;
-                END;
-                $$;
+            WITH batch AS (
+                SELECT * FROM UNNEST($1::TEXT[], $2::TIMESTAMPTZ[], $3::DOUBLE PRECISION[]) AS batch(feed_id, timestamp, price)
+            ), raw AS (
+                INSERT INTO raw_price_data (feed_id, timestamp, price)
+                SELECT feed_id, timestamp, price FROM batch
+                ON CONFLICT (feed_id, timestamp)
+                DO UPDATE SET price = EXCLUDED.price
+            ), latest AS (
+                INSERT INTO latest_price_data (feed_id, timestamp, price)
+                SELECT DISTINCT ON (feed_id) feed_id, timestamp, price FROM batch ORDER BY feed_id, timestamp DESC
+                ON CONFLICT (feed_id)
+                DO UPDATE SET timestamp = EXCLUDED.timestamp, price = EXCLUDED.price
+                WHERE latest_price_data.timestamp <= EXCLUDED.timestamp
+                RETURNING feed_id
+            )
+            SELECT pg_notify($4, feed_id) FROM latest
             "#
         )
-        .execute(pool)
+        .bind(feed_ids)
+        .bind(timestamps)
+        .bind(values)
+        .bind(LATEST_PRICE_CHANNEL)
+        .execute(&self.pool)
         .await?;
 
-        // Create the table if it doesn't exist
+        Ok(())
+    }
+
+    /// Latest stored value of one feed, or of all feeds when `feed_id` is `None`
+    pub async fn get_latest_prices(&self, feed_id: Option<&str>) -> AppResult<Vec<FeedData>> {
+        if !self.enabled {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query(
+            "SELECT feed_id, timestamp, price FROM latest_price_data WHERE $1::TEXT IS NULL OR feed_id = $1"
+        )
+        .bind(feed_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(stored_price).collect()
+    }
+
+    /// Stored raw prices of the given feeds within `[from, to]`, oldest first, streamed rather
+    /// than read at once, e.g. to export them
+    pub fn stream_price_range<'a>(
+        &'a self,
+        feed_ids: &'a [String],
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> BoxStream<'a, AppResult<FeedData>> {
+        sqlx::query(
+            "SELECT feed_id, timestamp, price FROM raw_price_data
+             WHERE feed_id = ANY($1) AND timestamp BETWEEN $2 AND $3 ORDER BY timestamp, feed_id"
+        )
+        .bind(feed_ids)
+        .bind(from)
+        .bind(to)
+        .fetch(&self.pool)
+        .map(|row| stored_price(&row?))
+        .boxed()
+    }
+
+    /// Stored values of the given indices within `[from, to]`, oldest first, streamed rather than
+    /// read at once
+    pub fn stream_index_values<'a>(
+        &'a self,
+        names: &'a [String],
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> BoxStream<'a, AppResult<StoredIndexValue>> {
+        sqlx::query(
+            "SELECT index_name, timestamp, value, raw_value, epoch, methodology_version FROM index_values
+             WHERE index_name = ANY($1) AND timestamp BETWEEN $2 AND $3 ORDER BY timestamp, index_name"
+        )
+        .bind(names)
+        .bind(from)
+        .bind(to)
+        .fetch(&self.pool)
+        .map(|row| stored_index_value(&row?))
+        .boxed()
+    }
+
+    /// Store the divisor of a divisor-based index, replacing the previous one
+    pub async fn save_divisor(&self, index: &str, state: &DivisorState) -> AppResult<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS raw_price_data (
-                id SERIAL,
-                feed_id TEXT NOT NULL,
-                timestamp TIMESTAMPTZ NOT NULL,
-                price DOUBLE PRECISION NOT NULL,
-                PRIMARY KEY (id, timestamp)
-            );
+            INSERT INTO index_divisors (index_name, divisor, level, constituents, updated_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (index_name)
+            DO UPDATE SET divisor = EXCLUDED.divisor, level = EXCLUDED.level,
+                          constituents = EXCLUDED.constituents, updated_at = EXCLUDED.updated_at
             "#
         )
-        .execute(pool)
+        .bind(index)
+        .bind(state.divisor)
+        .bind(state.level)
+        .bind(Json(&state.constituents))
+        .execute(&self.pool)
         .await?;
 
-        // Try to convert to hypertable
+        Ok(())
+    }
+
+    /// Stored divisors of all divisor-based indices by index name
+    pub async fn load_divisors(&self) -> AppResult<HashMap<String, DivisorState>> {
+        if !self.enabled {
+            return Ok(HashMap::new());
+        }
+
+        let rows = sqlx::query("SELECT index_name, divisor, level, constituents FROM index_divisors")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let Json(constituents) = row.try_get("constituents")?;
+                Ok((row.try_get("index_name")?, DivisorState {
+                    divisor: row.try_get("divisor")?,
+                    constituents,
+                    level: row.try_get("level")?,
+                }))
+            })
+            .collect()
+    }
+
+    /// Store a batch of published index values in one multi-row statement
+    pub async fn save_index_value_batch(&self, values: &[IndexResult]) -> AppResult<()> {
+        if !self.enabled || values.is_empty() {
+            return Ok(());
+        }
+
+        let values = last_per_key(values, |value| (value.name.as_str(), value.timestamp));
+        let names: Vec<&str> = values.iter().map(|value| value.name.as_str()).collect();
+        let timestamps: Vec<DateTime<Utc>> = values.iter().map(|value| value.timestamp).collect();
+        let index_values: Vec<f64> = values.iter().map(|value| value.value).collect();
+        let epochs: Vec<i64> = values.iter().map(|value| value.epoch as i64).collect();
+        let versions: Vec<i32> = values.iter().map(|value| value.methodology_version as i32).collect();
+        let raw_values: Vec<Option<f64>> = values.iter().map(|value| value.raw_value).collect();
+
         sqlx::query(
             r#"
-            SELECT create_hypertable('raw_price_data', 'timestamp',
-                                   chunk_time_interval => INTERVAL '1 day',
-                                   if_not_exists => TRUE);
+            INSERT INTO index_values (index_name, timestamp, value, epoch, methodology_version, raw_value)
+            SELECT * FROM UNNEST($1::TEXT[], $2::TIMESTAMPTZ[], $3::DOUBLE PRECISION[], $4::BIGINT[], $5::INTEGER[], $6::DOUBLE PRECISION[])
+            ON CONFLICT (index_name, timestamp)
+            DO UPDATE SET value = EXCLUDED.value, epoch = EXCLUDED.epoch, methodology_version = EXCLUDED.methodology_version,
+                          raw_value = EXCLUDED.raw_value
             "#
         )
-        .execute(pool)
+        .bind(names)
+        .bind(timestamps)
+        .bind(index_values)
+        .bind(epochs)
+        .bind(versions)
+        .bind(raw_values)
+        .execute(&self.pool)
         .await?;
 
-        // Create indexes
+        Ok(())
+    }
+
+    /// Store a batch of fetch telemetry in one multi-row statement
+    pub async fn save_fetch_metric_batch(&self, fetches: &[FetchMetric]) -> AppResult<()> {
+        if !self.enabled || fetches.is_empty() {
+            return Ok(());
+        }
+
+        let feed_ids: Vec<&str> = fetches.iter().map(|fetch| fetch.feed_id.as_str()).collect();
+        let exchanges: Vec<&str> = fetches.iter().map(|fetch| fetch.exchange.as_str()).collect();
+        let timestamps: Vec<DateTime<Utc>> = fetches.iter().map(|fetch| fetch.timestamp).collect();
+        let latencies: Vec<f64> = fetches.iter().map(|fetch| fetch.latency_ms).collect();
+        let statuses: Vec<Option<i32>> = fetches.iter().map(|fetch| fetch.status.map(i32::from)).collect();
+        let retries: Vec<i32> = fetches.iter().map(|fetch| fetch.retries as i32).collect();
+        let error_classes: Vec<Option<&str>> = fetches.iter().map(|fetch| fetch.error_class.map(|class| class.as_str())).collect();
+
         sqlx::query(
             r#"
-            CREATE INDEX IF NOT EXISTS idx_raw_price_data_timestamp ON raw_price_data (timestamp);
+            INSERT INTO feed_metrics (feed_id, exchange, timestamp, latency_ms, status, retries, error_class)
+            SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[], $3::TIMESTAMPTZ[], $4::DOUBLE PRECISION[], $5::INTEGER[], $6::INTEGER[], $7::TEXT[])
             "#
         )
-        .execute(pool)
+        .bind(feed_ids)
+        .bind(exchanges)
+        .bind(timestamps)
+        .bind(latencies)
+        .bind(statuses)
+        .bind(retries)
+        .bind(error_classes)
+        .execute(&self.pool)
         .await?;
 
+        Ok(())
+    }
+
+    /// Current methodology version of every index by name, registering a new version for every
+    /// index whose definition changed since the last registered one (or that is new)
+    pub async fn register_methodologies(&self, indices: &[IndexDefinition]) -> AppResult<HashMap<String, u32>> {
+        let mut versions = HashMap::new();
+        if !self.enabled {
+            return Ok(versions);
+        }
+
+        for index in indices {
+            let fingerprint = index.methodology_fingerprint();
+            let latest = sqlx::query(
+                "SELECT version, fingerprint FROM index_methodologies WHERE index_name = $1 ORDER BY version DESC LIMIT 1"
+            )
+            .bind(&index.name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            let (version, previous) = match latest {
+                Some(row) => (row.try_get::<i32, _>("version")?, Some(row.try_get::<String, _>("fingerprint")?)),
+                None => (0, None),
+            };
+            if previous.as_ref() == Some(&fingerprint) {
+                versions.insert(index.name.clone(), version as u32);
+                continue;
+            }
+
+            sqlx::query(
+                "INSERT INTO index_methodologies (index_name, version, fingerprint, definition, effective_from)
+                 VALUES ($1, $2, $3, $4, NOW())"
+            )
+            .bind(&index.name)
+            .bind(version + 1)
+            .bind(&fingerprint)
+            .bind(index.methodology_description())
+            .execute(&self.pool)
+            .await?;
+
+            if previous.is_some() {
+                warn!("[METHODOLOGY] Index: {}, definition changed, methodology version {} -> {}", index.name, version, version + 1);
+            } else {
+                info!("[METHODOLOGY] Index: {}, registered methodology version 1", index.name);
+            }
+            versions.insert(index.name.clone(), version as u32 + 1);
+        }
+
+        Ok(versions)
+    }
+
+    /// Store the values of one recomputation, calculated with the index definition of the given
+    /// methodology fingerprint
+    pub async fn save_recomputed_index_values(
+        &self,
+        values: &[IndexResult],
+        fingerprint: &str,
+        recomputed_at: DateTime<Utc>,
+    ) -> AppResult<()> {
+        if !self.enabled {
+            return Err("Database persistence is disabled, recomputed values cannot be stored".into());
+        }
+
+        let names: Vec<&str> = values.iter().map(|value| value.name.as_str()).collect();
+        let timestamps: Vec<DateTime<Utc>> = values.iter().map(|value| value.timestamp).collect();
+        let index_values: Vec<f64> = values.iter().map(|value| value.value).collect();
         sqlx::query(
             r#"
-            CREATE UNIQUE INDEX IF NOT EXISTS idx_raw_price_data_feed_timestamp
-            ON raw_price_data (feed_id, timestamp);
+            INSERT INTO recomputed_index_values (index_name, timestamp, value, fingerprint, recomputed_at)
+            SELECT name, timestamp, value, $4, $5 FROM UNNEST($1::TEXT[], $2::TIMESTAMPTZ[], $3::DOUBLE PRECISION[])
+                AS recomputed(name, timestamp, value)
             "#
         )
-        .execute(pool)
+        .bind(names)
+        .bind(timestamps)
+        .bind(index_values)
+        .bind(fingerprint)
+        .bind(recomputed_at)
+        .execute(&self.pool)
         .await?;
 
-        info!("[DATABASE] Schema initialized with TimescaleDB hypertable");
         Ok(())
     }
 
-    pub async fn save_price_data(&self, data: &FeedData) -> AppResult<()> {
+    /// Stored values of an index within `[from, to]`, oldest first. With a `resolution`, only
+    /// the last value of every bucket of that length is returned, buckets being aligned on the
+    /// Unix epoch.
+    pub async fn get_index_history(
+        &self,
+        name: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        resolution: Option<Duration>,
+    ) -> AppResult<Vec<StoredIndexValue>> {
+        if !self.enabled {
+            return Ok(Vec::new());
+        }
+
+        let rows = match resolution {
+            Some(resolution) => sqlx::query(
+                r#"
+                SELECT index_name, timestamp, value, raw_value, epoch, methodology_version FROM (
+                    SELECT DISTINCT ON (bucket) time_bucket(make_interval(secs => $4), timestamp) AS bucket, *
+                    FROM index_values
+                    WHERE index_name = $1 AND timestamp >= $2 AND timestamp <= $3
+                    ORDER BY bucket, timestamp DESC
+                ) AS buckets
+                ORDER BY timestamp
+                "#
+            )
+            .bind(name)
+            .bind(from)
+            .bind(to)
+            .bind(resolution.num_milliseconds() as f64 / 1000.0),
+            None => sqlx::query(
+                "SELECT index_name, timestamp, value, raw_value, epoch, methodology_version FROM index_values
+                 WHERE index_name = $1 AND timestamp >= $2 AND timestamp <= $3 ORDER BY timestamp"
+            )
+            .bind(name)
+            .bind(from)
+            .bind(to),
+        }
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(stored_index_value).collect()
+    }
+
+    /// Latest stored value of an index, `None` if it has none
+    pub async fn get_latest_index(&self, name: &str) -> AppResult<Option<StoredIndexValue>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        let row = sqlx::query(
+            "SELECT index_name, timestamp, value, raw_value, epoch, methodology_version FROM index_values
+             WHERE index_name = $1 ORDER BY timestamp DESC LIMIT 1"
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(stored_index_value).transpose()
+    }
+
+    /// Latest stored values of an index, newest first
+    pub async fn get_recent_index_values(&self, index: &str, limit: i64) -> AppResult<Vec<(DateTime<Utc>, f64)>> {
+        if !self.enabled {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query(
+            "SELECT timestamp, value FROM index_values WHERE index_name = $1 ORDER BY timestamp DESC LIMIT $2"
+        )
+        .bind(index)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok((row.try_get("timestamp")?, row.try_get("value")?)))
+            .collect()
+    }
+
+    pub async fn save_smoothing_state(&self, index: &str, snapshot: &SmoothingSnapshot) -> AppResult<()> {
         if !self.enabled {
             return Ok(());
         }
 
-        // Use ON CONFLICT to handle duplicates
         sqlx::query(
             r#"
-            INSERT INTO raw_price_data (feed_id, timestamp, price)
-            VALUES ($1, $2, $3)
-            ON CONFLICT (feed_id, timestamp)
-            DO UPDATE SET price = EXCLUDED.price
+            INSERT INTO index_smoothing (index_name, smoothing, state, updated_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (index_name)
+            DO UPDATE SET smoothing = EXCLUDED.smoothing, state = EXCLUDED.state, updated_at = EXCLUDED.updated_at
             "#
         )
-        .bind(&data.feed_id)
-        .bind(data.timestamp)
-        .bind(data.price)
+        .bind(index)
+        .bind(Json(&snapshot.smoothing))
+        .bind(Json(&snapshot.state))
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    pub async fn setup_retention_policy(&self, days: u32) -> AppResult<()> {
+    pub async fn save_return_state(&self, index: &str, state: &ReturnState) -> AppResult<()> {
         if !self.enabled {
             return Ok(());
         }
 
-        // Construct the SQL directly with the interval value
+        sqlx::query(
+            r#"
+            INSERT INTO index_returns (index_name, level, prices, anchored_at, updated_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (index_name)
+            DO UPDATE SET level = EXCLUDED.level, prices = EXCLUDED.prices,
+                          anchored_at = EXCLUDED.anchored_at, updated_at = EXCLUDED.updated_at
+            "#
+        )
+        .bind(index)
+        .bind(state.level)
+        .bind(Json(&state.prices))
+        .bind(state.anchored_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// State to continue the index calculation from after a restart: the stored smoothing states,
+    /// divisors and return levels, the last `history` values of the given feeds and of every given
+    /// index, or as many as its smoothing period if that is longer
+    pub async fn load_calculator_state(&self, feed_ids: &[String], indices: &[IndexDefinition], history: i64) -> AppResult<CalculatorState> {
+        let mut state = CalculatorState {
+            divisors: self.load_divisors().await?.into_iter().collect(),
+            ..Default::default()
+        };
+        if !self.enabled {
+            return Ok(state);
+        }
+
+        let rows = sqlx::query("SELECT index_name, smoothing, state FROM index_smoothing")
+            .fetch_all(&self.pool)
+            .await?;
+        for row in rows {
+            let index: String = row.try_get("index_name")?;
+            let Json(smoothing) = row.try_get("smoothing")?;
+            let Json(smoothing_state) = row.try_get("state")?;
+            let values = self.get_recent_index_values(&index, history).await?;
+            state.smoothing.insert(index, SmoothingSnapshot {
+                smoothing,
+                state: smoothing_state,
+                history: values.into_iter().map(|(_, value)| value).collect(),
+            });
+        }
+
+        let rows = sqlx::query("SELECT index_name, level, prices, anchored_at FROM index_returns")
+            .fetch_all(&self.pool)
+            .await?;
+        for row in rows {
+            let Json(prices) = row.try_get("prices")?;
+            state.returns.insert(row.try_get("index_name")?, ReturnState {
+                level: row.try_get("level")?,
+                prices,
+                anchored_at: row.try_get("anchored_at")?,
+            });
+        }
+
+        // Enough values to fill the smoothing window of indices whose state cannot be restored
+        for index in indices {
+            let limit = history.max(index.smoothing_period as i64);
+            let rows = sqlx::query(
+                "SELECT value, raw_value FROM index_values WHERE index_name = $1 ORDER BY timestamp DESC LIMIT $2"
+            )
+            .bind(&index.name)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+            let values = rows.into_iter()
+                .map(|row| Ok(StoredValue { value: row.try_get("value")?, raw_value: row.try_get("raw_value")? }))
+                .collect::<AppResult<Vec<_>>>()?;
+            if !values.is_empty() {
+                state.index_history.insert(index.name.clone(), values);
+            }
+        }
+
+        for feed_id in feed_ids {
+            let prices = self.get_recent_prices(feed_id, history).await?;
+            if !prices.is_empty() {
+                state.feed_history.insert(feed_id.clone(), prices.into_iter().map(|(_, price)| price).collect());
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Listen for latest-value changes on a dedicated connection
+    pub async fn listen_latest_prices(&self) -> AppResult<PgListener> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(LATEST_PRICE_CHANNEL).await?;
+        Ok(listener)
+    }
+
+    /// Candles of an index (by name) or feed (by id) with buckets starting within `[from, to]`,
+    /// oldest first, as materialized by the continuous aggregates
+    pub async fn get_candles(
+        &self,
+        series: CandleSeries,
+        name: &str,
+        interval: CandleInterval,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> AppResult<Vec<Candle>> {
+        if !self.enabled {
+            return Ok(Vec::new());
+        }
+
         let sql = format!(
-            "SELECT add_retention_policy('raw_price_data', INTERVAL '{} days', if_not_exists => TRUE);",
-            days
+            "SELECT bucket, open, high, low, close, samples FROM {} WHERE {} = $1 AND bucket >= $2 AND bucket <= $3 ORDER BY bucket",
+            candles::candle_view(series, interval), series.key_column()
         );
-
-        // Execute without parameter binding
-        sqlx::query(&sql)
-            .execute(&self.pool)
+        let rows = sqlx::query(&sql)
+            .bind(name)
+            .bind(from)
+            .bind(to)
+            .fetch_all(&self.pool)
             .await?;
 
+        rows.into_iter()
+            .map(|row| Ok(Candle {
+                bucket: row.try_get("bucket")?,
+                open: row.try_get("open")?,
+                high: row.try_get("high")?,
+                low: row.try_get("low")?,
+                close: row.try_get("close")?,
+                samples: row.try_get("samples")?,
+            }))
+            .collect()
+    }
+
+    /// Drop raw prices older than `days`, and compress the chunks of raw prices and index values
+    /// once they are older than `compress_after_days`, if set
+    pub async fn setup_retention_policy(&self, days: u32, compress_after_days: Option<u32>) -> AppResult<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        if let Some(compress_after_days) = compress_after_days {
+            // Changing the compression settings of a hypertable with compressed chunks fails, so
+            // compression is only turned on where it is still off
+            let compressed: HashSet<String> = sqlx::query_scalar(
+                "SELECT hypertable_name::TEXT FROM timescaledb_information.hypertables WHERE compression_enabled"
+            )
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+            for sql in compression_statements(compress_after_days, &compressed) {
+                sqlx::query(&sql).execute(&self.pool).await?;
+            }
+            info!("[DATABASE] Compression policy set to chunks older than {} days", compress_after_days);
+        }
+
+        // Construct the SQL directly with the interval value; fetch telemetry is kept as long as
+        // the raw prices it explains
+        for table in ["raw_price_data", "feed_metrics"] {
+            let sql = format!(
+                "SELECT add_retention_policy('{}', INTERVAL '{} days', if_not_exists => TRUE);",
+                table, days
+            );
+
+            // Execute without parameter binding
+            sqlx::query(&sql)
+                .execute(&self.pool)
+                .await?;
+        }
+
         info!("[DATABASE] Retention policy set to {} days", days);
         Ok(())
     }
@@ -179,4 +680,154 @@ impl Database {
 
         Ok(results)
     }
+
+    /// Stored raw prices of the given feeds within `[from, to]`, oldest first
+    pub async fn get_price_range(
+        &self,
+        feed_ids: &[String],
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> AppResult<Vec<FeedData>> {
+        if !self.enabled {
+            return Err("Database persistence is disabled, no stored data to read".into());
+        }
+
+        let rows = sqlx::query(
+            "SELECT feed_id, timestamp, price FROM raw_price_data
+             WHERE feed_id = ANY($1) AND timestamp BETWEEN $2 AND $3 ORDER BY timestamp"
+        )
+        .bind(feed_ids)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(FeedData {
+                feed_id: row.try_get("feed_id")?,
+                timestamp: row.try_get("timestamp")?,
+                price: row.try_get("price")?,
+                backup_feed: None,
+                heartbeat: false,
+                denomination: None,
+            }))
+            .collect()
+    }
+
+    /// Check that stored raw data, along with the `archived` raw prices read from outside the
+    /// database, covers `[from, to]` for every feed before it is replayed.
+    ///
+    /// Any stretch longer than `max_gap` without a stored or archived price is reported as a gap
+    /// for that feed, as beyond retention where it is older than `retention_days`.
+    pub async fn check_coverage(
+        &self,
+        feed_ids: &[String],
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        max_gap: Duration,
+        retention_days: u32,
+        archived: &[FeedData],
+    ) -> AppResult<CoverageReport> {
+        if !self.enabled {
+            return Err("Database persistence is disabled, no stored data to check".into());
+        }
+
+        let retention_horizon = Utc::now() - Duration::days(retention_days as i64);
+        let mut gaps = Vec::new();
+
+        for feed_id in feed_ids {
+            let mut timestamps: Vec<DateTime<Utc>> = sqlx::query_scalar(
+                "SELECT timestamp FROM raw_price_data WHERE feed_id = $1 AND timestamp BETWEEN $2 AND $3 ORDER BY timestamp"
+            )
+            .bind(feed_id)
+            .bind(from)
+            .bind(to)
+            .fetch_all(&self.pool)
+            .await?;
+            timestamps.extend(archived.iter().filter(|price| &price.feed_id == feed_id).map(|price| price.timestamp));
+            timestamps.sort();
+
+            gaps.extend(coverage::find_gaps(feed_id, &timestamps, from, to, max_gap, Some(retention_horizon)));
+        }
+
+        Ok(CoverageReport { from, to, gaps })
+    }
+}
+
+/// Statements turning on compression of the raw price and index value hypertables not in
+/// `compressed`, and adding the compression policy of all of them
+pub(super) fn compression_statements(compress_after_days: u32, compressed: &HashSet<String>) -> Vec<String> {
+    let mut statements = Vec::new();
+    // Compressed per series, so reading one feed or index decompresses only its segments
+    for (table, segment_by) in [("raw_price_data", "feed_id"), ("index_values", "index_name")] {
+        if !compressed.contains(table) {
+            statements.push(format!(
+                "ALTER TABLE {} SET (timescaledb.compress, timescaledb.compress_segmentby = '{}', timescaledb.compress_orderby = 'timestamp DESC');",
+                table, segment_by
+            ));
+        }
+        statements.push(format!(
+            "SELECT add_compression_policy('{}', INTERVAL '{} days', if_not_exists => TRUE);",
+            table, compress_after_days
+        ));
+    }
+    statements
+}
+
+fn stored_price(row: &PgRow) -> AppResult<FeedData> {
+    Ok(FeedData {
+        feed_id: row.try_get("feed_id")?,
+        timestamp: row.try_get("timestamp")?,
+        price: row.try_get("price")?,
+        backup_feed: None,
+        heartbeat: false,
+        denomination: None,
+    })
+}
+
+fn stored_index_value(row: &PgRow) -> AppResult<StoredIndexValue> {
+    Ok(StoredIndexValue {
+        name: row.try_get("index_name")?,
+        timestamp: row.try_get("timestamp")?,
+        value: row.try_get("value")?,
+        raw_value: row.try_get("raw_value")?,
+        epoch: row.try_get::<i64, _>("epoch")? as u64,
+        methodology_version: row.try_get::<i32, _>("methodology_version")? as u32,
+    })
+}
+
+#[async_trait]
+impl StorageSink for Database {
+    fn name(&self) -> &'static str {
+        "database"
+    }
+
+    async fn save_prices(&self, prices: &[FeedData]) -> AppResult<()> {
+        self.save_price_batch(prices).await
+    }
+
+    async fn save_index_values(&self, values: &[IndexResult]) -> AppResult<()> {
+        self.save_index_value_batch(values).await
+    }
+
+    async fn save_fetch_metrics(&self, fetches: &[FetchMetric]) -> AppResult<()> {
+        self.save_fetch_metric_batch(fetches).await
+    }
+}
+
+/// Records of a batch with one record per key (feed or index, and timestamp), the last one of
+/// the batch, since an insert cannot update the same row twice
+pub(super) fn last_per_key<'a, T, K: Eq + Hash>(records: &'a [T], key: impl Fn(&'a T) -> K) -> Vec<&'a T> {
+    let mut positions: HashMap<K, usize> = HashMap::new();
+    let mut unique: Vec<&T> = Vec::with_capacity(records.len());
+    for record in records {
+        match positions.entry(key(record)) {
+            Entry::Occupied(position) => unique[*position.get()] = record,
+            Entry::Vacant(position) => {
+                position.insert(unique.len());
+                unique.push(record);
+            }
+        }
+    }
+    unique
 }
@@ -0,0 +1,105 @@
+use std::io::Write;
+use std::str::FromStr;
+use serde::Serialize;
+
+use crate::error::AppResult;
+use crate::index::IndexResult;
+use crate::models::FeedData;
+use crate::serialization::WireFormat;
+use super::history::StoredIndexValue;
+
+/// Record written as one CSV row
+pub trait CsvRow {
+    /// Header line of the rows, without line break
+    const HEADER: &'static str;
+
+    /// Row of the record, without line break
+    fn csv_row(&self) -> String;
+}
+
+impl CsvRow for FeedData {
+    const HEADER: &'static str = "feed_id,timestamp,price";
+
+    fn csv_row(&self) -> String {
+        format!("{},{},{}", self.feed_id, self.timestamp.to_rfc3339(), self.price)
+    }
+}
+
+impl CsvRow for IndexResult {
+    const HEADER: &'static str = "name,timestamp,value,raw_value,epoch,methodology_version";
+
+    fn csv_row(&self) -> String {
+        format!("{},{},{},{},{},{}", self.name, self.timestamp.to_rfc3339(), self.value,
+                self.raw_value.map(|raw_value| raw_value.to_string()).unwrap_or_default(),
+                self.epoch, self.methodology_version)
+    }
+}
+
+impl CsvRow for StoredIndexValue {
+    const HEADER: &'static str = IndexResult::HEADER;
+
+    fn csv_row(&self) -> String {
+        format!("{},{},{},{},{},{}", self.name, self.timestamp.to_rfc3339(), self.value,
+                self.raw_value.map(|raw_value| raw_value.to_string()).unwrap_or_default(),
+                self.epoch, self.methodology_version)
+    }
+}
+
+/// Format of exported records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// CSV with a header line, like the file sink writes
+    #[default]
+    Csv,
+    /// One versioned JSON object per line (NDJSON)
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!("Unknown export format {}, expected csv or json", format)),
+        }
+    }
+}
+
+/// Writer of exported records, one per line
+pub struct ExportWriter<W: Write> {
+    out: W,
+    format: ExportFormat,
+    rows: u64,
+}
+
+impl<W: Write> ExportWriter<W> {
+    pub fn new(out: W, format: ExportFormat) -> Self {
+        Self { out, format, rows: 0 }
+    }
+
+    /// Write a record, preceded by the CSV header if it is the first
+    pub fn write<R: CsvRow + Serialize>(&mut self, record: &R) -> AppResult<()> {
+        match self.format {
+            ExportFormat::Csv => {
+                if self.rows == 0 {
+                    writeln!(self.out, "{}", R::HEADER)?;
+                }
+                writeln!(self.out, "{}", record.csv_row())?;
+            }
+            ExportFormat::Json => {
+                self.out.write_all(&WireFormat::Json.encode(record)?)?;
+                self.out.write_all(b"\n")?;
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Flush the output, returning the number of records written
+    pub fn finish(mut self) -> AppResult<u64> {
+        self.out.flush()?;
+        Ok(self.rows)
+    }
+}
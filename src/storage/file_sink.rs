@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::bundle::tables;
+use crate::config::FileFormat;
+use crate::error::AppResult;
+use crate::index::IndexResult;
+use crate::models::FeedData;
+use super::export::CsvRow;
+use super::sink::StorageSink;
+
+const RAW_PRICES_DIR: &str = "raw_prices";
+const INDEX_VALUES_DIR: &str = "index_values";
+
+/// Raw prices and index values written to files partitioned by day, for deployments without a
+/// database.
+///
+/// CSV files are appended to, one per day: `<directory>/raw_prices/2024-01-01.csv`. Parquet
+/// files cannot be appended to, so every flush writes a new file into a directory per day:
+/// `<directory>/raw_prices/2024-01-01/120000.000.parquet`. Days follow the records' timestamps.
+pub struct FileSink {
+    directory: PathBuf,
+    format: FileFormat,
+}
+
+impl FileSink {
+    pub fn new(directory: impl Into<PathBuf>, format: FileFormat) -> Self {
+        Self { directory: directory.into(), format }
+    }
+
+    /// Append raw prices to the files of their days
+    pub fn write_raw_prices(&self, prices: &[FeedData], written_at: DateTime<Utc>) -> AppResult<()> {
+        for (day, prices) in by_day(prices, |price| price.timestamp) {
+            match self.format {
+                FileFormat::Csv => {
+                    let rows: Vec<String> = prices.iter().map(|price| price.csv_row()).collect();
+                    self.append_csv(RAW_PRICES_DIR, day, FeedData::HEADER, &rows)?;
+                }
+                FileFormat::Parquet => {
+                    let prices: Vec<FeedData> = prices.into_iter().cloned().collect();
+                    self.write_parquet(RAW_PRICES_DIR, day, written_at, &tables::write_raw_prices(&prices)?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Append index values to the files of their days
+    pub fn write_index_values(&self, values: &[IndexResult], written_at: DateTime<Utc>) -> AppResult<()> {
+        for (day, values) in by_day(values, |value| value.timestamp) {
+            match self.format {
+                FileFormat::Csv => {
+                    let rows: Vec<String> = values.iter().map(|value| value.csv_row()).collect();
+                    self.append_csv(INDEX_VALUES_DIR, day, IndexResult::HEADER, &rows)?;
+                }
+                FileFormat::Parquet => {
+                    let values: Vec<IndexResult> = values.into_iter().cloned().collect();
+                    self.write_parquet(INDEX_VALUES_DIR, day, written_at, &tables::write_index_values(&values)?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Raw prices of the given feeds within `[from, to]` read back from the files, oldest first.
+    ///
+    /// Files are never deleted, so they serve as the archive of raw prices the database no
+    /// longer holds. Both formats are read, in case the format was changed at some point.
+    pub fn read_raw_prices(&self, feed_ids: &[String], from: DateTime<Utc>, to: DateTime<Utc>) -> AppResult<Vec<FeedData>> {
+        let mut prices = Vec::new();
+        for day in from.date_naive().iter_days().take_while(|day| *day <= to.date_naive()) {
+            let csv = self.csv_path(RAW_PRICES_DIR, day);
+            if csv.exists() {
+                for line in fs::read_to_string(&csv)?.lines().skip(1).filter(|line| !line.is_empty()) {
+                    prices.push(parse_raw_price(line)
+                        .ok_or_else(|| format!("Invalid raw price in {}: {}", csv.display(), line))?);
+                }
+            }
+            let dir = self.parquet_dir(RAW_PRICES_DIR, day);
+            if dir.is_dir() {
+                for entry in fs::read_dir(&dir)? {
+                    let path = entry?.path();
+                    if path.extension().is_some_and(|extension| extension == "parquet") {
+                        prices.extend(tables::read_raw_prices(fs::read(&path)?)?);
+                    }
+                }
+            }
+        }
+        prices.retain(|price| price.timestamp >= from && price.timestamp <= to && feed_ids.contains(&price.feed_id));
+        prices.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.feed_id.cmp(&b.feed_id)));
+        Ok(prices)
+    }
+
+    /// File of a day's CSV rows of a kind of record
+    pub fn csv_path(&self, kind: &str, day: NaiveDate) -> PathBuf {
+        self.directory.join(kind).join(format!("{}.csv", day))
+    }
+
+    /// Directory of a day's Parquet files of a kind of record
+    pub fn parquet_dir(&self, kind: &str, day: NaiveDate) -> PathBuf {
+        self.directory.join(kind).join(day.to_string())
+    }
+
+    fn append_csv(&self, kind: &str, day: NaiveDate, header: &str, rows: &[String]) -> AppResult<()> {
+        let path = self.csv_path(kind, day);
+        create_parent(&path)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut content = String::new();
+        if file.metadata()?.len() == 0 {
+            content.push_str(header);
+            content.push('\n');
+        }
+        for row in rows {
+            content.push_str(row);
+            content.push('\n');
+        }
+        file.write_all(content.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    fn write_parquet(&self, kind: &str, day: NaiveDate, written_at: DateTime<Utc>, table: &[u8]) -> AppResult<()> {
+        let dir = self.parquet_dir(kind, day);
+        fs::create_dir_all(&dir)?;
+        // Written under a temporary name, so readers never see a partial file
+        let mut path = dir.join(format!("{}.parquet", written_at.format("%H%M%S%.3f")));
+        let mut sequence = 1;
+        while path.exists() {
+            path = dir.join(format!("{}-{}.parquet", written_at.format("%H%M%S%.3f"), sequence));
+            sequence += 1;
+        }
+        let partial = path.with_extension("partial");
+        fs::write(&partial, table)?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+}
+
+/// Raw price of a CSV row, `feed_id,timestamp,price`
+fn parse_raw_price(line: &str) -> Option<FeedData> {
+    let mut fields = line.split(',');
+    let feed_id = fields.next()?.to_string();
+    let timestamp = DateTime::parse_from_rfc3339(fields.next()?).ok()?.with_timezone(&Utc);
+    let price = fields.next()?.parse().ok()?;
+    Some(FeedData { feed_id, timestamp, price, backup_feed: None, heartbeat: false, denomination: None })
+}
+
+fn create_parent(path: &Path) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+/// Records grouped by the UTC day of their timestamp, in order
+fn by_day<T>(records: &[T], timestamp: impl Fn(&T) -> DateTime<Utc>) -> BTreeMap<NaiveDate, Vec<&T>> {
+    let mut days: BTreeMap<NaiveDate, Vec<&T>> = BTreeMap::new();
+    for record in records {
+        days.entry(timestamp(record).date_naive()).or_default().push(record);
+    }
+    days
+}
+
+#[async_trait]
+impl StorageSink for FileSink {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn save_prices(&self, prices: &[FeedData]) -> AppResult<()> {
+        self.write_raw_prices(prices, Utc::now())
+    }
+
+    async fn save_index_values(&self, values: &[IndexResult]) -> AppResult<()> {
+        self.write_index_values(values, Utc::now())
+    }
+}
@@ -0,0 +1,14 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Index value as stored in `index_values`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredIndexValue {
+    pub name: String,
+    pub timestamp: DateTime<Utc>,
+    pub value: f64,
+    /// Value before smoothing, absent on values stored before it was recorded
+    pub raw_value: Option<f64>,
+    pub epoch: u64,
+    pub methodology_version: u32,
+}
@@ -0,0 +1,217 @@
+use std::collections::HashSet;
+use async_trait::async_trait;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use tokio::sync::Mutex;
+
+use crate::config::{KafkaFormat, KafkaIndexConfig, KafkaIndexKey, KafkaStorageConfig};
+use crate::error::{AppError, AppResult};
+use crate::index::IndexResult;
+use crate::metrics::metrics;
+use crate::models::FeedData;
+use crate::serialization::avro;
+use crate::serialization::WireFormat;
+use super::sink::StorageSink;
+
+/// Record produced to a topic
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KafkaRecord {
+    pub key: Option<Vec<u8>>,
+    pub value: Vec<u8>,
+    pub timestamp_ms: i64,
+}
+
+/// librdkafka settings of a producer: the configured ones, the `properties` passed through
+/// (TLS, SASL, compression, ...) and the SASL password from its environment variable.
+///
+/// Keyed records are partitioned with murmur2 like the Java client, so records of a key land on
+/// the same partition as those of other producers; records without a key are spread randomly.
+/// With `acks = -1` the producer is idempotent, so records retried by librdkafka after a lost
+/// acknowledgement are not written twice.
+pub fn client_config(config: &KafkaStorageConfig) -> AppResult<ClientConfig> {
+    let mut client = ClientConfig::new();
+    for (key, value) in &config.properties {
+        client.set(key, value);
+    }
+    client
+        .set("bootstrap.servers", config.brokers.join(","))
+        .set("client.id", &config.client_id)
+        .set("acks", config.acks.to_string())
+        .set("enable.idempotence", (config.acks == -1).to_string())
+        .set("partitioner", "murmur2_random")
+        .set("message.timeout.ms", config.timeout_ms.to_string())
+        .set("request.timeout.ms", config.timeout_ms.to_string());
+    if let Some(var) = &config.sasl_password_env {
+        let password = std::env::var(var)
+            .map_err(|_| AppError::Config(format!("Kafka SASL password variable {} is not set", var)))?;
+        client.set("sasl.password", password);
+    }
+    Ok(client)
+}
+
+/// Producer of batches of records to a topic, waiting for the acknowledgement of every record.
+///
+/// librdkafka retries failed requests itself until `timeout_ms` has passed. Records of a failed
+/// batch that were acknowledged nonetheless are remembered and skipped when the batch is
+/// retried, so a partly failed batch is not produced twice.
+pub struct KafkaProducer {
+    producer: FutureProducer,
+    topic: String,
+    delivered: Mutex<HashSet<KafkaRecord>>,
+}
+
+impl KafkaProducer {
+    pub fn new(config: &KafkaStorageConfig) -> AppResult<Self> {
+        let producer = client_config(config)?.create()
+            .map_err(|e| AppError::Config(format!("Cannot create Kafka producer for {}: {}", config.topic, e)))?;
+        Ok(Self { producer, topic: config.topic.clone(), delivered: Mutex::new(HashSet::new()) })
+    }
+
+    /// Produce records to the topic, waiting for their acknowledgement
+    pub async fn send(&self, records: &[KafkaRecord]) -> AppResult<()> {
+        let mut delivered = self.delivered.lock().await;
+        let pending: Vec<&KafkaRecord> = records.iter().filter(|record| !delivered.contains(*record)).collect();
+
+        let mut deliveries = Vec::with_capacity(pending.len());
+        let mut failure = None;
+        for record in &pending {
+            let mut future_record = FutureRecord::<[u8], [u8]>::to(&self.topic)
+                .payload(&record.value)
+                .timestamp(record.timestamp_ms);
+            if let Some(key) = &record.key {
+                future_record = future_record.key(key.as_slice());
+            }
+            match self.producer.send_result(future_record) {
+                Ok(delivery) => deliveries.push((*record, delivery)),
+                Err((e, _)) => {
+                    // The rest is produced with the retry, after the records already queued
+                    failure = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+
+        for (record, delivery) in deliveries {
+            match delivery.await {
+                Ok(Ok(_)) => {
+                    delivered.insert(record.clone());
+                }
+                Ok(Err((e, _))) => {
+                    failure.get_or_insert(e.to_string());
+                }
+                Err(_) => {
+                    failure.get_or_insert_with(|| "producer shut down".to_string());
+                }
+            }
+        }
+
+        match failure {
+            None => {
+                delivered.clear();
+                Ok(())
+            }
+            Some(e) => Err(AppError::Other(format!("Failed to produce to Kafka topic {}: {}", self.topic, e))),
+        }
+    }
+}
+
+/// Sink publishing every raw price to a Kafka topic, keyed by feed id so the prices of a feed
+/// stay in order within their partition
+pub struct KafkaSink {
+    config: KafkaStorageConfig,
+    producer: KafkaProducer,
+}
+
+impl KafkaSink {
+    pub fn new(config: KafkaStorageConfig) -> AppResult<Self> {
+        let producer = KafkaProducer::new(&config)?;
+        Ok(Self { config, producer })
+    }
+
+    /// Message value of a raw price in the configured format
+    pub fn encode_price(&self, price: &FeedData) -> AppResult<Vec<u8>> {
+        match self.config.format {
+            KafkaFormat::Json => WireFormat::Json.encode(price),
+            KafkaFormat::Avro => Ok(avro::confluent_framed(self.config.schema_id.unwrap_or_default(), &avro::encode_feed_data(price))),
+        }
+    }
+}
+
+#[async_trait]
+impl StorageSink for KafkaSink {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    async fn save_prices(&self, prices: &[FeedData]) -> AppResult<()> {
+        let records = prices.iter()
+            .map(|price| Ok(KafkaRecord {
+                key: Some(price.feed_id.as_bytes().to_vec()),
+                value: self.encode_price(price)?,
+                timestamp_ms: price.timestamp.timestamp_millis(),
+            }))
+            .collect::<AppResult<Vec<_>>>()?;
+        self.producer.send(&records).await?;
+        metrics().increment_by("kafka.prices_produced", records.len() as u64);
+        Ok(())
+    }
+
+    async fn save_index_values(&self, _values: &[IndexResult]) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+/// Sink publishing every index value to a Kafka topic of its own, separate from the raw prices,
+/// for downstream consumers of index levels
+pub struct KafkaIndexSink {
+    config: KafkaIndexConfig,
+    producer: KafkaProducer,
+}
+
+impl KafkaIndexSink {
+    pub fn new(config: KafkaIndexConfig) -> AppResult<Self> {
+        let producer = KafkaProducer::new(&config.producer)?;
+        Ok(Self { config, producer })
+    }
+
+    /// Record key of an index value, `None` to spread the values over the partitions
+    pub fn key(&self, value: &IndexResult) -> Option<Vec<u8>> {
+        match self.config.key {
+            KafkaIndexKey::Index => Some(value.name.as_bytes().to_vec()),
+            KafkaIndexKey::QuoteCurrency => Some(value.denomination.quote_currency.as_bytes().to_vec()),
+            KafkaIndexKey::None => None,
+        }
+    }
+
+    /// Message value of an index value in the configured format
+    pub fn encode_index_value(&self, value: &IndexResult) -> AppResult<Vec<u8>> {
+        match self.config.producer.format {
+            KafkaFormat::Json => WireFormat::Json.encode(value),
+            KafkaFormat::Avro => Ok(avro::confluent_framed(self.config.producer.schema_id.unwrap_or_default(), &avro::encode_index_result(value))),
+        }
+    }
+}
+
+#[async_trait]
+impl StorageSink for KafkaIndexSink {
+    fn name(&self) -> &'static str {
+        "kafka_indices"
+    }
+
+    async fn save_prices(&self, _prices: &[FeedData]) -> AppResult<()> {
+        Ok(())
+    }
+
+    async fn save_index_values(&self, values: &[IndexResult]) -> AppResult<()> {
+        let records = values.iter()
+            .map(|value| Ok(KafkaRecord {
+                key: self.key(value),
+                value: self.encode_index_value(value)?,
+                timestamp_ms: value.timestamp.timestamp_millis(),
+            }))
+            .collect::<AppResult<Vec<_>>>()?;
+        self.producer.send(&records).await?;
+        metrics().increment_by("kafka.index_values_produced", records.len() as u64);
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+use tokio::time::{self, Instant};
+use tracing::{debug, info, warn};
+
+use crate::config::LatestCacheConfig;
+use crate::error::AppResult;
+use crate::models::FeedData;
+use super::Database;
+
+/// Latest value per feed for read-only distribution servers.
+///
+/// The writer upserts `latest_price_data` and notifies on every change; the cache applies those
+/// notifications and reloads everything periodically, which also covers the time the LISTEN
+/// connection is down. Values are withheld once the cache has been out of sync for longer than
+/// `max_age_ms`, so readers never serve data older than that bound.
+#[derive(Debug)]
+pub struct LatestValueCache {
+    values: RwLock<HashMap<String, FeedData>>,
+    synced_at: RwLock<Option<Instant>>,
+    max_age: Duration,
+    poll_interval: Duration,
+}
+
+impl LatestValueCache {
+    pub fn new(config: &LatestCacheConfig) -> Self {
+        Self {
+            values: RwLock::new(HashMap::new()),
+            synced_at: RwLock::new(None),
+            max_age: Duration::from_millis(config.max_age_ms),
+            poll_interval: Duration::from_millis(config.poll_interval_ms.max(1)),
+        }
+    }
+
+    /// Latest value of a feed, or `None` if unknown or the cache is out of sync
+    pub fn get(&self, feed_id: &str) -> Option<FeedData> {
+        if !self.is_fresh() {
+            return None;
+        }
+        self.values.read().unwrap().get(feed_id).cloned()
+    }
+
+    /// Whether the cache has been in sync with the database within the configured bound
+    pub fn is_fresh(&self) -> bool {
+        self.synced_at.read().unwrap().is_some_and(|synced_at| synced_at.elapsed() <= self.max_age)
+    }
+
+    /// Apply values read from the database, keeping whichever of old and new is more recent
+    pub fn apply(&self, values: Vec<FeedData>) {
+        let mut cached = self.values.write().unwrap();
+        for value in values {
+            match cached.get(&value.feed_id) {
+                Some(existing) if existing.timestamp > value.timestamp => {}
+                _ => {
+                    cached.insert(value.feed_id.clone(), value);
+                }
+            }
+        }
+    }
+
+    pub(super) fn mark_synced(&self) {
+        *self.synced_at.write().unwrap() = Some(Instant::now());
+    }
+
+    async fn reload(&self, database: &Database, feed_id: Option<&str>) -> AppResult<()> {
+        let values = database.get_latest_prices(feed_id).await?;
+        self.apply(values);
+        Ok(())
+    }
+
+    /// Keep the cache in sync until shutdown
+    pub async fn run(&self, database: Database, mut shutdown: broadcast::Receiver<()>) {
+        let mut listener = None;
+        let mut poll = time::interval(self.poll_interval);
+        poll.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+        loop {
+            if listener.is_none() {
+                match database.listen_latest_prices().await {
+                    Ok(new_listener) => {
+                        info!("[CACHE] Listening for latest value notifications");
+                        listener = Some(new_listener);
+                    }
+                    Err(e) => warn!("[CACHE] LISTEN unavailable, falling back to polling: {}", e),
+                }
+            }
+
+            tokio::select! {
+                _ = poll.tick() => {
+                    match self.reload(&database, None).await {
+                        Ok(()) => self.mark_synced(),
+                        Err(e) => warn!("[CACHE] Failed to reload latest values: {}", e),
+                    }
+                }
+
+                notification = async { listener.as_mut().unwrap().recv().await }, if listener.is_some() => {
+                    match notification {
+                        Ok(notification) => {
+                            debug!("[CACHE] Latest value of {} changed", notification.payload());
+                            if let Err(e) = self.reload(&database, Some(notification.payload())).await {
+                                warn!("[CACHE] Failed to reload {}: {}", notification.payload(), e);
+                            }
+                        }
+                        Err(e) => {
+                            // Notifications may have been missed, the next poll catches up
+                            warn!("[CACHE] LISTEN connection lost: {}", e);
+                            listener = None;
+                        }
+                    }
+                }
+
+                _ = shutdown.recv() => {
+                    info!("[SHUTDOWN] Stopping latest value cache");
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -1,3 +1,32 @@
 mod database;
+pub mod candles;
+pub mod coverage;
+pub mod spool;
+pub mod wal;
+pub mod latest_cache;
+pub mod file_sink;
+pub mod history;
+pub mod export;
+pub mod write_buffer;
+pub mod redis_cache;
+pub mod kafka;
+pub mod mqtt;
+pub mod sink;
 
-pub use database::Database;
+#[cfg(test)]
+mod tests;
+
+pub use database::{Database, LATEST_PRICE_CHANNEL};
+pub use candles::{Candle, CandleInterval, CandleSeries};
+pub use coverage::{CoverageGap, CoverageReport, GapReason};
+pub use spool::{SpoolWriter, SpoolContents, read_spool};
+pub use wal::WriteAheadLog;
+pub use latest_cache::LatestValueCache;
+pub use file_sink::FileSink;
+pub use history::StoredIndexValue;
+pub use export::{CsvRow, ExportFormat, ExportWriter};
+pub use redis_cache::{RedisBroadcastSink, RedisConnection, RedisSink};
+pub use kafka::{KafkaIndexSink, KafkaProducer, KafkaSink};
+pub use mqtt::{MqttConnection, MqttSink};
+pub use sink::{configured_sinks, run_storage_sink, SinkSettings, StorageSink};
+pub use write_buffer::{BatchSink, PriceWriter, RecordSource, WriteBuffer};
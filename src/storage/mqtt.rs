@@ -0,0 +1,254 @@
+use std::collections::HashSet;
+use std::time::Duration;
+use async_trait::async_trait;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::info;
+use url::Url;
+
+use crate::config::MqttStorageConfig;
+use crate::error::{AppError, AppResult};
+use crate::index::IndexResult;
+use crate::metrics::metrics;
+use crate::models::FeedData;
+use crate::serialization::WireFormat;
+use super::sink::StorageSink;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const PUBREC: u8 = 0x50;
+/// PUBREL has the reserved flags `0010`
+const PUBREL: u8 = 0x62;
+const PUBCOMP: u8 = 0x70;
+
+/// Packets longer than this are treated as a broken connection
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+/// Append the remaining length of a packet, 7 bits per byte, least significant first
+pub fn encode_remaining_length(mut len: usize, buf: &mut Vec<u8>) {
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        if len == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    encode_remaining_length(body.len(), &mut packet);
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// Append a length-prefixed UTF-8 string or binary field
+fn push_field(buf: &mut Vec<u8>, field: &[u8]) {
+    buf.extend_from_slice(&(field.len() as u16).to_be_bytes());
+    buf.extend_from_slice(field);
+}
+
+/// CONNECT packet of an MQTT 3.1.1 client starting a clean session
+pub fn encode_connect(client_id: &str, username: Option<&str>, password: Option<&str>, keep_alive_secs: u16) -> Vec<u8> {
+    let mut flags = 0x02;
+    if username.is_some() {
+        flags |= 0x80;
+    }
+    if password.is_some() {
+        flags |= 0x40;
+    }
+    let mut body = Vec::new();
+    push_field(&mut body, b"MQTT");
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&keep_alive_secs.to_be_bytes());
+    push_field(&mut body, client_id.as_bytes());
+    for field in [username, password].into_iter().flatten() {
+        push_field(&mut body, field.as_bytes());
+    }
+    packet(CONNECT, &body)
+}
+
+/// PUBLISH packet, with a packet id at QoS 1 and 2
+pub fn encode_publish(topic: &str, payload: &[u8], qos: u8, retain: bool, packet_id: u16) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_field(&mut body, topic.as_bytes());
+    if qos > 0 {
+        body.extend_from_slice(&packet_id.to_be_bytes());
+    }
+    body.extend_from_slice(payload);
+    packet(PUBLISH | (qos << 1) | retain as u8, &body)
+}
+
+/// Topic of an index, `{name}` in the template replaced with its name
+pub fn index_topic(template: &str, name: &str) -> String {
+    template.replace("{name}", name)
+}
+
+/// Minimal MQTT 3.1.1 connection, speaking just enough of the protocol to publish
+pub struct MqttConnection {
+    stream: BufReader<TcpStream>,
+    next_packet_id: u16,
+}
+
+impl MqttConnection {
+    /// Connect to `mqtt://[user:password@]host[:port]` as `client_id`
+    pub async fn connect(url: &str, client_id: &str, keep_alive_secs: u16) -> AppResult<Self> {
+        let url = Url::parse(url).map_err(|e| AppError::Config(format!("Invalid MQTT URL {}: {}", url, e)))?;
+        let host = url.host_str().ok_or_else(|| AppError::Config(format!("MQTT URL {} has no host", url)))?;
+        let stream = TcpStream::connect((host, url.port().unwrap_or(1883))).await?;
+        stream.set_nodelay(true)?;
+        let mut connection = Self { stream: BufReader::new(stream), next_packet_id: 0 };
+
+        let username = Some(url.username()).filter(|username| !username.is_empty());
+        let connect = encode_connect(client_id, username, url.password(), keep_alive_secs);
+        connection.stream.get_mut().write_all(&connect).await?;
+        let (header, body) = connection.read_packet().await?;
+        match (header, body.get(1)) {
+            (CONNACK, Some(0)) => Ok(connection),
+            (CONNACK, Some(code)) => Err(AppError::Other(format!("MQTT broker refused the connection: {}", connack_reason(*code)))),
+            _ => Err(AppError::Other(format!("Unexpected MQTT packet {:#04x} instead of CONNACK", header))),
+        }
+    }
+
+    /// Publish messages in order, waiting until the broker has acknowledged all of them at QoS 1
+    /// and 2
+    pub async fn publish(&mut self, messages: &[(String, Vec<u8>)], qos: u8, retain: bool) -> AppResult<()> {
+        let mut unacknowledged = HashSet::new();
+        let mut packets = Vec::new();
+        for (topic, payload) in messages {
+            let packet_id = if qos > 0 { self.next_packet_id() } else { 0 };
+            if qos > 0 {
+                unacknowledged.insert(packet_id);
+            }
+            packets.extend(encode_publish(topic, payload, qos, retain, packet_id));
+        }
+        self.stream.get_mut().write_all(&packets).await?;
+
+        while !unacknowledged.is_empty() {
+            let (header, body) = self.read_packet().await?;
+            let Some(packet_id) = body.get(..2).map(|id| u16::from_be_bytes([id[0], id[1]])) else {
+                continue;
+            };
+            match header {
+                PUBACK | PUBCOMP => {
+                    unacknowledged.remove(&packet_id);
+                }
+                // Second step of QoS 2, completed by the broker's PUBCOMP
+                PUBREC => self.stream.get_mut().write_all(&packet(PUBREL, &packet_id.to_be_bytes())).await?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Next packet id, never 0
+    fn next_packet_id(&mut self) -> u16 {
+        self.next_packet_id = self.next_packet_id.checked_add(1).unwrap_or(1);
+        self.next_packet_id
+    }
+
+    async fn read_packet(&mut self) -> AppResult<(u8, Vec<u8>)> {
+        let header = self.stream.read_u8().await?;
+        let mut len = 0;
+        for shift in (0..28).step_by(7) {
+            let byte = self.stream.read_u8().await?;
+            len |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        if len > MAX_PACKET_SIZE {
+            return Err(AppError::Other(format!("MQTT packet of {} bytes", len)));
+        }
+        let mut body = vec![0; len];
+        self.stream.read_exact(&mut body).await?;
+        Ok((header, body))
+    }
+}
+
+fn connack_reason(code: u8) -> &'static str {
+    match code {
+        1 => "unacceptable protocol version",
+        2 => "client id rejected",
+        3 => "server unavailable",
+        4 => "bad user name or password",
+        5 => "not authorized",
+        _ => "unknown return code",
+    }
+}
+
+/// Sink publishing every index value to an MQTT topic of its own, for MQTT-native consumers
+/// such as signage and embedded displays. Values are versioned JSON like everywhere else. The
+/// connection is opened on the first write and reopened after it is lost or has been idle for
+/// longer than the keep-alive.
+pub struct MqttSink {
+    config: MqttStorageConfig,
+    connection: Mutex<Option<(MqttConnection, Instant)>>,
+}
+
+impl MqttSink {
+    pub fn new(config: MqttStorageConfig) -> Self {
+        Self { config, connection: Mutex::new(None) }
+    }
+
+    async fn write(&self, messages: &[(String, Vec<u8>)]) -> AppResult<()> {
+        let mut connection = self.connection.lock().await;
+        let keep_alive = Duration::from_secs(self.config.keep_alive_secs as u64);
+        // The broker drops connections idle for longer than the keep-alive
+        if connection.as_ref().is_some_and(|(_, used)| used.elapsed() >= keep_alive) {
+            *connection = None;
+        }
+
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let result = tokio::time::timeout(timeout, async {
+            if connection.is_none() {
+                let mqtt = MqttConnection::connect(&self.config.url, &self.config.client_id, self.config.keep_alive_secs).await?;
+                info!("[MQTT] Connected to broker {}", self.config.url);
+                *connection = Some((mqtt, Instant::now()));
+            }
+            let Some((mqtt, used)) = connection.as_mut() else {
+                return Err(AppError::Other("No MQTT connection".to_string()));
+            };
+            mqtt.publish(messages, self.config.qos, self.config.retain).await?;
+            *used = Instant::now();
+            Ok(())
+        }).await.unwrap_or_else(|_| Err(AppError::Other("Timed out waiting for the MQTT broker".to_string())));
+
+        match result {
+            Ok(()) => {
+                metrics().increment_by("mqtt.values_published", messages.len() as u64);
+                Ok(())
+            }
+            Err(e) => {
+                // Reconnected with the next attempt
+                *connection = None;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StorageSink for MqttSink {
+    fn name(&self) -> &'static str {
+        "mqtt"
+    }
+
+    async fn save_prices(&self, _prices: &[FeedData]) -> AppResult<()> {
+        Ok(())
+    }
+
+    async fn save_index_values(&self, values: &[IndexResult]) -> AppResult<()> {
+        let messages = values.iter()
+            .map(|value| Ok((index_topic(&self.config.topic, &value.name), WireFormat::Json.encode(value)?)))
+            .collect::<AppResult<Vec<_>>>()?;
+        self.write(&messages).await
+    }
+}
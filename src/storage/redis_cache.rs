@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tracing::error;
+use url::Url;
+
+use crate::config::{RedisBroadcastConfig, RedisStorageConfig};
+use crate::error::{AppError, AppResult};
+use crate::index::IndexResult;
+use crate::metrics::metrics;
+use crate::models::FeedData;
+use crate::serialization::WireFormat;
+use super::sink::StorageSink;
+
+/// Key (and channel) holding the latest value of a feed, e.g. `crypto-index:feed:coinbase_btc_usd`
+pub fn feed_key(prefix: &str, feed_id: &str) -> String {
+    format!("{}:feed:{}", prefix, feed_id)
+}
+
+/// Key (and channel) holding the latest value of an index, e.g. `crypto-index:index:BTC-USD-INDEX`
+pub fn index_key(prefix: &str, name: &str) -> String {
+    format!("{}:index:{}", prefix, name)
+}
+
+/// Encode a command as a RESP array of bulk strings
+pub fn encode_command(args: &[Vec<u8>]) -> Vec<u8> {
+    let mut command = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        command.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        command.extend_from_slice(arg);
+        command.extend_from_slice(b"\r\n");
+    }
+    command
+}
+
+/// Minimal Redis connection, speaking just enough RESP to set keys and publish
+pub struct RedisConnection {
+    stream: BufReader<TcpStream>,
+}
+
+impl RedisConnection {
+    /// Connect to `redis://[user:password@]host[:port][/db]`, authenticating and selecting the
+    /// database when given
+    pub async fn connect(url: &str) -> AppResult<Self> {
+        let url = Url::parse(url).map_err(|e| AppError::Config(format!("Invalid Redis URL {}: {}", url, e)))?;
+        let host = url.host_str().ok_or_else(|| AppError::Config(format!("Redis URL {} has no host", url)))?;
+        let stream = TcpStream::connect((host, url.port().unwrap_or(6379))).await?;
+        stream.set_nodelay(true)?;
+        let mut connection = Self { stream: BufReader::new(stream) };
+
+        if let Some(password) = url.password() {
+            match url.username() {
+                "" => connection.execute(&[vec![b"AUTH".to_vec(), password.into()]]).await?,
+                user => connection.execute(&[vec![b"AUTH".to_vec(), user.into(), password.into()]]).await?,
+            }
+        }
+        let db = url.path().trim_start_matches('/');
+        if !db.is_empty() {
+            connection.execute(&[vec![b"SELECT".to_vec(), db.into()]]).await?;
+        }
+        Ok(connection)
+    }
+
+    /// Send commands in one pipeline and wait for their replies, failing on the first error
+    /// reply
+    pub async fn execute(&mut self, commands: &[Vec<Vec<u8>>]) -> AppResult<()> {
+        let pipeline: Vec<u8> = commands.iter().flat_map(|args| encode_command(args)).collect();
+        self.stream.get_mut().write_all(&pipeline).await?;
+        let mut failure = None;
+        for _ in commands {
+            if let Err(e) = self.read_reply().await {
+                // Replies of the remaining commands are still read, keeping the connection in sync
+                if matches!(e, AppError::Io(_)) {
+                    return Err(e);
+                }
+                failure.get_or_insert(e);
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    async fn read_reply(&mut self) -> AppResult<()> {
+        let line = self.read_line().await?;
+        let (kind, rest) = line.split_at(1);
+        match kind {
+            "+" | ":" => Ok(()),
+            "-" => Err(AppError::Database(format!("Redis error: {}", rest))),
+            "$" => {
+                let len: i64 = parse_len(rest)?;
+                if len >= 0 {
+                    // Content and trailing CRLF
+                    let mut content = vec![0; len as usize + 2];
+                    self.stream.read_exact(&mut content).await?;
+                }
+                Ok(())
+            }
+            "*" => {
+                let len: i64 = parse_len(rest)?;
+                for _ in 0..len.max(0) {
+                    Box::pin(self.read_reply()).await?;
+                }
+                Ok(())
+            }
+            _ => Err(AppError::Database(format!("Unexpected Redis reply: {}", line))),
+        }
+    }
+
+    async fn read_line(&mut self) -> AppResult<String> {
+        let mut line = String::new();
+        if self.stream.read_line(&mut line).await? == 0 {
+            return Err(AppError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        let line = line.trim_end_matches("\r\n").to_string();
+        if line.is_empty() {
+            return Err(AppError::Database("Empty Redis reply".to_string()));
+        }
+        Ok(line)
+    }
+}
+
+fn parse_len(len: &str) -> AppResult<i64> {
+    len.parse().map_err(|_| AppError::Database(format!("Invalid Redis reply length: {}", len)))
+}
+
+/// Values of a batch to write, by key; a newer value of a key replaces the earlier one
+#[derive(Default)]
+pub struct PendingValues {
+    values: BTreeMap<String, Vec<u8>>,
+}
+
+impl PendingValues {
+    pub fn insert(&mut self, key: String, value: Vec<u8>) {
+        self.values.insert(key, value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// `SET` (with an expiry when `ttl_secs` is given) of every pending value, and a `PUBLISH`
+    /// to the channel of the same name when `publish` is set
+    pub fn commands(&self, ttl_secs: Option<u64>, publish: bool) -> Vec<Vec<Vec<u8>>> {
+        let mut commands = Vec::new();
+        for (key, value) in &self.values {
+            let mut set = vec![b"SET".to_vec(), key.as_bytes().to_vec(), value.clone()];
+            if let Some(ttl_secs) = ttl_secs {
+                set.extend([b"EX".to_vec(), ttl_secs.to_string().into_bytes()]);
+            }
+            commands.push(set);
+            if publish {
+                commands.push(vec![b"PUBLISH".to_vec(), key.as_bytes().to_vec(), value.clone()]);
+            }
+        }
+        commands
+    }
+}
+
+/// Send commands on a shared connection, opening it first when there is none and dropping it
+/// when it is lost, so it is reopened with the next attempt
+async fn execute_shared(connection: &Mutex<Option<RedisConnection>>, url: &str, commands: &[Vec<Vec<u8>>]) -> AppResult<()> {
+    let mut connection = connection.lock().await;
+    let redis = match connection.as_mut() {
+        Some(redis) => redis,
+        None => connection.insert(RedisConnection::connect(url).await?),
+    };
+    let result = redis.execute(commands).await;
+    if matches!(result, Err(AppError::Io(_))) {
+        *connection = None;
+    }
+    result
+}
+
+/// Sink keeping the latest value of every feed and index in Redis keys, so other services can
+/// read the current index value without subscribing to the WebSocket. Values are versioned JSON
+/// like everywhere else. The connection is opened on the first write and reopened after it is
+/// lost.
+pub struct RedisSink {
+    config: RedisStorageConfig,
+    connection: Mutex<Option<RedisConnection>>,
+}
+
+impl RedisSink {
+    pub fn new(config: RedisStorageConfig) -> Self {
+        Self { config, connection: Mutex::new(None) }
+    }
+
+    async fn write(&self, pending: PendingValues) -> AppResult<()> {
+        match execute_shared(&self.connection, &self.config.url, &pending.commands(self.config.ttl_secs, self.config.publish)).await {
+            Ok(()) => {
+                metrics().increment_by("redis.values_written", pending.len() as u64);
+                Ok(())
+            }
+            Err(AppError::Io(e)) => Err(AppError::Io(e)),
+            Err(e) => {
+                // Rejected by Redis, retrying would not help
+                error!("[REDIS] Redis rejected {} values: {}", pending.len(), e);
+                metrics().increment("redis.rejected_writes");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StorageSink for RedisSink {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn save_prices(&self, prices: &[FeedData]) -> AppResult<()> {
+        let mut pending = PendingValues::default();
+        for price in prices {
+            pending.insert(feed_key(&self.config.key_prefix, &price.feed_id), WireFormat::Json.encode(price)?);
+        }
+        self.write(pending).await
+    }
+
+    async fn save_index_values(&self, values: &[IndexResult]) -> AppResult<()> {
+        let mut pending = PendingValues::default();
+        for value in values {
+            pending.insert(index_key(&self.config.key_prefix, &value.name), WireFormat::Json.encode(value)?);
+        }
+        self.write(pending).await
+    }
+}
+
+/// `PUBLISH` of an index update to its channel, and an `XADD` to the stream when one is
+/// configured, with the index name and the update as fields
+pub fn broadcast_commands(config: &RedisBroadcastConfig, name: &str, value: &[u8]) -> Vec<Vec<Vec<u8>>> {
+    let mut commands = vec![vec![b"PUBLISH".to_vec(), config.channel.replace("{name}", name).into_bytes(), value.to_vec()]];
+    if let Some(stream) = &config.stream {
+        let mut xadd = vec![b"XADD".to_vec(), stream.as_bytes().to_vec()];
+        if let Some(max_len) = config.stream_max_len {
+            xadd.extend([b"MAXLEN".to_vec(), b"~".to_vec(), max_len.to_string().into_bytes()]);
+        }
+        xadd.extend([b"*".to_vec(), b"index".to_vec(), name.as_bytes().to_vec(), b"value".to_vec(), value.to_vec()]);
+        commands.push(xadd);
+    }
+    commands
+}
+
+/// Sink broadcasting every index update on a Redis channel, and optionally a stream, so
+/// Redis-centric services can consume updates without another protocol. Unlike [`RedisSink`],
+/// every update is published, in order, rather than only the latest per index.
+pub struct RedisBroadcastSink {
+    config: RedisBroadcastConfig,
+    connection: Mutex<Option<RedisConnection>>,
+}
+
+impl RedisBroadcastSink {
+    pub fn new(config: RedisBroadcastConfig) -> Self {
+        Self { config, connection: Mutex::new(None) }
+    }
+}
+
+#[async_trait]
+impl StorageSink for RedisBroadcastSink {
+    fn name(&self) -> &'static str {
+        "redis_broadcast"
+    }
+
+    async fn save_prices(&self, _prices: &[FeedData]) -> AppResult<()> {
+        Ok(())
+    }
+
+    async fn save_index_values(&self, values: &[IndexResult]) -> AppResult<()> {
+        let mut commands = Vec::new();
+        for value in values {
+            commands.extend(broadcast_commands(&self.config, &value.name, &WireFormat::Json.encode(value)?));
+        }
+        match execute_shared(&self.connection, &self.config.url, &commands).await {
+            Ok(()) => {
+                metrics().increment_by("redis.updates_broadcast", values.len() as u64);
+                Ok(())
+            }
+            Err(AppError::Io(e)) => Err(AppError::Io(e)),
+            Err(e) => {
+                error!("[REDIS] Redis rejected the broadcast of {} updates: {}", values.len(), e);
+                metrics().increment("redis.rejected_writes");
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use crate::config::{Config, DatabaseConfig, FileStorageConfig, KafkaStorageConfig, MqttStorageConfig, RedisBroadcastConfig, RedisStorageConfig, WalConfig};
+use crate::error::AppResult;
+use crate::exchange::FetchMetric;
+use crate::index::IndexResult;
+use crate::models::FeedData;
+use crate::notification::NotificationQueue;
+use super::file_sink::FileSink;
+use super::kafka::{KafkaIndexSink, KafkaSink};
+use super::mqtt::MqttSink;
+use super::redis_cache::{RedisBroadcastSink, RedisSink};
+use super::wal::WriteAheadLog;
+use super::write_buffer::{RecordSource, WriteBuffer};
+
+/// Records buffered by sinks without a configured capacity
+const DEFAULT_BUFFER_CAPACITY: usize = 100_000;
+
+/// Outages of sinks without a configured threshold are notified after this long
+const DEFAULT_OUTAGE_NOTIFY_AFTER: Duration = Duration::from_secs(60);
+
+/// Destination raw prices and published index values are stored in: the database, files,
+/// Redis, Kafka, ...
+///
+/// Every configured sink runs in its own [`run_storage_sink`] task with its own buffers, so a
+/// failing sink neither blocks nor loses the records of the others.
+#[async_trait]
+pub trait StorageSink: Send + Sync {
+    /// Name of the sink in logs and metrics, e.g. `database`
+    fn name(&self) -> &'static str;
+
+    /// Store a batch of raw prices, oldest first
+    async fn save_prices(&self, prices: &[FeedData]) -> AppResult<()>;
+
+    /// Store a batch of published index values, oldest first
+    async fn save_index_values(&self, values: &[IndexResult]) -> AppResult<()>;
+
+    /// Store a batch of fetch telemetry, oldest first; dropped by sinks not storing it
+    async fn save_fetch_metrics(&self, _fetches: &[FetchMetric]) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+/// How a sink's records are buffered and flushed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkSettings {
+    /// Records are written in batches of up to this many
+    pub batch_size: usize,
+    /// Records kept buffered per kind while the sink fails; older ones are dropped
+    pub capacity: usize,
+    /// Interval batches are written at when they do not fill up before
+    pub flush_interval: Duration,
+    /// Failing writes lasting longer than this are notified, as is their recovery
+    pub notify_after: Duration,
+    /// Write-ahead logs records are moved to while the sink is down
+    pub wal: Option<WalConfig>,
+}
+
+impl SinkSettings {
+    pub fn database(config: &DatabaseConfig) -> Self {
+        Self {
+            batch_size: config.write_batch_size,
+            capacity: config.write_buffer_capacity,
+            flush_interval: Duration::from_millis(config.write_flush_interval_ms),
+            notify_after: Duration::from_secs(config.outage_notify_after_secs),
+            wal: None,
+        }
+    }
+
+    /// Everything buffered is written with every flush
+    pub fn file(config: &FileStorageConfig) -> Self {
+        Self {
+            batch_size: DEFAULT_BUFFER_CAPACITY,
+            capacity: DEFAULT_BUFFER_CAPACITY,
+            flush_interval: Duration::from_secs(config.flush_interval_secs),
+            notify_after: DEFAULT_OUTAGE_NOTIFY_AFTER,
+            wal: None,
+        }
+    }
+
+    pub fn redis(config: &RedisStorageConfig) -> Self {
+        Self {
+            batch_size: config.batch_size,
+            capacity: DEFAULT_BUFFER_CAPACITY,
+            flush_interval: Duration::from_millis(config.flush_interval_ms),
+            notify_after: DEFAULT_OUTAGE_NOTIFY_AFTER,
+            wal: None,
+        }
+    }
+
+    pub fn with_wal(mut self, wal: Option<&WalConfig>) -> Self {
+        self.wal = wal.cloned();
+        self
+    }
+
+    pub fn kafka(config: &KafkaStorageConfig) -> Self {
+        Self {
+            batch_size: config.batch_size,
+            capacity: DEFAULT_BUFFER_CAPACITY,
+            flush_interval: Duration::from_millis(config.flush_interval_ms),
+            notify_after: DEFAULT_OUTAGE_NOTIFY_AFTER,
+            wal: None,
+        }
+    }
+
+    pub fn redis_broadcast(config: &RedisBroadcastConfig) -> Self {
+        Self {
+            batch_size: config.batch_size,
+            capacity: DEFAULT_BUFFER_CAPACITY,
+            flush_interval: Duration::from_millis(config.flush_interval_ms),
+            notify_after: DEFAULT_OUTAGE_NOTIFY_AFTER,
+            wal: None,
+        }
+    }
+
+    pub fn mqtt(config: &MqttStorageConfig) -> Self {
+        Self {
+            batch_size: config.batch_size,
+            capacity: DEFAULT_BUFFER_CAPACITY,
+            flush_interval: Duration::from_millis(config.flush_interval_ms),
+            notify_after: DEFAULT_OUTAGE_NOTIFY_AFTER,
+            wal: None,
+        }
+    }
+}
+
+/// Sinks configured besides the database, which is set up separately as the collector also reads
+/// from it. Fails when a sink cannot be set up, e.g. a Kafka producer with invalid properties.
+pub fn configured_sinks(config: &Config) -> AppResult<Vec<(Arc<dyn StorageSink>, SinkSettings)>> {
+    let wal = config.storage.wal.as_ref();
+    let mut sinks: Vec<(Arc<dyn StorageSink>, SinkSettings)> = Vec::new();
+    if let Some(file) = &config.storage.file {
+        sinks.push((Arc::new(FileSink::new(&file.directory, file.format)), SinkSettings::file(file).with_wal(wal)));
+    }
+    if let Some(redis) = &config.storage.redis {
+        sinks.push((Arc::new(RedisSink::new(redis.clone())), SinkSettings::redis(redis).with_wal(wal)));
+    }
+    if let Some(kafka) = &config.storage.kafka {
+        sinks.push((Arc::new(KafkaSink::new(kafka.clone())?), SinkSettings::kafka(kafka).with_wal(wal)));
+    }
+    if let Some(kafka) = &config.storage.kafka_indices {
+        sinks.push((Arc::new(KafkaIndexSink::new(kafka.clone())?), SinkSettings::kafka(&kafka.producer).with_wal(wal)));
+    }
+    if let Some(mqtt) = &config.storage.mqtt {
+        sinks.push((Arc::new(MqttSink::new(mqtt.clone())), SinkSettings::mqtt(mqtt).with_wal(wal)));
+    }
+    if let Some(redis) = &config.redis {
+        sinks.push((Arc::new(RedisBroadcastSink::new(redis.clone())), SinkSettings::redis_broadcast(redis).with_wal(wal)));
+    }
+    Ok(sinks)
+}
+
+/// Write-ahead log of a sink's records of one kind, e.g. `database_price.wal`; the sink runs
+/// without one if it cannot be opened
+fn open_wal(config: &WalConfig, sink: &str, kind: &str) -> Option<WriteAheadLog> {
+    let path = Path::new(&config.directory).join(format!("{}_{}.wal", sink, kind));
+    match WriteAheadLog::open(&path, config.max_mb * 1024 * 1024) {
+        Ok(wal) => Some(wal),
+        Err(e) => {
+            error!("[{}] Failed to open the write-ahead log {}, buffering in memory only: {}", sink.to_uppercase(), path.display(), e);
+            None
+        }
+    }
+}
+
+/// Store raw prices, index values and, if given a source of them, fetch telemetry in a sink, in
+/// batches written once a batch is full or every flush interval, and once more on shutdown.
+/// Failed writes stay buffered, in the write-ahead logs of `settings` if any, and are retried
+/// with backoff, see [`WriteBuffer`].
+pub async fn run_storage_sink(
+    sink: Arc<dyn StorageSink>,
+    mut prices: impl RecordSource<FeedData>,
+    mut values: impl RecordSource<IndexResult>,
+    mut fetches: Option<impl RecordSource<FetchMetric>>,
+    settings: SinkSettings,
+    notifier: Option<NotificationQueue>,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let name = sink.name();
+    let mut price_buffer = WriteBuffer::new(name, "price", settings.batch_size, settings.capacity);
+    let mut value_buffer = WriteBuffer::new(name, "index_value", settings.batch_size, settings.capacity);
+    let mut fetch_buffer = WriteBuffer::new(name, "fetch_metric", settings.batch_size, settings.capacity);
+    if let Some(notifier) = notifier {
+        price_buffer = price_buffer.with_notifier(notifier.clone(), settings.notify_after);
+        value_buffer = value_buffer.with_notifier(notifier.clone(), settings.notify_after);
+        fetch_buffer = fetch_buffer.with_notifier(notifier, settings.notify_after);
+    }
+    if let Some(wal) = &settings.wal {
+        if let Some(wal) = open_wal(wal, name, "price") {
+            price_buffer = price_buffer.with_wal(wal);
+        }
+        if let Some(wal) = open_wal(wal, name, "index_value") {
+            value_buffer = value_buffer.with_wal(wal);
+        }
+        if let Some(wal) = open_wal(wal, name, "fetch_metric").filter(|_| fetches.is_some()) {
+            fetch_buffer = fetch_buffer.with_wal(wal);
+        }
+    }
+    let mut interval = tokio::time::interval(settings.flush_interval);
+
+    loop {
+        let stopping = tokio::select! {
+            price = prices.next() => match price {
+                Some(price) => {
+                    price_buffer.push(price);
+                    if !price_buffer.is_full() || price_buffer.is_backing_off(Instant::now()) {
+                        continue;
+                    }
+                    false
+                }
+                None => true,
+            },
+            value = values.next() => match value {
+                Some(value) => {
+                    value_buffer.push(value);
+                    if !value_buffer.is_full() || value_buffer.is_backing_off(Instant::now()) {
+                        continue;
+                    }
+                    false
+                }
+                None => true,
+            },
+            fetch = fetches.next() => match fetch {
+                Some(fetch) => {
+                    fetch_buffer.push(fetch);
+                    if !fetch_buffer.is_full() || fetch_buffer.is_backing_off(Instant::now()) {
+                        continue;
+                    }
+                    false
+                }
+                None => true,
+            },
+            _ = interval.tick() => false,
+            _ = shutdown.recv() => true,
+        };
+
+        if stopping {
+            while let Some(price) = prices.next_queued() {
+                price_buffer.push(price);
+            }
+            while let Some(value) = values.next_queued() {
+                value_buffer.push(value);
+            }
+            while let Some(fetch) = fetches.next_queued() {
+                fetch_buffer.push(fetch);
+            }
+            // One last attempt, even while backing off
+            price_buffer.retry_now();
+            value_buffer.retry_now();
+            fetch_buffer.retry_now();
+        }
+        price_buffer.flush(sink.as_ref()).await;
+        value_buffer.flush(sink.as_ref()).await;
+        fetch_buffer.flush(sink.as_ref()).await;
+
+        if stopping {
+            let persisted = price_buffer.persist() + value_buffer.persist() + fetch_buffer.persist();
+            if persisted > 0 {
+                info!("[SHUTDOWN] Moved {} unsaved records to the write-ahead log of the {}, replayed with the next start", persisted, name);
+            }
+            let unsaved = price_buffer.len() + value_buffer.len() + fetch_buffer.len();
+            if unsaved == 0 && persisted == 0 {
+                info!("[SHUTDOWN] Saved all buffered records to the {}", name);
+            } else if unsaved > 0 {
+                warn!("[SHUTDOWN] {} buffered records could not be saved to the {}", unsaved, name);
+            }
+            return;
+        }
+    }
+}
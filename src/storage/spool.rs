@@ -0,0 +1,138 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::warn;
+
+use crate::error::{AppError, AppResult};
+use crate::serialization::WireFormat;
+
+/// Size of the record header: payload length and CRC32, both little-endian u32
+const HEADER_LEN: usize = 8;
+
+/// Upper bound for a single record, so a corrupt length can't make the reader allocate gigabytes
+pub const MAX_RECORD_LEN: usize = 16 * 1024 * 1024;
+
+/// Frame a payload as `[len: u32 LE][crc32(payload): u32 LE][payload]`
+pub fn encode_record(payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(HEADER_LEN + payload.len());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    record.extend_from_slice(payload);
+    record
+}
+
+/// Split spool bytes into record payloads.
+///
+/// Reading stops at the first torn or corrupt record, since nothing after it can be trusted.
+/// Returns the payloads and the length of the intact prefix.
+pub fn decode_records(bytes: &[u8]) -> (Vec<&[u8]>, usize) {
+    let mut payloads = Vec::new();
+    let mut offset = 0;
+
+    while bytes.len() - offset >= HEADER_LEN {
+        let header = &bytes[offset..offset + HEADER_LEN];
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+        if len > MAX_RECORD_LEN || bytes.len() - offset - HEADER_LEN < len {
+            break;
+        }
+
+        let payload = &bytes[offset + HEADER_LEN..offset + HEADER_LEN + len];
+        if crc32fast::hash(payload) != crc {
+            break;
+        }
+
+        payloads.push(payload);
+        offset += HEADER_LEN + len;
+    }
+
+    (payloads, offset)
+}
+
+/// Records recovered from a spool file
+#[derive(Debug)]
+pub struct SpoolContents<T> {
+    pub records: Vec<T>,
+    /// Bytes after the last intact record, left behind by a crash mid-write
+    pub discarded_bytes: usize,
+}
+
+/// Read all intact records of a spool file; a missing file is an empty spool
+pub fn read_spool<T: DeserializeOwned>(path: &Path, format: WireFormat) -> AppResult<SpoolContents<T>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let (payloads, valid_len) = decode_records(&bytes);
+    let discarded_bytes = bytes.len() - valid_len;
+    if discarded_bytes > 0 {
+        warn!("[SPOOL] Discarding {} trailing bytes of torn or corrupt records in {}", discarded_bytes, path.display());
+    }
+
+    let mut records = Vec::with_capacity(payloads.len());
+    for payload in payloads {
+        // The checksum matched, so a decode failure is a schema problem of this record only
+        match format.decode(payload) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("[SPOOL] Skipping undecodable record in {}: {}", path.display(), e),
+        }
+    }
+
+    Ok(SpoolContents { records, discarded_bytes })
+}
+
+/// Append-only writer of a spool file
+pub struct SpoolWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    format: WireFormat,
+}
+
+impl SpoolWriter {
+    /// Open a spool for appending, first cutting off a torn tail left by a previous crash
+    pub fn open(path: impl Into<PathBuf>, format: WireFormat) -> AppResult<Self> {
+        let path = path.into();
+
+        if path.exists() {
+            let bytes = std::fs::read(&path)?;
+            let (_, valid_len) = decode_records(&bytes);
+            if valid_len < bytes.len() {
+                warn!("[SPOOL] Truncating {} from {} to {} bytes after an incomplete write",
+                      path.display(), bytes.len(), valid_len);
+                OpenOptions::new().write(true).open(&path)?.set_len(valid_len as u64)?;
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, file: BufWriter::new(file), format })
+    }
+
+    /// Buffer one record, returning its size with its header; call [`SpoolWriter::sync`] to
+    /// make it durable
+    pub fn append<T: Serialize>(&mut self, record: &T) -> AppResult<usize> {
+        let payload = self.format.encode(record)?;
+        if payload.len() > MAX_RECORD_LEN {
+            return Err(AppError::Serialization(format!(
+                "Spool record of {} bytes exceeds the {} byte limit", payload.len(), MAX_RECORD_LEN)));
+        }
+
+        self.file.write_all(&encode_record(&payload))?;
+        Ok(HEADER_LEN + payload.len())
+    }
+
+    /// Flush buffered records and fsync them to disk
+    pub fn sync(&mut self) -> AppResult<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_data()?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
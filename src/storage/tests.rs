@@ -0,0 +1,880 @@
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use super::candles::{candle_view, CandleInterval, CandleSeries};
+use super::coverage::{find_gaps, GapReason};
+use super::spool::{decode_records, encode_record, read_spool, SpoolWriter};
+use super::file_sink::FileSink;
+use super::export::{ExportFormat, ExportWriter};
+use super::database::{compression_statements, last_per_key};
+use super::sink::{run_storage_sink, SinkSettings, StorageSink};
+use super::wal::WriteAheadLog;
+use super::write_buffer::WriteBuffer;
+use super::redis_cache::{broadcast_commands, encode_command, feed_key, index_key, PendingValues, RedisConnection};
+use super::kafka::{client_config, KafkaIndexSink, KafkaProducer, KafkaRecord};
+use super::mqtt::{encode_connect, encode_publish, encode_remaining_length, index_topic, MqttConnection};
+use super::latest_cache::LatestValueCache;
+
+#[cfg(test)]
+mod coverage_tests {
+    use super::*;
+
+    #[test]
+    fn test_full_coverage_has_no_gaps() {
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let to = from + Duration::seconds(20);
+        let timestamps: Vec<_> = (0..=4).map(|i| from + Duration::seconds(i * 5)).collect();
+
+        assert!(find_gaps("feed", &timestamps, from, to, Duration::seconds(10), None).is_empty());
+    }
+
+    #[test]
+    fn test_reports_each_gap() {
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let to = from + Duration::seconds(100);
+        let timestamps = vec![from + Duration::seconds(5), from + Duration::seconds(50)];
+
+        let gaps = find_gaps("feed", &timestamps, from, to, Duration::seconds(10), None);
+
+        // Between the two observations and after the last one
+        assert_eq!(gaps.len(), 2);
+        assert_eq!(gaps[0].from, from + Duration::seconds(5));
+        assert_eq!(gaps[0].to, from + Duration::seconds(50));
+        assert_eq!(gaps[1].to, to);
+        assert!(gaps.iter().all(|gap| gap.reason == GapReason::NoData));
+    }
+
+    #[test]
+    fn test_range_beyond_retention() {
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let horizon = from + Duration::seconds(30);
+        let to = from + Duration::seconds(40);
+        let timestamps = vec![from + Duration::seconds(35), to];
+
+        let gaps = find_gaps("feed", &timestamps, from, to, Duration::seconds(10), Some(horizon));
+
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].reason, GapReason::BeyondRetention);
+        assert_eq!(gaps[0].to, horizon);
+    }
+
+    #[test]
+    fn test_archived_observations_cover_the_range_beyond_retention() {
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let horizon = from + Duration::seconds(30);
+        let to = from + Duration::seconds(60);
+        // Archived until 15s, stored from 50s
+        let timestamps = vec![from, from + Duration::seconds(5), from + Duration::seconds(15), from + Duration::seconds(50), to];
+
+        let gaps = find_gaps("feed", &timestamps, from, to, Duration::seconds(10), Some(horizon));
+
+        assert_eq!(gaps.len(), 2);
+        assert_eq!((gaps[0].from, gaps[0].to, gaps[0].reason), (from + Duration::seconds(15), horizon, GapReason::BeyondRetention));
+        assert_eq!((gaps[1].from, gaps[1].to, gaps[1].reason), (horizon, from + Duration::seconds(50), GapReason::NoData));
+
+        let archived: Vec<_> = (0..=12).map(|i| from + Duration::seconds(i * 5)).collect();
+        assert!(find_gaps("feed", &archived, from, to, Duration::seconds(10), Some(horizon)).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod spool_tests {
+    use super::*;
+    use std::path::PathBuf;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use crate::models::FeedData;
+    use crate::serialization::WireFormat;
+
+    fn temp_spool(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("spool-{}-{}.bin", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn feed_data(i: i64) -> FeedData {
+        FeedData {
+            feed_id: "coinbase_btc_usd".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::seconds(i),
+            price: 40000.0 + i as f64,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        }
+    }
+
+    fn sample_spool(count: usize) -> (Vec<u8>, Vec<usize>) {
+        let mut bytes = Vec::new();
+        let mut boundaries = vec![0];
+        for i in 0..count {
+            bytes.extend(encode_record(format!("record-{}", i).as_bytes()));
+            boundaries.push(bytes.len());
+        }
+        (bytes, boundaries)
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let path = temp_spool("roundtrip");
+        let mut writer = SpoolWriter::open(&path, WireFormat::Json).unwrap();
+        for i in 0..3 {
+            writer.append(&feed_data(i)).unwrap();
+        }
+        writer.sync().unwrap();
+
+        let contents = read_spool::<FeedData>(&path, WireFormat::Json).unwrap();
+        assert_eq!(contents.records, (0..3).map(feed_data).collect::<Vec<_>>());
+        assert_eq!(contents.discarded_bytes, 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_open_truncates_torn_tail() {
+        let path = temp_spool("torn");
+        let mut writer = SpoolWriter::open(&path, WireFormat::Json).unwrap();
+        writer.append(&feed_data(0)).unwrap();
+        writer.append(&feed_data(1)).unwrap();
+        writer.sync().unwrap();
+        drop(writer);
+
+        // Simulate a crash halfway through the second record
+        let len = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 10).unwrap();
+
+        let mut writer = SpoolWriter::open(&path, WireFormat::Json).unwrap();
+        writer.append(&feed_data(2)).unwrap();
+        writer.sync().unwrap();
+
+        let contents = read_spool::<FeedData>(&path, WireFormat::Json).unwrap();
+        assert_eq!(contents.records, vec![feed_data(0), feed_data(2)]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn fuzz_truncation_keeps_intact_prefix() {
+        let (bytes, boundaries) = sample_spool(8);
+
+        for cut in 0..=bytes.len() {
+            let (payloads, valid_len) = decode_records(&bytes[..cut]);
+            let complete = boundaries.iter().filter(|&&b| b > 0 && b <= cut).count();
+
+            assert_eq!(payloads.len(), complete, "cut at {}", cut);
+            assert_eq!(valid_len, boundaries[complete]);
+        }
+    }
+
+    #[test]
+    fn fuzz_bit_flips_never_yield_corrupt_records() {
+        let (bytes, boundaries) = sample_spool(8);
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+
+        for _ in 0..2000 {
+            let mut corrupted = bytes.clone();
+            let position = rng.gen_range(0..corrupted.len());
+            corrupted[position] ^= 1 << rng.gen_range(0..8);
+
+            let (payloads, _) = decode_records(&corrupted);
+            let damaged_record = boundaries.iter().filter(|&&b| b <= position).count() - 1;
+
+            // Records before the flipped bit survive, the damaged one and everything after is dropped
+            assert_eq!(payloads.len(), damaged_record);
+            for (i, payload) in payloads.iter().enumerate() {
+                assert_eq!(*payload, format!("record-{}", i).as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn fuzz_random_input_does_not_panic() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..256);
+            let garbage: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+
+            let (payloads, valid_len) = decode_records(&garbage);
+            assert!(valid_len <= garbage.len());
+            assert!(payloads.iter().all(|p| p.len() <= valid_len));
+        }
+    }
+}
+
+#[cfg(test)]
+mod file_sink_tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::bundle::tables::read_raw_prices;
+    use crate::config::FileFormat;
+    use crate::models::FeedData;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("file-sink-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn prices(hours: &[i64]) -> Vec<FeedData> {
+        hours.iter()
+            .map(|hour| FeedData {
+                feed_id: "coinbase_btc_usd".to_string(),
+                timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::hours(*hour),
+                price: 40000.0 + *hour as f64,
+                backup_feed: None,
+                heartbeat: false,
+                denomination: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_csv_files_are_appended_per_day() {
+        let dir = temp_dir("csv");
+        let sink = FileSink::new(&dir, FileFormat::Csv);
+        sink.write_raw_prices(&prices(&[1, 2]), Utc::now()).unwrap();
+        sink.write_raw_prices(&prices(&[23, 25]), Utc::now()).unwrap();
+
+        let first_day = std::fs::read_to_string(sink.csv_path("raw_prices", NaiveDate::from_ymd_opt(2024, 1, 1).unwrap())).unwrap();
+        assert_eq!(first_day.lines().collect::<Vec<_>>(), [
+            "feed_id,timestamp,price",
+            "coinbase_btc_usd,2024-01-01T01:00:00+00:00,40001",
+            "coinbase_btc_usd,2024-01-01T02:00:00+00:00,40002",
+            "coinbase_btc_usd,2024-01-01T23:00:00+00:00,40023",
+        ]);
+        let second_day = std::fs::read_to_string(sink.csv_path("raw_prices", NaiveDate::from_ymd_opt(2024, 1, 2).unwrap())).unwrap();
+        assert_eq!(second_day.lines().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parquet_flushes_write_new_files() {
+        let dir = temp_dir("parquet");
+        let sink = FileSink::new(&dir, FileFormat::Parquet);
+        let written_at = Utc::now();
+        sink.write_raw_prices(&prices(&[1]), written_at).unwrap();
+        sink.write_raw_prices(&prices(&[2]), written_at).unwrap();
+
+        let day_dir = sink.parquet_dir("raw_prices", NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        let mut read = Vec::new();
+        for entry in std::fs::read_dir(&day_dir).unwrap() {
+            read.extend(read_raw_prices(std::fs::read(entry.unwrap().path()).unwrap()).unwrap());
+        }
+        read.sort_by_key(|price| price.timestamp);
+        assert_eq!(read, prices(&[1, 2]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_raw_prices_are_read_back_from_both_formats() {
+        let dir = temp_dir("archive");
+        FileSink::new(&dir, FileFormat::Csv).write_raw_prices(&prices(&[1, 23]), Utc::now()).unwrap();
+        let sink = FileSink::new(&dir, FileFormat::Parquet);
+        sink.write_raw_prices(&prices(&[2, 25, 50]), Utc::now()).unwrap();
+
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 2, 0, 0).unwrap();
+        let read = sink.read_raw_prices(&["coinbase_btc_usd".to_string()], from, from + Duration::hours(23)).unwrap();
+        assert_eq!(read, prices(&[2, 23, 25]));
+        assert!(sink.read_raw_prices(&["binance_btc_usd".to_string()], from, from + Duration::hours(23)).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod write_buffer_tests {
+    use super::*;
+    use std::sync::Mutex;
+    use async_trait::async_trait;
+    use std::sync::Arc;
+    use tokio::sync::{broadcast, mpsc};
+    use crate::error::{AppError, AppResult};
+    use crate::exchange::FetchMetric;
+    use crate::index::IndexResult;
+    use crate::models::FeedData;
+
+    fn price(feed_id: &str, secs: i64, price: f64) -> FeedData {
+        FeedData {
+            feed_id: feed_id.to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::seconds(secs),
+            price,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        }
+    }
+
+    /// Sink failing while `down`, recording the sizes of the batches of prices it saved
+    #[derive(Default)]
+    struct FlakySink {
+        down: Mutex<bool>,
+        saved: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl StorageSink for FlakySink {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+
+        async fn save_prices(&self, prices: &[FeedData]) -> AppResult<()> {
+            if *self.down.lock().unwrap() {
+                return Err(AppError::Database("connection refused".to_string()));
+            }
+            self.saved.lock().unwrap().push(prices.len());
+            Ok(())
+        }
+
+        async fn save_index_values(&self, _values: &[IndexResult]) -> AppResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_batches_keep_the_last_price_per_feed_and_timestamp() {
+        let batch = [price("a", 0, 1.0), price("b", 0, 2.0), price("a", 0, 3.0), price("a", 1, 4.0)];
+
+        let prices: Vec<f64> = last_per_key(&batch, |price| (price.feed_id.as_str(), price.timestamp))
+            .iter().map(|price| price.price).collect();
+        assert_eq!(prices, [3.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn test_buffer_drops_the_oldest_records_beyond_its_capacity() {
+        let mut buffer = WriteBuffer::new("database", "price", 2, 40);
+        for secs in 0..45 {
+            buffer.push(price("a", secs, secs as f64));
+        }
+
+        assert_eq!(buffer.len(), 40);
+        assert!(buffer.is_full());
+    }
+
+    #[tokio::test]
+    async fn test_buffer_backs_off_during_an_outage_and_drains_on_recovery() {
+        let sink = FlakySink::default();
+        let mut buffer = WriteBuffer::new("flaky", "price", 2, 100);
+        for secs in 0..5 {
+            buffer.push(price("a", secs, 1.0));
+        }
+        let start = tokio::time::Instant::now();
+
+        *sink.down.lock().unwrap() = true;
+        buffer.flush_at(&sink, start).await;
+        assert!(buffer.is_failing());
+        assert_eq!(buffer.len(), 5);
+        // Flushes are held back until the retry, at most a second after the first failure
+        assert!(buffer.is_backing_off(start));
+        assert!(!buffer.is_backing_off(start + std::time::Duration::from_secs(1)));
+
+        *sink.down.lock().unwrap() = false;
+        buffer.flush_at(&sink, start).await;
+        assert_eq!(buffer.len(), 5);
+
+        buffer.flush_at(&sink, start + std::time::Duration::from_secs(1)).await;
+        assert!(!buffer.is_failing());
+        assert!(buffer.is_empty());
+        assert_eq!(*sink.saved.lock().unwrap(), [2, 2, 1]);
+    }
+
+    fn temp_wal(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("wal-{}-{}.wal", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_wal_keeps_records_across_reopening() {
+        let path = temp_wal("reopen");
+        let mut wal = WriteAheadLog::open(&path, 1024 * 1024).unwrap();
+        assert!(wal.is_empty());
+        assert!(!path.exists());
+
+        let prices: Vec<FeedData> = (0..3).map(|secs| price("a", secs, secs as f64)).collect();
+        assert_eq!(wal.append(&prices).unwrap(), 3);
+        drop(wal);
+
+        let mut wal = WriteAheadLog::open(&path, 1024 * 1024).unwrap();
+        assert_eq!(wal.len(), 3);
+        assert_eq!(wal.read::<FeedData>().unwrap(), prices);
+
+        wal.replace(&prices[2..]).unwrap();
+        assert_eq!(WriteAheadLog::open(&path, 1024 * 1024).unwrap().read::<FeedData>().unwrap(), &prices[2..]);
+        wal.replace::<FeedData>(&[]).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_wal_stops_appending_once_full() {
+        let path = temp_wal("full");
+        let mut wal = WriteAheadLog::open(&path, 1).unwrap();
+        let prices: Vec<FeedData> = (0..3).map(|secs| price("a", secs, 1.0)).collect();
+
+        assert_eq!(wal.append(&prices).unwrap(), 1);
+        assert!(wal.is_full());
+        assert_eq!(wal.append(&prices).unwrap(), 0);
+        wal.replace::<FeedData>(&[]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_records_of_an_outage_survive_a_restart_in_the_wal() {
+        let path = temp_wal("restart");
+        let sink = FlakySink::default();
+        *sink.down.lock().unwrap() = true;
+        let start = tokio::time::Instant::now();
+
+        let mut buffer = WriteBuffer::new("flaky", "price", 2, 100).with_wal(WriteAheadLog::open(&path, 1024 * 1024).unwrap());
+        buffer.push(price("a", 0, 0.0));
+        buffer.flush_at(&sink, start).await;
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.wal_len(), 1);
+        // Moved to the log a batch at a time during the outage, and on shutdown
+        for secs in 1..4 {
+            buffer.push(price("a", secs, secs as f64));
+        }
+        assert_eq!((buffer.len(), buffer.wal_len()), (1, 3));
+        assert_eq!(buffer.persist(), 1);
+        drop(buffer);
+
+        *sink.down.lock().unwrap() = false;
+        let mut buffer = WriteBuffer::new("flaky", "price", 2, 100).with_wal(WriteAheadLog::open(&path, 1024 * 1024).unwrap());
+        buffer.push(price("a", 4, 4.0));
+        buffer.flush_at(&sink, start).await;
+
+        assert_eq!((buffer.len(), buffer.wal_len()), (0, 0));
+        assert_eq!(*sink.saved.lock().unwrap(), [2, 2, 1]);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_a_failing_sink_does_not_hold_up_the_others() {
+        let (shutdown_tx, _) = broadcast::channel(1);
+        let settings = SinkSettings {
+            batch_size: 2,
+            capacity: 10,
+            flush_interval: std::time::Duration::from_secs(60),
+            notify_after: std::time::Duration::from_secs(60),
+            wal: None,
+        };
+        let mut runs = Vec::new();
+        for down in [true, false] {
+            let sink = Arc::new(FlakySink::default());
+            *sink.down.lock().unwrap() = down;
+            let (prices_tx, prices) = mpsc::channel(10);
+            let (values_tx, values) = mpsc::channel::<IndexResult>(10);
+            let fetches = None::<mpsc::Receiver<FetchMetric>>;
+            let handle = tokio::spawn(run_storage_sink(sink.clone(), prices, values, fetches, settings.clone(), None, shutdown_tx.subscribe()));
+            runs.push((sink, prices_tx, values_tx, handle));
+        }
+
+        for (_, prices_tx, _, _) in &runs {
+            for secs in 0..3 {
+                prices_tx.send(price("a", secs, 1.0)).await.unwrap();
+            }
+        }
+        shutdown_tx.send(()).unwrap();
+        let mut saved = Vec::new();
+        for (sink, _, _, handle) in runs {
+            handle.await.unwrap();
+            saved.push(sink.saved.lock().unwrap().iter().sum::<usize>());
+        }
+
+        assert_eq!(saved, [0, 3]);
+    }
+}
+
+#[cfg(test)]
+mod candle_tests {
+    use super::*;
+
+    #[test]
+    fn test_candle_views_are_created_by_the_migrations() {
+        let migration = include_str!("../../migrations/0003_candles.sql");
+        assert!(migration.starts_with("-- no-transaction"));
+
+        for series in CandleSeries::ALL {
+            for interval in CandleInterval::ALL {
+                let view = candle_view(series, interval);
+                assert!(migration.contains(&format!("CREATE MATERIALIZED VIEW IF NOT EXISTS {} ", view)));
+                assert!(migration.contains(&format!("add_continuous_aggregate_policy('{}'", view)));
+            }
+        }
+        assert_eq!(candle_view(CandleSeries::Feed, CandleInterval::Hour), "price_candles_1h");
+    }
+}
+
+#[cfg(test)]
+mod retention_tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_compression_is_turned_on_once() {
+        let statements = compression_statements(7, &HashSet::new());
+        assert_eq!(statements.len(), 4);
+        assert!(statements[0].starts_with("ALTER TABLE raw_price_data SET (timescaledb.compress, timescaledb.compress_segmentby = 'feed_id'"));
+        assert_eq!(statements[1], "SELECT add_compression_policy('raw_price_data', INTERVAL '7 days', if_not_exists => TRUE);");
+        assert!(statements[2].starts_with("ALTER TABLE index_values SET (timescaledb.compress, timescaledb.compress_segmentby = 'index_name'"));
+
+        let compressed = HashSet::from(["raw_price_data".to_string()]);
+        let statements = compression_statements(7, &compressed);
+        assert_eq!(statements.len(), 3);
+        assert!(statements[0].starts_with("SELECT add_compression_policy('raw_price_data'"));
+        assert!(statements[1].starts_with("ALTER TABLE index_values"));
+
+        let compressed = HashSet::from(["raw_price_data".to_string(), "index_values".to_string()]);
+        assert!(compression_statements(7, &compressed).iter().all(|sql| sql.starts_with("SELECT add_compression_policy(")));
+    }
+}
+
+#[cfg(test)]
+mod redis_cache_tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn command(args: &[&str]) -> Vec<Vec<u8>> {
+        args.iter().map(|arg| arg.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_commands_are_encoded_as_resp_arrays() {
+        assert_eq!(encode_command(&command(&["SET", "key", "{}"])), b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$2\r\n{}\r\n");
+        assert_eq!(feed_key("crypto-index", "coinbase_btc_usd"), "crypto-index:feed:coinbase_btc_usd");
+        assert_eq!(index_key("crypto-index", "BTC-USD-INDEX"), "crypto-index:index:BTC-USD-INDEX");
+    }
+
+    #[test]
+    fn test_pending_values_keep_the_latest_value_per_key() {
+        let mut pending = PendingValues::default();
+        pending.insert("a".to_string(), b"1".to_vec());
+        pending.insert("b".to_string(), b"2".to_vec());
+        pending.insert("a".to_string(), b"3".to_vec());
+
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending.commands(Some(60), true), [
+            command(&["SET", "a", "3", "EX", "60"]),
+            command(&["PUBLISH", "a", "3"]),
+            command(&["SET", "b", "2", "EX", "60"]),
+            command(&["PUBLISH", "b", "2"]),
+        ]);
+        assert_eq!(pending.commands(None, false), [command(&["SET", "a", "3"]), command(&["SET", "b", "2"])]);
+    }
+
+    #[test]
+    fn test_broadcast_publishes_and_appends_to_the_stream() {
+        let mut config = crate::config::RedisBroadcastConfig {
+            url: "redis://127.0.0.1".to_string(),
+            channel: "indices:{name}".to_string(),
+            stream: None,
+            stream_max_len: None,
+            batch_size: 100,
+            flush_interval_ms: 100,
+        };
+        assert_eq!(broadcast_commands(&config, "BTC-USD-INDEX", b"{}"), [command(&["PUBLISH", "indices:BTC-USD-INDEX", "{}"])]);
+
+        config.stream = Some("index-updates".to_string());
+        config.stream_max_len = Some(1000);
+        assert_eq!(broadcast_commands(&config, "BTC-USD-INDEX", b"{}"), [
+            command(&["PUBLISH", "indices:BTC-USD-INDEX", "{}"]),
+            command(&["XADD", "index-updates", "MAXLEN", "~", "1000", "*", "index", "BTC-USD-INDEX", "value", "{}"]),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_connection_selects_the_database_and_reports_error_replies() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            for reply in [&b"+OK\r\n"[..], b"+OK\r\n-WRONGTYPE Operation against a key\r\n:1\r\n"] {
+                let mut buf = [0; 1024];
+                let n = socket.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+                socket.write_all(reply).await.unwrap();
+            }
+            received
+        });
+
+        let mut connection = RedisConnection::connect(&format!("redis://127.0.0.1:{}/2", port)).await.unwrap();
+        let result = connection.execute(&[
+            command(&["SET", "a", "1"]),
+            command(&["SET", "b", "2"]),
+            command(&["PUBLISH", "a", "1"]),
+        ]).await;
+
+        assert!(result.unwrap_err().to_string().contains("WRONGTYPE"));
+        let received = String::from_utf8(server.await.unwrap()).unwrap();
+        assert!(received.starts_with("*2\r\n$6\r\nSELECT\r\n$1\r\n2\r\n*3\r\n$3\r\nSET"));
+    }
+}
+
+#[cfg(test)]
+mod kafka_tests {
+    use super::*;
+    use crate::config::{KafkaFormat, KafkaIndexConfig, KafkaIndexKey, KafkaStorageConfig};
+
+    fn producer_config(acks: i16) -> KafkaStorageConfig {
+        KafkaStorageConfig {
+            brokers: vec!["127.0.0.1:9092".to_string(), "127.0.0.1:9093".to_string()],
+            topic: "index-levels".to_string(),
+            format: KafkaFormat::Json,
+            schema_id: None,
+            client_id: "collector".to_string(),
+            acks,
+            batch_size: 100,
+            flush_interval_ms: 100,
+            timeout_ms: 1000,
+            properties: Default::default(),
+            sasl_password_env: None,
+        }
+    }
+
+    #[test]
+    fn test_producer_is_idempotent_and_partitions_like_the_java_client() {
+        let mut config = producer_config(-1);
+        config.properties.insert("compression.type".to_string(), "zstd".to_string());
+        config.properties.insert("security.protocol".to_string(), "sasl_ssl".to_string());
+        let client = client_config(&config).unwrap();
+
+        assert_eq!(client.get("bootstrap.servers"), Some("127.0.0.1:9092,127.0.0.1:9093"));
+        assert_eq!(client.get("partitioner"), Some("murmur2_random"));
+        assert_eq!(client.get("enable.idempotence"), Some("true"));
+        assert_eq!(client.get("compression.type"), Some("zstd"));
+        assert_eq!(client.get("security.protocol"), Some("sasl_ssl"));
+        assert_eq!(client.get("message.timeout.ms"), Some("1000"));
+        assert_eq!(client_config(&producer_config(1)).unwrap().get("enable.idempotence"), Some("false"));
+    }
+
+    #[test]
+    fn test_sasl_password_is_read_from_the_environment() {
+        let mut config = producer_config(-1);
+        config.sasl_password_env = Some("KAFKA_TEST_SASL_PASSWORD".to_string());
+        assert!(client_config(&config).unwrap_err().to_string().contains("KAFKA_TEST_SASL_PASSWORD"));
+
+        std::env::set_var("KAFKA_TEST_SASL_PASSWORD", "s3cret");
+        assert_eq!(client_config(&config).unwrap().get("sasl.password"), Some("s3cret"));
+    }
+
+    #[tokio::test]
+    async fn test_unacknowledged_records_fail_the_batch() {
+        let mut config = producer_config(-1);
+        config.brokers = vec!["127.0.0.1:1".to_string()];
+        config.timeout_ms = 200;
+        let producer = KafkaProducer::new(&config).unwrap();
+        let record = KafkaRecord { key: Some(b"BTC-USD-INDEX".to_vec()), value: b"{}".to_vec(), timestamp_ms: 1000 };
+
+        let error = producer.send(&[record]).await.unwrap_err();
+        assert!(error.to_string().contains("index-levels"), "{}", error);
+        producer.send(&[]).await.unwrap();
+    }
+
+    #[test]
+    fn test_index_values_are_keyed_as_configured() {
+        use crate::index::IndexResult;
+        use crate::models::Denomination;
+
+        let sink = |key| KafkaIndexSink::new(KafkaIndexConfig { producer: producer_config(-1), key }).unwrap();
+        let value = IndexResult {
+            name: "BTC-USD-INDEX".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            value: 64000.0,
+            raw_value: None,
+            epoch: 3,
+            denomination: Denomination {
+                base_currency: "BTC".to_string(),
+                quote_currency: "USD".to_string(),
+                decimals: 2,
+            },
+            quality: Default::default(),
+            change: Default::default(),
+            methodology_version: 0,
+            warming_up: false,
+        };
+
+        assert_eq!(sink(KafkaIndexKey::Index).key(&value).as_deref(), Some(&b"BTC-USD-INDEX"[..]));
+        assert_eq!(sink(KafkaIndexKey::QuoteCurrency).key(&value).as_deref(), Some(&b"USD"[..]));
+        assert_eq!(sink(KafkaIndexKey::None).key(&value), None);
+
+        let json: serde_json::Value = serde_json::from_slice(&sink(KafkaIndexKey::Index).encode_index_value(&value).unwrap()).unwrap();
+        assert_eq!(json["name"], "BTC-USD-INDEX");
+        assert_eq!(json["value"], 64000.0);
+    }
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+    use crate::models::FeedData;
+    use crate::storage::StoredIndexValue;
+
+    fn price(secs: i64) -> FeedData {
+        FeedData {
+            feed_id: "coinbase_btc_usd".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::seconds(secs),
+            price: 42000.5,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        }
+    }
+
+    #[test]
+    fn test_csv_export_starts_with_a_header() {
+        let mut out = Vec::new();
+        let mut writer = ExportWriter::new(&mut out, ExportFormat::Csv);
+        writer.write(&price(0)).unwrap();
+        writer.write(&price(1)).unwrap();
+        assert_eq!(writer.finish().unwrap(), 2);
+
+        assert_eq!(String::from_utf8(out).unwrap(), "feed_id,timestamp,price\n\
+            coinbase_btc_usd,2024-01-01T00:00:00+00:00,42000.5\n\
+            coinbase_btc_usd,2024-01-01T00:00:01+00:00,42000.5\n");
+    }
+
+    #[test]
+    fn test_json_export_writes_one_record_per_line() {
+        let value = StoredIndexValue {
+            name: "BTC-USD-INDEX".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            value: 42000.0,
+            raw_value: None,
+            epoch: 3,
+            methodology_version: 1,
+        };
+        let mut out = Vec::new();
+        let mut writer = ExportWriter::new(&mut out, ExportFormat::Json);
+        writer.write(&value).unwrap();
+        writer.write(&value).unwrap();
+        writer.finish().unwrap();
+
+        let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(lines.len(), 2);
+        let decoded: StoredIndexValue = crate::serialization::WireFormat::Json.decode(lines[0].as_bytes()).unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!("json".parse::<ExportFormat>().unwrap(), ExportFormat::Json);
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+}
+
+#[cfg(test)]
+mod mqtt_tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_packets_are_encoded_as_mqtt_3_1_1() {
+        let mut len = Vec::new();
+        encode_remaining_length(321, &mut len);
+        assert_eq!(len, [0xc1, 0x02]);
+
+        let connect = encode_connect("collector", Some("user"), Some("pw"), 60);
+        assert_eq!(&connect[..2], [0x10, 31]);
+        assert_eq!(&connect[2..12], b"\x00\x04MQTT\x04\xc2\x00\x3c");
+        assert!(connect.ends_with(b"\x00\x04user\x00\x02pw"));
+
+        assert_eq!(encode_publish("indices/BTC", b"{}", 0, false, 0), b"\x30\x0f\x00\x0bindices/BTC{}");
+        assert_eq!(encode_publish("indices/BTC", b"{}", 1, true, 7), b"\x33\x11\x00\x0bindices/BTC\x00\x07{}");
+        assert_eq!(index_topic("indices/{name}", "BTC-USD-INDEX"), "indices/BTC-USD-INDEX");
+    }
+
+    #[tokio::test]
+    async fn test_publish_waits_for_the_qos_2_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            assert!(socket.read(&mut buf).await.unwrap() > 0);
+            socket.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+            let n = socket.read(&mut buf).await.unwrap();
+            let published = buf[..n].to_vec();
+            socket.write_all(&[0x50, 0x02, 0x00, 0x01]).await.unwrap();
+            let n = socket.read(&mut buf).await.unwrap();
+            let released = buf[..n].to_vec();
+            socket.write_all(&[0x70, 0x02, 0x00, 0x01]).await.unwrap();
+            (published, released)
+        });
+
+        let mut connection = MqttConnection::connect(&format!("mqtt://127.0.0.1:{}", port), "collector", 60).await.unwrap();
+        connection.publish(&[("indices/BTC".to_string(), b"{}".to_vec())], 2, false).await.unwrap();
+
+        let (published, released) = server.await.unwrap();
+        assert_eq!(published, b"\x34\x11\x00\x0bindices/BTC\x00\x01{}");
+        assert_eq!(released, [0x62, 0x02, 0x00, 0x01]);
+    }
+
+    #[tokio::test]
+    async fn test_refused_connection_reports_the_reason() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            assert!(socket.read(&mut buf).await.unwrap() > 0);
+            socket.write_all(&[0x20, 0x02, 0x00, 0x05]).await.unwrap();
+        });
+
+        let error = MqttConnection::connect(&format!("mqtt://127.0.0.1:{}", port), "collector", 60).await.err().unwrap();
+        assert!(error.to_string().contains("not authorized"), "{}", error);
+    }
+}
+
+#[cfg(test)]
+mod latest_cache_tests {
+    use super::*;
+    use crate::config::LatestCacheConfig;
+    use crate::models::FeedData;
+
+    fn price(feed_id: &str, seconds: i64, price: f64) -> FeedData {
+        FeedData {
+            feed_id: feed_id.to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::seconds(seconds),
+            price,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        }
+    }
+
+    #[test]
+    fn test_nothing_is_served_before_the_first_sync() {
+        let cache = LatestValueCache::new(&LatestCacheConfig::default());
+        cache.apply(vec![price("coinbase_btc", 0, 64000.0)]);
+
+        assert!(!cache.is_fresh());
+        assert!(cache.get("coinbase_btc").is_none());
+
+        cache.mark_synced();
+        assert_eq!(cache.get("coinbase_btc").unwrap().price, 64000.0);
+        assert!(cache.get("binance_btc").is_none());
+    }
+
+    #[test]
+    fn test_older_values_do_not_replace_newer_ones() {
+        let cache = LatestValueCache::new(&LatestCacheConfig::default());
+        cache.mark_synced();
+
+        cache.apply(vec![price("coinbase_btc", 10, 64100.0)]);
+        // A reload racing a notification may return an older row
+        cache.apply(vec![price("coinbase_btc", 5, 64000.0), price("binance_btc", 5, 63900.0)]);
+        assert_eq!(cache.get("coinbase_btc").unwrap().price, 64100.0);
+        assert_eq!(cache.get("binance_btc").unwrap().price, 63900.0);
+
+        cache.apply(vec![price("coinbase_btc", 15, 64200.0)]);
+        assert_eq!(cache.get("coinbase_btc").unwrap().price, 64200.0);
+    }
+
+    #[test]
+    fn test_values_are_withheld_once_out_of_sync_longer_than_max_age() {
+        let cache = LatestValueCache::new(&LatestCacheConfig { max_age_ms: 20, ..Default::default() });
+        cache.apply(vec![price("coinbase_btc", 0, 64000.0)]);
+        cache.mark_synced();
+        assert!(cache.get("coinbase_btc").is_some());
+
+        std::thread::sleep(std::time::Duration::from_millis(40));
+        assert!(!cache.is_fresh());
+        assert!(cache.get("coinbase_btc").is_none());
+
+        // The next successful reload or notification serves it again
+        cache.mark_synced();
+        assert!(cache.get("coinbase_btc").is_some());
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::AppResult;
+use crate::serialization::WireFormat;
+use super::spool::{decode_records, read_spool, SpoolWriter};
+
+/// Write-ahead log of records a sink could not store, kept in a spool file until they are
+/// replayed. Survives restarts: records left by a previous run are replayed like any other.
+pub struct WriteAheadLog {
+    path: PathBuf,
+    max_bytes: u64,
+    writer: Option<SpoolWriter>,
+    records: usize,
+    bytes: u64,
+}
+
+impl WriteAheadLog {
+    /// Open the log at `path`, holding up to `max_bytes` of records; the file is only created
+    /// once records are appended
+    pub fn open(path: impl Into<PathBuf>, max_bytes: u64) -> AppResult<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut wal = Self { path, max_bytes, writer: None, records: 0, bytes: 0 };
+        if wal.path.exists() {
+            // Cuts off a torn tail of a previous crash
+            wal.writer = Some(SpoolWriter::open(&wal.path, WireFormat::Json)?);
+            let bytes = std::fs::read(&wal.path)?;
+            let (records, valid_len) = decode_records(&bytes);
+            wal.records = records.len();
+            wal.bytes = valid_len as u64;
+        }
+        Ok(wal)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records in the log
+    pub fn len(&self) -> usize {
+        self.records
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records == 0
+    }
+
+    /// Whether the log holds `max_bytes` of records, taking no more
+    pub fn is_full(&self) -> bool {
+        self.bytes >= self.max_bytes
+    }
+
+    /// Append records and sync them to disk, stopping once the log holds `max_bytes`; returns
+    /// how many were written.
+    pub fn append<'a, T: Serialize + 'a>(&mut self, records: impl IntoIterator<Item = &'a T>) -> AppResult<usize> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => self.writer.insert(SpoolWriter::open(&self.path, WireFormat::Json)?),
+        };
+        let mut appended = 0;
+        for record in records {
+            if self.bytes >= self.max_bytes {
+                break;
+            }
+            self.bytes += writer.append(record)? as u64;
+            appended += 1;
+        }
+        writer.sync()?;
+        self.records += appended;
+        Ok(appended)
+    }
+
+    /// All records of the log, oldest first
+    pub fn read<T: DeserializeOwned>(&mut self) -> AppResult<Vec<T>> {
+        if let Some(writer) = &mut self.writer {
+            writer.sync()?;
+        }
+        Ok(read_spool(&self.path, WireFormat::Json)?.records)
+    }
+
+    /// Replace the records of the log with `remaining`, e.g. the ones left after a partial
+    /// replay; the file is removed once nothing remains
+    pub fn replace<T: Serialize>(&mut self, remaining: &[T]) -> AppResult<()> {
+        self.writer = None;
+        if remaining.is_empty() {
+            match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            self.records = 0;
+            self.bytes = 0;
+            return Ok(());
+        }
+
+        // Written aside and renamed over the log, so a crash leaves either version intact
+        let rewritten = self.path.with_extension("wal.tmp");
+        let _ = std::fs::remove_file(&rewritten);
+        let mut writer = SpoolWriter::open(&rewritten, WireFormat::Json)?;
+        for record in remaining {
+            writer.append(record)?;
+        }
+        writer.sync()?;
+        drop(writer);
+        std::fs::rename(&rewritten, &self.path)?;
+        self.records = remaining.len();
+        self.bytes = std::fs::metadata(&self.path)?.len();
+        Ok(())
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{debug, error, info, warn};
+
+use crate::error::AppResult;
+use crate::exchange::FetchMetric;
+use crate::exchange::retry::RetryPolicy;
+use crate::index::IndexResult;
+use crate::metrics::{metrics, Subscriber};
+use crate::models::FeedData;
+use crate::notification::{NotificationQueue, Severity};
+use super::sink::StorageSink;
+use super::wal::WriteAheadLog;
+
+/// Backoff between flushes while writes keep failing
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Destination of buffered records, written a batch at a time
+#[async_trait]
+pub trait BatchSink<T>: Send + Sync {
+    async fn save_batch(&self, batch: &[T]) -> AppResult<()>;
+}
+
+#[async_trait]
+impl<S: StorageSink + ?Sized> BatchSink<FeedData> for S {
+    async fn save_batch(&self, batch: &[FeedData]) -> AppResult<()> {
+        self.save_prices(batch).await
+    }
+}
+
+#[async_trait]
+impl<S: StorageSink + ?Sized> BatchSink<IndexResult> for S {
+    async fn save_batch(&self, batch: &[IndexResult]) -> AppResult<()> {
+        self.save_index_values(batch).await
+    }
+}
+
+#[async_trait]
+impl<S: StorageSink + ?Sized> BatchSink<FetchMetric> for S {
+    async fn save_batch(&self, batch: &[FetchMetric]) -> AppResult<()> {
+        self.save_fetch_metrics(batch).await
+    }
+}
+
+/// Source of records to buffer: the queue of a [`PriceWriter`] or a broadcast subscriber
+#[async_trait]
+pub trait RecordSource<T>: Send {
+    /// Next record, `None` once the source is closed
+    async fn next(&mut self) -> Option<T>;
+
+    /// Next record already queued, without waiting
+    fn next_queued(&mut self) -> Option<T> {
+        None
+    }
+}
+
+#[async_trait]
+impl<T: Send> RecordSource<T> for mpsc::Receiver<T> {
+    async fn next(&mut self) -> Option<T> {
+        self.recv().await
+    }
+
+    fn next_queued(&mut self) -> Option<T> {
+        self.try_recv().ok()
+    }
+}
+
+#[async_trait]
+impl<T: Clone + Send> RecordSource<T> for Subscriber<T> {
+    async fn next(&mut self) -> Option<T> {
+        self.recv().await
+    }
+}
+
+/// No source at all, never yielding a record
+#[async_trait]
+impl<T: Send, S: RecordSource<T>> RecordSource<T> for Option<S> {
+    async fn next(&mut self) -> Option<T> {
+        match self {
+            Some(source) => source.next().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn next_queued(&mut self) -> Option<T> {
+        self.as_mut().and_then(RecordSource::next_queued)
+    }
+}
+
+/// Queue of prices to store, written to the database in batches by [`run_storage_sink`](super::run_storage_sink)
+#[derive(Clone)]
+pub struct PriceWriter {
+    tx: mpsc::Sender<FeedData>,
+}
+
+impl PriceWriter {
+    /// Writer queueing up to `capacity` prices, and the receiving end for [`run_storage_sink`](super::run_storage_sink)
+    pub fn new(capacity: usize) -> (Self, mpsc::Receiver<FeedData>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (Self { tx }, rx)
+    }
+
+    /// Queue a price, waiting while the queue is full; `false` once the writer has stopped
+    pub async fn queue(&self, price: FeedData) -> bool {
+        self.tx.send(price).await.is_ok()
+    }
+}
+
+/// Records waiting to be written to a sink, flushed in batches.
+///
+/// While writes fail (e.g. the database is down or unreachable), records stay buffered up to
+/// `capacity`, the oldest ones being dropped beyond that, and flushes back off exponentially
+/// instead of hammering the database. Once the outage has lasted `notify_after`, it is notified;
+/// so is the recovery, after which the buffer is drained. Metrics are named after the sink and
+/// the `kind` of records, e.g. `database.price_write_failures`.
+///
+/// With a [`WriteAheadLog`], records are moved to disk during an outage instead, a batch at a
+/// time, and on shutdown, so neither a long outage nor a restart during one loses them. The
+/// log is replayed, oldest records first, before anything buffered in memory is written.
+pub struct WriteBuffer<T> {
+    sink: &'static str,
+    tag: String,
+    kind: &'static str,
+    records: VecDeque<T>,
+    batch_size: usize,
+    capacity: usize,
+    backoff: RetryPolicy,
+    outage: Option<Outage>,
+    notify_after: Duration,
+    notifier: Option<NotificationQueue>,
+    wal: Option<WriteAheadLog>,
+}
+
+/// Failing writes since `since`
+struct Outage {
+    since: Instant,
+    failures: u32,
+    retry_at: Instant,
+    dropped: u64,
+    notified: bool,
+}
+
+impl<T: Clone + Serialize + DeserializeOwned> WriteBuffer<T> {
+    pub fn new(sink: &'static str, kind: &'static str, batch_size: usize, capacity: usize) -> Self {
+        Self {
+            sink,
+            tag: format!("[{}]", sink.to_uppercase()),
+            kind,
+            records: VecDeque::new(),
+            batch_size,
+            capacity: capacity.max(batch_size),
+            backoff: RetryPolicy::new(u32::MAX, RETRY_BASE_DELAY, RETRY_MAX_DELAY, 0.2),
+            outage: None,
+            notify_after: Duration::from_secs(60),
+            notifier: None,
+            wal: None,
+        }
+    }
+
+    /// Move records to `wal` during outages instead of keeping them in memory
+    pub fn with_wal(mut self, wal: WriteAheadLog) -> Self {
+        if !wal.is_empty() {
+            info!("{} Replaying {} {}s left in {}", self.tag, wal.len(), self.kind, wal.path().display());
+        }
+        self.wal = Some(wal);
+        self
+    }
+
+    /// Notify outages lasting longer than `notify_after`, and their recovery
+    pub fn with_notifier(mut self, notifier: NotificationQueue, notify_after: Duration) -> Self {
+        self.notifier = Some(notifier);
+        self.notify_after = notify_after;
+        self
+    }
+
+    pub fn push(&mut self, record: T) {
+        self.records.push_back(record);
+        if self.outage.is_some() && self.records.len() >= self.batch_size {
+            self.spill();
+        }
+        if self.records.len() > self.capacity {
+            let dropped = self.records.len() - self.capacity;
+            self.records.drain(..dropped);
+            metrics().increment_by(&format!("{}.{}_writes_dropped", self.sink, self.kind), dropped as u64);
+            if let Some(outage) = &mut self.outage {
+                outage.dropped += dropped as u64;
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Records waiting in the write-ahead log
+    pub fn wal_len(&self) -> usize {
+        self.wal.as_ref().map_or(0, WriteAheadLog::len)
+    }
+
+    /// Move the records buffered in memory to the write-ahead log, e.g. on shutdown; returns how
+    /// many were moved
+    pub fn persist(&mut self) -> usize {
+        let buffered = self.records.len();
+        self.spill();
+        buffered - self.records.len()
+    }
+
+    /// Whether a full batch is buffered
+    pub fn is_full(&self) -> bool {
+        self.records.len() >= self.batch_size
+    }
+
+    /// Whether writes are currently failing
+    pub fn is_failing(&self) -> bool {
+        self.outage.is_some()
+    }
+
+    /// Retry with the next flush, e.g. once more on shutdown
+    pub fn retry_now(&mut self) {
+        if let Some(outage) = &mut self.outage {
+            outage.retry_at = Instant::now();
+        }
+    }
+
+    /// Whether flushes are held back until the next retry after failed writes
+    pub fn is_backing_off(&self, now: Instant) -> bool {
+        self.outage.as_ref().is_some_and(|outage| now < outage.retry_at)
+    }
+
+    /// Write the buffered records in batches, oldest first, unless backing off after failed
+    /// writes. A failed batch stays buffered, along with everything after it, to be retried once
+    /// the backoff has passed.
+    pub async fn flush<S: BatchSink<T> + ?Sized>(&mut self, sink: &S) {
+        self.flush_at(sink, Instant::now()).await;
+    }
+
+    /// [`flush`](Self::flush) as of `now`
+    pub(super) async fn flush_at<S: BatchSink<T> + ?Sized>(&mut self, sink: &S, now: Instant) {
+        if self.is_backing_off(now) {
+            return;
+        }
+        if !self.replay(sink, now).await {
+            self.spill();
+            self.update_gauges();
+            return;
+        }
+        while !self.records.is_empty() {
+            let size = self.records.len().min(self.batch_size);
+            let batch: Vec<T> = self.records.range(..size).cloned().collect();
+            match sink.save_batch(&batch).await {
+                Ok(()) => {
+                    self.records.drain(..size);
+                    metrics().increment(&format!("{}.{}_batches", self.sink, self.kind));
+                    debug!("{} Saved a batch of {} {}s", self.tag, size, self.kind);
+                    self.recovered(now);
+                }
+                Err(e) => {
+                    self.failed(now, size, &e.to_string());
+                    self.spill();
+                    break;
+                }
+            }
+        }
+        self.update_gauges();
+    }
+
+    /// Write the records of the write-ahead log in batches; `false` if one failed, leaving it
+    /// and the records after it in the log
+    async fn replay<S: BatchSink<T> + ?Sized>(&mut self, sink: &S, now: Instant) -> bool {
+        let Some(wal) = self.wal.as_mut().filter(|wal| !wal.is_empty()) else {
+            return true;
+        };
+        let records: Vec<T> = match wal.read() {
+            Ok(records) => records,
+            Err(e) => {
+                let (size, reason) = (wal.len(), format!("reading {}: {}", wal.path().display(), e));
+                self.failed(now, size, &reason);
+                return false;
+            }
+        };
+
+        let mut saved = 0;
+        let mut failure = None;
+        for batch in records.chunks(self.batch_size) {
+            match sink.save_batch(batch).await {
+                Ok(()) => saved += batch.len(),
+                Err(e) => {
+                    failure = Some((batch.len(), e.to_string()));
+                    break;
+                }
+            }
+        }
+        if saved > 0 {
+            metrics().increment_by(&format!("{}.{}_wal_replayed", self.sink, self.kind), saved as u64);
+            info!("{} Replayed {} of {} {}s from the write-ahead log", self.tag, saved, records.len(), self.kind);
+            if let Err(e) = wal.replace(&records[saved..]) {
+                // Replayed again with the next flush
+                error!("{} Failed to remove replayed records from {}: {}", self.tag, wal.path().display(), e);
+            }
+        }
+        match failure {
+            Some((size, reason)) => {
+                self.failed(now, size, &reason);
+                false
+            }
+            None => {
+                self.recovered(now);
+                true
+            }
+        }
+    }
+
+    /// Move the records buffered in memory to the write-ahead log, if there is one
+    fn spill(&mut self) {
+        let Some(wal) = self.wal.as_mut().filter(|wal| !wal.is_full()) else {
+            return;
+        };
+        if self.records.is_empty() {
+            return;
+        }
+        match wal.append(self.records.iter()) {
+            Ok(appended) => {
+                self.records.drain(..appended);
+                debug!("{} Moved {} {}s to the write-ahead log", self.tag, appended, self.kind);
+                if !self.records.is_empty() {
+                    warn!("{} Write-ahead log {} is full, keeping {} {}s in memory",
+                          self.tag, wal.path().display(), self.records.len(), self.kind);
+                }
+            }
+            Err(e) => {
+                metrics().increment(&format!("{}.{}_wal_failures", self.sink, self.kind));
+                error!("{} Failed to append {} {}s to the write-ahead log {}: {}",
+                       self.tag, self.records.len(), self.kind, wal.path().display(), e);
+            }
+        }
+    }
+
+    fn update_gauges(&self) {
+        metrics().set_gauge(&format!("{}.{}_buffer", self.sink, self.kind), self.records.len() as f64);
+        if let Some(wal) = &self.wal {
+            metrics().set_gauge(&format!("{}.{}_wal_records", self.sink, self.kind), wal.len() as f64);
+        }
+    }
+
+    fn failed(&mut self, now: Instant, size: usize, reason: &str) {
+        metrics().increment(&format!("{}.{}_write_failures", self.sink, self.kind));
+        let outage = self.outage.get_or_insert(Outage { since: now, failures: 0, retry_at: now, dropped: 0, notified: false });
+        outage.failures += 1;
+        let delay = self.backoff.delay(outage.failures);
+        outage.retry_at = now + delay;
+        error!("{} Failed to save a batch of {} {}s, {} buffered, retrying in {:?}: {}",
+               self.tag, size, self.kind, self.records.len(), delay, reason);
+
+        let down_for = now.duration_since(outage.since);
+        if !outage.notified && down_for >= self.notify_after {
+            outage.notified = true;
+            if let Some(notifier) = &self.notifier {
+                notifier.send(Severity::Error, format!(
+                    "Writes of {}s to the {} failing for {}s, {} buffered (up to {}, older ones are dropped): {}",
+                    self.kind, self.sink, down_for.as_secs(), self.records.len(), self.capacity, reason
+                ));
+            }
+        }
+    }
+
+    fn recovered(&mut self, now: Instant) {
+        let Some(outage) = self.outage.take() else {
+            return;
+        };
+        let down_for = now.duration_since(outage.since);
+        info!("{} Writes of {}s recovered after {:?} and {} failed attempts, {} dropped",
+              self.tag, self.kind, down_for, outage.failures, outage.dropped);
+        if outage.notified {
+            if let Some(notifier) = &self.notifier {
+                notifier.send(Severity::Info, format!(
+                    "Writes of {}s to the {} recovered after {}s, {} dropped during the outage",
+                    self.kind, self.sink, down_for.as_secs(), outage.dropped
+                ));
+            }
+        }
+    }
+}
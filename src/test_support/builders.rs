@@ -0,0 +1,223 @@
+use crate::index::expression::Expression;
+use crate::models::{
+    default_base_level, default_change_windows_secs, default_scale, default_max_staleness_secs, default_smoothing_factor,
+    default_smoothing_period, default_trim_pct, Aggregation,
+    BackupSource, FeedPriority, IndexComponent, IndexDefinition, Lifecycle, Methodology, MissingFeeds, PriceFeed,
+    PublishSchedule, RateConversion, SmoothingType, Transform, WarmUp, Weighting,
+};
+
+/// A feed on a test exchange with the given weight
+pub fn price_feed(id: &str, weight: u32) -> PriceFeed {
+    PriceFeed {
+        id: id.to_string(),
+        exchange: "test".to_string(),
+        symbol: id.to_uppercase(),
+        weight,
+        conversion: None,
+        priority: FeedPriority::Normal,
+        backup: None,
+        bounds: Default::default(),
+        heartbeat_secs: None,
+        aliases: Vec::new(),
+        market_cap_id: None,
+        denomination: Default::default(),
+    }
+}
+
+/// Builder for index definitions, starting from an unsmoothed index without feeds
+#[derive(Debug, Clone)]
+pub struct IndexDefinitionBuilder {
+    definition: IndexDefinition,
+}
+
+impl IndexDefinitionBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            definition: IndexDefinition {
+                name: name.to_string(),
+                feeds: Vec::new(),
+                components: Vec::new(),
+                expression: None,
+                smoothing: SmoothingType::None,
+                smoothing_period: default_smoothing_period(),
+                smoothing_factor: default_smoothing_factor(),
+                conversion_feeds: Vec::new(),
+                max_staleness_secs: default_max_staleness_secs(),
+                max_deviation_pct: None,
+                alignment_window_ms: None,
+                smoothing_band_pct: None,
+                min_feeds: None,
+                missing_feeds: Default::default(),
+                warm_up: Default::default(),
+                publish: Default::default(),
+                change_windows_secs: default_change_windows_secs(),
+                weighting: Default::default(),
+                aggregation: Default::default(),
+                trim_pct: default_trim_pct(),
+                methodology: Default::default(),
+                base_level: default_base_level(),
+                transform: Default::default(),
+                scale: default_scale(),
+                denomination: Default::default(),
+                family: None,
+                lifecycle: Default::default(),
+                labels: Default::default(),
+            },
+        }
+    }
+
+    /// Add a constituent feed
+    pub fn feed(mut self, id: &str, weight: u32) -> Self {
+        self.definition.feeds.push(price_feed(id, weight));
+        self
+    }
+
+    /// Add another index as a constituent, making this a composite index
+    pub fn component(mut self, index: &str, weight: u32) -> Self {
+        self.definition.components.push(IndexComponent { index: index.to_string(), weight });
+        self
+    }
+
+    /// Calculate the index from other feeds and indices, making this a derived index
+    pub fn expression(mut self, source: &str) -> Self {
+        self.definition.expression = Some(Expression::parse(source).expect("valid expression"));
+        self
+    }
+
+    /// Add a fully specified constituent feed
+    pub fn price_feed(mut self, feed: PriceFeed) -> Self {
+        self.definition.feeds.push(feed);
+        self
+    }
+
+    /// Add a constituent quoted in another currency, converted with the rate of `conversion_id`
+    pub fn converted_feed(mut self, id: &str, weight: u32, conversion_id: &str, invert: bool) -> Self {
+        let mut feed = price_feed(id, weight);
+        feed.conversion = Some(RateConversion { feed_id: conversion_id.to_string(), invert });
+        self.definition.feeds.push(feed);
+
+        if !self.definition.conversion_feeds.iter().any(|f| f.id == conversion_id) {
+            self.definition.conversion_feeds.push(price_feed(conversion_id, 0));
+        }
+        self
+    }
+
+    /// Give the most recently added feed a backup source
+    pub fn backup(mut self, backup_id: &str, failover_after: u32) -> Self {
+        if let Some(feed) = self.definition.feeds.last_mut() {
+            feed.backup = Some(BackupSource {
+                feed_id: backup_id.to_string(),
+                exchange: "test".to_string(),
+                symbol: backup_id.to_uppercase(),
+                failover_after,
+            });
+        }
+        self
+    }
+
+    pub fn smoothing(mut self, smoothing: SmoothingType) -> Self {
+        self.definition.smoothing = smoothing;
+        self
+    }
+
+    pub fn smoothing_params(mut self, period: usize, factor: f64) -> Self {
+        self.definition.smoothing_period = period;
+        self.definition.smoothing_factor = factor;
+        self
+    }
+
+    pub fn max_staleness_secs(mut self, secs: u64) -> Self {
+        self.definition.max_staleness_secs = secs;
+        self
+    }
+
+    pub fn max_deviation_pct(mut self, pct: f64) -> Self {
+        self.definition.max_deviation_pct = Some(pct);
+        self
+    }
+
+    pub fn smoothing_band_pct(mut self, band_pct: f64) -> Self {
+        self.definition.smoothing_band_pct = Some(band_pct);
+        self
+    }
+
+    pub fn alignment_window_ms(mut self, window_ms: u64) -> Self {
+        self.definition.alignment_window_ms = Some(window_ms);
+        self
+    }
+
+    pub fn min_feeds(mut self, min_feeds: usize) -> Self {
+        self.definition.min_feeds = Some(min_feeds);
+        self
+    }
+
+    /// Publish nothing while the smoothing is warming up
+    pub fn withhold_warm_up(mut self) -> Self {
+        self.definition.warm_up = WarmUp::Withhold;
+        self
+    }
+
+    /// Publish from the priced feeds while others have no price yet
+    pub fn reweight_missing_feeds(mut self) -> Self {
+        self.definition.missing_feeds = MissingFeeds::Reweight;
+        self
+    }
+
+    pub fn publish(mut self, schedule: PublishSchedule) -> Self {
+        self.definition.publish = schedule;
+        self
+    }
+
+    pub fn aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.definition.aggregation = aggregation;
+        self
+    }
+
+    /// Aggregate with a mean trimmed by `trim_pct` percent at each end
+    pub fn trimmed_mean(mut self, trim_pct: f64) -> Self {
+        self.definition.aggregation = Aggregation::TrimmedMean;
+        self.definition.trim_pct = trim_pct;
+        self
+    }
+
+    /// Publish a divisor-based level starting at `base_level`
+    pub fn divisor(mut self, base_level: f64) -> Self {
+        self.definition.methodology = Methodology::Divisor;
+        self.definition.base_level = base_level;
+        self
+    }
+
+    /// Publish a level compounded by log returns, starting at `base_level`
+    pub fn log_returns(mut self, base_level: f64) -> Self {
+        self.definition.methodology = Methodology::LogReturn;
+        self.definition.base_level = base_level;
+        self
+    }
+
+    /// Publish the inverse of the aggregated value, times `scale`
+    pub fn inverse(mut self, scale: f64) -> Self {
+        self.definition.transform = Transform::Inverse;
+        self.definition.scale = scale;
+        self
+    }
+
+    /// List the index in the catalog under `family` at the given lifecycle stage
+    pub fn family(mut self, family: &str, lifecycle: Lifecycle) -> Self {
+        self.definition.family = Some(family.to_string());
+        self.definition.lifecycle = lifecycle;
+        self
+    }
+
+    /// Weight the index by market cap, with the given asset id for each feed
+    pub fn market_cap_weighted(mut self, assets: &[(&str, &str)]) -> Self {
+        self.definition.weighting = Weighting::MarketCap;
+        for feed in &mut self.definition.feeds {
+            feed.market_cap_id = assets.iter().find(|(id, _)| *id == feed.id).map(|(_, asset)| asset.to_string());
+        }
+        self
+    }
+
+    pub fn build(self) -> IndexDefinition {
+        self.definition
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use tokio::sync::mpsc;
+
+use crate::error::{AppError, AppResult};
+use crate::exchange::Exchange;
+use crate::index::{IndexCalculator, IndexResult};
+use crate::models::{FeedData, IndexDefinition};
+
+/// Drives an [`IndexCalculator`] with scripted feed updates and records everything it publishes
+pub struct IndexHarness {
+    calculator: IndexCalculator,
+    sender: mpsc::Sender<FeedData>,
+    published: Vec<IndexResult>,
+}
+
+impl IndexHarness {
+    pub fn new(indices: Vec<IndexDefinition>) -> Self {
+        let (sender, receiver) = mpsc::channel(1024);
+        Self {
+            calculator: IndexCalculator::new(indices, receiver),
+            sender,
+            published: Vec::new(),
+        }
+    }
+
+    /// Queue a fresh price update
+    pub fn push(&mut self, feed_id: &str, price: f64) -> &mut Self {
+        self.push_aged(feed_id, price, 0)
+    }
+
+    /// Queue a price update fetched `age_secs` seconds ago
+    pub fn push_aged(&mut self, feed_id: &str, price: f64, age_secs: i64) -> &mut Self {
+        self.sender
+            .try_send(FeedData {
+                feed_id: feed_id.to_string(),
+                timestamp: Utc::now() - Duration::seconds(age_secs),
+                price,
+                backup_feed: None,
+                heartbeat: false,
+                denomination: None,
+            })
+            .expect("harness channel full, calculate more often");
+        self
+    }
+
+    /// Queue a heartbeat confirming the feed's unchanged price
+    pub fn push_heartbeat(&mut self, feed_id: &str, price: f64) -> &mut Self {
+        self.sender
+            .try_send(FeedData {
+                feed_id: feed_id.to_string(),
+                timestamp: Utc::now(),
+                price,
+                backup_feed: None,
+                heartbeat: true,
+                denomination: None,
+            })
+            .expect("harness channel full, calculate more often");
+        self
+    }
+
+    /// Queue a series of updates, one per calculation tick, and calculate after each of them
+    pub fn replay(&mut self, feed_id: &str, prices: &[f64]) -> Vec<IndexResult> {
+        prices.iter()
+            .flat_map(|&price| {
+                self.push(feed_id, price);
+                self.calculate()
+            })
+            .collect()
+    }
+
+    /// Run one calculation, recording and returning its results
+    pub fn calculate(&mut self) -> Vec<IndexResult> {
+        let results = self.calculator.calculate_indices().expect("index calculation failed");
+        self.published.extend(results.iter().cloned());
+        results
+    }
+
+    /// All values published for an index so far, oldest first
+    pub fn published_values(&self, index_name: &str) -> Vec<f64> {
+        self.published.iter()
+            .filter(|result| result.name == index_name)
+            .map(|result| result.value)
+            .collect()
+    }
+
+    /// All results published so far, oldest first
+    pub fn published(&self) -> &[IndexResult] {
+        &self.published
+    }
+
+    /// Access the calculator, e.g. to bootstrap history
+    pub fn calculator(&mut self) -> &mut IndexCalculator {
+        &mut self.calculator
+    }
+}
+
+/// Exchange returning scripted prices per symbol, in order; an exhausted script fails
+#[derive(Default)]
+pub struct ScriptedExchange {
+    prices: Mutex<HashMap<String, VecDeque<AppResult<f64>>>>,
+    closes: HashMap<String, Vec<f64>>,
+}
+
+impl ScriptedExchange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue successful price responses for a symbol
+    pub fn prices(self, symbol: &str, prices: &[f64]) -> Self {
+        self.prices.lock().unwrap().entry(symbol.to_string()).or_default()
+            .extend(prices.iter().map(|&price| Ok(price)));
+        self
+    }
+
+    /// Queue a failed response for a symbol
+    pub fn failure(self, symbol: &str, message: &str) -> Self {
+        self.prices.lock().unwrap().entry(symbol.to_string()).or_default()
+            .push_back(Err(AppError::Exchange(message.to_string())));
+        self
+    }
+
+    /// Candle closes returned for a symbol
+    pub fn closes(mut self, symbol: &str, closes: &[f64]) -> Self {
+        self.closes.insert(symbol.to_string(), closes.to_vec());
+        self
+    }
+}
+
+#[async_trait]
+impl Exchange for ScriptedExchange {
+    async fn fetch_price(&self, symbol: &str) -> AppResult<f64> {
+        self.prices.lock().unwrap()
+            .get_mut(symbol)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or_else(|| Err(AppError::Exchange(format!("No scripted price left for {}", symbol))))
+    }
+
+    async fn fetch_recent_closes(&self, symbol: &str, limit: usize) -> AppResult<Vec<f64>> {
+        let closes = self.closes.get(symbol)
+            .ok_or_else(|| AppError::Exchange(format!("No scripted candles for {}", symbol)))?;
+        Ok(closes[closes.len().saturating_sub(limit)..].to_vec())
+    }
+}
+
+/// Assert that two value sequences have the same length and match within `tolerance`
+#[track_caller]
+pub fn assert_values_close(actual: &[f64], expected: &[f64], tolerance: f64) {
+    assert_eq!(actual.len(), expected.len(), "sequence lengths differ: {:?} vs {:?}", actual, expected);
+
+    for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+        assert!((a - e).abs() <= tolerance,
+                "value {} differs: {} vs expected {} (tolerance {})\n  actual:   {:?}\n  expected: {:?}",
+                i, a, e, tolerance, actual, expected);
+    }
+}
@@ -0,0 +1,14 @@
+//! Fixtures for testing code built on this crate (new exchanges, new smoothing algorithms).
+//!
+//! Enabled with the `test_support` feature:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! crypto-index-collector = { path = "...", features = ["test_support"] }
+//! ```
+
+mod builders;
+mod harness;
+
+pub use builders::{IndexDefinitionBuilder, price_feed};
+pub use harness::{IndexHarness, ScriptedExchange, assert_values_close};
@@ -0,0 +1,45 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+
+/// Client addresses and browser origins allowed to connect; empty lists allow everyone
+#[derive(Debug, Clone, Default)]
+pub struct Allowlist {
+    networks: Vec<IpNet>,
+    origins: Vec<String>,
+}
+
+impl Allowlist {
+    /// Allowlist of CIDR ranges or single addresses, and of `Origin` header values such as
+    /// `https://dashboard.example.com`
+    pub fn new(networks: &[String], origins: &[String]) -> Result<Self, String> {
+        let networks = networks.iter()
+            .map(|network| {
+                network.parse::<IpNet>()
+                    .or_else(|_| network.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("Invalid network {}, expected a CIDR range or an IP address", network))
+            })
+            .collect::<Result<_, _>>()?;
+        let origins = origins.iter().map(|origin| origin.trim_end_matches('/').to_ascii_lowercase()).collect();
+        Ok(Self { networks, origins })
+    }
+
+    /// Whether a client address is allowed; IPv4 clients of a dual-stack listener are matched
+    /// by their IPv4 address
+    pub fn allows_ip(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.networks.is_empty() || self.networks.iter().any(|network| network.contains(&ip))
+    }
+
+    /// Whether the `Origin` of a handshake is allowed. Only browsers send one, so clients
+    /// without an origin are left to the address allowlist.
+    pub fn allows_origin(&self, origin: Option<&str>) -> bool {
+        match origin {
+            Some(origin) if !self.origins.is_empty() => {
+                let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+                self.origins.contains(&origin)
+            }
+            _ => true,
+        }
+    }
+}
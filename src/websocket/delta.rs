@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::index::IndexResult;
+
+/// Per-connection filter of the snapshot-plus-delta mode: after the snapshot, only updates
+/// changing the value of their index are sent
+#[derive(Debug, Default)]
+pub struct DeltaFilter {
+    sent: HashMap<String, (f64, DateTime<Utc>)>,
+}
+
+impl DeltaFilter {
+    /// Start from the values of a snapshot sent to the client
+    pub fn new(snapshot: &[IndexResult]) -> Self {
+        Self {
+            sent: snapshot.iter().map(|value| (value.name.clone(), (value.value, value.timestamp))).collect(),
+        }
+    }
+
+    /// Whether an update changes the value last sent for its index, recording it if so. Updates
+    /// not newer than the sent value, e.g. queued before the snapshot was taken, are no deltas.
+    pub fn is_delta(&mut self, update: &IndexResult) -> bool {
+        if let Some((value, timestamp)) = self.sent.get(&update.name) {
+            if update.timestamp <= *timestamp || update.value == *value {
+                return false;
+            }
+        }
+        self.sent.insert(update.name.clone(), (update.value, update.timestamp));
+        true
+    }
+}
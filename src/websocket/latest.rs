@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::index::IndexResult;
+use crate::metrics::Subscriber;
+
+/// Latest published value of every index, answering `get` requests without waiting for the
+/// next update
+#[derive(Default)]
+pub struct LatestIndexValues {
+    values: RwLock<HashMap<String, IndexResult>>,
+}
+
+impl LatestIndexValues {
+    pub fn get(&self, index: &str) -> Option<IndexResult> {
+        self.values.read().unwrap().get(index).cloned()
+    }
+
+    /// Latest value of every index published so far, by name
+    pub fn all(&self) -> Vec<IndexResult> {
+        let mut values: Vec<IndexResult> = self.values.read().unwrap().values().cloned().collect();
+        values.sort_by(|a, b| a.name.cmp(&b.name));
+        values
+    }
+
+    pub fn update(&self, value: IndexResult) {
+        self.values.write().unwrap().insert(value.name.clone(), value);
+    }
+
+    /// Keep up with the published values until the publisher stops
+    pub async fn track(&self, mut updates: Subscriber<IndexResult>) {
+        while let Some(value) = updates.recv().await {
+            self.update(value);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+use tracing::info;
+
+use crate::config::{ListenerConfig, TlsConfig};
+use crate::error::AppResult;
+
+/// Byte stream of a client connection, whichever listener accepted it
+pub trait ClientStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ClientStream for T {}
+
+/// Remote end of a client connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Peer {
+    Tcp(SocketAddr),
+    /// Same-host client of the Unix domain socket at the path
+    Unix(String),
+}
+
+impl Peer {
+    /// Address of a TCP client, which allowlists and per-address limits apply to
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            Peer::Tcp(addr) => Some(addr.ip()),
+            Peer::Unix(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Peer::Tcp(addr) => write!(f, "{}", addr),
+            Peer::Unix(path) => write!(f, "unix:{}", path),
+        }
+    }
+}
+
+/// Connection accepted by a listener, before its TLS handshake
+pub struct Incoming {
+    stream: Box<dyn ClientStream>,
+    tls: Option<TlsAcceptor>,
+}
+
+impl Incoming {
+    /// Stream of the connection, after the TLS handshake of a TLS listener
+    pub async fn into_stream(self) -> AppResult<Box<dyn ClientStream>> {
+        match self.tls {
+            Some(tls) => Ok(Box::new(tls.accept(self.stream).await?)),
+            None => Ok(self.stream),
+        }
+    }
+}
+
+/// Bound listener of the WebSocket server
+pub enum Listener {
+    Tcp { listener: TcpListener, tls: Option<TlsAcceptor> },
+    #[cfg(unix)]
+    Unix { listener: UnixListener, path: String },
+}
+
+impl Listener {
+    pub async fn bind(config: &ListenerConfig) -> AppResult<Self> {
+        if let Some(path) = &config.unix_socket {
+            return Self::bind_unix(path);
+        }
+
+        let address = config.address.as_deref().unwrap_or_default();
+        let addr: SocketAddr = address.parse()
+            .map_err(|e| format!("Invalid WebSocket address {}: {}", address, e))?;
+        let tls = config.tls.as_ref().map(tls_acceptor).transpose()?;
+
+        let listener = match TcpListener::bind(&addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                if e.kind() == std::io::ErrorKind::AddrInUse {
+                    let port = addr.port();
+                    return Err(format!("WebSocket port {} is already in use. This could be due to:\n\
+                    1. Another instance of the collector is already running\n\
+                    2. Another application is using this port\n\
+                    Try running 'lsof -i :{}' to identify the process, then terminate it with 'kill <PID>'.",
+                    port, port).into());
+                } else {
+                    return Err(format!("Failed to bind WebSocket server: {}", e).into());
+                }
+            }
+        };
+
+        info!("[WEBSOCKET SERVER] Listening on: {}{}", address, if tls.is_some() { " (TLS)" } else { "" });
+        Ok(Listener::Tcp { listener, tls })
+    }
+
+    #[cfg(unix)]
+    fn bind_unix(path: &str) -> AppResult<Self> {
+        use std::os::unix::fs::FileTypeExt;
+
+        // A socket left behind by a previous run would fail the bind
+        if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)
+            .map_err(|e| format!("Failed to bind WebSocket server to {}: {}", path, e))?;
+
+        info!("[WEBSOCKET SERVER] Listening on: unix:{}", path);
+        Ok(Listener::Unix { listener, path: path.to_string() })
+    }
+
+    #[cfg(not(unix))]
+    fn bind_unix(path: &str) -> AppResult<Self> {
+        Err(format!("Cannot listen on {}: Unix domain sockets are not supported on this platform", path).into())
+    }
+
+    pub async fn accept(&self) -> std::io::Result<(Incoming, Peer)> {
+        match self {
+            Listener::Tcp { listener, tls } => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((Incoming { stream: Box::new(stream), tls: tls.clone() }, Peer::Tcp(addr)))
+            }
+            #[cfg(unix)]
+            Listener::Unix { listener, path } => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Incoming { stream: Box::new(stream), tls: None }, Peer::Unix(path.clone())))
+            }
+        }
+    }
+
+    /// Stop listening, removing the socket file of a Unix domain socket
+    pub fn close(self) {
+        #[cfg(unix)]
+        if let Listener::Unix { path, .. } = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// TLS acceptor with the certificate chain and private key of a listener
+pub fn tls_acceptor(tls: &TlsConfig) -> AppResult<TlsAcceptor> {
+    let open = |path: &str| File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("Failed to read {}: {}", path, e));
+
+    let certs = rustls_pemfile::certs(&mut open(&tls.cert_path)?).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(format!("No certificate in {}", tls.cert_path).into());
+    }
+    let key = rustls_pemfile::private_key(&mut open(&tls.key_path)?)?
+        .ok_or_else(|| format!("No private key in {}", tls.key_path))?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| format!("Invalid TLS certificate or key: {}", e))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
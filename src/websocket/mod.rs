@@ -1,3 +1,22 @@
 mod server;
+pub mod protocol;
+pub mod latest;
+pub mod delta;
+pub mod throttle;
+pub mod allowlist;
+pub mod outbound;
+pub mod listener;
+pub mod what_if;
 
-pub use server::start_websocket_server;
+#[cfg(test)]
+mod tests;
+
+pub use server::{start_websocket_server, ServerContext};
+pub use protocol::{CatalogIndex, Channel, ClientOp, CloseReason, ClientRequest, IndexUpdate, ServerMessage, StreamMode, PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS};
+pub use latest::LatestIndexValues;
+pub use delta::DeltaFilter;
+pub use throttle::Throttle;
+pub use allowlist::Allowlist;
+pub use outbound::Outbound;
+pub use listener::{Listener, Peer};
+pub use what_if::WhatIfLimiter;
@@ -0,0 +1,149 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use futures::{Sink, SinkExt};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, warn};
+
+use crate::config::SlowClientPolicy;
+use crate::logging::{record_stage, PipelineStage};
+use crate::metrics::metrics;
+
+/// How long a closing connection gets to write its close frame
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Queued message, with the calculation time of the index value it carries, if any
+type Queued = (Message, Option<DateTime<Utc>>);
+
+/// Bounded queue of the messages to a client, written to its socket by a task of its own, so a
+/// slow client can't stall its connection. Once the queue is full, the client counts as slow
+/// and is handled by its [`SlowClientPolicy`].
+pub struct Outbound {
+    peer: String,
+    policy: SlowClientPolicy,
+    queue: mpsc::Sender<Queued>,
+    /// Close frame, written ahead of the queued messages
+    close: mpsc::Sender<Message>,
+    closing: Option<Message>,
+    writer: JoinHandle<()>,
+}
+
+impl Outbound {
+    /// Start writing to the sending half of a connection
+    pub fn spawn<S>(sink: S, peer: String, capacity: usize, policy: SlowClientPolicy) -> Self
+    where
+        S: Sink<Message> + Unpin + Send + 'static,
+        S::Error: Display,
+    {
+        let (queue, queued) = mpsc::channel(capacity);
+        let (close, closed) = mpsc::channel(1);
+        let writer = tokio::spawn(write(sink, queued, closed, peer.clone()));
+        Self { peer, policy, queue, close, closing: None, writer }
+    }
+
+    pub fn policy(&self) -> SlowClientPolicy {
+        self.policy
+    }
+
+    /// Whether another message fits into the queue
+    pub fn has_room(&self) -> bool {
+        self.queue.capacity() > 0
+    }
+
+    /// Queue a message the client must receive; `false` if the connection has to end, because
+    /// it failed or the client is too slow
+    pub fn send(&mut self, message: Message) -> bool {
+        self.send_index(message, None)
+    }
+
+    /// Queue a message the client must receive, recording the latency of its delivery since
+    /// `calculated_at` once written
+    pub fn send_index(&mut self, message: Message, calculated_at: Option<DateTime<Utc>>) -> bool {
+        match self.queue.try_send((message, calculated_at)) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.overload();
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Queue a message that may be dropped while the client is slow, unless slow clients are
+    /// disconnected; `false` if the connection has to end
+    pub fn send_droppable(&mut self, message: Message) -> bool {
+        match self.queue.try_send((message, None)) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) if self.policy == SlowClientPolicy::Coalesce => {
+                metrics().increment("websocket.dropped_messages");
+                true
+            }
+            Err(TrySendError::Full(_)) => {
+                self.overload();
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Mark the client as too slow, to be closed with the overload close code
+    pub fn overload(&mut self) {
+        warn!("[WEBSOCKET] Disconnecting client {}: not keeping up with its messages", self.peer);
+        metrics().increment("websocket.slow_clients_disconnected");
+        self.close_with(super::server::close_message(super::CloseReason::Overload, Some("client too slow")));
+    }
+
+    /// Close frame to end the connection with, instead of the plain close
+    pub fn close_with(&mut self, message: Message) {
+        self.closing = Some(message);
+    }
+
+    /// Write the close frame, if any, skipping queued messages, or else the queued messages,
+    /// and wait a moment for the writer to finish
+    pub async fn finish(self) {
+        let Self { close, closing, queue, mut writer, .. } = self;
+        if let Some(message) = closing {
+            let _ = close.try_send(message);
+        }
+        drop((close, queue));
+        if tokio::time::timeout(CLOSE_TIMEOUT, &mut writer).await.is_err() {
+            writer.abort();
+        }
+    }
+}
+
+async fn write<S>(mut sink: S, mut queue: mpsc::Receiver<Queued>, mut close: mpsc::Receiver<Message>, peer: String)
+where
+    S: Sink<Message> + Unpin,
+    S::Error: Display,
+{
+    loop {
+        tokio::select! {
+            biased;
+            Some(message) = close.recv() => {
+                let _ = sink.send(message).await;
+                break;
+            }
+            message = queue.recv() => {
+                let Some((message, calculated_at)) = message else {
+                    break;
+                };
+                if let Err(e) = sink.send(message).await {
+                    metrics().increment("websocket.send_errors");
+                    error!("[WEBSOCKET ERROR] Failed to send to: {}, Error: {}", peer, e);
+                    return;
+                }
+                metrics().increment("websocket.messages_sent");
+                if let Some(calculated_at) = calculated_at {
+                    let latency = Utc::now() - calculated_at;
+                    record_stage(PipelineStage::Delivery, latency.num_microseconds().unwrap_or(i64::MAX) as f64 / 1000.0);
+                }
+            }
+        }
+    }
+    let _ = sink.close().await;
+}
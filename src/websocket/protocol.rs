@@ -0,0 +1,332 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppResult;
+use crate::health::{FeedStatus, IndexHealth};
+use crate::index::{IndexCatalogEntry, IndexChange, IndexQuality, IndexResult};
+use crate::models::{Denomination, FeedData};
+use crate::serialization::WireFormat;
+
+/// Version of the WebSocket protocol, raised on changes existing clients can't follow
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Protocol versions the server speaks, oldest first
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[u32] = &[1];
+
+/// Message sent to WebSocket clients, tagged with its `type` and, like every payload leaving
+/// the process, versioned with a `schema_version`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// First message of every connection, and the reply to a `hello` request
+    Hello {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        /// Protocol version of the connection from now on
+        protocol_version: u32,
+        supported_versions: Vec<u32>,
+        /// Version of the collector
+        server_version: String,
+        /// Optional features the server offers, e.g. `set_encoding`
+        capabilities: Vec<String>,
+    },
+    /// A published index value
+    Index(IndexUpdate),
+    /// A raw price of a feed on a joined `feed:<id>` channel
+    Feed(FeedData),
+    /// Latest value of an index, replying to a `get` request
+    Value {
+        /// Id of the request, if it had one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(flatten)]
+        update: IndexUpdate,
+    },
+    /// A request that failed
+    Error {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        message: String,
+    },
+    /// Current value of every published index, replying to a `subscribe` request. Updates
+    /// following it continue its `sequence`.
+    Snapshot {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        mode: StreamMode,
+        /// Interval at which updates are coalesced, if requested
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        interval_ms: Option<u64>,
+        sequence: u64,
+        indices: Vec<IndexUpdate>,
+    },
+    /// Channels the connection has joined, replying to `join` and `leave` requests
+    Channels {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        channels: Vec<String>,
+    },
+    /// Configured indices and feeds with their current health, sent on connect and in reply to
+    /// a `list` request
+    Catalog {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        indices: Vec<CatalogIndex>,
+        feeds: Vec<FeedStatus>,
+    },
+    /// Encoding of the index updates from now on, confirming a `set_encoding` request
+    Encoding {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        encoding: WireFormat,
+    },
+}
+
+impl ServerMessage {
+    /// Text frame of the message
+    pub fn to_json(&self) -> AppResult<String> {
+        let bytes = WireFormat::Json.encode(self)?;
+        // Serialized by serde_json, so always UTF-8
+        Ok(String::from_utf8(bytes).unwrap_or_default())
+    }
+
+    /// Message of a text frame
+    pub fn from_json(text: &str) -> AppResult<Self> {
+        WireFormat::Json.decode(text.as_bytes())
+    }
+
+    /// Binary frame of the message in a negotiated encoding
+    pub fn to_binary(&self, encoding: WireFormat) -> AppResult<Vec<u8>> {
+        encoding.encode(self)
+    }
+
+    /// Message of a binary frame in a negotiated encoding
+    pub fn from_binary(bytes: &[u8], encoding: WireFormat) -> AppResult<Self> {
+        encoding.decode(bytes)
+    }
+}
+
+/// JSON request of a client, with an optional `id` echoed in the reply
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub op: ClientOp,
+}
+
+/// Operation of a client request, tagged with its `op`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ClientOp {
+    /// Speak a protocol `version` from now on, or the current one if absent, replied to with a
+    /// `hello`
+    Hello {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<u32>,
+    },
+    /// Catalog of the configured indices and feeds
+    List,
+    /// Latest published value of an index, independent of the stream of updates
+    Get { index: String },
+    /// Encoding of the index updates: `json` text frames (the default), or `msgpack` or `cbor`
+    /// binary frames. Replies to requests stay JSON text frames.
+    SetEncoding { encoding: WireFormat },
+    /// Snapshot of every index, followed by the updates of the requested `mode`
+    Subscribe {
+        #[serde(default)]
+        mode: StreamMode,
+        /// Send updates at most this often, only the latest of every index since the previous
+        /// interval; every update as it is published if absent
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        interval_ms: Option<u64>,
+    },
+    /// Also receive the messages of a channel, e.g. `feed:coinbase_btc_usd`
+    Join { channel: String },
+    /// Stop receiving the messages of a joined channel
+    Leave { channel: String },
+}
+
+/// Index listed in a catalog, with its current health
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogIndex {
+    #[serde(flatten)]
+    pub entry: IndexCatalogEntry,
+    pub health: IndexHealth,
+}
+
+/// Channel of raw data a client can join next to the index updates
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Channel {
+    /// Raw prices of a feed, `feed:<feed id>`, or of every feed, `feed:*`
+    Feed(String),
+}
+
+impl Channel {
+    /// Whether a raw price belongs on the channel
+    pub fn carries(&self, tick: &FeedData) -> bool {
+        match self {
+            Channel::Feed(feed_id) => feed_id == "*" || *feed_id == tick.feed_id,
+        }
+    }
+}
+
+impl std::str::FromStr for Channel {
+    type Err = String;
+
+    fn from_str(channel: &str) -> Result<Self, Self::Err> {
+        match channel.split_once(':') {
+            Some(("feed", feed_id)) if !feed_id.is_empty() => Ok(Channel::Feed(feed_id.to_string())),
+            _ => Err(format!("Unknown channel {}, expected feed:<feed id>", channel)),
+        }
+    }
+}
+
+impl std::fmt::Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Channel::Feed(feed_id) => write!(f, "feed:{}", feed_id),
+        }
+    }
+}
+
+/// Which index updates a client receives
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamMode {
+    /// Every published update
+    #[default]
+    Full,
+    /// Only updates changing the value of their index
+    Delta,
+}
+
+impl ClientRequest {
+    pub fn from_json(text: &str) -> AppResult<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+}
+
+/// Published index value as streamed to clients
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexUpdate {
+    pub index: String,
+    pub value: f64,
+    /// Value before smoothing, absent where it was not recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_value: Option<f64>,
+    pub timestamp: DateTime<Utc>,
+    /// Number of the update on its connection, starting at 1; a gap means updates were missed.
+    /// Absent on replies to requests, which are not part of the stream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+    /// Calculation epoch; values sharing an epoch were calculated from the same feed values
+    pub epoch: u64,
+    pub denomination: Denomination,
+    #[serde(default)]
+    pub quality: IndexQuality,
+    #[serde(default)]
+    pub change: IndexChange,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub warming_up: bool,
+    #[serde(default)]
+    pub methodology_version: u32,
+}
+
+impl IndexUpdate {
+    pub fn new(result: &IndexResult, sequence: Option<u64>) -> Self {
+        Self {
+            index: result.name.clone(),
+            value: result.value,
+            raw_value: result.raw_value,
+            timestamp: result.timestamp,
+            sequence,
+            epoch: result.epoch,
+            denomination: result.denomination.clone(),
+            quality: result.quality.clone(),
+            change: result.change.clone(),
+            warming_up: result.warming_up,
+            methodology_version: result.methodology_version,
+        }
+    }
+}
+
+/// Why the server closes a connection, sent as the code and reason of its close frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The collector is shutting down; reconnect later or to another instance
+    Shutdown,
+    /// The client broke a server policy, e.g. opened too many connections from its address
+    PolicyViolation,
+    /// The client is not allowed to connect
+    Unauthorized,
+    /// The collector is overloaded or the client can't keep up; reconnect later
+    Overload,
+}
+
+impl CloseReason {
+    /// Close code of the reason: `1001` (going away), `1008` (policy violation), `4001`
+    /// (unauthorized, from the range reserved for applications) or `1013` (try again later)
+    pub fn code(&self) -> u16 {
+        match self {
+            CloseReason::Shutdown => 1001,
+            CloseReason::PolicyViolation => 1008,
+            CloseReason::Unauthorized => 4001,
+            CloseReason::Overload => 1013,
+        }
+    }
+
+    /// Reason of a close code sent by the server
+    pub fn from_code(code: u16) -> Option<Self> {
+        [CloseReason::Shutdown, CloseReason::PolicyViolation, CloseReason::Unauthorized, CloseReason::Overload]
+            .into_iter()
+            .find(|reason| reason.code() == code)
+    }
+}
+
+impl std::fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CloseReason::Shutdown => "server shutting down",
+            CloseReason::PolicyViolation => "policy violation",
+            CloseReason::Unauthorized => "unauthorized",
+            CloseReason::Overload => "server overloaded",
+        })
+    }
+}
+
+/// Legacy text frame of an index update, sent instead of [`IndexUpdate`]s with
+/// `legacy_text_frames`
+pub fn format_index_message(index: &IndexResult) -> String {
+    let mut message = format!("INDEX: {} | TIMESTAMP: {} | VALUE: {} | EPOCH: {} | CURRENCY: {} | DECIMALS: {} | FEEDS: {} | CONFIDENCE: {:.3}",
+        index.name, index.timestamp, index.value, index.epoch, index.denomination.quote_currency, index.denomination.decimals,
+        index.quality.contributors, index.quality.confidence);
+    if let Some(raw_value) = index.raw_value.filter(|raw_value| *raw_value != index.value) {
+        message.push_str(&format!(" | RAW: {}", raw_value));
+    }
+    if let Some(percent) = index.change.percent {
+        message.push_str(&format!(" | CHANGE: {:+.4}%", percent));
+    }
+    if index.warming_up {
+        message.push_str(" | WARMING UP");
+    }
+    if index.methodology_version > 0 {
+        message.push_str(&format!(" | METHODOLOGY: v{}", index.methodology_version));
+    }
+    if !index.quality.failover_feeds.is_empty() {
+        message.push_str(&format!(" | FAILOVER: {}", index.quality.failover_feeds.join(",")));
+    }
+    if !index.quality.stale_feeds.is_empty() {
+        message.push_str(&format!(" | STALE: {}", index.quality.stale_feeds.join(",")));
+    }
+    if !index.quality.outlier_feeds.is_empty() {
+        message.push_str(&format!(" | OUTLIERS: {}", index.quality.outlier_feeds.join(",")));
+    }
+    if !index.quality.missing_feeds.is_empty() {
+        message.push_str(&format!(" | MISSING: {}", index.quality.missing_feeds.join(",")));
+    }
+    if !index.quality.lagging_feeds.is_empty() {
+        message.push_str(&format!(" | LAGGING: {}", index.quality.lagging_feeds.join(",")));
+    }
+    message
+}
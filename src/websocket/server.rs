@@ -1,53 +1,211 @@
-use std::net::SocketAddr;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
+use futures::future::join_all;
 use futures::{SinkExt, StreamExt};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{RwLock, broadcast};
-use tokio::time::Duration;
-use tokio_tungstenite::{accept_async, WebSocketStream, tungstenite::Message};
+use tokio::sync::broadcast;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+use tokio_tungstenite::{accept_async, accept_hdr_async, WebSocketStream};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame, Message};
 
 use tracing::{info, error, warn};
 
-use crate::index::IndexCalculator;
-use crate::error::AppResult;
+use crate::index::{simulate, IndexCalculator, IndexCatalogEntry, IndexResult, WhatIfRequest};
+use crate::models::{FeedData, IndexDefinition};
+use crate::storage::Database;
+use crate::limits::{ConnectionRefusal, ResourceGuard};
+use crate::health::{FeedHealthRegistry, HealthEvent};
+use crate::error::{AppError, AppResult};
+use crate::metrics::{metrics, Subscriber};
+use crate::notification::NotificationQueue;
+use crate::config::{ListenerConfig, SlowClientPolicy};
+use crate::serialization::WireFormat;
+use super::delta::DeltaFilter;
+use super::allowlist::Allowlist;
+use super::latest::LatestIndexValues;
+use super::listener::{ClientStream, Incoming, Listener, Peer};
+use super::outbound::Outbound;
+use super::throttle::Throttle;
+use super::what_if::WhatIfLimiter;
+use super::protocol::{format_index_message, CatalogIndex, Channel, ClientOp, CloseReason, ClientRequest, IndexUpdate, ServerMessage, StreamMode,
+    PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS};
 
-/// Start a WebSocket server for streaming index updates
-pub async fn start_websocket_server(
-    address: &str,
-    index_calc: Arc<RwLock<IndexCalculator>>,
-    mut shutdown: broadcast::Receiver<()>,
-) -> AppResult<()> {
-    let addr: SocketAddr = address.parse()
-        .map_err(|e| format!("Invalid WebSocket address: {}", e))?;
-
-    let listener = match TcpListener::bind(&addr).await {
-        Ok(listener) => listener,
-        Err(e) => {
-            if e.kind() == std::io::ErrorKind::AddrInUse {
-                let port = addr.port();
-                return Err(format!("WebSocket port {} is already in use. This could be due to:\n\
-                1. Another instance of the collector is already running\n\
-                2. Another application is using this port\n\
-                Try running 'lsof -i :{}' to identify the process, then terminate it with 'kill <PID>'.",
-                port, port).into());
-            } else {
-                return Err(format!("Failed to bind WebSocket server: {}", e).into());
+/// Shared state handed to every WebSocket connection
+#[derive(Clone)]
+pub struct ServerContext {
+    /// Published index values, see [`crate::index::run_publisher`]
+    pub index_updates: broadcast::Sender<IndexResult>,
+    /// Calculator of the published values, for coordinated snapshots of all indices
+    pub index_calc: Arc<RwLock<IndexCalculator>>,
+    pub resources: Arc<ResourceGuard>,
+    pub feed_health: Arc<FeedHealthRegistry>,
+    /// Feed and index health transitions, streamed to clients subscribed to them
+    pub health_events: broadcast::Sender<HealthEvent>,
+    /// Raw feed prices, streamed to clients joining their `feed:<id>` channels
+    pub raw_ticks: broadcast::Sender<FeedData>,
+    /// Current index definitions, for the catalog and what-if simulations
+    pub indices: Arc<Vec<IndexDefinition>>,
+    /// Stored raw data, what-if simulations are unavailable without it
+    pub database: Option<Database>,
+    /// Admission of what-if simulations
+    pub what_if: Arc<WhatIfLimiter>,
+    /// Notified about clients repeatedly falling behind on updates
+    pub notifier: NotificationQueue,
+    /// Send index updates as the legacy `INDEX: ... | VALUE: ...` text instead of JSON
+    pub legacy_text_frames: bool,
+    /// Client addresses and origins allowed to connect
+    pub allowlist: Allowlist,
+    /// Messages queued for a client before it counts as slow
+    pub send_queue_capacity: usize,
+    pub slow_client_policy: SlowClientPolicy,
+}
+
+/// Index update stream of a connection, as negotiated by its requests
+#[derive(Default)]
+struct StreamState {
+    /// Protocol version requested with a `hello` request, the current one otherwise
+    protocol_version: Option<u32>,
+    /// Number of the last update sent
+    sequence: u64,
+    /// Encoding of the index updates, negotiated with a `set_encoding` request
+    encoding: WireFormat,
+    /// Filter of a `subscribe` request in delta mode
+    delta: Option<DeltaFilter>,
+    /// Coalescing of a `subscribe` request with an `interval_ms`
+    throttle: Option<Throttle>,
+    /// Channels joined with `join` requests
+    channels: BTreeSet<Channel>,
+    /// Latest update of every index not queued yet because the client is slow, by name
+    backlog: BTreeMap<String, IndexResult>,
+}
+
+impl StreamState {
+    /// Frame of an index update, numbered with the next sequence; `None` if the delta filter
+    /// drops it
+    fn frame(&mut self, context: &ServerContext, index: &IndexResult) -> Option<Message> {
+        if self.delta.as_mut().is_some_and(|delta| !delta.is_delta(index)) {
+            return None;
+        }
+        self.sequence += 1;
+        if context.legacy_text_frames {
+            return Some(Message::Text(format_index_message(index).into()));
+        }
+        self.encode(&ServerMessage::Index(IndexUpdate::new(index, Some(self.sequence))))
+    }
+
+    /// Queue an index update, coalescing it with pending updates of its index while the client
+    /// is slow; `false` if the connection has to end
+    fn queue_index(&mut self, context: &ServerContext, outbound: &mut Outbound, index: IndexResult) -> bool {
+        if self.backlog.is_empty() && outbound.has_room() {
+            return self.frame(context, &index).is_none_or(|message| outbound.send_index(message, Some(index.timestamp)));
+        }
+        if outbound.policy() == SlowClientPolicy::Disconnect {
+            outbound.overload();
+            return false;
+        }
+        if self.backlog.insert(index.name.clone(), index).is_some() {
+            // The replaced update is skipped, leaving a gap in the sequence
+            self.sequence += 1;
+            metrics().increment("websocket.coalesced_updates");
+        }
+        self.flush_backlog(context, outbound)
+    }
+
+    /// Queue pending updates of a slow client while there is room; `false` if the connection
+    /// has to end
+    fn flush_backlog(&mut self, context: &ServerContext, outbound: &mut Outbound) -> bool {
+        while outbound.has_room() {
+            let Some((_, index)) = self.backlog.pop_first() else {
+                break;
+            };
+            if let Some(message) = self.frame(context, &index) {
+                if !outbound.send_index(message, Some(index.timestamp)) {
+                    return false;
+                }
             }
         }
-    };
+        true
+    }
+
+    /// Frame of a streamed message in the negotiated encoding
+    fn encode(&self, message: &ServerMessage) -> Option<Message> {
+        let frame = match self.encoding {
+            WireFormat::Json => message.to_json().map(|text| Message::Text(text.into())),
+            encoding => message.to_binary(encoding).map(|bytes| Message::Binary(bytes.into())),
+        };
+        frame.map_err(|e| error!("[WEBSOCKET ERROR] Failed to serialize message: {}", e)).ok()
+    }
+}
+
+/// Start a WebSocket server for streaming index updates on every listener
+pub async fn start_websocket_server(
+    listeners: &[ListenerConfig],
+    context: ServerContext,
+    shutdown: broadcast::Receiver<()>,
+) -> AppResult<()> {
+    // Bound up front, so a bad listener fails the server before any client connects
+    let mut bound = Vec::with_capacity(listeners.len());
+    for listener in listeners {
+        bound.push(Listener::bind(listener).await?);
+    }
+
+    // Latest value of every index, for `get` requests
+    let latest = Arc::new(LatestIndexValues::default());
+    let updates = Subscriber::new(context.index_updates.subscribe(), "index", "websocket:latest");
+    tokio::spawn({
+        let latest = latest.clone();
+        async move { latest.track(updates).await }
+    });
 
-    info!("[WEBSOCKET SERVER] Listening on: {}", address);
+    join_all(bound.into_iter().map(|listener| {
+        accept_connections(listener, context.clone(), latest.clone(), shutdown.resubscribe())
+    })).await;
 
+    info!("[WEBSOCKET SERVER] Server stopped gracefully");
+    Ok(())
+}
+
+/// Accept the connections of one listener until shutdown
+async fn accept_connections(
+    listener: Listener,
+    context: ServerContext,
+    latest: Arc<LatestIndexValues>,
+    mut shutdown: broadcast::Receiver<()>,
+) {
     loop {
         tokio::select! {
             accept_result = listener.accept() => {
                 match accept_result {
-                    Ok((stream, addr)) => {
-                        let index_calc_clone = index_calc.clone();
+                    Ok((incoming, peer)) => {
+                        // Dropped without a handshake, telling nothing to clients outside the perimeter
+                        if peer.ip().is_some_and(|ip| !context.allowlist.allows_ip(ip)) {
+                            warn!("[WEBSOCKET SERVER] Refusing connection from {}: address not allowed", peer);
+                            metrics().increment("websocket.rejected_addresses");
+                            continue;
+                        }
+
+                        let permit = match context.resources.try_open_connection(peer.ip()) {
+                            Ok(permit) => permit,
+                            Err(refusal) => {
+                                warn!("[WEBSOCKET SERVER] Refusing connection from {}: {}", peer, refusal);
+                                let reason = match refusal {
+                                    ConnectionRefusal::AddressLimit => CloseReason::PolicyViolation,
+                                    ConnectionRefusal::Degraded | ConnectionRefusal::ConnectionLimit => CloseReason::Overload,
+                                };
+                                tokio::spawn(reject_connection(incoming, reason, refusal.to_string()));
+                                continue;
+                            }
+                        };
+
+                        let context_clone = context.clone();
+                        let latest = latest.clone();
                         let shutdown_rx = shutdown.resubscribe();
 
                         tokio::spawn(async move {
-                            if let Err(e) = handle_connection(stream, addr, index_calc_clone, shutdown_rx).await {
+                            let _permit = permit;
+                            if let Err(e) = handle_connection(incoming, peer, context_clone, latest, shutdown_rx).await {
                                 error!("Error handling WebSocket connection: {}", e);
                             }
                         });
@@ -64,106 +222,489 @@ pub async fn start_websocket_server(
         }
     }
 
-    info!("[WEBSOCKET SERVER] Server stopped gracefully");
-    Ok(())
+    listener.close();
+}
+
+/// Complete the handshake of a refused connection only to close it with a reason, so the
+/// client can tell a refusal from a network failure
+async fn reject_connection(incoming: Incoming, reason: CloseReason, detail: String) {
+    let reject = async {
+        let mut ws_stream = accept_async(incoming.into_stream().await?).await?;
+        ws_stream.send(close_message(reason, Some(&detail))).await?;
+        // Wait for the client's close frame, or for it to hang up
+        while ws_stream.next().await.is_some_and(|message| message.is_ok()) {}
+        Ok::<_, AppError>(())
+    };
+    let _ = tokio::time::timeout(Duration::from_secs(5), reject).await;
+}
+
+/// Close frame telling the client why the connection ends
+pub(super) fn close_message(reason: CloseReason, detail: Option<&str>) -> Message {
+    let text = match detail {
+        Some(detail) => format!("{}: {}", reason, detail),
+        None => reason.to_string(),
+    };
+    Message::Close(Some(CloseFrame { code: CloseCode::from(reason.code()), reason: text.into() }))
 }
 
 async fn handle_connection(
-    stream: TcpStream,
-    addr: SocketAddr,
-    index_calc: Arc<RwLock<IndexCalculator>>,
+    incoming: Incoming,
+    peer: Peer,
+    context: ServerContext,
+    latest: Arc<LatestIndexValues>,
     shutdown: broadcast::Receiver<()>,
 ) -> AppResult<()> {
-    info!("[WEBSOCKET CONNECTION] Incoming connection from: {}", addr);
+    info!("[WEBSOCKET CONNECTION] Incoming connection from: {}", peer);
 
-    let ws_stream = accept_async(stream).await?;
+    let mut origin = None;
+    let stream = incoming.into_stream().await?;
+    // The error response type is tungstenite's
+    #[allow(clippy::result_large_err)]
+    let mut ws_stream = accept_hdr_async(stream, |request: &Request, response: Response| {
+        origin = request.headers().get("origin").and_then(|origin| origin.to_str().ok()).map(str::to_string);
+        Ok(response)
+    }).await?;
 
-    info!("[WEBSOCKET ESTABLISHED] Connection established with: {}", addr);
+    if !context.allowlist.allows_origin(origin.as_deref()) {
+        warn!("[WEBSOCKET SERVER] Refusing connection from {}: origin {} not allowed", peer, origin.unwrap_or_default());
+        metrics().increment("websocket.rejected_origins");
+        let _ = ws_stream.send(close_message(CloseReason::Unauthorized, Some("origin not allowed"))).await;
+        return Ok(());
+    }
 
-    handle_websocket(ws_stream, addr, index_calc, shutdown).await;
+    info!("[WEBSOCKET ESTABLISHED] Connection established with: {}", peer);
+
+    handle_websocket(ws_stream, peer, context, latest, shutdown).await;
 
     Ok(())
 }
 
 async fn handle_websocket(
-    mut ws_stream: WebSocketStream<TcpStream>,
-    addr: SocketAddr,
-    index_calc: Arc<RwLock<IndexCalculator>>,
+    mut ws_stream: WebSocketStream<Box<dyn ClientStream>>,
+    peer: Peer,
+    context: ServerContext,
+    latest: Arc<LatestIndexValues>,
     mut shutdown: broadcast::Receiver<()>,
 ) {
-    // Send welcome message
-    let welcome = format!("Connected to Crypto Index Collector. Client: {}", addr);
-    info!("[WEBSOCKET WELCOME] Sending welcome message to: {}", addr);
+    // Greet with the protocol version and capabilities
+    let welcome = hello(&context, None, PROTOCOL_VERSION).to_json().unwrap_or_else(|e| format!("ERROR: {}", e));
+    info!("[WEBSOCKET WELCOME] Sending hello to: {}", peer);
 
     // Try to send a close frame when shutting down
     if shutdown.try_recv().is_ok() {
-        info!("[WEBSOCKET] Closing connection to client: {}", addr);
+        info!("[WEBSOCKET] Closing connection to client: {}", peer);
         // Send close frame
-        if let Err(e) = ws_stream.send(Message::Close(None)).await {
-            warn!("[WEBSOCKET] Error sending close frame to {}: {}", addr, e);
+        if let Err(e) = ws_stream.send(close_message(CloseReason::Shutdown, None)).await {
+            warn!("[WEBSOCKET] Error sending close frame to {}: {}", peer, e);
         }
         return;
     }
 
-    let _ = ws_stream.send(Message::Text(welcome.into())).await;
+    let (sink, mut ws_stream) = ws_stream.split();
+    let mut outbound = Outbound::spawn(sink, peer.to_string(), context.send_queue_capacity, context.slow_client_policy);
+    // Followed by the catalog, so clients can discover what to subscribe to
+    let catalog = catalog(&context, None).await.to_json().unwrap_or_else(|e| format!("ERROR: {}", e));
+    if !outbound.send(Message::Text(welcome.into())) || !outbound.send(Message::Text(catalog.into())) {
+        return;
+    }
 
     // Start a heartbeat task
     let heartbeat_interval = Duration::from_secs(30);
     let mut heartbeat_timer = tokio::time::interval(heartbeat_interval);
 
     let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut index_updates = Subscriber::new(context.index_updates.subscribe(), "index", format!("websocket:{}", peer))
+        .with_notifier(context.notifier.clone());
+    let mut health_events: Option<Subscriber<HealthEvent>> = None;
+    let mut raw_ticks: Option<Subscriber<FeedData>> = None;
+    let mut stream = StreamState::default();
+    // Simulations run apart from the connection, which keeps streaming meanwhile
+    let mut what_if_run: Option<JoinHandle<String>> = None;
+    let mut what_if_started: Option<Instant> = None;
 
     loop {
         tokio::select! {
             msg = ws_stream.next() => {
                 match msg {
                     Some(Ok(msg)) => {
-                        info!("[WEBSOCKET RECEIVED] From: {}, Message: {:?}", addr, msg);
+                        info!("[WEBSOCKET RECEIVED] From: {}, Message: {:?}", peer, msg);
+
+                        // JSON requests, answered with a JSON reply
+                        if let Message::Text(text) = &msg {
+                            if text.trim_start().starts_with('{') {
+                                let reply = handle_request(&context, &latest, &mut stream, text).await;
+                                if stream.channels.is_empty() {
+                                    raw_ticks = None;
+                                } else if raw_ticks.is_none() {
+                                    raw_ticks = Some(Subscriber::new(context.raw_ticks.subscribe(), "tick", format!("websocket:{}", peer))
+                                        .with_notifier(context.notifier.clone()));
+                                }
+                                if !outbound.send(Message::Text(reply.into())) {
+                                    break;
+                                }
+                                continue;
+                            }
+                        }
+
+                        // Clients can preview a methodology change against stored data
+                        if let Message::Text(text) = &msg {
+                            if let Some(request) = strip_command(text, "WHATIF") {
+                                if what_if_run.is_some() {
+                                    if !outbound.send(Message::Text("ERROR: A what-if simulation is already running on this connection".into())) {
+                                        break;
+                                    }
+                                    continue;
+                                }
+                                match start_what_if(&context, request, what_if_started) {
+                                    Ok(run) => {
+                                        what_if_started = Some(Instant::now());
+                                        what_if_run = Some(run);
+                                    }
+                                    Err(e) => {
+                                        metrics().increment("websocket.what_if_rejected");
+                                        if !outbound.send(Message::Text(format!("ERROR: {}", e).into())) {
+                                            break;
+                                        }
+                                    }
+                                }
+                                continue;
+                            }
+                        }
+
+                        // Operators can inspect the price distribution of every feed
+                        if matches!(&msg, Message::Text(text) if text.trim().eq_ignore_ascii_case("DISTRIBUTION")) {
+                            let distributions = context.index_calc.read().await.feed_distributions();
+                            let reply = serde_json::to_string(&distributions)
+                                .unwrap_or_else(|e| format!("ERROR: {}", e));
+                            if !outbound.send(Message::Text(reply.into())) {
+                                break;
+                            }
+                            continue;
+                        }
+
+                        // Consumers can discover the available indices instead of being configured with them
+                        if matches!(&msg, Message::Text(text) if text.trim().eq_ignore_ascii_case("LIST_INDICES")) {
+                            let reply = list_indices(&context);
+                            if !outbound.send(Message::Text(reply.into())) {
+                                break;
+                            }
+                            continue;
+                        }
+
+                        // Clients can ask for all indices calculated from the same feed values
+                        if matches!(&msg, Message::Text(text) if text.trim().eq_ignore_ascii_case("SNAPSHOT")) {
+                            let reply = snapshot(&context).await;
+                            if !outbound.send(Message::Text(reply.into())) {
+                                break;
+                            }
+                            continue;
+                        }
+
+                        // Monitoring clients can subscribe to health transitions
+                        if let Message::Text(text) = &msg {
+                            if strip_command(text, "SUBSCRIBE").is_some_and(|topic| topic.eq_ignore_ascii_case("HEALTH")) {
+                                info!("[WEBSOCKET] Client {} subscribed to health events", peer);
+                                health_events = Some(Subscriber::new(context.health_events.subscribe(), "health", format!("websocket:{}", peer))
+                                    .with_notifier(context.notifier.clone()));
+                                continue;
+                            }
+                            if strip_command(text, "UNSUBSCRIBE").is_some_and(|topic| topic.eq_ignore_ascii_case("HEALTH")) {
+                                health_events = None;
+                                continue;
+                            }
+                        }
+
+                        // Clients can ask for the health of all feeds
+                        if matches!(&msg, Message::Text(text) if text.trim().eq_ignore_ascii_case("HEALTH")) {
+                            let sent = context.feed_health.snapshot().into_iter().all(|status| {
+                                let message = format!("FEED: {} | HEALTH: {} | SINCE: {} | FAILURES: {}",
+                                    status.feed_id, status.health, status.since, status.consecutive_failures);
+                                outbound.send(Message::Text(message.into()))
+                            });
+                            if !sent {
+                                break;
+                            }
+                        }
                     }
                     Some(Err(e)) => {
-                        error!("[WEBSOCKET ERROR] From: {}, Error: {}", addr, e);
+                        error!("[WEBSOCKET ERROR] From: {}, Error: {}", peer, e);
                         break;
                     }
                     None => {
-                        info!("[WEBSOCKET CLOSED] Connection closed by client: {}", addr);
+                        info!("[WEBSOCKET CLOSED] Connection closed by client: {}", peer);
                         break;
                     }
                 }
             }
 
             _ = interval.tick() => {
-                match index_calc.write().await.calculate_indices() {
-                    Ok(indices) => {
-                        for index in indices {
-                            let message = format!("INDEX: {} | TIMESTAMP: {} | VALUE: {}",
-                                index.name, index.timestamp, index.value);
-
-                            if let Err(e) = ws_stream.send(Message::Text(message.into())).await {
-                                error!("[WEBSOCKET ERROR] Failed to send to: {}, Error: {}", addr, e);
-                                return;
+                if context.resources.take_shed_request() {
+                    warn!("[WEBSOCKET] Shedding client {} to relieve resource pressure", peer);
+                    outbound.close_with(close_message(CloseReason::Overload, Some("shed to relieve resource pressure")));
+                    break;
+                }
+                if !stream.flush_backlog(&context, &mut outbound) {
+                    break;
+                }
+            }
+
+            update = index_updates.recv() => {
+                match update {
+                    Some(index) => {
+                        if let Some(throttle) = &mut stream.throttle {
+                            throttle.push(index);
+                            continue;
+                        }
+                        if !stream.queue_index(&context, &mut outbound, index) {
+                            break;
+                        }
+                    }
+                    None => break,
+                }
+            }
+
+            _ = next_throttle_tick(&mut stream.throttle) => {
+                let pending = stream.throttle.as_mut().map(Throttle::take).unwrap_or_default();
+                if !pending.into_iter().all(|index| stream.queue_index(&context, &mut outbound, index)) {
+                    break;
+                }
+            }
+
+            tick = next_event(&mut raw_ticks) => {
+                match tick {
+                    Some(tick) => {
+                        if !stream.channels.iter().any(|channel| channel.carries(&tick)) {
+                            continue;
+                        }
+                        let Some(message) = stream.encode(&ServerMessage::Feed(tick)) else {
+                            continue;
+                        };
+                        if !outbound.send_droppable(message) {
+                            break;
+                        }
+                    }
+                    None => raw_ticks = None,
+                }
+            }
+
+            reply = next_what_if(&mut what_if_run) => {
+                what_if_run = None;
+                if !outbound.send(Message::Text(reply.into())) {
+                    break;
+                }
+            }
+
+            event = next_event(&mut health_events) => {
+                match event {
+                    Some(event) => {
+                        let message = match serde_json::to_string(&event) {
+                            Ok(message) => message,
+                            Err(e) => {
+                                error!("[WEBSOCKET ERROR] Failed to serialize health event: {}", e);
+                                continue;
                             }
+                        };
+                        if !outbound.send_droppable(Message::Text(message.into())) {
+                            break;
                         }
                     }
-                    Err(e) => error!("Failed to calculate indices: {}", e)
+                    None => health_events = None,
                 }
             }
 
             _ = shutdown.recv() => {
-                info!("[WEBSOCKET CONNECTION] Shutdown signal received, closing connection with: {}", addr);
-                let _ = ws_stream.send(Message::Close(None)).await;
+                info!("[WEBSOCKET CONNECTION] Shutdown signal received, closing connection with: {}", peer);
+                outbound.close_with(close_message(CloseReason::Shutdown, None));
                 break;
             }
 
             _ = heartbeat_timer.tick() => {
                 // Send ping frame as heartbeat
-                info!("[WEBSOCKET HEARTBEAT] Sending ping to: {}", addr);
-                if let Err(e) = ws_stream.send(Message::Ping(vec![].into())).await {
-                    error!("[WEBSOCKET ERROR] Failed to send ping to: {}, Error: {}", addr, e);
+                info!("[WEBSOCKET HEARTBEAT] Sending ping to: {}", peer);
+                if !outbound.send_droppable(Message::Ping(vec![].into())) {
                     break;
                 }
             }
         }
     }
 
-    info!("[WEBSOCKET CLOSED] Connection terminated with: {}", addr);
+    if let Some(run) = what_if_run {
+        run.abort();
+    }
+    outbound.finish().await;
+    info!("[WEBSOCKET CLOSED] Connection terminated with: {}", peer);
+}
+
+/// Next event of a subscribed connection, never resolving while unsubscribed
+async fn next_event<T: Clone>(events: &mut Option<Subscriber<T>>) -> Option<T> {
+    match events {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Reply of the running what-if simulation, never resolving while none is running
+async fn next_what_if(run: &mut Option<JoinHandle<String>>) -> String {
+    match run {
+        Some(run) => run.await.unwrap_or_else(|e| format!("ERROR: What-if simulation failed: {}", e)),
+        None => std::future::pending().await,
+    }
+}
+
+/// Next tick of a throttled connection, never resolving while updates are not throttled
+async fn next_throttle_tick(throttle: &mut Option<Throttle>) {
+    match throttle {
+        Some(throttle) => throttle.tick().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Reply to a JSON request, changing the connection's `stream` of index updates on
+/// `set_encoding` and `subscribe`
+async fn handle_request(context: &ServerContext, latest: &LatestIndexValues, stream: &mut StreamState, text: &str) -> String {
+    let reply = match ClientRequest::from_json(text) {
+        Ok(ClientRequest { id, op: ClientOp::Hello { version } }) => {
+            match version.unwrap_or(stream.protocol_version.unwrap_or(PROTOCOL_VERSION)) {
+                version if SUPPORTED_PROTOCOL_VERSIONS.contains(&version) => {
+                    stream.protocol_version = Some(version);
+                    hello(context, id, version)
+                }
+                version => ServerMessage::Error {
+                    id,
+                    message: format!("Unsupported protocol version {}, supported versions are {:?}", version, SUPPORTED_PROTOCOL_VERSIONS),
+                },
+            }
+        }
+        Ok(ClientRequest { id, op: ClientOp::List }) => catalog(context, id).await,
+        Ok(ClientRequest { id, op: ClientOp::Get { index } }) => match latest.get(&index) {
+            Some(value) => ServerMessage::Value { id, update: IndexUpdate::new(&value, None) },
+            None if context.indices.iter().any(|definition| definition.name == index) => {
+                ServerMessage::Error { id, message: format!("Index {} has not been published yet", index) }
+            }
+            None => ServerMessage::Error { id, message: format!("Unknown index {}", index) },
+        },
+        Ok(ClientRequest { id, op: ClientOp::SetEncoding { encoding: requested } }) => {
+            if context.legacy_text_frames && requested != WireFormat::Json {
+                ServerMessage::Error { id, message: "Binary encodings are unavailable while the server sends legacy text frames".to_string() }
+            } else {
+                stream.encoding = requested;
+                ServerMessage::Encoding { id, encoding: requested }
+            }
+        }
+        Ok(ClientRequest { id, op: ClientOp::Join { channel } }) => match channel.parse::<Channel>() {
+            Ok(Channel::Feed(feed_id)) if feed_id != "*"
+                && !context.feed_health.snapshot().iter().any(|status| status.feed_id == feed_id) => {
+                ServerMessage::Error { id, message: format!("Unknown feed {}", feed_id) }
+            }
+            Ok(channel) => {
+                stream.channels.insert(channel);
+                ServerMessage::Channels { id, channels: stream.channels.iter().map(Channel::to_string).collect() }
+            }
+            Err(e) => ServerMessage::Error { id, message: e },
+        },
+        Ok(ClientRequest { id, op: ClientOp::Leave { channel } }) => {
+            stream.channels.retain(|joined| joined.to_string() != channel);
+            ServerMessage::Channels { id, channels: stream.channels.iter().map(Channel::to_string).collect() }
+        }
+        Ok(ClientRequest { id, op: ClientOp::Subscribe { interval_ms: Some(0), .. } }) => {
+            ServerMessage::Error { id, message: "interval_ms must be positive".to_string() }
+        }
+        Ok(ClientRequest { id, op: ClientOp::Subscribe { mode, interval_ms } }) => {
+            let values = latest.all();
+            stream.delta = (mode == StreamMode::Delta).then(|| DeltaFilter::new(&values));
+            stream.throttle = interval_ms.map(|interval_ms| Throttle::new(Duration::from_millis(interval_ms)));
+            stream.sequence += 1;
+            let indices = values.iter().map(|value| IndexUpdate::new(value, None)).collect();
+            ServerMessage::Snapshot { id, mode, interval_ms, sequence: stream.sequence, indices }
+        }
+        Err(e) => ServerMessage::Error { id: None, message: format!("Invalid request: {}", e) },
+    };
+    reply.to_json().unwrap_or_else(|e| format!("ERROR: {}", e))
+}
+
+/// Hello of the server, advertising the protocol version of the connection and the optional
+/// features it can use
+fn hello(context: &ServerContext, id: Option<String>, protocol_version: u32) -> ServerMessage {
+    let mut capabilities = vec!["list", "get", "subscribe", "subscribe.delta", "subscribe.interval", "join"];
+    if !context.legacy_text_frames {
+        capabilities.push("set_encoding");
+    }
+    if context.database.is_some() {
+        capabilities.push("what_if");
+    }
+    ServerMessage::Hello {
+        id,
+        protocol_version,
+        supported_versions: SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        capabilities: capabilities.into_iter().map(str::to_string).collect(),
+    }
+}
+
+/// Catalog of the configured indices and feeds, with the current health of each
+async fn catalog(context: &ServerContext, id: Option<String>) -> ServerMessage {
+    let calculator = context.index_calc.read().await;
+    let indices = context.indices.iter()
+        .map(|index| CatalogIndex { entry: IndexCatalogEntry::from(index), health: calculator.index_health(&index.name) })
+        .collect();
+    ServerMessage::Catalog { id, indices, feeds: context.feed_health.snapshot() }
+}
+
+/// Arguments of a text command, if the text is that command: the command is the whole first
+/// word, so `WHATIFX` is not `WHATIF`
+pub(super) fn strip_command<'a>(text: &'a str, command: &str) -> Option<&'a str> {
+    let text = text.trim_start();
+    let prefix = text.get(..command.len())?;
+    let arguments = &text[command.len()..];
+    (prefix.eq_ignore_ascii_case(command) && (arguments.is_empty() || arguments.starts_with(char::is_whitespace)))
+        .then(|| arguments.trim())
+}
+
+/// Start a what-if simulation for a `WHATIF <json request>` command in a task of its own,
+/// resolving to the reply; fails when the request is invalid or not admitted
+fn start_what_if(context: &ServerContext, request: &str, last_started: Option<Instant>) -> AppResult<JoinHandle<String>> {
+    let request: WhatIfRequest = serde_json::from_str(request)?;
+    let database = context.database.clone()
+        .ok_or("What-if simulations need database persistence to be enabled")?;
+    let current = context.indices.iter().find(|index| index.name == request.index)
+        .ok_or_else(|| format!("Unknown index {}", request.index))?
+        .clone();
+
+    // Fail on invalid overrides before querying
+    request.apply(&current)?;
+    let permit = context.what_if.admit(&request, last_started, Instant::now())?;
+
+    Ok(tokio::spawn(async move {
+        let _permit = permit;
+        let result = async {
+            let feed_ids: Vec<String> = current.feeds.iter().chain(&current.conversion_feeds)
+                .map(|feed| feed.id.clone())
+                .collect();
+            let (from, to) = request.range();
+            let ticks = database.get_price_range(&feed_ids, from, to).await?;
+
+            info!("[WHATIF] Simulating index {} over {} stored prices", request.index, ticks.len());
+            let result = tokio::task::spawn_blocking(move || simulate(&current, &request, &ticks, from, to)).await
+                .map_err(|e| AppError::Other(e.to_string()))??;
+            Ok::<_, AppError>(serde_json::to_string(&result)?)
+        };
+
+        result.await.unwrap_or_else(|e| format!("ERROR: {}", e))
+    }))
+}
+
+/// Calculate every index in one calculation epoch for a `SNAPSHOT` command, replying with the JSON snapshot
+async fn snapshot(context: &ServerContext) -> String {
+    let result = async {
+        let snapshot = context.index_calc.write().await.snapshot()?;
+        Ok::<_, AppError>(serde_json::to_string(&snapshot)?)
+    };
+
+    result.await.unwrap_or_else(|e| format!("ERROR: {}", e))
+}
+
+/// Catalog of every index for a `LIST_INDICES` command, replying with a JSON array
+fn list_indices(context: &ServerContext) -> String {
+    let catalog: Vec<IndexCatalogEntry> = context.indices.iter().map(IndexCatalogEntry::from).collect();
+    serde_json::to_string(&catalog).unwrap_or_else(|e| format!("ERROR: {}", e))
 }
@@ -0,0 +1,465 @@
+use super::*;
+use super::protocol::format_index_message;
+use crate::index::IndexResult;
+use crate::models::Denomination;
+use chrono::{TimeZone, Utc};
+
+fn index_result() -> IndexResult {
+    IndexResult {
+        name: "BTC-USD-INDEX".to_string(),
+        timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+        value: 64000.0,
+        epoch: 7,
+        denomination: Denomination {
+            base_currency: "BTC".to_string(),
+            quote_currency: "USD".to_string(),
+            decimals: 2,
+        },
+        quality: Default::default(),
+        change: Default::default(),
+        methodology_version: 2,
+        raw_value: Some(64010.5),
+        warming_up: false,
+    }
+}
+
+#[cfg(test)]
+mod protocol_tests {
+    use super::*;
+
+    #[test]
+    fn test_index_update_is_a_typed_json_message() {
+        let message = ServerMessage::Index(IndexUpdate::new(&index_result(), Some(3))).to_json().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&message).unwrap();
+
+        assert_eq!(json["type"], "index");
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["index"], "BTC-USD-INDEX");
+        assert_eq!(json["value"], 64000.0);
+        assert_eq!(json["raw_value"], 64010.5);
+        assert_eq!(json["timestamp"], "2024-05-01T12:00:00Z");
+        assert_eq!(json["sequence"], 3);
+        assert_eq!(json["denomination"]["quote_currency"], "USD");
+        assert_eq!(ServerMessage::from_json(&message).unwrap(), ServerMessage::Index(IndexUpdate::new(&index_result(), Some(3))));
+    }
+
+    #[test]
+    fn test_legacy_text_frame() {
+        assert_eq!(format_index_message(&index_result()),
+                   "INDEX: BTC-USD-INDEX | TIMESTAMP: 2024-05-01 12:00:00 UTC | VALUE: 64000 | EPOCH: 7 | CURRENCY: USD \
+                    | DECIMALS: 2 | FEEDS: 0 | CONFIDENCE: 0.000 | RAW: 64010.5 | METHODOLOGY: v2");
+        assert!(ServerMessage::from_json(&format_index_message(&index_result())).is_err());
+    }
+}
+
+#[cfg(test)]
+mod request_tests {
+    use super::*;
+
+    #[test]
+    fn test_get_request_and_reply() {
+        let request = ClientRequest::from_json(r#"{"op": "get", "index": "BTC-USD-INDEX", "id": "42"}"#).unwrap();
+        assert_eq!(request, ClientRequest { id: Some("42".to_string()), op: ClientOp::Get { index: "BTC-USD-INDEX".to_string() } });
+        assert!(ClientRequest::from_json(r#"{"op": "get"}"#).is_err());
+        assert!(ClientRequest::from_json(r#"{"op": "delete", "index": "BTC-USD-INDEX"}"#).is_err());
+
+        let latest = LatestIndexValues::default();
+        assert!(latest.get("BTC-USD-INDEX").is_none());
+        latest.update(index_result());
+        let reply = ServerMessage::Value { id: request.id, update: IndexUpdate::new(&latest.get("BTC-USD-INDEX").unwrap(), None) };
+
+        let json: serde_json::Value = serde_json::from_str(&reply.to_json().unwrap()).unwrap();
+        assert_eq!(json["type"], "value");
+        assert_eq!(json["id"], "42");
+        assert_eq!(json["index"], "BTC-USD-INDEX");
+        assert!(json.get("sequence").is_none());
+        assert_eq!(ServerMessage::from_json(&reply.to_json().unwrap()).unwrap(), reply);
+    }
+}
+
+#[cfg(test)]
+mod hello_tests {
+    use super::*;
+
+    #[test]
+    fn test_hello_request_and_reply() {
+        let request = ClientRequest::from_json(r#"{"op": "hello", "version": 1}"#).unwrap();
+        assert_eq!(request.op, ClientOp::Hello { version: Some(1) });
+        assert_eq!(ClientRequest::from_json(r#"{"op": "hello"}"#).unwrap().op, ClientOp::Hello { version: None });
+        assert!(ClientRequest::from_json(r#"{"op": "hello", "version": "latest"}"#).is_err());
+
+        let hello = ServerMessage::Hello {
+            id: None,
+            protocol_version: PROTOCOL_VERSION,
+            supported_versions: SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
+            server_version: "0.1.0".to_string(),
+            capabilities: vec!["get".to_string(), "set_encoding".to_string()],
+        };
+        let json: serde_json::Value = serde_json::from_str(&hello.to_json().unwrap()).unwrap();
+        assert_eq!(json["type"], "hello");
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["protocol_version"], 1);
+        assert_eq!(json["supported_versions"], serde_json::json!([1]));
+        assert_eq!(json["capabilities"], serde_json::json!(["get", "set_encoding"]));
+        assert_eq!(ServerMessage::from_json(&hello.to_json().unwrap()).unwrap(), hello);
+        assert!(SUPPORTED_PROTOCOL_VERSIONS.contains(&PROTOCOL_VERSION));
+    }
+}
+
+#[cfg(test)]
+mod catalog_tests {
+    use super::*;
+    use crate::health::{FeedHealth, FeedStatus, IndexHealth};
+    use crate::index::IndexCatalogEntry;
+    use crate::test_support::IndexDefinitionBuilder;
+
+    #[test]
+    fn test_list_request_and_catalog() {
+        let request = ClientRequest::from_json(r#"{"op": "list", "id": "c1"}"#).unwrap();
+        assert_eq!(request, ClientRequest { id: Some("c1".to_string()), op: ClientOp::List });
+
+        let index = IndexDefinitionBuilder::new("BTC-USD-INDEX").feed("coinbase_btc", 100).build();
+        let catalog = ServerMessage::Catalog {
+            id: request.id,
+            indices: vec![CatalogIndex { entry: IndexCatalogEntry::from(&index), health: IndexHealth::Degraded }],
+            feeds: vec![FeedStatus {
+                feed_id: "coinbase_btc".to_string(),
+                health: FeedHealth::Healthy,
+                since: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+                consecutive_failures: 0,
+                last_success: None,
+                on_backup: false,
+            }],
+        };
+        let json: serde_json::Value = serde_json::from_str(&catalog.to_json().unwrap()).unwrap();
+        assert_eq!(json["type"], "catalog");
+        assert_eq!(json["id"], "c1");
+        assert_eq!(json["indices"][0]["name"], "BTC-USD-INDEX");
+        assert_eq!(json["indices"][0]["constituents"], serde_json::json!(["coinbase_btc"]));
+        assert_eq!(json["indices"][0]["smoothing"]["algorithm"], "none");
+        assert_eq!(json["indices"][0]["health"], "degraded");
+        assert_eq!(json["feeds"][0]["health"], "healthy");
+        assert_eq!(ServerMessage::from_json(&catalog.to_json().unwrap()).unwrap(), catalog);
+    }
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::*;
+    use crate::serialization::WireFormat;
+
+    #[test]
+    fn test_set_encoding_request() {
+        let request = ClientRequest::from_json(r#"{"op": "set_encoding", "encoding": "msgpack"}"#).unwrap();
+        assert_eq!(request.op, ClientOp::SetEncoding { encoding: WireFormat::MessagePack });
+        assert!(ClientRequest::from_json(r#"{"op": "set_encoding", "encoding": "xml"}"#).is_err());
+
+        let reply = ServerMessage::Encoding { id: None, encoding: WireFormat::Cbor }.to_json().unwrap();
+        assert_eq!(reply, r#"{"schema_version":1,"type":"encoding","encoding":"cbor"}"#);
+    }
+
+    #[test]
+    fn test_binary_index_updates() {
+        let update = ServerMessage::Index(IndexUpdate::new(&index_result(), Some(3)));
+        for encoding in [WireFormat::MessagePack, WireFormat::Cbor] {
+            let frame = update.to_binary(encoding).unwrap();
+            assert!(frame.len() < update.to_json().unwrap().len());
+            assert_eq!(ServerMessage::from_binary(&frame, encoding).unwrap(), update);
+        }
+    }
+}
+
+#[cfg(test)]
+mod delta_tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn update(name: &str, value: f64, seconds: i64) -> IndexResult {
+        IndexResult {
+            name: name.to_string(),
+            value,
+            timestamp: index_result().timestamp + Duration::seconds(seconds),
+            ..index_result()
+        }
+    }
+
+    #[test]
+    fn test_delta_filter_sends_changed_values_only() {
+        let mut filter = DeltaFilter::new(&[update("BTC-USD-INDEX", 64000.0, 0)]);
+
+        // Unchanged, then queued before the snapshot
+        assert!(!filter.is_delta(&update("BTC-USD-INDEX", 64000.0, 1)));
+        assert!(!filter.is_delta(&update("BTC-USD-INDEX", 63990.0, -1)));
+        assert!(filter.is_delta(&update("BTC-USD-INDEX", 64010.0, 2)));
+        assert!(!filter.is_delta(&update("BTC-USD-INDEX", 64010.0, 3)));
+        // First value of an index missing from the snapshot
+        assert!(filter.is_delta(&update("ETH-USD-INDEX", 3000.0, 2)));
+    }
+
+    #[test]
+    fn test_subscribe_request_and_snapshot() {
+        let request = ClientRequest::from_json(r#"{"op": "subscribe", "mode": "delta"}"#).unwrap();
+        assert_eq!(request.op, ClientOp::Subscribe { mode: StreamMode::Delta, interval_ms: None });
+        let request = ClientRequest::from_json(r#"{"op": "subscribe", "interval_ms": 5000}"#).unwrap();
+        assert_eq!(request.op, ClientOp::Subscribe { mode: StreamMode::Full, interval_ms: Some(5000) });
+
+        let latest = LatestIndexValues::default();
+        latest.update(update("ETH-USD-INDEX", 3000.0, 0));
+        latest.update(update("BTC-USD-INDEX", 64000.0, 0));
+        let indices: Vec<IndexUpdate> = latest.all().iter().map(|value| IndexUpdate::new(value, None)).collect();
+        let snapshot = ServerMessage::Snapshot { id: None, mode: StreamMode::Delta, interval_ms: None, sequence: 1, indices };
+
+        let json: serde_json::Value = serde_json::from_str(&snapshot.to_json().unwrap()).unwrap();
+        assert_eq!(json["type"], "snapshot");
+        assert_eq!(json["mode"], "delta");
+        assert_eq!(json["sequence"], 1);
+        assert_eq!(json["indices"][0]["index"], "BTC-USD-INDEX");
+        assert_eq!(json["indices"][1]["index"], "ETH-USD-INDEX");
+        assert_eq!(ServerMessage::from_json(&snapshot.to_json().unwrap()).unwrap(), snapshot);
+    }
+}
+
+#[cfg(test)]
+mod throttle_tests {
+    use super::*;
+    use tokio::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_throttle_coalesces_updates() {
+        let mut throttle = Throttle::new(Duration::from_millis(50));
+        let start = Instant::now();
+
+        throttle.push(IndexResult { name: "ETH-USD-INDEX".to_string(), value: 3000.0, ..index_result() });
+        throttle.push(IndexResult { value: 64000.0, ..index_result() });
+        throttle.push(IndexResult { value: 64010.0, ..index_result() });
+        throttle.tick().await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let pending = throttle.take();
+        assert_eq!(pending.iter().map(|update| (update.name.as_str(), update.value)).collect::<Vec<_>>(),
+                   [("BTC-USD-INDEX", 64010.0), ("ETH-USD-INDEX", 3000.0)]);
+        assert!(throttle.take().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod channel_tests {
+    use super::*;
+    use crate::models::FeedData;
+
+    fn tick(feed_id: &str) -> FeedData {
+        FeedData {
+            feed_id: feed_id.to_string(),
+            timestamp: index_result().timestamp,
+            price: 64123.45,
+            backup_feed: None,
+            heartbeat: false,
+            denomination: None,
+        }
+    }
+
+    #[test]
+    fn test_feed_channels() {
+        let channel: Channel = "feed:coinbase_btc_usd".parse().unwrap();
+        assert_eq!(channel, Channel::Feed("coinbase_btc_usd".to_string()));
+        assert_eq!(channel.to_string(), "feed:coinbase_btc_usd");
+        assert!(channel.carries(&tick("coinbase_btc_usd")));
+        assert!(!channel.carries(&tick("binance_btc_usd")));
+        assert!("feed:*".parse::<Channel>().unwrap().carries(&tick("binance_btc_usd")));
+
+        assert!("feed:".parse::<Channel>().is_err());
+        assert!("index:BTC-USD-INDEX".parse::<Channel>().is_err());
+    }
+
+    #[test]
+    fn test_join_request_and_feed_message() {
+        let request = ClientRequest::from_json(r#"{"op": "join", "channel": "feed:coinbase_btc_usd"}"#).unwrap();
+        assert_eq!(request.op, ClientOp::Join { channel: "feed:coinbase_btc_usd".to_string() });
+
+        let message = ServerMessage::Feed(tick("coinbase_btc_usd"));
+        let json: serde_json::Value = serde_json::from_str(&message.to_json().unwrap()).unwrap();
+        assert_eq!(json["type"], "feed");
+        assert_eq!(json["feed_id"], "coinbase_btc_usd");
+        assert_eq!(json["price"], 64123.45);
+        assert_eq!(ServerMessage::from_json(&message.to_json().unwrap()).unwrap(), message);
+    }
+}
+
+#[cfg(test)]
+mod close_tests {
+    use super::*;
+
+    #[test]
+    fn test_close_codes() {
+        assert_eq!(CloseReason::Shutdown.code(), 1001);
+        assert_eq!(CloseReason::PolicyViolation.code(), 1008);
+        assert_eq!(CloseReason::Overload.code(), 1013);
+        assert_eq!(CloseReason::Unauthorized.code(), 4001);
+        assert_eq!(CloseReason::from_code(1013), Some(CloseReason::Overload));
+        assert_eq!(CloseReason::from_code(1000), None);
+        assert_eq!(CloseReason::Shutdown.to_string(), "server shutting down");
+    }
+}
+
+#[cfg(test)]
+mod allowlist_tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_network_allowlist() {
+        let allowlist = Allowlist::new(&strings(&["10.0.0.0/8", "192.168.1.20", "fd00::/8"]), &[]).unwrap();
+
+        assert!(allowlist.allows_ip("10.20.30.40".parse().unwrap()));
+        assert!(allowlist.allows_ip("192.168.1.20".parse().unwrap()));
+        assert!(!allowlist.allows_ip("192.168.1.21".parse().unwrap()));
+        assert!(allowlist.allows_ip("fd12::1".parse().unwrap()));
+        // IPv4 clients of an IPv6 listener
+        assert!(allowlist.allows_ip("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(!allowlist.allows_ip("8.8.8.8".parse().unwrap()));
+
+        assert!(Allowlist::default().allows_ip("8.8.8.8".parse().unwrap()));
+        assert!(Allowlist::new(&strings(&["10.0.0.0/33"]), &[]).is_err());
+        assert!(Allowlist::new(&strings(&["localhost"]), &[]).is_err());
+    }
+
+    #[test]
+    fn test_origin_allowlist() {
+        let allowlist = Allowlist::new(&[], &strings(&["https://dashboard.example.com/"])).unwrap();
+
+        assert!(allowlist.allows_origin(Some("https://Dashboard.example.com")));
+        assert!(!allowlist.allows_origin(Some("https://evil.example.com")));
+        // Non-browser clients
+        assert!(allowlist.allows_origin(None));
+        assert!(Allowlist::default().allows_origin(Some("https://evil.example.com")));
+    }
+}
+
+#[cfg(test)]
+mod outbound_tests {
+    use super::*;
+    use crate::config::SlowClientPolicy;
+    use futures::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    fn text(text: &str) -> Message {
+        Message::Text(text.into())
+    }
+
+    // The writer only runs once the test yields, so the queue fills deterministically
+    #[tokio::test]
+    async fn test_slow_client_is_closed_with_overload() {
+        let (sink, mut received) = futures::channel::mpsc::channel::<Message>(16);
+        let mut outbound = Outbound::spawn(sink, "127.0.0.1:4000".to_string(), 2, SlowClientPolicy::Coalesce);
+
+        assert!(outbound.send(text("1")) && outbound.send(text("2")));
+        assert!(!outbound.has_room());
+        // Streamed messages are dropped while coalescing, replies end the connection
+        assert!(outbound.send_droppable(text("3")));
+        assert_eq!(outbound.policy(), SlowClientPolicy::Coalesce);
+        assert!(!outbound.send(text("4")));
+        outbound.finish().await;
+
+        // The close frame skips the queue
+        let Some(Message::Close(Some(frame))) = received.next().await else {
+            panic!("expected a close frame");
+        };
+        assert_eq!(u16::from(frame.code), CloseReason::Overload.code());
+        assert_eq!(frame.reason.as_str(), "server overloaded: client too slow");
+    }
+
+    #[tokio::test]
+    async fn test_slow_client_is_disconnected_on_streamed_messages() {
+        let (sink, _received) = futures::channel::mpsc::channel::<Message>(0);
+        let mut outbound = Outbound::spawn(sink, "127.0.0.1:4000".to_string(), 1, SlowClientPolicy::Disconnect);
+
+        assert!(outbound.send(text("1")));
+        assert!(!outbound.send_droppable(text("2")));
+    }
+
+    #[tokio::test]
+    async fn test_queued_messages_are_written_in_order() {
+        let (sink, received) = futures::channel::mpsc::channel::<Message>(16);
+        let mut outbound = Outbound::spawn(sink, "127.0.0.1:4000".to_string(), 2, SlowClientPolicy::Disconnect);
+
+        assert!(outbound.send(text("1")) && outbound.send(text("2")));
+        outbound.finish().await;
+
+        assert_eq!(received.collect::<Vec<_>>().await, [text("1"), text("2")]);
+    }
+}
+
+#[cfg(test)]
+mod listener_tests {
+    use super::*;
+    use crate::config::{ListenerConfig, TlsConfig};
+    use super::listener::tls_acceptor;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_listener_replaces_stale_socket_and_removes_it_on_close() {
+        let path = std::env::temp_dir().join(format!("collector-ws-{}.sock", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let config = ListenerConfig { address: None, unix_socket: Some(path.clone()), tls: None };
+
+        // Left behind as if by a crashed run
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let listener = Listener::bind(&config).await.unwrap();
+
+        let _client = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let (_, peer) = listener.accept().await.unwrap();
+        assert_eq!(peer, Peer::Unix(path.clone()));
+        assert_eq!(peer.ip(), None);
+        assert_eq!(peer.to_string(), format!("unix:{}", path));
+
+        listener.close();
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn test_tls_acceptor_reports_unreadable_files() {
+        let tls = TlsConfig { cert_path: "/nonexistent/cert.pem".to_string(), key_path: "/nonexistent/key.pem".to_string() };
+        let error = tls_acceptor(&tls).err().unwrap().to_string();
+        assert!(error.contains("/nonexistent/cert.pem"), "{}", error);
+    }
+}
+
+#[cfg(test)]
+mod what_if_tests {
+    use super::*;
+    use tokio::time::{Duration, Instant};
+    use crate::config::WhatIfConfig;
+    use crate::index::WhatIfRequest;
+    use super::server::strip_command;
+
+    fn request(hours: u64) -> WhatIfRequest {
+        serde_json::from_str(&format!(r#"{{"index": "BTC-USD-INDEX", "hours": {}}}"#, hours)).unwrap()
+    }
+
+    #[test]
+    fn test_commands_match_the_whole_word() {
+        assert_eq!(strip_command("  whatif {\"index\": \"X\"}", "WHATIF"), Some("{\"index\": \"X\"}"));
+        assert_eq!(strip_command("WHATIF", "WHATIF"), Some(""));
+        assert_eq!(strip_command("WHATIFX {}", "WHATIF"), None);
+        assert_eq!(strip_command("SUBSCRIBE HEALTH", "SUBSCRIBE"), Some("HEALTH"));
+        assert_eq!(strip_command("SUBSCRIBEHEALTH", "SUBSCRIBE"), None);
+    }
+
+    #[test]
+    fn test_simulations_are_capped_and_rate_limited() {
+        let limiter = WhatIfLimiter::new(WhatIfConfig { max_hours: 24, max_concurrent: 1, min_interval_secs: 10 });
+        let now = Instant::now();
+
+        assert_eq!(limiter.admit(&request(48), None, now).err().unwrap(), "hours must be at most 24");
+        assert!(limiter.admit(&request(24), Some(now - Duration::from_secs(5)), now).is_err());
+
+        let permit = limiter.admit(&request(24), Some(now - Duration::from_secs(10)), now).unwrap();
+        let busy = limiter.admit(&request(1), None, now).err().unwrap();
+        assert!(busy.starts_with("Too many what-if simulations running"), "{}", busy);
+        drop(permit);
+        assert!(limiter.admit(&request(1), None, now).is_ok());
+    }
+}
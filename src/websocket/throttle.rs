@@ -0,0 +1,35 @@
+use std::collections::BTreeMap;
+
+use tokio::time::{interval_at, Duration, Instant, Interval, MissedTickBehavior};
+
+use crate::index::IndexResult;
+
+/// Per-connection coalescing of index updates for clients asking for a slower update interval:
+/// only the latest update of every index is kept until the next tick
+pub struct Throttle {
+    timer: Interval,
+    pending: BTreeMap<String, IndexResult>,
+}
+
+impl Throttle {
+    pub fn new(interval: Duration) -> Self {
+        let mut timer = interval_at(Instant::now() + interval, interval);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Self { timer, pending: BTreeMap::new() }
+    }
+
+    /// Hold an update until the next tick, replacing a pending update of the same index
+    pub fn push(&mut self, update: IndexResult) {
+        self.pending.insert(update.name.clone(), update);
+    }
+
+    /// Pending updates, by index name
+    pub fn take(&mut self) -> Vec<IndexResult> {
+        std::mem::take(&mut self.pending).into_values().collect()
+    }
+
+    /// Wait for the next tick
+    pub async fn tick(&mut self) {
+        self.timer.tick().await;
+    }
+}
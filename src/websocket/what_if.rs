@@ -0,0 +1,35 @@
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Duration, Instant};
+
+use crate::config::WhatIfConfig;
+use crate::index::WhatIfRequest;
+
+/// Admission of `WHATIF` simulations, which read and recompute hours of stored raw prices:
+/// their period is capped, a connection may start one every `min_interval_secs` and only
+/// `max_concurrent` run at the same time over all connections
+pub struct WhatIfLimiter {
+    config: WhatIfConfig,
+    slots: Arc<Semaphore>,
+}
+
+impl WhatIfLimiter {
+    pub fn new(config: WhatIfConfig) -> Self {
+        let slots = Arc::new(Semaphore::new(config.max_concurrent.max(1)));
+        Self { config, slots }
+    }
+
+    /// Slot to run a simulation in, for a connection whose last simulation started at
+    /// `last_started`; the slot is freed when the permit is dropped
+    pub fn admit(&self, request: &WhatIfRequest, last_started: Option<Instant>, now: Instant) -> Result<OwnedSemaphorePermit, String> {
+        if request.hours > self.config.max_hours {
+            return Err(format!("hours must be at most {}", self.config.max_hours));
+        }
+        let min_interval = Duration::from_secs(self.config.min_interval_secs);
+        if last_started.is_some_and(|started| now.saturating_duration_since(started) < min_interval) {
+            return Err(format!("At most one what-if simulation every {}s per connection", self.config.min_interval_secs));
+        }
+        self.slots.clone().try_acquire_owned()
+            .map_err(|_| "Too many what-if simulations running, try again later".to_string())
+    }
+}